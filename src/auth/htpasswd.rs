@@ -0,0 +1,192 @@
+use argon2::password_hash::{PasswordHash, PasswordVerifier};
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::fs;
+
+// Apache의 htpasswd 도구로 만든 "사용자:해시" 형식의 파일.
+//
+// admin:$2y$10$...          htpasswd -B 로 만든 bcrypt
+// ops:$argon2id$v=19$...    argon2 PHC 문자열
+// legacy:{SHA}qUqP5cyx...   htpasswd -s 로 만든 SHA-1. 소금이 없어서 새로 만들 때는 쓰지 않는 게 좋다.
+//
+// $apr1$(MD5)이나 crypt 같은 다른 형식은 안전하지 않아서 읽지 않고 오류를 낸다.
+#[derive(Clone)]
+pub struct Htpasswd {
+    users: HashMap<String, Hash>,
+    // 모르는 사용자도 이 해시로 검사한다. 바로 거절하면 응답이 빨라서 어떤 사용자 이름이 있는지 드러난다.
+    // 파일에 있는 해시 중 하나라서 검사에 걸리는 시간이 실제 사용자와 비슷하다.
+    dummy: Option<Hash>,
+}
+
+#[derive(Clone)]
+enum Hash {
+    Bcrypt(String),
+    Argon2(String),
+    Sha1(Vec<u8>),
+}
+
+impl Htpasswd {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    // 빈 줄과 #으로 시작하는 줄은 건너뛴다.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut users = HashMap::new();
+        let mut dummy: Option<Hash> = None;
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (user, hash) = line
+                .split_once(':')
+                .ok_or_else(|| format!("line {}: expected user:hash", i + 1))?;
+            let hash = Hash::parse(hash).ok_or_else(|| {
+                format!(
+                    "line {}: unsupported hash for {}, use bcrypt, argon2 or {{SHA}}",
+                    i + 1,
+                    user
+                )
+            })?;
+            // 소금이 없는 SHA-1보다는 느린 해시를 고른다.
+            if dummy
+                .as_ref()
+                .is_none_or(|dummy| dummy.is_fast() && !hash.is_fast())
+            {
+                dummy = Some(hash.clone());
+            }
+            users.insert(user.to_string(), hash);
+        }
+        Ok(Self { users, dummy })
+    }
+
+    // bcrypt와 argon2는 일부러 느리게 만든 해시라서 이 함수는 수십 밀리초가 걸릴 수 있다.
+    pub fn verify(&self, user: &str, password: &str) -> bool {
+        match (self.users.get(user), &self.dummy) {
+            (Some(hash), _) => hash.verify(password),
+            (None, Some(dummy)) => {
+                dummy.verify(password);
+                false
+            }
+            (None, None) => false,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.users.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty()
+    }
+}
+
+impl Debug for Htpasswd {
+    // 해시가 로그에 찍히지 않도록 사용자 이름만 보여준다.
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Htpasswd")
+            .field("users", &self.users.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Hash {
+    // 읽을 때 형식을 검사해 두어서 잘못된 해시가 요청을 처리할 때에야 드러나지 않게 한다.
+    fn parse(hash: &str) -> Option<Self> {
+        if hash.starts_with("$2a$") || hash.starts_with("$2b$") || hash.starts_with("$2y$") {
+            hash.parse::<bcrypt::HashParts>().ok()?;
+            Some(Self::Bcrypt(hash.to_string()))
+        } else if hash.starts_with("$argon2") {
+            PasswordHash::new(hash).ok()?;
+            Some(Self::Argon2(hash.to_string()))
+        } else if let Some(digest) = hash.strip_prefix("{SHA}") {
+            let digest = STANDARD.decode(digest).ok()?;
+            (digest.len() == 20).then_some(Self::Sha1(digest))
+        } else {
+            None
+        }
+    }
+
+    fn is_fast(&self) -> bool {
+        matches!(self, Self::Sha1(_))
+    }
+
+    fn verify(&self, password: &str) -> bool {
+        match self {
+            Self::Bcrypt(hash) => bcrypt::verify(password, hash).unwrap_or(false),
+            Self::Argon2(hash) => match PasswordHash::new(hash) {
+                Ok(hash) => Argon2::default()
+                    .verify_password(password.as_bytes(), &hash)
+                    .is_ok(),
+                Err(_) => false,
+            },
+            // 해시끼리 비교하므로 비교에 걸리는 시간으로는 비밀번호를 알아낼 수 없다.
+            Self::Sha1(digest) => Sha1::digest(password.as_bytes()).as_slice() == digest.as_slice(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sha1(password: &str) -> String {
+        format!(
+            "{{SHA}}{}",
+            STANDARD.encode(Sha1::digest(password.as_bytes()))
+        )
+    }
+
+    #[test]
+    fn verifies_each_hash_format() {
+        let bcrypt = bcrypt::hash("bcrypt-pw", 4).unwrap();
+        let text = format!("# users\n\nalice:{}\nbob:{}\n", bcrypt, sha1("sha-pw"));
+        let htpasswd = Htpasswd::parse(&text).unwrap();
+        assert_eq!(htpasswd.len(), 2);
+        assert!(htpasswd.verify("alice", "bcrypt-pw"));
+        assert!(!htpasswd.verify("alice", "sha-pw"));
+        assert!(htpasswd.verify("bob", "sha-pw"));
+        assert!(!htpasswd.verify("bob", "bcrypt-pw"));
+        // 다른 사용자의 비밀번호로 모르는 사용자를 검사해도 통과하지 않는다.
+        assert!(!htpasswd.verify("mallory", "bcrypt-pw"));
+    }
+
+    #[test]
+    fn unknown_users_are_checked_against_the_slowest_hash() {
+        let bcrypt = bcrypt::hash("pw", 4).unwrap();
+        let text = format!("bob:{}\nalice:{}\n", sha1("pw"), bcrypt);
+        let htpasswd = Htpasswd::parse(&text).unwrap();
+        assert!(matches!(&htpasswd.dummy, Some(Hash::Bcrypt(hash)) if *hash == bcrypt));
+        assert!(Htpasswd::parse("").unwrap().dummy.is_none());
+        assert!(!Htpasswd::parse("").unwrap().verify("alice", "pw"));
+    }
+
+    #[test]
+    fn rejects_weak_or_malformed_lines() {
+        for text in [
+            "alice:$apr1$salt$hash",
+            "alice:plaintext",
+            "alice:{SHA}short",
+            "alice:$2y$10$tooshort",
+            "no colon",
+        ] {
+            assert!(Htpasswd::parse(text).is_err(), "{}", text);
+        }
+        let e = Htpasswd::parse("\nalice:plaintext").unwrap_err();
+        assert!(e.starts_with("line 2:"), "{}", e);
+    }
+
+    #[test]
+    fn debug_output_hides_hashes() {
+        let htpasswd = Htpasswd::parse(&format!("alice:{}", sha1("pw"))).unwrap();
+        let debug = format!("{:?}", htpasswd);
+        assert!(debug.contains("alice"));
+        assert!(!debug.contains("SHA"));
+    }
+}
@@ -0,0 +1,360 @@
+// 경로 접두사마다 Basic 인증(RFC 7617)이나 Bearer 토큰(RFC 6750)을 요구한다.
+//
+// 인증에 성공하면 사용자 이름을 붙인 요청을 안쪽 핸들러에게 넘긴다. 안쪽 핸들러는 request.principal()로 읽는다.
+// 실패하면 안쪽 핸들러를 부르지 않고 WWW-Authenticate 헤더와 함께 401로 응답한다.
+//
+// let admin = Realm::new("Admin").basic(Htpasswd::load("admin.htpasswd")?).bearer("deploy", token);
+// let handler = Auth::new(WebsiteHandler::new(root)).protect("/admin", admin);
+use crate::http::path::{has_prefix, normalize};
+use crate::http::{ParseError, Request, Response, StatusCode};
+use crate::server::Handler;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ring::digest::{digest, SHA256};
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+pub use htpasswd::Htpasswd;
+
+mod htpasswd;
+
+// 확인한 Basic 인증 정보를 이만큼까지 기억한다. 넘치면 모두 잊고 다시 채운다.
+const VERIFIED_CACHE_SIZE: usize = 1024;
+
+// 같은 인증 정보로 보호하는 영역 하나. 브라우저는 realm 이름이 같은 영역에 같은 비밀번호를 다시 보낸다.
+#[derive(Debug)]
+pub struct Realm {
+    name: String,
+    htpasswd: Option<Htpasswd>,
+    // (토큰 이름, 토큰의 SHA-256). 토큰 자체는 메모리에 두지 않는다.
+    tokens: Vec<(String, Vec<u8>)>,
+    // 이미 확인한 "사용자:비밀번호"의 SHA-256. bcrypt 같은 느린 해시를 페이지의 파일마다 다시 계산하지 않게 한다.
+    verified: Mutex<HashSet<Vec<u8>>>,
+}
+
+impl Realm {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            htpasswd: None,
+            tokens: Vec::new(),
+            verified: Mutex::new(HashSet::new()),
+        }
+    }
+
+    // Basic 인증을 받는다.
+    pub fn basic(mut self, htpasswd: Htpasswd) -> Self {
+        self.htpasswd = Some(htpasswd);
+        self
+    }
+
+    // 이 토큰을 Bearer로 보내면 name으로 인증된다. 여러 번 불러서 토큰을 여러 개 둘 수 있다.
+    pub fn bearer(mut self, name: impl Into<String>, token: &str) -> Self {
+        self.tokens.push((name.into(), sha256(token)));
+        self
+    }
+
+    fn authenticate(&self, request: &Request) -> Result<String, Failure> {
+        let authorization = request
+            .header("Authorization")
+            .ok_or(Failure::Missing)?
+            .trim();
+        let (scheme, credentials) = authorization.split_once(' ').ok_or(Failure::Invalid)?;
+        let credentials = credentials.trim();
+
+        // 인증 방식 이름은 대소문자를 구분하지 않는다.
+        if scheme.eq_ignore_ascii_case("Basic") {
+            let htpasswd = self.htpasswd.as_ref().ok_or(Failure::Invalid)?;
+            let decoded = STANDARD.decode(credentials).map_err(|_| Failure::Invalid)?;
+            let decoded = String::from_utf8(decoded).map_err(|_| Failure::Invalid)?;
+            let (user, password) = decoded.split_once(':').ok_or(Failure::Invalid)?;
+
+            let key = sha256(&decoded);
+            if self.verified.lock().unwrap().contains(&key) {
+                return Ok(user.to_string());
+            }
+            if !htpasswd.verify(user, password) {
+                warn!("Authentication failed for user {}", user);
+                return Err(Failure::Invalid);
+            }
+            let mut verified = self.verified.lock().unwrap();
+            if verified.len() >= VERIFIED_CACHE_SIZE {
+                verified.clear();
+            }
+            verified.insert(key);
+            Ok(user.to_string())
+        } else if scheme.eq_ignore_ascii_case("Bearer") && !self.tokens.is_empty() {
+            // 해시끼리 비교하므로 비교에 걸리는 시간으로는 토큰을 알아낼 수 없다.
+            let hash = sha256(credentials);
+            match self.tokens.iter().find(|(_, token)| *token == hash) {
+                Some((name, _)) => Ok(name.clone()),
+                None => {
+                    warn!("Authentication failed for bearer token");
+                    Err(Failure::InvalidToken)
+                }
+            }
+        } else {
+            Err(Failure::Invalid)
+        }
+    }
+
+    // 받을 수 있는 인증 방식마다 WWW-Authenticate 헤더를 하나씩 붙인다.
+    fn challenge(&self, failure: Failure) -> Response {
+        let realm = quote(&self.name);
+        let mut response = Response::new(StatusCode::Unauthorized, None);
+        if self.htpasswd.is_some() {
+            response.append_header(
+                "WWW-Authenticate",
+                format!("Basic realm={}, charset=\"UTF-8\"", realm),
+            );
+        }
+        if !self.tokens.is_empty() {
+            let challenge = match failure {
+                Failure::InvalidToken => {
+                    format!("Bearer realm={}, error=\"invalid_token\"", realm)
+                }
+                _ => format!("Bearer realm={}", realm),
+            };
+            response.append_header("WWW-Authenticate", challenge);
+        }
+        response
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Failure {
+    // Authorization 헤더가 없다.
+    Missing,
+    // 모르는 방식이거나, 형식이 틀리거나, 비밀번호가 틀렸다.
+    Invalid,
+    InvalidToken,
+}
+
+pub struct Auth<H> {
+    inner: H,
+    // (경로 접두사, 영역). 여러 접두사가 맞으면 가장 긴 것을 쓴다.
+    protected: Vec<(String, Realm)>,
+}
+
+impl<H: Handler> Auth<H> {
+    pub fn new(inner: H) -> Self {
+        Self {
+            inner,
+            protected: Vec::new(),
+        }
+    }
+
+    // prefix와 그 아래의 경로에 인증을 요구한다.
+    pub fn protect(mut self, prefix: &str, realm: Realm) -> Self {
+        self.protected.push((normalize(prefix), realm));
+        self
+    }
+
+    fn realm(&self, path: &str) -> Option<&Realm> {
+        let path = normalize(path);
+        self.protected
+            .iter()
+            .filter(|(prefix, _)| has_prefix(&path, prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, realm)| realm)
+    }
+}
+
+impl<H: Handler> Handler for Auth<H> {
+    fn handle_request(&self, request: &Request) -> Response {
+        let realm = match self.realm(request.path()) {
+            Some(realm) => realm,
+            None => return self.inner.handle_request(request),
+        };
+        match realm.authenticate(request) {
+            Ok(principal) => self
+                .inner
+                .handle_request(&request.with_principal(principal)),
+            Err(failure) => realm.challenge(failure),
+        }
+    }
+
+    fn handle_bad_request(&self, e: &ParseError) -> Response {
+        self.inner.handle_bad_request(e)
+    }
+
+    fn accept_upgrade<'buf>(&self, request: &Request<'buf>) -> Result<Request<'buf>, Response> {
+        let realm = match self.realm(request.path()) {
+            Some(realm) => realm,
+            None => return self.inner.accept_upgrade(request),
+        };
+        match realm.authenticate(request) {
+            Ok(principal) => self
+                .inner
+                .accept_upgrade(&request.with_principal(principal)),
+            Err(failure) => Err(realm.challenge(failure)),
+        }
+    }
+}
+
+fn sha256(s: &str) -> Vec<u8> {
+    digest(&SHA256, s.as_bytes()).as_ref().to_vec()
+}
+
+// realm="..." 처럼 따옴표로 감싼다. 안에 있는 \와 "는 앞에 \를 붙인다.
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    // 인증된 사용자를 본문으로 돌려준다.
+    struct Whoami;
+
+    impl Handler for Whoami {
+        fn handle_request(&self, request: &Request) -> Response {
+            let principal = request.principal().unwrap_or("anonymous");
+            Response::new(StatusCode::Ok, Some(principal.to_string()))
+        }
+    }
+
+    fn auth() -> Auth<Whoami> {
+        let htpasswd = format!("alice:{}", bcrypt::hash("wonderland", 4).unwrap());
+        let admin = Realm::new("Admin")
+            .basic(Htpasswd::parse(&htpasswd).unwrap())
+            .bearer("deploy", "s3cret-token");
+        let docs = Realm::new("Docs \"internal\"").bearer("reader", "docs-token");
+        Auth::new(Whoami)
+            .protect("/admin", admin)
+            .protect("/admin/docs", docs)
+    }
+
+    fn get(auth: &Auth<Whoami>, target: &str, authorization: Option<&str>) -> Response {
+        let authorization = authorization
+            .map(|value| format!("Authorization: {}\r\n", value))
+            .unwrap_or_default();
+        let raw = format!(
+            "GET {} HTTP/1.1\r\nHost: localhost\r\n{}\r\n",
+            target, authorization
+        );
+        auth.handle_request(&Request::try_from(raw.as_bytes()).unwrap())
+    }
+
+    fn basic(user: &str, password: &str) -> String {
+        format!(
+            "Basic {}",
+            STANDARD.encode(format!("{}:{}", user, password))
+        )
+    }
+
+    fn challenges(response: &Response) -> Vec<&str> {
+        response
+            .headers()
+            .filter(|(name, _)| name.eq_ignore_ascii_case("WWW-Authenticate"))
+            .map(|(_, value)| value)
+            .collect()
+    }
+
+    fn body(response: &Response) -> &str {
+        std::str::from_utf8(response.body().unwrap_or_default()).unwrap()
+    }
+
+    #[test]
+    fn challenges_requests_without_credentials() {
+        let auth = auth();
+        let response = get(&auth, "/admin/users", None);
+        assert_eq!(response.status_code(), StatusCode::Unauthorized);
+        assert_eq!(
+            challenges(&response),
+            [
+                "Basic realm=\"Admin\", charset=\"UTF-8\"",
+                "Bearer realm=\"Admin\""
+            ]
+        );
+
+        for authorization in [
+            basic("alice", "wrong"),
+            basic("mallory", "wonderland"),
+            "Basic not-base64!".to_string(),
+            "Digest username=\"alice\"".to_string(),
+        ] {
+            let response = get(&auth, "/admin", Some(&authorization));
+            assert_eq!(response.status_code(), StatusCode::Unauthorized);
+        }
+
+        let response = get(&auth, "/admin", Some("Bearer wrong"));
+        assert_eq!(
+            challenges(&response)[1],
+            "Bearer realm=\"Admin\", error=\"invalid_token\""
+        );
+    }
+
+    #[test]
+    fn passes_the_principal_to_the_inner_handler() {
+        let auth = auth();
+        let response = get(&auth, "/admin", Some(&basic("alice", "wonderland")));
+        assert_eq!(response.status_code(), StatusCode::Ok);
+        assert_eq!(body(&response), "alice");
+        // 캐시에서 찾은 경우에도 같다.
+        let response = get(&auth, "/admin", Some(&basic("alice", "wonderland")));
+        assert_eq!(body(&response), "alice");
+
+        let response = get(&auth, "/admin", Some("bearer s3cret-token"));
+        assert_eq!(body(&response), "deploy");
+        assert_eq!(body(&get(&auth, "/public", None)), "anonymous");
+    }
+
+    #[test]
+    fn uses_the_longest_matching_prefix() {
+        let auth = auth();
+        let response = get(&auth, "/admin/docs/guide", None);
+        assert_eq!(
+            challenges(&response),
+            ["Bearer realm=\"Docs \\\"internal\\\"\""]
+        );
+        // 더 긴 접두사의 영역만 쓰므로 바깥 영역의 인증 정보로는 들어갈 수 없다.
+        let response = get(&auth, "/admin/docs", Some("Bearer s3cret-token"));
+        assert_eq!(response.status_code(), StatusCode::Unauthorized);
+        let response = get(&auth, "/admin/docs", Some("Bearer docs-token"));
+        assert_eq!(body(&response), "reader");
+
+        // 접두사는 경로 부분 단위로 비교한다.
+        assert_eq!(body(&get(&auth, "/administrator", None)), "anonymous");
+        let response = get(&auth, "/admin/docsx", Some("Bearer s3cret-token"));
+        assert_eq!(body(&response), "deploy");
+    }
+
+    #[test]
+    fn disguised_paths_stay_protected() {
+        let auth = auth();
+        for target in [
+            "/x/../admin",
+            "/%61dmin",
+            "/admin/",
+            "//admin",
+            "/./admin/./users",
+            "/admin%2fusers",
+            "/admin/%64ocs/../docs",
+        ] {
+            let response = get(&auth, target, None);
+            assert_eq!(
+                response.status_code(),
+                StatusCode::Unauthorized,
+                "{}",
+                target
+            );
+        }
+    }
+
+    #[test]
+    fn upgrades_are_authenticated_too() {
+        let auth = auth();
+        let raw = "GET /admin/ws HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let request = Request::try_from(raw.as_bytes()).unwrap();
+        let response = auth.accept_upgrade(&request).unwrap_err();
+        assert_eq!(response.status_code(), StatusCode::Unauthorized);
+
+        let raw = "GET /admin/ws HTTP/1.1\r\nAuthorization: Bearer s3cret-token\r\n\r\n";
+        let request = Request::try_from(raw.as_bytes()).unwrap();
+        let request = auth.accept_upgrade(&request).unwrap();
+        assert_eq!(request.principal(), Some("deploy"));
+    }
+}
@@ -0,0 +1,338 @@
+use crate::log::Level;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use std::net::Ipv6Addr;
+
+pub const USAGE: &str = "\
+Usage: http_server [OPTIONS]
+
+Options:
+  -b, --bind <ADDR>     Address to listen on            [env: HTTP_BIND]    [default: 127.0.0.1]
+  -p, --port <PORT>     Port to listen on               [env: HTTP_PORT]    [default: 4000]
+  -r, --root <DIR>      Directory to serve files from   [env: PUBLIC_PATH]  [default: bundled public/]
+  -t, --threads <N>     Number of worker threads        [env: HTTP_THREADS] [default: CPU count]
+  -l, --log <LEVEL>     off, error, warn, info, debug   [env: HTTP_LOG]     [default: info]
+  -c, --config <FILE>   Read settings from a TOML file  [env: HTTP_CONFIG]
+  -h, --help            Print this help and exit
+  -V, --version         Print the version and exit
+
+Options given on the command line or in the environment override the config file.
+--bind and --port replace every listener in the config file with a single one.";
+
+// 명령행 인자를 해석한 결과. 도움말이나 버전만 출력하고 끝내야 하는 경우도 있다.
+#[derive(Debug)]
+pub enum Command {
+    Run(Options),
+    Help,
+    Version,
+}
+
+// 우선순위는 명령행 인자 > 환경 변수 > 설정 파일 > 기본값 순서다.
+// 명령행과 환경 변수 어디에도 없는 값은 None으로 남겨서 설정 파일의 값을 덮어쓰지 않게 한다.
+#[derive(Debug)]
+pub struct Options {
+    pub bind: Option<String>,
+    pub port: Option<u16>,
+    pub root: Option<String>,
+    pub threads: Option<usize>,
+    pub log: Option<Level>,
+    pub config: Option<String>,
+}
+
+impl Options {
+    // --bind나 --port 중 하나라도 주어졌다면 리스너 주소를 만든다. 빠진 쪽은 기본값을 쓴다.
+    // IPv6 주소는 포트와 구분하기 위해 대괄호로 감싸야 한다. ([::1]:4000)
+    pub fn addr(&self) -> Option<String> {
+        if self.bind.is_none() && self.port.is_none() {
+            return None;
+        }
+
+        let bind = self.bind.as_deref().unwrap_or("127.0.0.1");
+        let port = self.port.unwrap_or(4000);
+        if bind.parse::<Ipv6Addr>().is_ok() {
+            Some(format!("[{}]:{}", bind, port))
+        } else {
+            Some(format!("{}:{}", bind, port))
+        }
+    }
+}
+
+pub enum CliError {
+    UnknownOption(String),
+    MissingValue(String),
+    InvalidValue { option: String, message: String },
+}
+
+impl CliError {
+    fn invalid(option: &str, message: impl Into<String>) -> Self {
+        Self::InvalidValue {
+            option: option.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for CliError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::UnknownOption(option) => write!(f, "unknown option {}", option),
+            Self::MissingValue(option) => write!(f, "{} requires a value", option),
+            Self::InvalidValue { option, message } => {
+                write!(f, "invalid value for {}: {}", option, message)
+            }
+        }
+    }
+}
+
+impl Debug for CliError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}", self)
+    }
+}
+
+impl Error for CliError {}
+
+// 인자와 환경 변수를 파라미터로 받기 때문에 실제 프로세스 환경과 상관없이 해석할 수 있다.
+// args에는 프로그램 이름을 제외한 인자만 넣어야 한다.
+pub fn parse(
+    args: impl IntoIterator<Item = String>,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<Command, CliError> {
+    let mut bind = None;
+    let mut port = None;
+    let mut root = None;
+    let mut threads = None;
+    let mut log = None;
+    let mut config = None;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        // --port=4000 처럼 = 로 값을 붙여 쓴 경우와 --port 4000 처럼 띄어 쓴 경우를 모두 받는다.
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => {
+                (name.to_string(), Some(value.to_string()))
+            }
+            _ => (arg, None),
+        };
+
+        match name.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-V" | "--version" => return Ok(Command::Version),
+            "-b" | "--bind" | "-p" | "--port" | "-r" | "--root" | "-t" | "--threads" | "-l"
+            | "--log" | "-c" | "--config" => {
+                let value = match inline_value.or_else(|| args.next()) {
+                    Some(value) => value,
+                    None => return Err(CliError::MissingValue(name)),
+                };
+                match name.as_str() {
+                    "-b" | "--bind" => bind = Some(value),
+                    "-p" | "--port" => port = Some(parse_port(&name, &value)?),
+                    "-r" | "--root" => root = Some(value),
+                    "-t" | "--threads" => threads = Some(parse_threads(&name, &value)?),
+                    "-l" | "--log" => log = Some(parse_level(&name, &value)?),
+                    _ => config = Some(value),
+                }
+            }
+            _ => return Err(CliError::UnknownOption(name)),
+        }
+    }
+
+    let port = match port {
+        Some(port) => Some(port),
+        None => env("HTTP_PORT")
+            .map(|value| parse_port("HTTP_PORT", &value))
+            .transpose()?,
+    };
+    let threads = match threads {
+        Some(threads) => Some(threads),
+        None => env("HTTP_THREADS")
+            .map(|value| parse_threads("HTTP_THREADS", &value))
+            .transpose()?,
+    };
+    let log = match log {
+        Some(log) => Some(log),
+        None => env("HTTP_LOG")
+            .map(|value| parse_level("HTTP_LOG", &value))
+            .transpose()?,
+    };
+
+    Ok(Command::Run(Options {
+        bind: bind.or_else(|| env("HTTP_BIND")),
+        port,
+        root: root.or_else(|| env("PUBLIC_PATH")),
+        threads,
+        log,
+        config: config.or_else(|| env("HTTP_CONFIG")),
+    }))
+}
+
+fn parse_port(option: &str, value: &str) -> Result<u16, CliError> {
+    value
+        .parse()
+        .map_err(|_| CliError::invalid(option, format!("{} is not a port number", value)))
+}
+
+fn parse_threads(option: &str, value: &str) -> Result<usize, CliError> {
+    match value.parse::<usize>() {
+        Ok(threads) if threads > 0 => Ok(threads),
+        _ => Err(CliError::invalid(
+            option,
+            format!("{} is not a positive number", value),
+        )),
+    }
+}
+
+fn parse_level(option: &str, value: &str) -> Result<Level, CliError> {
+    value.parse().map_err(|e| CliError::invalid(option, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn run(args: &[&str], env: &[(&str, &str)]) -> Result<Options, CliError> {
+        let env: HashMap<String, String> = env
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        match parse(args.iter().map(|arg| arg.to_string()), |name| {
+            env.get(name).cloned()
+        })? {
+            Command::Run(options) => Ok(options),
+            command => panic!("expected Run, got {:?}", command),
+        }
+    }
+
+    #[test]
+    fn short_long_and_inline_values() {
+        let options = run(
+            &[
+                "-b",
+                "0.0.0.0",
+                "--port=8080",
+                "--root",
+                "site",
+                "-t",
+                "3",
+                "--log=debug",
+                "-c",
+                "a.toml",
+            ],
+            &[],
+        )
+        .unwrap();
+        assert_eq!(options.bind.as_deref(), Some("0.0.0.0"));
+        assert_eq!(options.port, Some(8080));
+        assert_eq!(options.root.as_deref(), Some("site"));
+        assert_eq!(options.threads, Some(3));
+        assert_eq!(options.log, Some(Level::Debug));
+        assert_eq!(options.config.as_deref(), Some("a.toml"));
+
+        // 값에 =가 들어 있어도 첫 번째 =까지만 이름이다.
+        let options = run(&["--root=a=b"], &[]).unwrap();
+        assert_eq!(options.root.as_deref(), Some("a=b"));
+        // 나중에 준 값이 이긴다.
+        let options = run(&["-p", "1", "-p", "2"], &[]).unwrap();
+        assert_eq!(options.port, Some(2));
+    }
+
+    #[test]
+    fn nothing_given_leaves_everything_unset() {
+        let options = run(&[], &[]).unwrap();
+        assert!(options.bind.is_none() && options.port.is_none() && options.root.is_none());
+        assert!(options.threads.is_none() && options.log.is_none() && options.config.is_none());
+        assert_eq!(options.addr(), None);
+    }
+
+    #[test]
+    fn help_and_version_stop_parsing() {
+        let parse = |args: &[&str]| parse(args.iter().map(|arg| arg.to_string()), |_| None);
+        assert!(matches!(parse(&["-h"]), Ok(Command::Help)));
+        assert!(matches!(
+            parse(&["--version", "--bogus"]),
+            Ok(Command::Version)
+        ));
+        assert!(matches!(parse(&["-p", "80", "--help"]), Ok(Command::Help)));
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        let error = |args: &[&str]| run(args, &[]).unwrap_err().to_string();
+        assert_eq!(error(&["--bogus"]), "unknown option --bogus");
+        assert_eq!(error(&["-x=1"]), "unknown option -x=1");
+        assert_eq!(error(&["--port"]), "--port requires a value");
+        assert_eq!(
+            error(&["-p", "70000"]),
+            "invalid value for -p: 70000 is not a port number"
+        );
+        assert_eq!(
+            error(&["--threads=0"]),
+            "invalid value for --threads: 0 is not a positive number"
+        );
+        assert!(error(&["--log", "loud"]).starts_with("invalid value for --log: "));
+    }
+
+    #[test]
+    fn command_line_overrides_environment() {
+        let env = [
+            ("HTTP_BIND", "10.0.0.1"),
+            ("HTTP_PORT", "5000"),
+            ("PUBLIC_PATH", "env-root"),
+            ("HTTP_THREADS", "7"),
+            ("HTTP_LOG", "warn"),
+            ("HTTP_CONFIG", "env.toml"),
+        ];
+        let options = run(&[], &env).unwrap();
+        assert_eq!(options.bind.as_deref(), Some("10.0.0.1"));
+        assert_eq!(options.port, Some(5000));
+        assert_eq!(options.root.as_deref(), Some("env-root"));
+        assert_eq!(options.threads, Some(7));
+        assert_eq!(options.log, Some(Level::Warn));
+        assert_eq!(options.config.as_deref(), Some("env.toml"));
+
+        let options = run(
+            &["-p", "6000", "-t", "2", "-l", "error", "-r", "cli-root"],
+            &env,
+        )
+        .unwrap();
+        assert_eq!(options.port, Some(6000));
+        assert_eq!(options.threads, Some(2));
+        assert_eq!(options.log, Some(Level::Error));
+        assert_eq!(options.root.as_deref(), Some("cli-root"));
+        // 명령행에 없는 값은 환경 변수에서 가져온다.
+        assert_eq!(options.bind.as_deref(), Some("10.0.0.1"));
+        assert_eq!(options.config.as_deref(), Some("env.toml"));
+    }
+
+    #[test]
+    fn invalid_environment_values_name_the_variable() {
+        let error = run(&[], &[("HTTP_PORT", "http")]).unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "invalid value for HTTP_PORT: http is not a port number"
+        );
+        // 명령행에 값이 있으면 환경 변수는 읽지 않는다.
+        assert_eq!(
+            run(&["-p", "80"], &[("HTTP_PORT", "http")]).unwrap().port,
+            Some(80)
+        );
+        assert!(run(&[], &[("HTTP_THREADS", "0")]).is_err());
+        assert!(run(&[], &[("HTTP_LOG", "loud")]).is_err());
+    }
+
+    #[test]
+    fn listener_address() {
+        let addr = |args: &[&str]| run(args, &[]).unwrap().addr();
+        assert_eq!(addr(&["-p", "8080"]).as_deref(), Some("127.0.0.1:8080"));
+        assert_eq!(addr(&["-b", "0.0.0.0"]).as_deref(), Some("0.0.0.0:4000"));
+        assert_eq!(
+            addr(&["-b", "::1", "-p", "80"]).as_deref(),
+            Some("[::1]:80")
+        );
+        assert_eq!(
+            addr(&["-b", "localhost"]).as_deref(),
+            Some("localhost:4000")
+        );
+    }
+}
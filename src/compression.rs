@@ -0,0 +1,504 @@
+use crate::http::{ParseError, Request, Response};
+use crate::server::Handler;
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression as Level;
+use std::io::{Result as IoResult, Write};
+
+// 이보다 작은 본문은 압축해도 헤더와 압축 형식의 오버헤드 때문에 얻는 게 거의 없다.
+const DEFAULT_MIN_SIZE: usize = 1024;
+// 요청마다 압축하므로 압축률보다 속도를 우선한 값을 쓴다. 미리 압축해 둔 .br 파일은 최고 품질로 만들면 된다.
+const BROTLI_QUALITY: u32 = 5;
+const BROTLI_WINDOW: u32 = 22;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Brotli,
+    Gzip,
+    // HTTP의 deflate는 이름과 달리 zlib 형식이다. (RFC 9110 8.4.1.2)
+    Deflate,
+}
+
+impl Encoding {
+    pub fn token(self) -> &'static str {
+        match self {
+            Self::Brotli => "br",
+            Self::Gzip => "gzip",
+            Self::Deflate => "deflate",
+        }
+    }
+
+    // 미리 압축해 둔 파일의 확장자. deflate로 압축한 파일은 흔하지 않아서 찾지 않는다.
+    pub fn extension(self) -> Option<&'static str> {
+        match self {
+            Self::Brotli => Some("br"),
+            Self::Gzip => Some("gz"),
+            Self::Deflate => None,
+        }
+    }
+
+    fn from_token(token: &str) -> Option<Self> {
+        match token.to_ascii_lowercase().as_str() {
+            "br" => Some(Self::Brotli),
+            "gzip" => Some(Self::Gzip),
+            "deflate" => Some(Self::Deflate),
+            _ => None,
+        }
+    }
+
+    fn compress(self, body: &[u8]) -> IoResult<Vec<u8>> {
+        match self {
+            Self::Brotli => {
+                let mut encoder =
+                    brotli::CompressorWriter::new(Vec::new(), 4096, BROTLI_QUALITY, BROTLI_WINDOW);
+                encoder.write_all(body)?;
+                encoder.flush()?;
+                Ok(encoder.into_inner())
+            }
+            Self::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Level::default());
+                encoder.write_all(body)?;
+                encoder.finish()
+            }
+            Self::Deflate => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Level::default());
+                encoder.write_all(body)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_token(s)
+            .ok_or_else(|| format!("unknown encoding {}, expected br, gzip or deflate", s))
+    }
+}
+
+// Accept-Encoding을 보고 available 중에서 보낼 인코딩을 고른다. q값이 같으면 available에서 앞에 있는 것을 고른다.
+// 압축하지 않고 보내는 게 낫다면(identity의 q값이 더 높거나 받을 수 있는 인코딩이 없다면) None을 리턴한다.
+// 헤더가 없으면 무엇이든 받는다는 뜻이지만, 압축을 모르는 오래된 클라이언트일 수 있어서 압축하지 않는다.
+//
+// "gzip;q=0.8, br" -> br, "gzip, *;q=0" -> gzip, "br;q=0, identity" -> None
+pub fn negotiate(request: &Request, available: &[Encoding]) -> Option<Encoding> {
+    request.header("Accept-Encoding")?;
+    // identity를 맨 뒤에 두어서 q값이 같으면 압축하는 쪽을 고르게 한다.
+    let mut tokens: Vec<&str> = available.iter().map(|encoding| encoding.token()).collect();
+    tokens.push("identity");
+    let chosen = request.negotiate_encoding(&tokens)?;
+    available
+        .iter()
+        .copied()
+        .find(|encoding| encoding.token() == chosen)
+}
+
+// Vary에 이미 같은 헤더 이름이 있으면 그대로 두고, 없으면 뒤에 덧붙인다.
+pub(crate) fn add_vary(response: &mut Response, name: &str) {
+    let vary = match response.header("Vary") {
+        Some(vary) => {
+            if vary
+                .split(',')
+                .any(|item| item.trim() == "*" || item.trim().eq_ignore_ascii_case(name))
+            {
+                return;
+            }
+            format!("{}, {}", vary, name)
+        }
+        None => name.to_string(),
+    };
+    response.set_header("Vary", vary);
+}
+
+// 텍스트처럼 압축이 잘 되는 형식만 압축한다. 이미지, 동영상, woff2, zip 같은 형식은 이미 압축되어 있어서
+// 다시 압축해도 크기가 거의 줄지 않고 CPU만 쓴다.
+pub fn is_compressible(content_type: &str) -> bool {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+    mime.starts_with("text/")
+        || mime.ends_with("+json")
+        || mime.ends_with("+xml")
+        || matches!(
+            mime.as_str(),
+            "application/json"
+                | "application/javascript"
+                | "application/x-javascript"
+                | "application/xml"
+                | "application/wasm"
+                | "image/svg+xml"
+                | "image/x-icon"
+                | "image/bmp"
+                | "font/ttf"
+                | "font/otf"
+        )
+}
+
+// 다른 핸들러가 만든 응답 본문을 클라이언트가 받을 수 있는 형식으로 압축한다.
+//
+// let handler = Compression::new(WebsiteHandler::new(root)).min_size(512);
+pub struct Compression<H> {
+    inner: H,
+    min_size: usize,
+    // 선호하는 순서. q값이 같으면 앞에 있는 것을 고른다.
+    encodings: Vec<Encoding>,
+}
+
+impl<H: Handler> Compression<H> {
+    pub fn new(inner: H) -> Self {
+        Self {
+            inner,
+            min_size: DEFAULT_MIN_SIZE,
+            encodings: vec![Encoding::Brotli, Encoding::Gzip, Encoding::Deflate],
+        }
+    }
+
+    // 본문이 이 크기보다 작으면 압축하지 않는다.
+    pub fn min_size(mut self, min_size: usize) -> Self {
+        self.min_size = min_size;
+        self
+    }
+
+    pub fn encodings(mut self, encodings: Vec<Encoding>) -> Self {
+        self.encodings = encodings;
+        self
+    }
+
+    fn compress(&self, request: &Request, response: &mut Response) {
+        // 스트리밍 응답은 본문을 미리 알 수 없고, 이미 인코딩된 본문이나 부분 응답은 건드리면 안 된다.
+        if response.is_stream()
+            || response.header("Content-Encoding").is_some()
+            || response.header("Content-Range").is_some()
+        {
+            return;
+        }
+        let compressible = response.header("Content-Type").is_some_and(is_compressible);
+        if !compressible {
+            return;
+        }
+        // 같은 주소라도 Accept-Encoding에 따라 본문이 달라진다는 걸 캐시에 알려야 한다.
+        // 크기 때문에 압축하지 않는 경우에도 같은 주소의 다른 응답은 압축될 수 있으므로 항상 붙인다.
+        add_vary(response, "Accept-Encoding");
+
+        let no_transform = response.header("Cache-Control").is_some_and(|value| {
+            value
+                .split(',')
+                .any(|directive| directive.trim().eq_ignore_ascii_case("no-transform"))
+        });
+        let body = match response.body() {
+            Some(body) if body.len() >= self.min_size && !no_transform => body,
+            _ => return,
+        };
+        let encoding = match negotiate(request, &self.encodings) {
+            Some(encoding) => encoding,
+            None => return,
+        };
+
+        match encoding.compress(body) {
+            // 압축했더니 오히려 커졌다면 원래 본문을 보낸다.
+            Ok(compressed) if compressed.len() < body.len() => {
+                response.set_body(compressed);
+                response.set_header("Content-Encoding", encoding.token());
+                // 압축한 본문은 원래 본문과 바이트가 다르므로 강한 ETag를 그대로 두면 안 된다. (RFC 9110 8.8.3)
+                // 약한 ETag로 바꾸면 If-None-Match로는 여전히 원래 본문과 같은 것으로 비교된다.
+                if let Some(etag) = response
+                    .header("ETag")
+                    .filter(|etag| !etag.starts_with("W/"))
+                {
+                    let weak = format!("W/{}", etag);
+                    response.set_header("ETag", weak);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => error!("Failed to compress response : {}", e),
+        }
+    }
+}
+
+impl<H: Handler> Handler for Compression<H> {
+    fn handle_request(&self, request: &Request) -> Response {
+        let mut response = self.inner.handle_request(request);
+        self.compress(request, &mut response);
+        response
+    }
+
+    fn handle_bad_request(&self, e: &ParseError) -> Response {
+        self.inner.handle_bad_request(e)
+    }
+
+    fn accept_upgrade<'buf>(&self, request: &Request<'buf>) -> Result<Request<'buf>, Response> {
+        self.inner.accept_upgrade(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::StatusCode;
+    use crate::website_handler::WebsiteHandler;
+    use flate2::read::{GzDecoder, ZlibDecoder};
+    use std::fs;
+    use std::io::Read;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // 만들어 둔 응답을 그대로 돌려준다.
+    struct Fixed(fn() -> Response);
+
+    impl Handler for Fixed {
+        fn handle_request(&self, _request: &Request) -> Response {
+            (self.0)()
+        }
+    }
+
+    fn text() -> Response {
+        Response::from_bytes(StatusCode::Ok, "hello world ".repeat(200).into_bytes())
+            .with_header("Content-Type", "text/plain; charset=utf-8")
+    }
+
+    fn request(accept_encoding: Option<&str>) -> String {
+        match accept_encoding {
+            Some(value) => format!("GET / HTTP/1.1\r\nAccept-Encoding: {}\r\n\r\n", value),
+            None => "GET / HTTP/1.1\r\n\r\n".to_string(),
+        }
+    }
+
+    fn get(handler: &impl Handler, accept_encoding: Option<&str>) -> Response {
+        let raw = request(accept_encoding);
+        handler.handle_request(&Request::try_from(raw.as_bytes()).unwrap())
+    }
+
+    fn decode(response: &Response) -> Vec<u8> {
+        let body = response.body().unwrap();
+        let mut out = Vec::new();
+        match response.header("Content-Encoding") {
+            Some("br") => brotli::Decompressor::new(body, 4096)
+                .read_to_end(&mut out)
+                .map(|_| ()),
+            Some("gzip") => GzDecoder::new(body).read_to_end(&mut out).map(|_| ()),
+            Some("deflate") => ZlibDecoder::new(body).read_to_end(&mut out).map(|_| ()),
+            _ => {
+                out.extend_from_slice(body);
+                Ok(())
+            }
+        }
+        .unwrap();
+        out
+    }
+
+    #[test]
+    fn chooses_by_q_value_then_preference() {
+        let all = [Encoding::Brotli, Encoding::Gzip, Encoding::Deflate];
+        for (accept_encoding, expected) in [
+            ("gzip, deflate, br", Some(Encoding::Brotli)),
+            ("gzip;q=0.8, br", Some(Encoding::Brotli)),
+            ("br;q=0.5, gzip", Some(Encoding::Gzip)),
+            (
+                "br;q=0, deflate;q=0.5, identity;q=0.1",
+                Some(Encoding::Deflate),
+            ),
+            // identity는 적지 않아도 받을 수 있으므로 q값이 더 높다.
+            ("br;q=0, gzip;q=0, deflate;q=0.1", None),
+            ("gzip, *;q=0", Some(Encoding::Gzip)),
+            ("*", Some(Encoding::Brotli)),
+            ("identity;q=1, gzip;q=0.5", None),
+            ("br;q=0, identity", None),
+            ("zstd", None),
+        ] {
+            let raw = request(Some(accept_encoding));
+            let request = Request::try_from(raw.as_bytes()).unwrap();
+            assert_eq!(negotiate(&request, &all), expected, "{}", accept_encoding);
+        }
+        // 헤더가 없으면 압축하지 않는다.
+        let raw = request(None);
+        assert_eq!(
+            negotiate(&Request::try_from(raw.as_bytes()).unwrap(), &all),
+            None
+        );
+    }
+
+    #[test]
+    fn compressed_bodies_decode_to_the_original() {
+        let handler = Compression::new(Fixed(text));
+        for (accept_encoding, token) in [("br", "br"), ("gzip", "gzip"), ("deflate", "deflate")] {
+            let response = get(&handler, Some(accept_encoding));
+            assert_eq!(response.header("Content-Encoding"), Some(token));
+            assert_eq!(response.header("Vary"), Some("Accept-Encoding"));
+            assert!(response.body().unwrap().len() < text().body().unwrap().len());
+            assert_eq!(decode(&response), text().body().unwrap());
+        }
+        let response = get(&handler, None);
+        assert_eq!(response.header("Content-Encoding"), None);
+        assert_eq!(response.header("Vary"), Some("Accept-Encoding"));
+    }
+
+    #[test]
+    fn min_size_threshold() {
+        fn sized() -> Response {
+            Response::from_bytes(StatusCode::Ok, vec![b'a'; 1024])
+                .with_header("Content-Type", "text/plain")
+        }
+        let response = get(&Compression::new(Fixed(sized)), Some("gzip"));
+        assert_eq!(response.header("Content-Encoding"), Some("gzip"));
+        let response = get(&Compression::new(Fixed(sized)).min_size(1025), Some("gzip"));
+        assert_eq!(response.header("Content-Encoding"), None);
+        assert_eq!(response.body().unwrap().len(), 1024);
+        // 작아서 압축하지 않아도 다른 크기의 응답은 압축될 수 있으므로 Vary는 붙인다.
+        assert_eq!(response.header("Vary"), Some("Accept-Encoding"));
+    }
+
+    #[test]
+    fn skips_incompressible_types_and_no_transform() {
+        fn png() -> Response {
+            text().with_header("Content-Type", "image/png")
+        }
+        fn untyped() -> Response {
+            let mut response = text();
+            response.remove_header("Content-Type");
+            response
+        }
+        fn no_transform() -> Response {
+            text().with_header("Cache-Control", "public, No-Transform")
+        }
+        fn encoded() -> Response {
+            text().with_header("Content-Encoding", "gzip")
+        }
+        for inner in [png, untyped, encoded] {
+            let response = get(&Compression::new(Fixed(inner)), Some("gzip"));
+            assert_eq!(response.body(), text().body());
+            assert_eq!(response.header("Vary"), None);
+        }
+        let response = get(&Compression::new(Fixed(no_transform)), Some("gzip"));
+        assert_eq!(response.header("Content-Encoding"), None);
+        assert_eq!(response.body(), text().body());
+
+        assert!(is_compressible("application/problem+json"));
+        assert!(is_compressible("image/svg+xml; charset=utf-8"));
+        assert!(!is_compressible("font/woff2"));
+        assert!(!is_compressible("application/zip"));
+    }
+
+    #[test]
+    fn merges_vary() {
+        fn origin() -> Response {
+            text().with_header("Vary", "Origin")
+        }
+        fn already() -> Response {
+            text().with_header("Vary", "origin, accept-encoding")
+        }
+        fn star() -> Response {
+            text().with_header("Vary", "*")
+        }
+        for (inner, expected) in [
+            (origin as fn() -> Response, "Origin, Accept-Encoding"),
+            (already, "origin, accept-encoding"),
+            (star, "*"),
+        ] {
+            let response = get(&Compression::new(Fixed(inner)), Some("gzip"));
+            assert_eq!(response.header("Vary"), Some(expected));
+        }
+    }
+
+    #[test]
+    fn weakens_etag_of_compressed_bodies() {
+        fn strong() -> Response {
+            text().with_header("ETag", "\"v1\"")
+        }
+        fn weak() -> Response {
+            text().with_header("ETag", "W/\"v1\"")
+        }
+        let handler = Compression::new(Fixed(strong));
+        assert_eq!(get(&handler, Some("gzip")).header("ETag"), Some("W/\"v1\""));
+        // 압축하지 않은 본문은 원래 ETag를 그대로 쓴다.
+        assert_eq!(get(&handler, None).header("ETag"), Some("\"v1\""));
+        let handler = Compression::new(Fixed(weak));
+        assert_eq!(get(&handler, Some("gzip")).header("ETag"), Some("W/\"v1\""));
+    }
+
+    // 테스트마다 새로 만드는 디렉터리. 값이 사라질 때 지운다.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            static NEXT: AtomicUsize = AtomicUsize::new(0);
+            let path = std::env::temp_dir().join(format!(
+                "http_server-compression-{}-{}",
+                std::process::id(),
+                NEXT.fetch_add(1, Ordering::Relaxed)
+            ));
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn serves_precompressed_files() {
+        let dir = TempDir::new();
+        let css = "body { margin: 0; }\n".repeat(50);
+        fs::write(dir.0.join("style.css"), &css).unwrap();
+        fs::write(dir.0.join("style.css.br"), "brotli bytes").unwrap();
+        fs::write(dir.0.join("style.css.gz"), "gzip bytes").unwrap();
+        fs::write(dir.0.join("app.js"), "let a;").unwrap();
+        fs::write(dir.0.join("app.js.gz"), "gzip js").unwrap();
+        let website = WebsiteHandler::new(dir.0.to_string_lossy().into_owned());
+        // 이미 Content-Encoding이 있으므로 Compression이 다시 압축하지 않는다.
+        let handler = Compression::new(website).min_size(0);
+
+        let get = |path: &str, accept_encoding: Option<&str>| {
+            let raw = match accept_encoding {
+                Some(value) => format!(
+                    "GET {} HTTP/1.1\r\nAccept-Encoding: {}\r\n\r\n",
+                    path, value
+                ),
+                None => format!("GET {} HTTP/1.1\r\n\r\n", path),
+            };
+            handler.handle_request(&Request::try_from(raw.as_bytes()).unwrap())
+        };
+        for (path, accept_encoding, encoding, body) in [
+            ("/style.css", Some("gzip, br"), Some("br"), "brotli bytes"),
+            (
+                "/style.css",
+                Some("br;q=0.5, gzip"),
+                Some("gzip"),
+                "gzip bytes",
+            ),
+            ("/style.css", None, None, css.as_str()),
+            ("/style.css", Some("deflate"), Some("deflate"), ""),
+            ("/app.js", Some("br, gzip"), Some("gzip"), "gzip js"),
+        ] {
+            let response = get(path, accept_encoding);
+            assert_eq!(response.status_code(), StatusCode::Ok);
+            assert_eq!(
+                response.header("Content-Encoding"),
+                encoding,
+                "{:?}",
+                accept_encoding
+            );
+            assert_eq!(response.header("Vary"), Some("Accept-Encoding"));
+            assert_eq!(
+                response.header("Content-Type"),
+                Some(if path == "/app.js" {
+                    "text/javascript; charset=utf-8"
+                } else {
+                    "text/css; charset=utf-8"
+                })
+            );
+            if encoding != Some("deflate") {
+                assert_eq!(response.body(), Some(body.as_bytes()));
+            } else {
+                // 미리 압축한 deflate 파일은 없으므로 원래 파일을 Compression이 압축한다.
+                assert_eq!(decode(&response), css.as_bytes());
+            }
+        }
+    }
+}
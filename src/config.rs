@@ -0,0 +1,1472 @@
+use crate::auth::{Auth, Htpasswd, Realm};
+use crate::compression::{Compression, Encoding};
+use crate::cors::{Cors, Origin};
+use crate::http::StatusCode;
+use crate::https_redirect::HttpsRedirect;
+use crate::log::Level;
+use crate::proxy::{Balance, HashKey, HealthCheck, ProxyHandler, Upstream};
+use crate::rate_limit::{Limit, RateLimit};
+use crate::server::{Handler, Server, ServerError, TlsConfig};
+use crate::virtual_hosts::VirtualHosts;
+use crate::website_handler::{Redirect, WebsiteHandler};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+use toml::{Table, Value};
+
+// 설정할 수 있는 가장 긴 시간. 타임아웃은 Instant::now()에 더해서 쓰므로 너무 크면 넘쳐서 패닉이 난다.
+const MAX_DURATION: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+// 설정 파일을 읽어서 만든 타입이 있는 설정. 이걸로 Server와 WebsiteHandler를 만든다.
+//
+// [server]
+// threads = 4
+// grace_period = "30s"
+// http2 = true
+//
+// [[listeners]]
+// bind = "127.0.0.1:4000"
+//
+// [[listeners]]
+// bind = "127.0.0.1:4443"
+//
+// [listeners.tls]
+// cert = "certs/site.pem"
+// key = "certs/site.key"
+//
+// [[listeners.tls.sni]]
+// names = ["blog.example.test"]
+// cert = "certs/blog.pem"
+// key = "certs/blog.key"
+//
+// [[listeners]]
+// bind = "127.0.0.1:4080"
+// https_redirect = { port = 4443, well_known = "acme" }
+//
+// [site]
+// root = "public"
+// index = "index.html"
+//
+// [[hosts]]
+// names = ["blog.example.test", "*.blog.example.test"]
+// root = "sites/blog"
+// default = true
+//
+// [mime]
+// wasm = "application/wasm"
+//
+// [headers]
+// X-Content-Type-Options = "nosniff"
+//
+// [[auth]]
+// path = "/admin"
+// realm = "Admin"
+// htpasswd = "admin.htpasswd"
+// tokens = { deploy = "token-from-ci" }
+//
+// [cors]
+// origins = ["https://app.example.test", "https://*.example.test"]
+// methods = ["GET", "POST", "PUT"]
+// headers = ["Content-Type", "Authorization"]
+// expose_headers = ["ETag"]
+// credentials = true
+// max_age = "10m"
+//
+// [[proxies]]
+// path = "/api"
+// upstream = "127.0.0.1:3000"
+// strip_prefix = true
+// preserve_host = false
+// connect_timeout = "5s"
+// timeout = "60s"
+//
+// [[proxies]]
+// path = "/app"
+// servers = ["127.0.0.1:4000", "127.0.0.1:4001"]
+// balance = "hash"
+// hash_key = "cookie:session"
+// max_fails = 3
+// fail_timeout = "10s"
+// health_check = { path = "/health", interval = "10s", timeout = "2s" }
+//
+// [[rate_limits]]
+// path = "/login"
+// requests = 5
+// period = "1m"
+// key = "ip"
+//
+// [compression]
+// enabled = true
+// min_size = 1024
+// encodings = ["br", "gzip", "deflate"]
+//
+// [timeouts]
+// read = "30s"
+// write = "30s"
+// header = "10s"
+//
+// [limits]
+// max_request_bytes = 8192
+// max_connections = 1024
+// max_connections_per_ip = 64
+// min_send_rate = 1024
+// min_recv_rate = 1024
+//
+// [[redirects]]
+// from = "/old"
+// to = "/new"
+// status = 301
+//
+// [log]
+// level = "info"
+// file = "server.log"
+#[derive(Debug)]
+pub struct Config {
+    pub threads: usize,
+    pub grace_period: Duration,
+    pub http2: bool,
+    pub listeners: Vec<ListenerConfig>,
+    pub site: SiteConfig,
+    pub auth: Vec<AuthConfig>,
+    // 없으면 CORS 헤더를 붙이지 않는다.
+    pub cors: Option<CorsConfig>,
+    // (경로 접두사, 업스트림)
+    pub proxies: Vec<(String, Upstream)>,
+    // (경로 접두사, 제한)
+    pub rate_limits: Vec<(String, Limit)>,
+    pub compression: CompressionConfig,
+    pub timeouts: Timeouts,
+    pub limits: Limits,
+    pub log: LogConfig,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ListenerConfig {
+    pub bind: String,
+    // 있으면 HTTPS 리스너가 된다.
+    pub tls: Option<TlsSettings>,
+    // 있으면 사이트를 보여주는 대신 모든 요청을 https:// 주소로 리다이렉트한다.
+    pub https_redirect: Option<HttpsRedirectSettings>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct HttpsRedirectSettings {
+    // 없으면 첫번째 TLS 리스너의 포트를 쓰고, TLS 리스너도 없으면 443을 쓴다.
+    pub port: Option<u16>,
+    // /.well-known/ 아래의 파일을 찾을 웹 루트
+    pub well_known: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TlsSettings {
+    pub cert: String,
+    pub key: String,
+    pub alpn: Option<Vec<String>>,
+    // SNI로 보낸 이름에 따라 기본 인증서 대신 쓸 인증서들
+    pub sni: Vec<SniCertificate>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SniCertificate {
+    pub names: Vec<String>,
+    pub cert: String,
+    pub key: String,
+}
+
+#[derive(Debug)]
+pub struct SiteConfig {
+    pub root: String,
+    pub index: String,
+    pub mime_types: HashMap<String, String>,
+    pub headers: Vec<(String, String)>,
+    pub redirects: Vec<Redirect>,
+    // 비어 있으면 모든 요청을 root에서 처리하고, 있으면 Host 헤더에 따라 나눠서 처리한다.
+    pub hosts: Vec<HostConfig>,
+}
+
+#[derive(Debug)]
+pub struct HostConfig {
+    pub names: Vec<String>,
+    pub root: String,
+    pub index: Option<String>,
+    // 어떤 이름에도 맞지 않는 요청을 이 사이트에서 처리한다.
+    pub default: bool,
+}
+
+#[derive(Debug)]
+pub struct AuthConfig {
+    // 이 경로와 그 아래를 보호한다.
+    pub path: String,
+    pub realm: String,
+    // 있으면 Basic 인증을 받는다.
+    pub htpasswd: Option<Htpasswd>,
+    // (토큰 이름, 토큰). 있으면 Bearer 토큰을 받는다.
+    pub tokens: Vec<(String, String)>,
+}
+
+#[derive(Debug)]
+pub struct CorsConfig {
+    pub origins: Vec<Origin>,
+    pub methods: Vec<String>,
+    pub headers: Vec<String>,
+    pub expose_headers: Vec<String>,
+    pub credentials: bool,
+    pub max_age: Option<Duration>,
+}
+
+#[derive(Debug)]
+pub struct CompressionConfig {
+    pub enabled: bool,
+    pub min_size: usize,
+    // 선호하는 순서
+    pub encodings: Vec<Encoding>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Timeouts {
+    pub read: Option<Duration>,
+    pub write: Option<Duration>,
+    // 요청 머리를 다 받을 때까지의 전체 시간
+    pub header: Option<Duration>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Limits {
+    pub max_request_bytes: usize,
+    // 0으로 설정하면 None이 되어 제한하지 않는다.
+    pub max_connections: Option<usize>,
+    pub max_connections_per_ip: Option<usize>,
+    // 초당 바이트
+    pub min_send_rate: Option<u64>,
+    // 요청 본문을 받을 때의 최소 속도 (초당 바이트)
+    pub min_recv_rate: Option<u64>,
+}
+
+#[derive(Debug)]
+pub struct LogConfig {
+    pub level: Level,
+    pub file: Option<String>,
+}
+
+impl Config {
+    // 설정 파일이 없을 때 쓰는 기본값. 명령행 인자로 일부를 덮어쓸 수 있다.
+    pub fn new(root: String) -> Self {
+        Self {
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            grace_period: Duration::from_secs(30),
+            http2: true,
+            listeners: vec![ListenerConfig {
+                bind: "127.0.0.1:4000".to_string(),
+                tls: None,
+                https_redirect: None,
+            }],
+            site: SiteConfig {
+                root,
+                index: "index.html".to_string(),
+                mime_types: HashMap::new(),
+                headers: Vec::new(),
+                redirects: Vec::new(),
+                hosts: Vec::new(),
+            },
+            auth: Vec::new(),
+            cors: None,
+            proxies: Vec::new(),
+            rate_limits: Vec::new(),
+            compression: CompressionConfig {
+                enabled: true,
+                min_size: 1024,
+                encodings: vec![Encoding::Brotli, Encoding::Gzip, Encoding::Deflate],
+            },
+            timeouts: Timeouts {
+                read: Some(Duration::from_secs(30)),
+                write: Some(Duration::from_secs(30)),
+                header: Some(Duration::from_secs(10)),
+            },
+            limits: Limits {
+                max_request_bytes: 8 * 1024,
+                max_connections: Some(1024),
+                max_connections_per_ip: Some(64),
+                min_send_rate: Some(1024),
+                min_recv_rate: Some(1024),
+            },
+            log: LogConfig {
+                level: Level::Info,
+                file: None,
+            },
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Self, ConfigError> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| ConfigError::new("", e.to_string()).in_file(path))?;
+        // 설정 파일 안의 상대 경로는 설정 파일이 있는 디렉터리를 기준으로 한다.
+        let base_dir = Path::new(path).parent().unwrap_or_else(|| Path::new("."));
+        Self::parse(&text, base_dir).map_err(|e| e.in_file(path))
+    }
+
+    pub fn parse(text: &str, base_dir: &Path) -> Result<Self, ConfigError> {
+        let table: Table = text
+            .parse()
+            .map_err(|e: toml::de::Error| ConfigError::new("", e.to_string().trim_end()))?;
+        let root = Section::new("", &table);
+        root.deny_unknown(&[
+            "server",
+            "listeners",
+            "site",
+            "hosts",
+            "auth",
+            "cors",
+            "proxies",
+            "rate_limits",
+            "mime",
+            "headers",
+            "compression",
+            "timeouts",
+            "limits",
+            "redirects",
+            "log",
+        ])?;
+
+        let mut config = Self::new(base_dir.join("public").to_string_lossy().into_owned());
+
+        if let Some(server) = root.table("server")? {
+            server.deny_unknown(&["threads", "grace_period", "http2"])?;
+            if let Some(threads) = server.positive("threads")? {
+                config.threads = threads;
+            }
+            if let Some(grace_period) = server.duration("grace_period")? {
+                config.grace_period = grace_period;
+            }
+            if let Some(http2) = server.boolean("http2")? {
+                config.http2 = http2;
+            }
+        }
+
+        if let Some(listeners) = root.tables("listeners")? {
+            if listeners.is_empty() {
+                return Err(ConfigError::new(
+                    "listeners",
+                    "at least one listener is required",
+                ));
+            }
+            config.listeners = listeners
+                .iter()
+                .map(|listener| parse_listener(listener, base_dir))
+                .collect::<Result<_, _>>()?;
+        }
+
+        if let Some(site) = root.table("site")? {
+            site.deny_unknown(&["root", "index"])?;
+            if let Some(root) = site.string("root")? {
+                config.site.root = base_dir.join(root).to_string_lossy().into_owned();
+            }
+            if let Some(index) = site.string("index")? {
+                if index.is_empty() || index.contains('/') {
+                    return Err(site.error("index", "must be a file name"));
+                }
+                config.site.index = index.to_string();
+            }
+        }
+        if !Path::new(&config.site.root).is_dir() {
+            return Err(ConfigError::new(
+                "site.root",
+                format!("{} is not a directory", config.site.root),
+            ));
+        }
+
+        if let Some(hosts) = root.tables("hosts")? {
+            config.site.hosts = hosts
+                .iter()
+                .map(|host| parse_host(host, base_dir))
+                .collect::<Result<_, _>>()?;
+            if config.site.hosts.iter().filter(|host| host.default).count() > 1 {
+                return Err(ConfigError::new(
+                    "hosts",
+                    "only one host can be the default",
+                ));
+            }
+        }
+
+        if let Some(auth) = root.tables("auth")? {
+            config.auth = auth
+                .iter()
+                .map(|auth| parse_auth(auth, base_dir))
+                .collect::<Result<_, _>>()?;
+        }
+
+        if let Some(cors) = root.table("cors")? {
+            config.cors = Some(parse_cors(&cors)?);
+        }
+
+        if let Some(proxies) = root.tables("proxies")? {
+            config.proxies = proxies.iter().map(parse_proxy).collect::<Result<_, _>>()?;
+        }
+
+        if let Some(rate_limits) = root.tables("rate_limits")? {
+            config.rate_limits = rate_limits
+                .iter()
+                .map(parse_rate_limit)
+                .collect::<Result<_, _>>()?;
+        }
+
+        if let Some(mime) = root.table("mime")? {
+            for key in mime.keys() {
+                let mime_type = mime.required_string(key)?;
+                let extension = key.trim_start_matches('.').to_ascii_lowercase();
+                if extension.is_empty() {
+                    return Err(mime.error(key, "extension must not be empty"));
+                }
+                config
+                    .site
+                    .mime_types
+                    .insert(extension, mime_type.to_string());
+            }
+        }
+
+        if let Some(headers) = root.table("headers")? {
+            for name in headers.keys() {
+                let value = headers.required_string(name)?;
+                if !is_token(name) {
+                    return Err(headers.error(name, "is not a valid header name"));
+                }
+                if value.contains(['\r', '\n']) {
+                    return Err(headers.error(name, "header values must not contain line breaks"));
+                }
+                config
+                    .site
+                    .headers
+                    .push((name.to_string(), value.to_string()));
+            }
+        }
+
+        if let Some(compression) = root.table("compression")? {
+            compression.deny_unknown(&["enabled", "min_size", "encodings"])?;
+            if let Some(enabled) = compression.boolean("enabled")? {
+                config.compression.enabled = enabled;
+            }
+            if let Some(min_size) = compression.integer("min_size")? {
+                config.compression.min_size = usize::try_from(min_size)
+                    .map_err(|_| compression.error("min_size", "must not be negative"))?;
+            }
+            if let Some(encodings) = compression.strings("encodings")? {
+                if encodings.is_empty() {
+                    return Err(compression.error("encodings", "must not be empty"));
+                }
+                config.compression.encodings = encodings
+                    .iter()
+                    .map(|encoding| encoding.parse())
+                    .collect::<Result<_, _>>()
+                    .map_err(|e: String| compression.error("encodings", e))?;
+            }
+        }
+
+        if let Some(timeouts) = root.table("timeouts")? {
+            timeouts.deny_unknown(&["read", "write", "header"])?;
+            if timeouts.contains("read") {
+                config.timeouts.read = timeouts.timeout("read")?;
+            }
+            if timeouts.contains("write") {
+                config.timeouts.write = timeouts.timeout("write")?;
+            }
+            if timeouts.contains("header") {
+                config.timeouts.header = timeouts.timeout("header")?;
+            }
+        }
+
+        if let Some(limits) = root.table("limits")? {
+            limits.deny_unknown(&[
+                "max_request_bytes",
+                "max_connections",
+                "max_connections_per_ip",
+                "min_send_rate",
+                "min_recv_rate",
+            ])?;
+            if let Some(max_request_bytes) = limits.positive("max_request_bytes")? {
+                if max_request_bytes < 256 {
+                    return Err(limits.error("max_request_bytes", "must be at least 256"));
+                }
+                config.limits.max_request_bytes = max_request_bytes;
+            }
+            if limits.contains("max_connections") {
+                config.limits.max_connections = limits.limit("max_connections")?;
+            }
+            if limits.contains("max_connections_per_ip") {
+                config.limits.max_connections_per_ip = limits.limit("max_connections_per_ip")?;
+            }
+            if limits.contains("min_send_rate") {
+                config.limits.min_send_rate = limits.limit("min_send_rate")?.map(|n| n as u64);
+            }
+            if limits.contains("min_recv_rate") {
+                config.limits.min_recv_rate = limits.limit("min_recv_rate")?.map(|n| n as u64);
+            }
+        }
+
+        if let Some(redirects) = root.tables("redirects")? {
+            config.site.redirects = redirects
+                .iter()
+                .map(parse_redirect)
+                .collect::<Result<_, _>>()?;
+        }
+
+        if let Some(log) = root.table("log")? {
+            log.deny_unknown(&["level", "file"])?;
+            if let Some(level) = log.string("level")? {
+                config.log.level = level.parse().map_err(|e| log.error("level", e))?;
+            }
+            if let Some(file) = log.string("file")? {
+                config.log.file = Some(base_dir.join(file).to_string_lossy().into_owned());
+            }
+        }
+
+        Ok(config)
+    }
+
+    // 리스너, 스레드, 타임아웃 같은 설정은 서버를 다시 시작해야 적용된다.
+    // 다시 읽은 설정에서 이런 값이 바뀌었다면 그 키 이름들을 리턴한다.
+    pub fn restart_required(&self, new: &Config) -> Vec<&'static str> {
+        let mut keys = Vec::new();
+        if self.listeners != new.listeners {
+            keys.push("listeners");
+        }
+        if self.threads != new.threads {
+            keys.push("server.threads");
+        }
+        if self.grace_period != new.grace_period {
+            keys.push("server.grace_period");
+        }
+        if self.http2 != new.http2 {
+            keys.push("server.http2");
+        }
+        if self.timeouts != new.timeouts {
+            keys.push("timeouts");
+        }
+        if self.limits != new.limits {
+            keys.push("limits");
+        }
+        keys
+    }
+
+    // 인증서는 여기서 다시 읽기 때문에 설정을 읽은 뒤에 파일이 바뀌었다면 오류가 날 수 있다.
+    pub fn server(&self) -> Result<Server, ServerError> {
+        let mut server: Option<Server> = None;
+        for listener in &self.listeners {
+            let bind = listener.bind.clone();
+            server = Some(match (server, &listener.tls, &listener.https_redirect) {
+                (None, Some(tls), _) => Server::new_tls(bind, tls.load()?),
+                (Some(server), Some(tls), _) => server.listen_tls(bind, tls.load()?),
+                (None, None, Some(redirect)) => {
+                    Server::new_with(bind, self.https_redirect(redirect))
+                }
+                (Some(server), None, Some(redirect)) => {
+                    server.listen_with(bind, self.https_redirect(redirect))
+                }
+                (None, None, None) => Server::new(bind),
+                (Some(server), None, None) => server.listen(bind),
+            });
+        }
+        let server =
+            server.ok_or_else(|| ServerError::Config("no listen address given".to_string()))?;
+
+        Ok(server
+            .threads(self.threads)
+            .grace_period(self.grace_period)
+            .http2(self.http2)
+            .read_timeout(self.timeouts.read)
+            .write_timeout(self.timeouts.write)
+            .header_timeout(self.timeouts.header)
+            .max_request_bytes(self.limits.max_request_bytes)
+            .max_connections(self.limits.max_connections)
+            .max_connections_per_ip(self.limits.max_connections_per_ip)
+            .min_send_rate(self.limits.min_send_rate)
+            .min_recv_rate(self.limits.min_recv_rate))
+    }
+
+    // 사이트 핸들러를 만들고, 설정에 따라 프록시, 인증, CORS, 압축, 요청 수 제한 핸들러로 차례로 감싼다.
+    // 프록시로 넘기는 경로에도 인증과 요청 수 제한이 걸리도록 프록시가 가장 안쪽에 있다.
+    // CORS 사전 요청에는 인증 정보가 없으므로 CORS가 인증보다 바깥에 있어야 하고,
+    // 제한에 걸린 요청은 아무 일도 하지 않도록 요청 수 제한이 가장 바깥에 있어야 한다.
+    pub fn handler(&self) -> Box<dyn Handler> {
+        let mut handler = self.site_handler();
+        if !self.proxies.is_empty() {
+            let mut proxy = ProxyHandler::new(handler);
+            for (path, upstream) in &self.proxies {
+                proxy = proxy.route(path, upstream.clone());
+            }
+            handler = Box::new(proxy);
+        }
+        if !self.auth.is_empty() {
+            let mut auth = Auth::new(handler);
+            for settings in &self.auth {
+                let mut realm = Realm::new(settings.realm.as_str());
+                if let Some(htpasswd) = &settings.htpasswd {
+                    realm = realm.basic(htpasswd.clone());
+                }
+                for (name, token) in &settings.tokens {
+                    realm = realm.bearer(name.as_str(), token);
+                }
+                auth = auth.protect(&settings.path, realm);
+            }
+            handler = Box::new(auth);
+        }
+        if let Some(settings) = &self.cors {
+            let mut cors = Cors::new(handler)
+                .methods(settings.methods.clone())
+                .headers(settings.headers.clone())
+                .expose_headers(settings.expose_headers.clone())
+                .credentials(settings.credentials);
+            for origin in &settings.origins {
+                cors = cors.origin(origin.clone());
+            }
+            if let Some(max_age) = settings.max_age {
+                cors = cors.max_age(max_age);
+            }
+            handler = Box::new(cors);
+        }
+        if self.compression.enabled {
+            handler = Box::new(
+                Compression::new(handler)
+                    .min_size(self.compression.min_size)
+                    .encodings(self.compression.encodings.clone()),
+            );
+        }
+        if !self.rate_limits.is_empty() {
+            let mut rate_limit = RateLimit::new(handler);
+            for (path, limit) in &self.rate_limits {
+                rate_limit = rate_limit.limit(path, limit.clone());
+            }
+            handler = Box::new(rate_limit);
+        }
+        handler
+    }
+
+    // 가상 호스트가 설정되어 있으면 Host 헤더로 사이트를 고르는 핸들러를, 아니면 root 하나만 서비스하는 핸들러를 만든다.
+    fn site_handler(&self) -> Box<dyn Handler> {
+        if self.site.hosts.is_empty() {
+            return Box::new(self.website_handler(&self.site.root, &self.site.index));
+        }
+
+        let mut hosts = VirtualHosts::new();
+        for host in &self.site.hosts {
+            let index = host.index.as_ref().unwrap_or(&self.site.index);
+            if !host.names.is_empty() {
+                hosts = hosts.host(&host.names, self.website_handler(&host.root, index));
+            }
+            if host.default {
+                hosts = hosts.default_host(self.website_handler(&host.root, index));
+            }
+        }
+        Box::new(hosts)
+    }
+
+    fn https_redirect(&self, settings: &HttpsRedirectSettings) -> HttpsRedirect {
+        let tls_port = self
+            .listeners
+            .iter()
+            .filter(|listener| listener.tls.is_some())
+            .find_map(|listener| listener.bind.rsplit_once(':')?.1.parse().ok());
+
+        let mut redirect = HttpsRedirect::new();
+        if let Some(port) = settings.port.or(tls_port) {
+            redirect = redirect.port(port);
+        }
+        if let Some(root) = &settings.well_known {
+            redirect = redirect.well_known(root.clone());
+        }
+        redirect
+    }
+
+    fn website_handler(&self, root: &str, index: &str) -> WebsiteHandler {
+        WebsiteHandler::new(root.to_string())
+            .index(index.to_string())
+            .mime_types(self.site.mime_types.clone())
+            .headers(self.site.headers.clone())
+            .redirects(self.site.redirects.clone())
+    }
+}
+
+fn parse_listener(listener: &Section, base_dir: &Path) -> Result<ListenerConfig, ConfigError> {
+    listener.deny_unknown(&["bind", "tls", "https_redirect"])?;
+    let bind = listener.required_string("bind")?;
+    // 실제로 주소를 해석하는 건 바인딩할 때 하고, 여기서는 포트가 붙어 있는지만 확인한다.
+    match bind.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {}
+        _ => return Err(listener.error("bind", "expected an address like 127.0.0.1:4000")),
+    }
+
+    let tls = match listener.table("tls")? {
+        Some(tls) => Some(parse_tls(&tls, base_dir)?),
+        None => None,
+    };
+
+    let https_redirect = match listener.table("https_redirect")? {
+        Some(redirect) => Some(parse_https_redirect(&redirect, base_dir)?),
+        None => None,
+    };
+    if tls.is_some() && https_redirect.is_some() {
+        return Err(listener.error(
+            "https_redirect",
+            "cannot be used on a listener that already has tls",
+        ));
+    }
+
+    Ok(ListenerConfig {
+        bind: bind.to_string(),
+        tls,
+        https_redirect,
+    })
+}
+
+fn parse_https_redirect(
+    redirect: &Section,
+    base_dir: &Path,
+) -> Result<HttpsRedirectSettings, ConfigError> {
+    redirect.deny_unknown(&["port", "well_known"])?;
+    let port = match redirect.integer("port")? {
+        Some(port) => match u16::try_from(port) {
+            Ok(port) if port > 0 => Some(port),
+            _ => return Err(redirect.error("port", "expected a port between 1 and 65535")),
+        },
+        None => None,
+    };
+
+    let well_known = match redirect.string("well_known")? {
+        Some(root) => {
+            let root = base_dir.join(root).to_string_lossy().into_owned();
+            if !Path::new(&root).is_dir() {
+                return Err(redirect.error("well_known", format!("{} is not a directory", root)));
+            }
+            Some(root)
+        }
+        None => None,
+    };
+
+    Ok(HttpsRedirectSettings { port, well_known })
+}
+
+// 인증서와 키를 여기서 한번 읽어봐서, 파일이 없거나 짝이 맞지 않으면 어느 키가 문제인지 알려준다.
+fn parse_tls(tls: &Section, base_dir: &Path) -> Result<TlsSettings, ConfigError> {
+    tls.deny_unknown(&["cert", "key", "alpn", "sni"])?;
+    let path = |key| -> Result<String, ConfigError> {
+        Ok(base_dir
+            .join(tls.required_string(key)?)
+            .to_string_lossy()
+            .into_owned())
+    };
+    let cert = path("cert")?;
+    let key = path("key")?;
+    TlsConfig::new(&cert, &key).map_err(|e| tls.error("cert", tls_message(e)))?;
+
+    let alpn = tls.strings("alpn")?;
+    if let Some(protocols) = &alpn {
+        if protocols.is_empty() || protocols.iter().any(|p| p.is_empty() || p.len() > 255) {
+            return Err(tls.error("alpn", "expected protocol names like \"http/1.1\""));
+        }
+    }
+
+    let mut sni = Vec::new();
+    for entry in tls.tables("sni")?.unwrap_or_default() {
+        entry.deny_unknown(&["names", "cert", "key"])?;
+        let names = entry.strings("names")?.unwrap_or_default();
+        if names.is_empty() {
+            return Err(entry.error("names", "at least one server name is required"));
+        }
+        let path = |key| -> Result<String, ConfigError> {
+            Ok(base_dir
+                .join(entry.required_string(key)?)
+                .to_string_lossy()
+                .into_owned())
+        };
+        let cert = path("cert")?;
+        let key = path("key")?;
+        TlsConfig::new(&cert, &key).map_err(|e| entry.error("cert", tls_message(e)))?;
+        sni.push(SniCertificate { names, cert, key });
+    }
+
+    Ok(TlsSettings {
+        cert,
+        key,
+        alpn,
+        sni,
+    })
+}
+
+// ServerError::Config의 앞에 붙는 "ConfigError: "는 설정 파일 오류 메시지에서는 중복이라 뗀다.
+fn tls_message(e: ServerError) -> String {
+    match e {
+        ServerError::Config(message) => message,
+        e => e.to_string(),
+    }
+}
+
+impl TlsSettings {
+    fn load(&self) -> Result<TlsConfig, ServerError> {
+        let mut tls = TlsConfig::new(&self.cert, &self.key)?;
+        for entry in &self.sni {
+            tls = tls.sni(&entry.names, &entry.cert, &entry.key)?;
+        }
+        if let Some(alpn) = &self.alpn {
+            tls = tls.alpn(alpn);
+        }
+        Ok(tls)
+    }
+}
+
+fn parse_host(host: &Section, base_dir: &Path) -> Result<HostConfig, ConfigError> {
+    host.deny_unknown(&["names", "root", "index", "default"])?;
+    let names = host.strings("names")?.unwrap_or_default();
+    let default = host.boolean("default")?.unwrap_or(false);
+    if names.is_empty() && !default {
+        return Err(host.error("names", "at least one host name is required"));
+    }
+    for name in &names {
+        let bare = name.strip_prefix("*.").unwrap_or(name);
+        if bare.is_empty() || bare.contains(['*', '/', ' ', ':']) {
+            return Err(host.error(
+                "names",
+                format!(
+                    "{} is not a host name like example.test or *.example.test",
+                    name
+                ),
+            ));
+        }
+    }
+
+    let root = base_dir
+        .join(host.required_string("root")?)
+        .to_string_lossy()
+        .into_owned();
+    if !Path::new(&root).is_dir() {
+        return Err(host.error("root", format!("{} is not a directory", root)));
+    }
+    let index = host.string("index")?.map(str::to_string);
+
+    Ok(HostConfig {
+        names,
+        root,
+        index,
+        default,
+    })
+}
+
+fn parse_auth(auth: &Section, base_dir: &Path) -> Result<AuthConfig, ConfigError> {
+    auth.deny_unknown(&["path", "realm", "htpasswd", "tokens"])?;
+    let path = auth.required_string("path")?;
+    if !path.starts_with('/') {
+        return Err(auth.error("path", "must start with /"));
+    }
+    let realm = auth.string("realm")?.unwrap_or("Restricted");
+    if realm.chars().any(char::is_control) {
+        return Err(auth.error("realm", "must not contain control characters"));
+    }
+
+    // 파일은 설정을 읽을 때 함께 읽는다. 비밀번호를 바꿨다면 SIGHUP으로 설정을 다시 읽으면 된다.
+    let htpasswd = match auth.string("htpasswd")? {
+        Some(file) => {
+            let file = base_dir.join(file).to_string_lossy().into_owned();
+            Some(Htpasswd::load(&file).map_err(|e| auth.error("htpasswd", e))?)
+        }
+        None => None,
+    };
+
+    let mut tokens = Vec::new();
+    if let Some(table) = auth.table("tokens")? {
+        for name in table.keys() {
+            let token = table.required_string(name)?;
+            if !is_token68(token) {
+                return Err(table.error(name, "is not a valid bearer token"));
+            }
+            tokens.push((name.to_string(), token.to_string()));
+        }
+    }
+
+    if htpasswd.is_none() && tokens.is_empty() {
+        return Err(auth.error("htpasswd", "htpasswd or tokens is required"));
+    }
+    Ok(AuthConfig {
+        path: path.to_string(),
+        realm: realm.to_string(),
+        htpasswd,
+        tokens,
+    })
+}
+
+fn parse_proxy(proxy: &Section) -> Result<(String, Upstream), ConfigError> {
+    proxy.deny_unknown(&[
+        "path",
+        "upstream",
+        "servers",
+        "balance",
+        "hash_key",
+        "max_fails",
+        "fail_timeout",
+        "health_check",
+        "strip_prefix",
+        "preserve_host",
+        "connect_timeout",
+        "timeout",
+    ])?;
+    let path = proxy.required_string("path")?;
+    if !path.starts_with('/') {
+        return Err(proxy.error("path", "must start with /"));
+    }
+
+    // 서버 하나면 upstream, 여러 개면 servers에 적는다.
+    let servers = match (proxy.string("upstream")?, proxy.strings("servers")?) {
+        (Some(addr), None) => vec![("upstream", addr.to_string())],
+        (None, Some(servers)) if !servers.is_empty() => {
+            servers.into_iter().map(|addr| ("servers", addr)).collect()
+        }
+        (None, Some(_)) => return Err(proxy.error("servers", "must not be empty")),
+        (Some(_), Some(_)) => return Err(proxy.error("servers", "cannot be used with upstream")),
+        (None, None) => return Err(proxy.error("upstream", "is required")),
+    };
+    for (key, addr) in &servers {
+        let has_port = addr
+            .rsplit_once(':')
+            .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok());
+        if !has_port {
+            return Err(proxy.error(key, "expected host:port like \"127.0.0.1:3000\""));
+        }
+    }
+    let mut servers = servers.into_iter().map(|(_, addr)| addr);
+    let mut upstream = Upstream::new(servers.next().unwrap());
+    for addr in servers {
+        upstream = upstream.server(addr);
+    }
+
+    let balance = match proxy.string("balance")? {
+        Some(balance) => Some(
+            balance
+                .parse::<Balance>()
+                .map_err(|e| proxy.error("balance", e))?,
+        ),
+        None => None,
+    };
+    if let Some(hash_key) = proxy.string("hash_key")? {
+        if !matches!(balance, Some(Balance::Hash(_))) {
+            return Err(proxy.error("hash_key", "requires balance = \"hash\""));
+        }
+        let hash_key: HashKey = hash_key.parse().map_err(|e| proxy.error("hash_key", e))?;
+        upstream = upstream.balance(Balance::Hash(hash_key));
+    } else if let Some(balance) = balance {
+        upstream = upstream.balance(balance);
+    }
+
+    if let Some(max_fails) = proxy.integer("max_fails")? {
+        let max_fails =
+            u32::try_from(max_fails).map_err(|_| proxy.error("max_fails", "is out of range"))?;
+        upstream = upstream.max_fails(max_fails);
+    }
+    if let Some(fail_timeout) = nonzero_duration(proxy, "fail_timeout")? {
+        upstream = upstream.fail_timeout(fail_timeout);
+    }
+    if let Some(health_check) = proxy.table("health_check")? {
+        upstream = upstream.health_check(parse_health_check(&health_check)?);
+    }
+    if let Some(strip_prefix) = proxy.boolean("strip_prefix")? {
+        upstream = upstream.strip_prefix(strip_prefix);
+    }
+    if let Some(preserve_host) = proxy.boolean("preserve_host")? {
+        upstream = upstream.preserve_host(preserve_host);
+    }
+    if let Some(timeout) = nonzero_duration(proxy, "connect_timeout")? {
+        upstream = upstream.connect_timeout(timeout);
+    }
+    if let Some(timeout) = nonzero_duration(proxy, "timeout")? {
+        upstream = upstream.timeout(timeout);
+    }
+    Ok((path.to_string(), upstream))
+}
+
+fn parse_health_check(health_check: &Section) -> Result<HealthCheck, ConfigError> {
+    health_check.deny_unknown(&["path", "interval", "timeout"])?;
+    let path = health_check.required_string("path")?;
+    if !path.starts_with('/') {
+        return Err(health_check.error("path", "must start with /"));
+    }
+    let mut check = HealthCheck::new(path);
+    if let Some(interval) = nonzero_duration(health_check, "interval")? {
+        check = check.interval(interval);
+    }
+    if let Some(timeout) = nonzero_duration(health_check, "timeout")? {
+        check = check.timeout(timeout);
+    }
+    Ok(check)
+}
+
+fn nonzero_duration(section: &Section, key: &str) -> Result<Option<Duration>, ConfigError> {
+    match section.duration(key)? {
+        Some(duration) if duration.is_zero() => Err(section.error(key, "must be greater than 0")),
+        duration => Ok(duration),
+    }
+}
+
+fn parse_rate_limit(rate_limit: &Section) -> Result<(String, Limit), ConfigError> {
+    rate_limit.deny_unknown(&["path", "requests", "period", "key"])?;
+    let path = rate_limit.string("path")?.unwrap_or("/");
+    if !path.starts_with('/') {
+        return Err(rate_limit.error("path", "must start with /"));
+    }
+    let requests = rate_limit
+        .positive("requests")?
+        .ok_or_else(|| rate_limit.error("requests", "is required"))?;
+    let requests =
+        u32::try_from(requests).map_err(|_| rate_limit.error("requests", "is too large"))?;
+    let period = rate_limit
+        .duration("period")?
+        .ok_or_else(|| rate_limit.error("period", "is required"))?;
+    if period.is_zero() {
+        return Err(rate_limit.error("period", "must be greater than 0"));
+    }
+
+    let mut limit = Limit::new(requests, period);
+    if let Some(key) = rate_limit.string("key")? {
+        limit = limit.key(key.parse().map_err(|e| rate_limit.error("key", e))?);
+    }
+    Ok((path.to_string(), limit))
+}
+
+fn parse_cors(cors: &Section) -> Result<CorsConfig, ConfigError> {
+    cors.deny_unknown(&[
+        "origins",
+        "methods",
+        "headers",
+        "expose_headers",
+        "credentials",
+        "max_age",
+    ])?;
+    let origins: Vec<Origin> = cors
+        .strings("origins")?
+        .unwrap_or_default()
+        .iter()
+        .map(|origin| origin.parse())
+        .collect::<Result<_, _>>()
+        .map_err(|e: String| cors.error("origins", e))?;
+    if origins.is_empty() {
+        return Err(cors.error("origins", "at least one origin is required"));
+    }
+    let credentials = cors.boolean("credentials")?.unwrap_or(false);
+    // 아무 출처에나 쿠키를 함께 보내게 하면 다른 사이트가 사용자의 권한으로 요청을 보낼 수 있다.
+    if credentials && origins.contains(&Origin::Any) {
+        return Err(cors.error("origins", "\"*\" cannot be used with credentials"));
+    }
+
+    let methods = cors
+        .strings("methods")?
+        .unwrap_or_else(|| vec!["GET".to_string(), "HEAD".to_string(), "POST".to_string()]);
+    if let Some(method) = methods.iter().find(|method| !is_token(method)) {
+        return Err(cors.error("methods", format!("{} is not a valid method", method)));
+    }
+    let headers = cors.strings("headers")?.unwrap_or_default();
+    let expose_headers = cors.strings("expose_headers")?.unwrap_or_default();
+    for (key, names) in [("headers", &headers), ("expose_headers", &expose_headers)] {
+        if let Some(name) = names.iter().find(|name| !is_token(name)) {
+            return Err(cors.error(key, format!("{} is not a valid header name", name)));
+        }
+    }
+
+    Ok(CorsConfig {
+        origins,
+        methods,
+        headers,
+        expose_headers,
+        credentials,
+        max_age: cors.duration("max_age")?,
+    })
+}
+
+// Bearer 토큰에 쓸 수 있는 글자들 (RFC 6750의 b64token)
+fn is_token68(s: &str) -> bool {
+    let body = s.trim_end_matches('=');
+    !body.is_empty()
+        && body
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-._~+/".contains(&b))
+}
+
+fn parse_redirect(redirect: &Section) -> Result<Redirect, ConfigError> {
+    redirect.deny_unknown(&["from", "to", "status"])?;
+    let from = redirect.required_string("from")?;
+    if !from.starts_with('/') {
+        return Err(redirect.error("from", "must start with /"));
+    }
+    let to = redirect.required_string("to")?;
+    let status = match redirect.integer("status")? {
+        Some(code) => match u16::try_from(code).ok().and_then(StatusCode::from_u16) {
+            Some(status) if status.is_redirect() => status,
+            _ => return Err(redirect.error("status", "expected one of 301, 302, 303, 307, 308")),
+        },
+        None => StatusCode::MovedPermanently,
+    };
+
+    Ok(Redirect {
+        from: from.to_string(),
+        to: to.to_string(),
+        status,
+    })
+}
+
+// 헤더 이름에 쓸 수 있는 글자들 (RFC 9110의 token)
+pub(crate) fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+// "30s", "500ms", "2m", "1h" 또는 초 단위 정수
+// 초로 바꾸다가 넘치면 Duration::MAX가 되고, 범위는 부르는 쪽에서 확인한다.
+fn parse_duration(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: u64 = number.parse().ok()?;
+    let seconds = |multiplier: u64| {
+        number
+            .checked_mul(multiplier)
+            .map_or(Duration::MAX, Duration::from_secs)
+    };
+    match unit.trim() {
+        "ms" => Some(Duration::from_millis(number)),
+        "" | "s" => Some(seconds(1)),
+        "m" => Some(seconds(60)),
+        "h" => Some(seconds(60 * 60)),
+        _ => None,
+    }
+}
+
+// TOML 테이블 하나와 그 테이블까지의 경로. 오류가 나면 어떤 키가 문제인지 경로로 알려준다.
+struct Section<'a> {
+    path: String,
+    table: &'a Table,
+}
+
+impl<'a> Section<'a> {
+    fn new(path: &str, table: &'a Table) -> Self {
+        Self {
+            path: path.to_string(),
+            table,
+        }
+    }
+
+    fn key_path(&self, key: &str) -> String {
+        if self.path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", self.path, key)
+        }
+    }
+
+    fn error(&self, key: &str, message: impl Into<String>) -> ConfigError {
+        ConfigError::new(&self.key_path(key), message)
+    }
+
+    fn keys(&self) -> impl Iterator<Item = &'a String> {
+        self.table.keys()
+    }
+
+    fn contains(&self, key: &str) -> bool {
+        self.table.contains_key(key)
+    }
+
+    // 오타가 난 키가 조용히 무시되지 않도록 모르는 키가 있으면 오류를 낸다.
+    fn deny_unknown(&self, known: &[&str]) -> Result<(), ConfigError> {
+        match self.table.keys().find(|key| !known.contains(&key.as_str())) {
+            Some(key) => Err(self.error(key, "unknown key")),
+            None => Ok(()),
+        }
+    }
+
+    fn string(&self, key: &str) -> Result<Option<&'a str>, ConfigError> {
+        match self.table.get(key) {
+            None => Ok(None),
+            Some(Value::String(s)) => Ok(Some(s)),
+            Some(_) => Err(self.error(key, "expected a string")),
+        }
+    }
+
+    fn required_string(&self, key: &str) -> Result<&'a str, ConfigError> {
+        self.string(key)?
+            .ok_or_else(|| self.error(key, "is required"))
+    }
+
+    fn strings(&self, key: &str) -> Result<Option<Vec<String>>, ConfigError> {
+        let array = match self.table.get(key) {
+            None => return Ok(None),
+            Some(Value::Array(array)) => array,
+            Some(_) => return Err(self.error(key, "expected an array of strings")),
+        };
+
+        array
+            .iter()
+            .map(|value| match value {
+                Value::String(s) => Ok(s.clone()),
+                _ => Err(self.error(key, "expected an array of strings")),
+            })
+            .collect::<Result<_, _>>()
+            .map(Some)
+    }
+
+    fn boolean(&self, key: &str) -> Result<Option<bool>, ConfigError> {
+        match self.table.get(key) {
+            None => Ok(None),
+            Some(Value::Boolean(b)) => Ok(Some(*b)),
+            Some(_) => Err(self.error(key, "expected true or false")),
+        }
+    }
+
+    fn integer(&self, key: &str) -> Result<Option<i64>, ConfigError> {
+        match self.table.get(key) {
+            None => Ok(None),
+            Some(Value::Integer(i)) => Ok(Some(*i)),
+            Some(_) => Err(self.error(key, "expected an integer")),
+        }
+    }
+
+    fn positive(&self, key: &str) -> Result<Option<usize>, ConfigError> {
+        match self.integer(key)? {
+            None => Ok(None),
+            Some(i) if i > 0 => Ok(Some(i as usize)),
+            Some(_) => Err(self.error(key, "must be greater than 0")),
+        }
+    }
+
+    fn duration(&self, key: &str) -> Result<Option<Duration>, ConfigError> {
+        let duration = match self.table.get(key) {
+            None => return Ok(None),
+            Some(Value::Integer(i)) if *i >= 0 => Some(Duration::from_secs(*i as u64)),
+            Some(Value::String(s)) => parse_duration(s),
+            Some(_) => None,
+        };
+        match duration {
+            Some(duration) if duration <= MAX_DURATION => Ok(Some(duration)),
+            Some(_) => Err(self.error(key, "must be at most 365 days")),
+            None => Err(self.error(key, "expected a duration like \"30s\" or \"500ms\"")),
+        }
+    }
+
+    // 타임아웃은 0이면 끈다는 의미로 쓴다.
+    fn timeout(&self, key: &str) -> Result<Option<Duration>, ConfigError> {
+        Ok(self.duration(key)?.filter(|duration| !duration.is_zero()))
+    }
+
+    // 제한도 0이면 끈다는 의미로 쓴다.
+    fn limit(&self, key: &str) -> Result<Option<usize>, ConfigError> {
+        match self.integer(key)? {
+            Some(i) if i < 0 => Err(self.error(key, "must not be negative")),
+            i => Ok(i.map(|i| i as usize).filter(|i| *i > 0)),
+        }
+    }
+
+    fn table(&self, key: &str) -> Result<Option<Section<'a>>, ConfigError> {
+        match self.table.get(key) {
+            None => Ok(None),
+            Some(Value::Table(table)) => Ok(Some(Section::new(&self.key_path(key), table))),
+            Some(_) => Err(self.error(key, "expected a table")),
+        }
+    }
+
+    // [[listeners]] 처럼 테이블의 배열
+    fn tables(&self, key: &str) -> Result<Option<Vec<Section<'a>>>, ConfigError> {
+        let array = match self.table.get(key) {
+            None => return Ok(None),
+            Some(Value::Array(array)) => array,
+            Some(_) => return Err(self.error(key, "expected an array of tables")),
+        };
+
+        array
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                let path = format!("{}[{}]", self.key_path(key), i);
+                match value {
+                    Value::Table(table) => Ok(Section::new(&path, table)),
+                    _ => Err(ConfigError::new(&path, "expected a table")),
+                }
+            })
+            .collect::<Result<_, _>>()
+            .map(Some)
+    }
+}
+
+// 설정 파일의 어느 키가 왜 잘못되었는지를 담는다.
+pub struct ConfigError {
+    file: Option<String>,
+    key: String,
+    message: String,
+}
+
+impl ConfigError {
+    fn new(key: &str, message: impl Into<String>) -> Self {
+        Self {
+            file: None,
+            key: key.to_string(),
+            message: message.into(),
+        }
+    }
+
+    fn in_file(mut self, file: &str) -> Self {
+        self.file = Some(file.to_string());
+        self
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        if let Some(file) = &self.file {
+            write!(f, "{}: ", file)?;
+        }
+        if !self.key.is_empty() {
+            write!(f, "{}: ", self.key)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Debug for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}", self)
+    }
+}
+
+impl Error for ConfigError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<Config, ConfigError> {
+        Config::parse(text, Path::new(env!("CARGO_MANIFEST_DIR")))
+    }
+
+    // 오류가 난 키의 경로와 메시지
+    fn error(text: &str) -> (String, String) {
+        let e = parse(text).unwrap_err();
+        (e.key().to_string(), e.message)
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("500ms"), Some(Duration::from_millis(500)));
+        assert_eq!(parse_duration("30s"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_duration(" 30 "), Some(Duration::from_secs(30)));
+        assert_eq!(parse_duration("2m"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_duration("1h"), Some(Duration::from_secs(3600)));
+        for invalid in ["", "s", "1.5s", "-1s", "10d", "99999999999999999999s"] {
+            assert_eq!(parse_duration(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn overflowing_durations_saturate() {
+        assert_eq!(parse_duration("18446744073709551615m"), Some(Duration::MAX));
+        assert_eq!(parse_duration("5124095576030432h"), Some(Duration::MAX));
+    }
+
+    #[test]
+    fn durations_are_capped() {
+        let config = parse("[server]\ngrace_period = \"8760h\"").unwrap();
+        assert_eq!(config.grace_period, MAX_DURATION);
+        let config = parse("[server]\ngrace_period = 10").unwrap();
+        assert_eq!(config.grace_period, Duration::from_secs(10));
+
+        for value in ["\"8761h\"", "\"5124095576030432h\"", "9223372036854775807"] {
+            let text = format!("[timeouts]\nheader = {}", value);
+            assert_eq!(
+                error(&text),
+                (
+                    "timeouts.header".to_string(),
+                    "must be at most 365 days".to_string()
+                ),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn zero_turns_timeouts_and_limits_off() {
+        let config = parse("[timeouts]\nread = 0\n[limits]\nmax_connections = 0").unwrap();
+        assert_eq!(config.timeouts.read, None);
+        assert_eq!(config.limits.max_connections, None);
+    }
+
+    #[test]
+    fn errors_name_the_key() {
+        let cases = [
+            ("[server]\nthread = 4", "server.thread", "unknown key"),
+            (
+                "[server]\nthreads = \"4\"",
+                "server.threads",
+                "expected an integer",
+            ),
+            (
+                "[server]\nthreads = 0",
+                "server.threads",
+                "must be greater than 0",
+            ),
+            (
+                "[server]\nhttp2 = \"yes\"",
+                "server.http2",
+                "expected true or false",
+            ),
+            (
+                "[timeouts]\nread = \"soon\"",
+                "timeouts.read",
+                "expected a duration like \"30s\" or \"500ms\"",
+            ),
+            (
+                "[timeouts]\nread = -1",
+                "timeouts.read",
+                "expected a duration like \"30s\" or \"500ms\"",
+            ),
+            (
+                "[limits]\nmax_request_bytes = 100",
+                "limits.max_request_bytes",
+                "must be at least 256",
+            ),
+            (
+                "[limits]\nmax_connections = -1",
+                "limits.max_connections",
+                "must not be negative",
+            ),
+            ("limits = 1", "limits", "expected a table"),
+            (
+                "[[listeners]]\nbind = \"127.0.0.1:4000\"\n[[listeners]]\nbind = \"localhost\"",
+                "listeners[1].bind",
+                "expected an address like 127.0.0.1:4000",
+            ),
+            (
+                "[[redirects]]\nfrom = \"/old\"",
+                "redirects[0].to",
+                "is required",
+            ),
+        ];
+        for (text, key, message) in cases {
+            assert_eq!(
+                error(text),
+                (key.to_string(), message.to_string()),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn errors_show_the_file_and_key() {
+        let e = parse("[server]\nthreads = 0")
+            .unwrap_err()
+            .in_file("site.toml");
+        assert_eq!(
+            e.to_string(),
+            "site.toml: server.threads: must be greater than 0"
+        );
+    }
+}
@@ -0,0 +1,315 @@
+use crate::compression::add_vary;
+use crate::http::{Method, ParseError, Request, Response, StatusCode};
+use crate::server::Handler;
+use std::time::Duration;
+
+// 다른 출처(Origin)의 페이지가 브라우저에서 이 서버를 부를 수 있게 허락한다. (Fetch 표준의 CORS)
+//
+// 브라우저는 단순하지 않은 요청을 보내기 전에 OPTIONS로 사전 요청(preflight)을 보내서 허락을 받는다.
+// 사전 요청은 안쪽 핸들러까지 가지 않고 여기서 답한다. 인증보다 바깥에 두어야 하는 이유다.
+// 실제 요청에는 안쪽 핸들러의 응답에 Access-Control-Allow-Origin 같은 헤더를 붙인다.
+//
+// let handler = Cors::new(handler)
+//     .origin("https://app.example.test")
+//     .origin("https://*.example.test")
+//     .methods(vec!["GET".to_string(), "PUT".to_string()])
+//     .credentials(true);
+pub struct Cors<H> {
+    inner: H,
+    origins: Vec<Origin>,
+    methods: Vec<String>,
+    // 비어 있으면 CORS 안전 목록에 있는 헤더(Accept, Content-Type 등)만 보낼 수 있다. "*"는 모든 헤더다.
+    headers: Vec<String>,
+    expose_headers: Vec<String>,
+    credentials: bool,
+    max_age: Option<Duration>,
+}
+
+// 허락한 출처 하나
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    Any,
+    // "https://example.test:8443" 처럼 스킴, 호스트, 포트까지 모두 같아야 한다.
+    Exact(String),
+    // "https://*.example.test"는 https://a.example.test, https://a.b.example.test에 맞고
+    // https://example.test에는 맞지 않는다. (앞부분, 뒷부분)으로 나눠서 둔다.
+    Wildcard(String, String),
+}
+
+impl Origin {
+    fn matches(&self, origin: &str) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Exact(exact) => exact.eq_ignore_ascii_case(origin),
+            Self::Wildcard(scheme, suffix) => {
+                let origin = origin.to_ascii_lowercase();
+                origin
+                    .strip_prefix(scheme.as_str())
+                    .and_then(|rest| rest.strip_suffix(suffix.as_str()))
+                    // 가운데 부분은 호스트 이름의 앞쪽 레이블들이다. 경로나 포트가 끼어들면 안 된다.
+                    .is_some_and(|labels| {
+                        !labels.is_empty()
+                            && labels
+                                .bytes()
+                                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'.')
+                    })
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Origin {
+    type Err = String;
+
+    // "*", "https://example.test", "http://localhost:3000", "https://*.example.test"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "*" {
+            return Ok(Self::Any);
+        }
+        let invalid = || {
+            format!(
+                "{} is not an origin like https://example.test or https://*.example.test",
+                s
+            )
+        };
+        let (scheme, host) = s.split_once("://").ok_or_else(invalid)?;
+        if scheme.is_empty() || host.is_empty() || host.contains('/') {
+            return Err(invalid());
+        }
+        let s = s.to_ascii_lowercase();
+        match host.strip_prefix("*.") {
+            Some(rest) if !rest.is_empty() && !rest.contains('*') => Ok(Self::Wildcard(
+                format!("{}://", scheme.to_ascii_lowercase()),
+                format!(".{}", rest.to_ascii_lowercase()),
+            )),
+            Some(_) => Err(invalid()),
+            None if host.contains('*') => Err(invalid()),
+            None => Ok(Self::Exact(s)),
+        }
+    }
+}
+
+impl<H: Handler> Cors<H> {
+    pub fn new(inner: H) -> Self {
+        Self {
+            inner,
+            origins: Vec::new(),
+            methods: vec!["GET".to_string(), "HEAD".to_string(), "POST".to_string()],
+            headers: Vec::new(),
+            expose_headers: Vec::new(),
+            credentials: false,
+            max_age: None,
+        }
+    }
+
+    pub fn origin(mut self, origin: Origin) -> Self {
+        self.origins.push(origin);
+        self
+    }
+
+    pub fn methods(mut self, methods: Vec<String>) -> Self {
+        self.methods = methods;
+        self
+    }
+
+    pub fn headers(mut self, headers: Vec<String>) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    // 브라우저의 스크립트가 응답에서 읽을 수 있는 헤더. 기본으로는 Content-Type 같은 몇 개만 읽을 수 있다.
+    pub fn expose_headers(mut self, headers: Vec<String>) -> Self {
+        self.expose_headers = headers;
+        self
+    }
+
+    // 쿠키와 Authorization 헤더를 함께 보내도 되는지. 켜면 "*" 대신 요청한 출처를 그대로 돌려준다.
+    // 아무 출처에나 쿠키를 함께 보내게 하면 다른 사이트가 사용자의 권한으로 요청을 보낼 수 있으므로,
+    // 켜면 Origin::Any는 무시하고 Exact와 Wildcard로 준 출처만 허락한다.
+    pub fn credentials(mut self, credentials: bool) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    // 브라우저가 사전 요청의 결과를 이만큼 기억해서 같은 요청에 다시 묻지 않는다.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    fn is_allowed(&self, origin: &str) -> bool {
+        self.origins
+            .iter()
+            .filter(|allowed| !(self.credentials && **allowed == Origin::Any))
+            .any(|allowed| allowed.matches(origin))
+    }
+
+    // 모든 출처를 허락하고 쿠키를 받지 않으면 응답이 출처에 따라 달라지지 않으므로 "*"를 보낸다.
+    fn allows_any(&self) -> bool {
+        !self.credentials && self.origins.contains(&Origin::Any)
+    }
+
+    fn allow_origin(&self, response: &mut Response, origin: &str) {
+        if self.allows_any() {
+            response.set_header("Access-Control-Allow-Origin", "*");
+        } else {
+            response.set_header("Access-Control-Allow-Origin", origin);
+        }
+        if self.credentials {
+            response.set_header("Access-Control-Allow-Credentials", "true");
+        }
+    }
+
+    fn preflight(&self, request: &Request, origin: &str, method: &str) -> Response {
+        let mut response = Response::new(StatusCode::NoContent, None);
+        for name in [
+            "Origin",
+            "Access-Control-Request-Method",
+            "Access-Control-Request-Headers",
+        ] {
+            add_vary(&mut response, name);
+        }
+
+        // 허락하지 않는 요청이면 CORS 헤더 없이 답한다. 그러면 브라우저가 실제 요청을 보내지 않는다.
+        let method_allowed = self.methods.iter().any(|allowed| allowed == method);
+        let requested: Vec<&str> = request
+            .headers()
+            .get_all("Access-Control-Request-Headers")
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .collect();
+        let any_header = self.headers.iter().any(|name| name == "*");
+        let headers_allowed = any_header
+            || requested.iter().all(|name| {
+                self.headers
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(name))
+            });
+        if !self.is_allowed(origin) || !method_allowed || !headers_allowed {
+            debug!(
+                "CORS preflight rejected for {} {} from {}",
+                method,
+                request.path(),
+                origin
+            );
+            return response;
+        }
+
+        self.allow_origin(&mut response, origin);
+        response.set_header("Access-Control-Allow-Methods", self.methods.join(", "));
+        if !requested.is_empty() {
+            // 쿠키를 받을 때는 "*"가 글자 그대로의 이름으로 해석되므로 요청한 헤더 이름을 그대로 돌려준다.
+            let allowed = if any_header && !self.credentials {
+                "*".to_string()
+            } else {
+                requested.join(", ")
+            };
+            response.set_header("Access-Control-Allow-Headers", allowed);
+        }
+        if let Some(max_age) = self.max_age {
+            response.set_header("Access-Control-Max-Age", max_age.as_secs().to_string());
+        }
+        response
+    }
+}
+
+impl<H: Handler> Handler for Cors<H> {
+    fn handle_request(&self, request: &Request) -> Response {
+        let origin = request.header("Origin");
+        let preflight_method = request.header("Access-Control-Request-Method");
+        if let (Method::OPTIONS, Some(origin), Some(method)) =
+            (request.method(), origin, preflight_method)
+        {
+            return self.preflight(request, origin, method.trim());
+        }
+
+        let mut response = self.inner.handle_request(request);
+        // Origin이 없는 요청의 응답도 캐시에 남았다가 다른 출처의 요청에 쓰일 수 있으므로 항상 붙인다.
+        if !self.allows_any() {
+            add_vary(&mut response, "Origin");
+        }
+        if let Some(origin) = origin.filter(|origin| self.is_allowed(origin)) {
+            self.allow_origin(&mut response, origin);
+            if !self.expose_headers.is_empty() {
+                response.set_header(
+                    "Access-Control-Expose-Headers",
+                    self.expose_headers.join(", "),
+                );
+            }
+        }
+        response
+    }
+
+    fn handle_bad_request(&self, e: &ParseError) -> Response {
+        self.inner.handle_bad_request(e)
+    }
+
+    fn accept_upgrade<'buf>(&self, request: &Request<'buf>) -> Result<Request<'buf>, Response> {
+        self.inner.accept_upgrade(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    struct Ok200;
+
+    impl Handler for Ok200 {
+        fn handle_request(&self, _: &Request) -> Response {
+            Response::new(StatusCode::Ok, None)
+        }
+    }
+
+    fn get(cors: &Cors<Ok200>, origin: &str) -> Response {
+        let raw = format!("GET / HTTP/1.1\r\nOrigin: {}\r\n\r\n", origin);
+        cors.handle_request(&Request::try_from(raw.as_bytes()).unwrap())
+    }
+
+    fn preflight(cors: &Cors<Ok200>, origin: &str) -> Response {
+        let raw = format!(
+            "OPTIONS / HTTP/1.1\r\nOrigin: {}\r\nAccess-Control-Request-Method: GET\r\n\r\n",
+            origin
+        );
+        cors.handle_request(&Request::try_from(raw.as_bytes()).unwrap())
+    }
+
+    #[test]
+    fn any_origin_without_credentials_sends_star() {
+        let cors = Cors::new(Ok200).origin(Origin::Any);
+        let response = get(&cors, "https://evil.test");
+        assert_eq!(response.header("Access-Control-Allow-Origin"), Some("*"));
+        assert_eq!(response.header("Access-Control-Allow-Credentials"), None);
+    }
+
+    #[test]
+    fn any_origin_is_ignored_with_credentials() {
+        // credentials()를 먼저 불러도 나중에 불러도 같아야 한다.
+        let cors = Cors::new(Ok200).origin(Origin::Any).credentials(true);
+        for response in [
+            get(&cors, "https://evil.test"),
+            preflight(&cors, "https://evil.test"),
+        ] {
+            assert_eq!(response.header("Access-Control-Allow-Origin"), None);
+            assert_eq!(response.header("Access-Control-Allow-Credentials"), None);
+        }
+
+        let cors = Cors::new(Ok200)
+            .credentials(true)
+            .origin(Origin::Any)
+            .origin("https://app.example.test".parse().unwrap());
+        let response = get(&cors, "https://evil.test");
+        assert_eq!(response.header("Access-Control-Allow-Origin"), None);
+        let response = get(&cors, "https://app.example.test");
+        assert_eq!(
+            response.header("Access-Control-Allow-Origin"),
+            Some("https://app.example.test")
+        );
+        assert_eq!(
+            response.header("Access-Control-Allow-Credentials"),
+            Some("true")
+        );
+    }
+}
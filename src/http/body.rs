@@ -0,0 +1,53 @@
+use std::cell::RefCell;
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::io::{Read, Result as IoResult};
+
+// 요청 본문. 연결에서 읽는 대로 핸들러에게 넘겨주므로 한 번만 읽을 수 있다.
+//
+// 핸들러는 &Request를 받기 때문에 본문을 읽는 쪽은 RefCell로 감싸서 빌려준다.
+// Request를 복제해도 같은 본문을 가리키고, 어느 복제본에서 읽어도 이어서 읽힌다.
+//
+// let mut body = request.body()?;
+// let mut text = String::new();
+// body.read_to_string(&mut text)?;
+#[derive(Clone, Copy)]
+pub struct Body<'a> {
+    reader: &'a (dyn SharedRead + 'a),
+    length: Option<u64>,
+}
+
+impl<'a> Body<'a> {
+    // length는 Content-Length처럼 미리 알고 있는 본문의 길이다. chunked 본문이면 None이다.
+    pub fn new<R: Read>(reader: &'a RefCell<R>, length: Option<u64>) -> Self {
+        Self { reader, length }
+    }
+
+    pub fn length(&self) -> Option<u64> {
+        self.length
+    }
+}
+
+impl Read for Body<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        self.reader.read_shared(buf)
+    }
+}
+
+impl Debug for Body<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Body")
+            .field("length", &self.length)
+            .finish_non_exhaustive()
+    }
+}
+
+// &RefCell<R>를 &dyn Read로 바꿀 수는 없으므로, &self로 읽을 수 있는 트레이트를 따로 둔다.
+trait SharedRead {
+    fn read_shared(&self, buf: &mut [u8]) -> IoResult<usize>;
+}
+
+impl<R: Read> SharedRead for RefCell<R> {
+    fn read_shared(&self, buf: &mut [u8]) -> IoResult<usize> {
+        self.borrow_mut().read(buf)
+    }
+}
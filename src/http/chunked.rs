@@ -0,0 +1,175 @@
+// Transfer-Encoding: chunked (RFC 9112 7.1)
+//
+// 1a\r\n
+// 26바이트의 데이터\r\n
+// 0\r\n
+// \r\n
+//
+// 길이를 미리 알 수 없는 본문을 조각마다 16진수 길이를 붙여서 보낸다. 길이가 0인 조각이 본문의 끝이다.
+use std::io::{BufRead, Error, ErrorKind, Read, Result as IoResult, Write};
+
+// 조각의 길이 줄과 트레일러 줄의 최대 길이. 끝없이 긴 줄로 메모리를 채우지 못하게 한다.
+const MAX_LINE: usize = 4096;
+// 트레일러 줄의 최대 개수
+const MAX_TRAILERS: usize = 64;
+
+// chunked 본문을 읽어서 원래의 바이트만 돌려준다. 트레일러는 읽고 버린다.
+// 본문이 끝나면 0을 돌려주고, 본문 뒤의 바이트는 읽지 않는다.
+pub struct ChunkedReader<R> {
+    inner: R,
+    state: State,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum State {
+    Size,
+    // 조각에 남은 바이트 수
+    Data(u64),
+    // 조각 뒤의 \r\n
+    DataEnd,
+    Done,
+}
+
+impl<R: BufRead> ChunkedReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            state: State::Size,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    // 줄 끝의 \r\n(또는 \n)은 빼고 돌려준다.
+    fn read_line(&mut self) -> IoResult<String> {
+        let mut line = Vec::new();
+        loop {
+            let available = self.inner.fill_buf()?;
+            if available.is_empty() {
+                return Err(Error::from(ErrorKind::UnexpectedEof));
+            }
+            match available.iter().position(|b| *b == b'\n') {
+                Some(i) => {
+                    line.extend_from_slice(&available[..i]);
+                    self.inner.consume(i + 1);
+                    break;
+                }
+                None => {
+                    let len = available.len();
+                    line.extend_from_slice(available);
+                    self.inner.consume(len);
+                }
+            }
+            if line.len() > MAX_LINE {
+                return Err(invalid("chunk line is too long"));
+            }
+        }
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        String::from_utf8(line).map_err(|_| invalid("chunk line is not valid UTF-8"))
+    }
+
+    fn read_size(&mut self) -> IoResult<u64> {
+        let line = self.read_line()?;
+        // "1a;name=value" 처럼 확장이 붙을 수 있다. 확장은 쓰지 않는다.
+        let size = line
+            .split(';')
+            .next()
+            .unwrap_or("")
+            .trim_end_matches([' ', '\t']);
+        if size.is_empty() || !size.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid("invalid chunk size"));
+        }
+        u64::from_str_radix(size, 16).map_err(|_| invalid("chunk size is too large"))
+    }
+
+    fn skip_trailers(&mut self) -> IoResult<()> {
+        for _ in 0..MAX_TRAILERS {
+            if self.read_line()?.is_empty() {
+                return Ok(());
+            }
+        }
+        Err(invalid("too many trailer fields"))
+    }
+}
+
+impl<R: BufRead> Read for ChunkedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        loop {
+            match self.state {
+                State::Done => return Ok(0),
+                State::Size => match self.read_size()? {
+                    0 => {
+                        self.skip_trailers()?;
+                        self.state = State::Done;
+                    }
+                    size => self.state = State::Data(size),
+                },
+                State::Data(remaining) => {
+                    if buf.is_empty() {
+                        return Ok(0);
+                    }
+                    let max = buf.len().min(remaining.min(usize::MAX as u64) as usize);
+                    let len = self.inner.read(&mut buf[..max])?;
+                    if len == 0 {
+                        return Err(Error::from(ErrorKind::UnexpectedEof));
+                    }
+                    self.state = match remaining - len as u64 {
+                        0 => State::DataEnd,
+                        remaining => State::Data(remaining),
+                    };
+                    return Ok(len);
+                }
+                State::DataEnd => {
+                    if !self.read_line()?.is_empty() {
+                        return Err(invalid("missing CRLF after chunk data"));
+                    }
+                    self.state = State::Size;
+                }
+            }
+        }
+    }
+}
+
+// 쓰는 데이터를 조각으로 나눠서 보낸다. 다 쓴 뒤에는 꼭 finish()를 불러야 본문이 끝난다.
+// write() 한 번이 조각 하나가 되므로 작은 조각이 많이 생기지 않도록 BufWriter 같은 걸로 감싸서 쓰는 게 좋다.
+pub struct ChunkedWriter<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> ChunkedWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner }
+    }
+
+    // 길이가 0인 조각을 보내서 본문을 끝내고, 안쪽 writer를 돌려준다.
+    pub fn finish(mut self) -> IoResult<W> {
+        self.inner.write_all(b"0\r\n\r\n")?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for ChunkedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        // 길이가 0인 조각은 본문의 끝이라는 뜻이므로 빈 데이터는 보내지 않는다.
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        write!(self.inner, "{:x}\r\n", buf.len())?;
+        self.inner.write_all(buf)?;
+        self.inner.write_all(b"\r\n")?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.flush()
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message.to_string())
+}
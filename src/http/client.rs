@@ -0,0 +1,550 @@
+// 다른 HTTP/1.1 서버에 요청을 보내고 응답을 받는 블로킹 클라이언트. TLS(https)는 지원하지 않는다.
+//
+// 응답을 다 읽은 연결은 닫지 않고 호스트마다 모아 두었다가 다음 요청에 다시 쓴다.
+// Client는 여러 스레드에서 같이 써도 되므로 핸들러에 하나 만들어 두고 쓰면 된다.
+//
+// let client = Client::new().timeout(Duration::from_secs(5));
+// let response = client.get("http://127.0.0.1:3000/users")?;
+// let response = client.send(
+//     &ClientRequest::new(Method::POST, "http://127.0.0.1:3000/users")?
+//         .header("Content-Type", "application/json")
+//         .body(r#"{"name":"abc"}"#),
+// )?;
+use super::chunked::ChunkedReader;
+use super::response::{read_head, Framing, ResponseHead};
+use super::{Method, RequestBuilder, Response, ResponseError};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_MAX_IDLE_PER_HOST: usize = 8;
+const DEFAULT_MAX_RESPONSE_SIZE: usize = 16 * 1024 * 1024;
+// 상태 줄과 헤더를 합친 최대 크기
+const MAX_RESPONSE_HEAD: usize = 64 * 1024;
+
+pub struct Client {
+    connect_timeout: Duration,
+    timeout: Duration,
+    idle_timeout: Duration,
+    max_idle_per_host: usize,
+    max_response_size: usize,
+    // "호스트:포트" -> 응답을 다 읽고 쉬고 있는 연결들. 가장 최근에 쓴 연결이 뒤에 있다.
+    idle: Mutex<HashMap<String, Vec<Idle>>>,
+}
+
+struct Idle {
+    reader: BufReader<TcpStream>,
+    since: Instant,
+}
+
+// 보낼 요청. 연결할 주소와 RequestBuilder를 가지고 있다.
+#[derive(Debug, Clone)]
+pub struct ClientRequest {
+    // 연결할 "호스트:포트"
+    addr: String,
+    request: RequestBuilder,
+}
+
+#[derive(Debug)]
+pub enum ClientError {
+    InvalidUrl,
+    UnsupportedScheme,
+    InvalidHeader,
+    InvalidResponse(ResponseError),
+    // Upgrade를 보내지 않았는데 101 Switching Protocols가 왔다.
+    UnexpectedUpgrade,
+    ResponseTooLarge,
+    Io(io::Error),
+}
+
+impl Client {
+    pub fn new() -> Self {
+        Self {
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            timeout: DEFAULT_TIMEOUT,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            max_idle_per_host: DEFAULT_MAX_IDLE_PER_HOST,
+            max_response_size: DEFAULT_MAX_RESPONSE_SIZE,
+            idle: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    // 요청을 쓰거나 응답을 읽을 때 한 번에 기다리는 시간
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // 쉬고 있는 연결을 이 시간이 지나면 다시 쓰지 않고 닫는다.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    // 호스트마다 남겨둘 연결 수. 0이면 연결을 다시 쓰지 않고 요청마다 닫는다.
+    pub fn max_idle_per_host(mut self, max_idle_per_host: usize) -> Self {
+        self.max_idle_per_host = max_idle_per_host;
+        self
+    }
+
+    // 응답 본문은 메모리에 모두 읽으므로 크기를 제한한다.
+    pub fn max_response_size(mut self, max_response_size: usize) -> Self {
+        self.max_response_size = max_response_size;
+        self
+    }
+
+    pub fn get(&self, url: &str) -> Result<Response, ClientError> {
+        self.send(&ClientRequest::new(Method::GET, url)?)
+    }
+
+    // 응답의 상태 코드가 모르는 값이면 같은 종류의 x00 코드로 바꾼다. (299 -> 200)
+    pub fn send(&self, request: &ClientRequest) -> Result<Response, ClientError> {
+        request.validate()?;
+        if let Some(reader) = self.take_idle(&request.addr) {
+            match self.exchange(reader, request) {
+                Ok(response) => return Ok(response),
+                // 쉬는 동안 서버가 닫은 연결일 수 있다. 응답을 하나도 받지 못했으면 새 연결로 한 번 더 보낸다.
+                // 서버가 요청을 처리했는지 알 수 없으므로 다시 보내도 되는 메서드만 그렇게 한다. (RFC 9110 9.2.2)
+                Err(Exchange::Stale(e)) if !is_idempotent(request.request.method()) => {
+                    return Err(e)
+                }
+                Err(Exchange::Stale(_)) => {}
+                Err(Exchange::Failed(e)) => return Err(e),
+            }
+        }
+
+        let stream = self.connect(&request.addr)?;
+        match self.exchange(BufReader::new(stream), request) {
+            Ok(response) => Ok(response),
+            Err(Exchange::Stale(e) | Exchange::Failed(e)) => Err(e),
+        }
+    }
+
+    fn connect(&self, addr: &str) -> Result<TcpStream, ClientError> {
+        let mut last_error =
+            io::Error::new(ErrorKind::NotFound, format!("{} did not resolve", addr));
+        for addr in addr.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, self.connect_timeout) {
+                Ok(stream) => {
+                    stream.set_nodelay(true)?;
+                    return Ok(stream);
+                }
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error.into())
+    }
+
+    fn take_idle(&self, addr: &str) -> Option<BufReader<TcpStream>> {
+        let mut idle = self.idle.lock().unwrap();
+        let connections = idle.get_mut(addr)?;
+        while let Some(connection) = connections.pop() {
+            if connection.since.elapsed() < self.idle_timeout && is_open(&connection.reader) {
+                return Some(connection.reader);
+            }
+        }
+        None
+    }
+
+    fn put_idle(&self, addr: &str, reader: BufReader<TcpStream>) {
+        let mut idle = self.idle.lock().unwrap();
+        let connections = idle.entry(addr.to_string()).or_default();
+        connections.retain(|connection| connection.since.elapsed() < self.idle_timeout);
+        if connections.len() >= self.max_idle_per_host {
+            return;
+        }
+        connections.push(Idle {
+            reader,
+            since: Instant::now(),
+        });
+    }
+
+    fn exchange(
+        &self,
+        mut reader: BufReader<TcpStream>,
+        request: &ClientRequest,
+    ) -> Result<Response, Exchange> {
+        let stream = reader.get_ref();
+        stream
+            .set_read_timeout(Some(self.timeout))
+            .and_then(|_| stream.set_write_timeout(Some(self.timeout)))
+            .map_err(|e| Exchange::Failed(e.into()))?;
+
+        let keep_alive = self.max_idle_per_host > 0;
+        let mut writer = BufWriter::new(reader.get_ref());
+        request
+            .write_to(&mut writer, keep_alive)
+            .and_then(|_| writer.flush())
+            .map_err(|e| Exchange::Stale(e.into()))?;
+        drop(writer);
+
+        // 100 Continue 같은 중간 응답은 건너뛴다. Upgrade를 보내지 않으므로 101은 오면 안 된다.
+        let mut received = false;
+        let head = loop {
+            let head = match read_head(&mut reader, MAX_RESPONSE_HEAD) {
+                Ok(head) => head,
+                Err(e) if !received && is_stale(&e) => return Err(Exchange::Stale(e.into())),
+                Err(e) => return Err(Exchange::Failed(e.into())),
+            };
+            received = true;
+            let head = ResponseHead::parse(&head).map_err(|e| Exchange::Failed(e.into()))?;
+            match head.code {
+                101 => {
+                    return Err(Exchange::Failed(ClientError::UnexpectedUpgrade));
+                }
+                100..=199 => continue,
+                _ => break head,
+            }
+        };
+
+        let head_request = matches!(request.request.method(), Method::HEAD);
+        let framing = head
+            .framing(head_request)
+            .map_err(|e| Exchange::Failed(e.into()))?;
+        let persistent = head.is_persistent();
+        let mut response = head
+            .into_response()
+            .map_err(|e| Exchange::Failed(e.into()))?;
+        if framing != Framing::None {
+            let body = self
+                .read_body(&mut reader, framing)
+                .map_err(Exchange::Failed)?;
+            response.set_body(body);
+        }
+
+        // 연결이 닫힐 때까지가 본문인 응답은 연결을 다시 쓸 수 없다.
+        if keep_alive && persistent && framing != Framing::Close {
+            self.put_idle(&request.addr, reader);
+        }
+        Ok(response)
+    }
+
+    fn read_body(
+        &self,
+        reader: &mut BufReader<TcpStream>,
+        framing: Framing,
+    ) -> Result<Vec<u8>, ClientError> {
+        let limit = self.max_response_size as u64 + 1;
+        let mut body = Vec::new();
+        match framing {
+            Framing::None => {}
+            Framing::Length(length) => {
+                if length >= limit {
+                    return Err(ClientError::ResponseTooLarge);
+                }
+                reader.take(length).read_to_end(&mut body)?;
+                if (body.len() as u64) < length {
+                    return Err(io::Error::from(ErrorKind::UnexpectedEof).into());
+                }
+            }
+            Framing::Chunked => {
+                ChunkedReader::new(&mut *reader)
+                    .take(limit)
+                    .read_to_end(&mut body)?;
+            }
+            Framing::Close => {
+                reader.take(limit).read_to_end(&mut body)?;
+            }
+        }
+        if body.len() > self.max_response_size {
+            return Err(ClientError::ResponseTooLarge);
+        }
+        Ok(body)
+    }
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// 요청을 주고받다가 실패한 까닭
+enum Exchange {
+    // 응답을 하나도 받기 전에 연결이 끊겼다. 다시 쓰던 연결이라면 서버가 이미 닫은 연결이었을 수 있다.
+    Stale(ClientError),
+    Failed(ClientError),
+}
+
+impl ClientRequest {
+    // url은 "http://호스트[:포트][/경로][?쿼리]" 꼴이다. 경로의 %XX는 그대로 두고, 한글 같은 문자는 %XX로 바꿔서 보낸다.
+    pub fn new(method: Method, url: &str) -> Result<Self, ClientError> {
+        let (scheme, rest) = url.split_once("://").ok_or(ClientError::InvalidUrl)?;
+        if !scheme.eq_ignore_ascii_case("http") {
+            return Err(ClientError::UnsupportedScheme);
+        }
+        let (authority, target) = match rest.find(['/', '?']) {
+            Some(i) if rest[i..].starts_with('?') => (&rest[..i], format!("/{}", &rest[i..])),
+            Some(i) => (&rest[..i], rest[i..].to_string()),
+            None => (rest, "/".to_string()),
+        };
+        // 프래그먼트는 서버에 보내지 않는다.
+        let target = match target.split_once('#') {
+            Some((target, _)) => target.to_string(),
+            None => target,
+        };
+        if authority.contains('@') || target.bytes().any(|b| b.is_ascii_control() || b == b' ') {
+            return Err(ClientError::InvalidUrl);
+        }
+
+        // [::1]:8080 처럼 IPv6 주소는 대괄호로 감싼다.
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => (
+                host,
+                port.parse::<u16>().map_err(|_| ClientError::InvalidUrl)?,
+            ),
+            _ => (authority, 80),
+        };
+        if host.is_empty() || host.bytes().any(|b| b.is_ascii_control() || b == b' ') {
+            return Err(ClientError::InvalidUrl);
+        }
+        // Host 헤더에는 기본 포트(80)를 적지 않는다.
+        let host_header = match port {
+            80 => host.to_string(),
+            port => format!("{}:{}", host, port),
+        };
+        Ok(Self {
+            addr: format!("{}:{}", host, port),
+            request: RequestBuilder::new(method, &target).header("Host", host_header),
+        })
+    }
+
+    // Host, Content-Length, Connection은 보낼 때 채우므로 넣어도 무시한다.
+    pub fn header(mut self, name: &str, value: impl Into<String>) -> Self {
+        let replaced = ["Host", "Connection"]
+            .iter()
+            .any(|replaced| replaced.eq_ignore_ascii_case(name));
+        if !replaced {
+            self.request = self.request.header(name, value);
+        }
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.request = self.request.body(body);
+        self
+    }
+
+    fn validate(&self) -> Result<(), ClientError> {
+        match self.request.has_valid_headers() {
+            true => Ok(()),
+            false => Err(ClientError::InvalidHeader),
+        }
+    }
+
+    fn write_to(&self, writer: &mut impl Write, keep_alive: bool) -> io::Result<()> {
+        let user_agent = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+        let mut extra = Vec::new();
+        if !self
+            .request
+            .headers()
+            .any(|(name, _)| name.eq_ignore_ascii_case("User-Agent"))
+        {
+            extra.push(("User-Agent", user_agent));
+        }
+        if !keep_alive {
+            extra.push(("Connection", "close"));
+        }
+        self.request.write_with(writer, &extra)
+    }
+}
+
+impl Display for ClientError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Self::InvalidUrl => write!(f, "invalid URL"),
+            Self::UnsupportedScheme => write!(f, "only http:// URLs are supported"),
+            Self::InvalidHeader => write!(f, "invalid request header"),
+            Self::InvalidResponse(e) => write!(f, "invalid response : {}", e),
+            Self::UnexpectedUpgrade => write!(f, "unexpected 101 Switching Protocols"),
+            Self::ResponseTooLarge => write!(f, "response is too large"),
+            Self::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for ClientError {}
+
+impl From<io::Error> for ClientError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<ResponseError> for ClientError {
+    fn from(e: ResponseError) -> Self {
+        Self::InvalidResponse(e)
+    }
+}
+
+impl ClientError {
+    // 연결하거나 응답을 기다리다가 시간이 지났는지
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, Self::Io(e) if matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock))
+    }
+}
+
+fn is_idempotent(method: &Method) -> bool {
+    !matches!(method, Method::POST | Method::PATCH | Method::CONNECT)
+}
+
+fn is_stale(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        ErrorKind::UnexpectedEof | ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted
+    )
+}
+
+// 쉬고 있던 연결을 서버가 닫지 않았는지 기다리지 않고 확인한다. 읽을 게 있다면 닫혔거나(EOF)
+// 요청하지 않은 데이터가 온 것이므로 어느 쪽이든 다시 쓰지 않는다.
+fn is_open(reader: &BufReader<TcpStream>) -> bool {
+    if !reader.buffer().is_empty() {
+        return false;
+    }
+    let stream = reader.get_ref();
+    if stream.set_nonblocking(true).is_err() {
+        return false;
+    }
+    let open = matches!(stream.peek(&mut [0]), Err(e) if e.kind() == ErrorKind::WouldBlock);
+    stream.set_nonblocking(false).is_ok() && open
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{Request, StatusCode};
+    use crate::server::testing::TestServer;
+    use crate::server::{Handler, Server};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    struct Echo;
+
+    impl Handler for Echo {
+        fn handle_request(&self, request: &Request) -> Response {
+            let mut body = Vec::new();
+            if let Some(mut reader) = request.body() {
+                reader.read_to_end(&mut body).unwrap();
+            }
+            Response::from_bytes(StatusCode::Ok, body)
+        }
+    }
+
+    #[test]
+    fn sequential_posts_to_our_server() {
+        let server = TestServer::start(Server::new("127.0.0.1:0".to_string()), Echo);
+        let client = Client::new();
+        let url = format!("http://{}/echo", server.addr());
+        for body in ["first", "second", "third"] {
+            let request = ClientRequest::new(Method::POST, &url).unwrap().body(body);
+            let response = client.send(&request).unwrap();
+            assert_eq!(response.status_code(), StatusCode::Ok);
+            assert_eq!(response.header("Connection"), Some("close"));
+            assert_eq!(response.body(), Some(body.as_bytes()));
+            // 서버가 연결을 닫는다고 알렸으므로 다시 쓰려고 모아 두지 않는다.
+            assert!(client.idle.lock().unwrap().values().all(Vec::is_empty));
+        }
+    }
+
+    // 연결을 닫지 않는 업스트림. 연결마다 answers개의 요청에 "연결 번호:요청 번호"로 답하고,
+    // 그다음 요청은 읽기만 하고 답하지 않은 채 연결을 닫는다. 받은 연결 수를 함께 돌려준다.
+    fn keep_alive_upstream(answers: usize) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let connections = Arc::new(AtomicUsize::new(0));
+        let accepted = connections.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = stream.unwrap();
+                let connection = accepted.fetch_add(1, Ordering::SeqCst) + 1;
+                thread::spawn(move || {
+                    let mut reader = BufReader::new(stream);
+                    for request in 1.. {
+                        let head = match read_head(&mut reader, MAX_RESPONSE_HEAD) {
+                            Ok(head) => head,
+                            Err(_) => return,
+                        };
+                        let length = head
+                            .lines()
+                            .find_map(|line| line.strip_prefix("Content-Length: "))
+                            .map_or(0, |length| length.trim().parse().unwrap());
+                        reader
+                            .by_ref()
+                            .take(length)
+                            .read_to_end(&mut Vec::new())
+                            .unwrap();
+                        if request > answers {
+                            return;
+                        }
+                        let body = format!("{}:{}", connection, request);
+                        write!(
+                            reader.get_mut(),
+                            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                            body.len(),
+                            body
+                        )
+                        .unwrap();
+                    }
+                });
+            }
+        });
+        (addr, connections)
+    }
+
+    fn body(response: Result<Response, ClientError>) -> String {
+        String::from_utf8(response.unwrap().body().unwrap().to_vec()).unwrap()
+    }
+
+    #[test]
+    fn reuses_keep_alive_connections() {
+        let (addr, connections) = keep_alive_upstream(usize::MAX);
+        let client = Client::new();
+        let url = format!("http://{}/", addr);
+        assert_eq!(body(client.get(&url)), "1:1");
+        let request = ClientRequest::new(Method::POST, &url).unwrap().body("abc");
+        assert_eq!(body(client.send(&request)), "1:2");
+        assert_eq!(body(client.get(&url)), "1:3");
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn retries_idempotent_requests_on_stale_connections() {
+        let (addr, connections) = keep_alive_upstream(1);
+        let client = Client::new();
+        let url = format!("http://{}/", addr);
+        assert_eq!(body(client.get(&url)), "1:1");
+        // 다시 쓴 연결이 답 없이 닫혔으므로 새 연결로 한 번 더 보낸다.
+        assert_eq!(body(client.get(&url)), "2:1");
+        let request = ClientRequest::new(Method::PUT, &url).unwrap().body("abc");
+        assert_eq!(body(client.send(&request)), "3:1");
+        assert_eq!(connections.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn does_not_retry_post_on_stale_connections() {
+        let (addr, connections) = keep_alive_upstream(1);
+        let client = Client::new();
+        let url = format!("http://{}/", addr);
+        assert_eq!(body(client.get(&url)), "1:1");
+        // 서버가 요청을 처리했는지 알 수 없으므로 다시 보내지 않고 에러를 돌려준다.
+        let request = ClientRequest::new(Method::POST, &url).unwrap().body("abc");
+        assert!(matches!(client.send(&request), Err(ClientError::Io(_))));
+        // 다음 요청이 두 번째 연결의 첫 요청이므로 POST는 새 연결로 가지 않았다.
+        assert_eq!(body(client.get(&url)), "2:1");
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,641 @@
+use super::date;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use std::time::{Duration, SystemTime};
+
+// 요청의 Cookie 헤더에 담긴 이름=값 목록. QueryString처럼 요청을 읽은 버퍼를 가리킨다.
+// Cookie: session=abc; theme=dark
+#[derive(Debug, Default)]
+pub struct Cookies<'buf> {
+    data: HashMap<&'buf str, &'buf str>,
+}
+
+impl<'buf> Cookies<'buf> {
+    // Cookie::new()로 보낸 쿠키는 허용되지 않는 문자를 %XX로 인코딩해 두었으므로 다시 풀어서 돌려준다.
+    pub fn get(&self, name: &str) -> Option<Cow<'buf, str>> {
+        self.data.get(name).map(|value| percent_decode(value))
+    }
+
+    // 인코딩을 풀지 않은 그대로의 값
+    pub fn get_raw(&self, name: &str) -> Option<&'buf str> {
+        self.data.get(name).copied()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.data.contains_key(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&'buf str, &'buf str)> + '_ {
+        self.data.iter().map(|(name, value)| (*name, *value))
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    // CookieKey::sign()으로 서명한 쿠키. 서명이 맞지 않으면 누군가 값을 바꾼 것이므로 None이다.
+    pub fn signed(&self, name: &str, key: &CookieKey) -> Option<String> {
+        key.verify(name, &self.get(name)?)
+    }
+
+    // CookieKey::encrypt()로 암호화한 쿠키
+    pub fn private(&self, name: &str, key: &CookieKey) -> Option<String> {
+        key.decrypt(name, &self.get(name)?)
+    }
+
+    // 같은 이름이 여러 번 나오면 첫번째 것을 쓴다. 브라우저는 Path가 더 구체적인 쿠키를 먼저 보낸다. (RFC 6265 5.4)
+    pub(super) fn parse(&mut self, header: &'buf str) {
+        for pair in header.split(';') {
+            let (name, value) = match pair.split_once('=') {
+                Some((name, value)) => (name.trim(), value.trim()),
+                None => continue,
+            };
+            if name.is_empty() {
+                continue;
+            }
+            // 값은 큰따옴표로 감쌀 수 있고, 따옴표는 값에 포함되지 않는다.
+            let value = value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .unwrap_or(value);
+            self.data.entry(name).or_insert(value);
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    // 다른 사이트의 요청에도 보낸다. 브라우저는 Secure 쿠키에만 허용한다.
+    None,
+}
+
+impl SameSite {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Strict => "Strict",
+            Self::Lax => "Lax",
+            Self::None => "None",
+        }
+    }
+}
+
+// 응답에 담아 보낼 쿠키. Response::set_cookie()로 Set-Cookie 헤더를 만든다.
+//
+// let cookie = Cookie::new("theme", "dark").path("/").max_age(Duration::from_secs(86_400)).http_only(true);
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cookie {
+    name: String,
+    value: String,
+    path: Option<String>,
+    domain: Option<String>,
+    expires: Option<SystemTime>,
+    max_age: Option<Duration>,
+    secure: bool,
+    http_only: bool,
+    same_site: Option<SameSite>,
+}
+
+impl Cookie {
+    pub fn new(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            value: value.into(),
+            path: None,
+            domain: None,
+            expires: None,
+            max_age: None,
+            secure: false,
+            http_only: false,
+            same_site: None,
+        }
+    }
+
+    // 브라우저에 저장된 쿠키를 지우는 쿠키. 처음 보낼 때와 같은 Path와 Domain을 줘야 지워진다.
+    pub fn removal(name: impl Into<String>) -> Self {
+        Self::new(name, "")
+            .max_age(Duration::ZERO)
+            .expires(SystemTime::UNIX_EPOCH)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    pub fn domain(mut self, domain: impl Into<String>) -> Self {
+        self.domain = Some(domain.into());
+        self
+    }
+
+    pub fn expires(mut self, expires: SystemTime) -> Self {
+        self.expires = Some(expires);
+        self
+    }
+
+    // Expires보다 우선한다. 클라이언트 시계가 틀려도 영향을 받지 않는다.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    // 자바스크립트에서 읽을 수 없게 한다.
+    pub fn http_only(mut self, http_only: bool) -> Self {
+        self.http_only = http_only;
+        self
+    }
+
+    pub fn same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = Some(same_site);
+        self
+    }
+
+    fn with_value(mut self, value: String) -> Self {
+        self.value = value;
+        self
+    }
+
+    // Set-Cookie 헤더 값을 만든다. 값에 쓸 수 없는 문자는 %XX로 인코딩하고,
+    // 이름이나 속성이 잘못되어 브라우저가 무시할 쿠키라면 오류를 리턴한다.
+    pub fn to_header(&self) -> Result<String, CookieError> {
+        if self.name.is_empty() || !self.name.bytes().all(is_tchar) {
+            return Err(CookieError::InvalidName(self.name.clone()));
+        }
+
+        let mut header = format!("{}={}", self.name, percent_encode(&self.value));
+        if let Some(path) = &self.path {
+            if !path.starts_with('/') || !is_attribute_value(path) {
+                return Err(CookieError::InvalidAttribute("Path"));
+            }
+            header.push_str("; Path=");
+            header.push_str(path);
+        }
+        if let Some(domain) = &self.domain {
+            // 앞의 점은 예전 형식이고 브라우저가 무시한다.
+            let domain = domain.strip_prefix('.').unwrap_or(domain);
+            let valid = !domain.is_empty()
+                && domain
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'.');
+            if !valid {
+                return Err(CookieError::InvalidAttribute("Domain"));
+            }
+            header.push_str("; Domain=");
+            header.push_str(domain);
+        }
+        if let Some(expires) = self.expires {
+            header.push_str("; Expires=");
+            header.push_str(&date::format(expires));
+        }
+        if let Some(max_age) = self.max_age {
+            header.push_str(&format!("; Max-Age={}", max_age.as_secs()));
+        }
+        if self.secure {
+            header.push_str("; Secure");
+        }
+        if self.http_only {
+            header.push_str("; HttpOnly");
+        }
+        if let Some(same_site) = self.same_site {
+            if same_site == SameSite::None && !self.secure {
+                return Err(CookieError::InsecureSameSiteNone);
+            }
+            header.push_str("; SameSite=");
+            header.push_str(same_site.as_str());
+        }
+
+        // 이름 접두사로 브라우저에게 추가 규칙을 요구할 수 있다. 규칙을 어긴 쿠키는 브라우저가 저장하지 않는다.
+        if self.name.starts_with("__Secure-") && !self.secure {
+            return Err(CookieError::Prefix("__Secure- cookies must be Secure"));
+        }
+        if self.name.starts_with("__Host-")
+            && (!self.secure || self.domain.is_some() || self.path.as_deref() != Some("/"))
+        {
+            return Err(CookieError::Prefix(
+                "__Host- cookies must be Secure, have Path=/ and no Domain",
+            ));
+        }
+        Ok(header)
+    }
+}
+
+pub enum CookieError {
+    InvalidName(String),
+    // 세미콜론이나 제어 문자가 들어 있는 속성
+    InvalidAttribute(&'static str),
+    InsecureSameSiteNone,
+    Prefix(&'static str),
+    // CookieKey를 만들 비밀 키가 너무 짧다.
+    KeyTooShort,
+}
+
+impl Display for CookieError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::InvalidName(name) => write!(f, "invalid cookie name {:?}", name),
+            Self::InvalidAttribute(attribute) => write!(f, "invalid cookie {}", attribute),
+            Self::InsecureSameSiteNone => write!(f, "SameSite=None cookies must be Secure"),
+            Self::Prefix(message) => write!(f, "{}", message),
+            Self::KeyTooShort => write!(
+                f,
+                "cookie key must be at least {} bytes",
+                CookieKey::MIN_SECRET_LEN
+            ),
+        }
+    }
+}
+
+impl Debug for CookieError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}", self)
+    }
+}
+
+impl Error for CookieError {}
+
+// 서버만 아는 비밀 키로 쿠키를 서명하거나 암호화한다.
+// 서명한 쿠키는 클라이언트가 읽을 수는 있지만 바꾸면 알 수 있고, 암호화한 쿠키는 읽을 수도 없다.
+// 서버를 다시 시작해도 쿠키가 유효하려면 generate() 대신 설정에 둔 같은 비밀 키로 만들어야 한다.
+pub struct CookieKey {
+    signing: hmac::Key,
+    encryption: LessSafeKey,
+}
+
+impl CookieKey {
+    pub const MIN_SECRET_LEN: usize = 32;
+
+    // 비밀 키 하나에서 서명용 키와 암호화용 키를 따로 만든다. 한 키를 두 용도로 쓰지 않기 위해서다.
+    pub fn new(secret: &[u8]) -> Result<Self, CookieError> {
+        if secret.len() < Self::MIN_SECRET_LEN {
+            return Err(CookieError::KeyTooShort);
+        }
+        let master = hmac::Key::new(hmac::HMAC_SHA256, secret);
+        let signing = hmac::sign(&master, b"cookie signing");
+        let encryption = hmac::sign(&master, b"cookie encryption");
+        let encryption = UnboundKey::new(&AES_256_GCM, encryption.as_ref())
+            .expect("HMAC-SHA256 output is a valid AES-256 key");
+        Ok(Self {
+            signing: hmac::Key::new(hmac::HMAC_SHA256, signing.as_ref()),
+            encryption: LessSafeKey::new(encryption),
+        })
+    }
+
+    // 무작위 키. 프로세스가 끝나면 이 키로 만든 쿠키는 모두 쓸 수 없게 된다.
+    pub fn generate() -> Self {
+        let mut secret = [0; 32];
+        SystemRandom::new()
+            .fill(&mut secret)
+            .expect("system random number generator failed");
+        Self::new(&secret).expect("generated key is long enough")
+    }
+
+    // 값 뒤에 "이름=값"의 HMAC-SHA256을 붙인다. 이름도 서명해서 다른 쿠키의 값으로 옮겨 쓰지 못하게 한다.
+    pub fn sign(&self, cookie: Cookie) -> Cookie {
+        let tag = hmac::sign(
+            &self.signing,
+            signed_message(&cookie.name, &cookie.value).as_bytes(),
+        );
+        let value = format!("{}.{}", cookie.value, URL_SAFE_NO_PAD.encode(tag.as_ref()));
+        cookie.with_value(value)
+    }
+
+    pub fn verify(&self, name: &str, value: &str) -> Option<String> {
+        let (value, tag) = value.rsplit_once('.')?;
+        let tag = URL_SAFE_NO_PAD.decode(tag).ok()?;
+        // verify()는 비교 시간이 일정해서 서명을 한 바이트씩 맞춰보는 공격을 막는다.
+        hmac::verify(&self.signing, signed_message(name, value).as_bytes(), &tag).ok()?;
+        Some(value.to_string())
+    }
+
+    // AES-256-GCM으로 암호화한다. 쿠키 이름을 추가 인증 데이터로 넣어서 다른 쿠키로 옮겨 쓰지 못하게 한다.
+    pub fn encrypt(&self, cookie: Cookie) -> Cookie {
+        let mut nonce = [0; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .expect("system random number generator failed");
+        let mut data = cookie.value.as_bytes().to_vec();
+        self.encryption
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(cookie.name.as_bytes()),
+                &mut data,
+            )
+            .expect("cookie value is too large to encrypt");
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&data);
+        let value = URL_SAFE_NO_PAD.encode(sealed);
+        cookie.with_value(value)
+    }
+
+    pub fn decrypt(&self, name: &str, value: &str) -> Option<String> {
+        let sealed = URL_SAFE_NO_PAD.decode(value).ok()?;
+        if sealed.len() < NONCE_LEN + AES_256_GCM.tag_len() {
+            return None;
+        }
+        let (nonce, data) = sealed.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).ok()?;
+        let mut data = data.to_vec();
+        let plain = self
+            .encryption
+            .open_in_place(nonce, Aad::from(name.as_bytes()), &mut data)
+            .ok()?;
+        String::from_utf8(plain.to_vec()).ok()
+    }
+}
+
+impl Debug for CookieKey {
+    // 키가 로그에 찍히면 안 된다.
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "CookieKey(..)")
+    }
+}
+
+fn signed_message(name: &str, value: &str) -> String {
+    format!("{}={}", name, value)
+}
+
+// 토큰에 쓸 수 있는 문자 (RFC 9110 5.6.2)
+fn is_tchar(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+// 세미콜론과 제어 문자만 아니면 된다. (RFC 6265 4.1.1의 av-octet)
+fn is_attribute_value(value: &str) -> bool {
+    value
+        .bytes()
+        .all(|b| (0x20..0x7f).contains(&b) && b != b';')
+}
+
+// 쿠키 값에 그대로 쓸 수 있는 문자 (RFC 6265 4.1.1의 cookie-octet). %는 인코딩한 문자와 구분하기 위해 뺀다.
+fn is_cookie_octet(b: u8) -> bool {
+    matches!(b, 0x21 | 0x23..=0x2b | 0x2d..=0x3a | 0x3c..=0x5b | 0x5d..=0x7e) && b != b'%'
+}
+
+fn percent_encode(value: &str) -> Cow<'_, str> {
+    if value.bytes().all(is_cookie_octet) {
+        return Cow::Borrowed(value);
+    }
+    let mut encoded = String::with_capacity(value.len() * 3);
+    for b in value.bytes() {
+        if is_cookie_octet(b) {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    Cow::Owned(encoded)
+}
+
+// 잘못된 %XX는 그대로 둔다. 다른 프로그램이 인코딩하지 않고 넣은 %일 수 있다.
+fn percent_decode(value: &str) -> Cow<'_, str> {
+    if !value.contains('%') {
+        return Cow::Borrowed(value);
+    }
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                decoded.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+    match String::from_utf8(decoded) {
+        Ok(decoded) => Cow::Owned(decoded),
+        Err(e) => Cow::Owned(String::from_utf8_lossy(e.as_bytes()).into_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(header: &str) -> Cookies<'_> {
+        let mut cookies = Cookies::default();
+        cookies.parse(header);
+        cookies
+    }
+
+    #[test]
+    fn parses_cookie_headers() {
+        let cookies =
+            parse(" session=abc ; theme=\"dark mode\"; session=second; flag; =empty; a=b=c");
+        assert_eq!(cookies.len(), 3);
+        assert_eq!(cookies.get_raw("session"), Some("abc"));
+        assert_eq!(cookies.get_raw("theme"), Some("dark mode"));
+        assert_eq!(cookies.get_raw("a"), Some("b=c"));
+        assert!(!cookies.contains("flag"));
+        // 따옴표가 한쪽만 있으면 값의 일부다.
+        assert_eq!(parse("x=\"half").get_raw("x"), Some("\"half"));
+    }
+
+    #[test]
+    fn builds_set_cookie_headers() {
+        let cookie = Cookie::new("theme", "dark")
+            .path("/app")
+            .domain(".example.test")
+            .expires(SystemTime::UNIX_EPOCH + Duration::from_secs(784_111_777))
+            .max_age(Duration::from_secs(3600))
+            .secure(true)
+            .http_only(true)
+            .same_site(SameSite::Strict);
+        assert_eq!(
+            cookie.to_header().unwrap(),
+            "theme=dark; Path=/app; Domain=example.test; \
+             Expires=Sun, 06 Nov 1994 08:49:37 GMT; Max-Age=3600; Secure; HttpOnly; SameSite=Strict"
+        );
+        assert_eq!(
+            Cookie::removal("sid").path("/").to_header().unwrap(),
+            "sid=; Path=/; Expires=Thu, 01 Jan 1970 00:00:00 GMT; Max-Age=0"
+        );
+    }
+
+    #[test]
+    fn rejects_cookies_browsers_would_ignore() {
+        let invalid = [
+            (Cookie::new("", "v"), "invalid cookie name \"\""),
+            (Cookie::new("a b", "v"), "invalid cookie name \"a b\""),
+            (Cookie::new("a;b", "v"), "invalid cookie name \"a;b\""),
+            (
+                Cookie::new("a", "v").path("/x;Secure"),
+                "invalid cookie Path",
+            ),
+            (
+                Cookie::new("a", "v").path("relative"),
+                "invalid cookie Path",
+            ),
+            (Cookie::new("a", "v").path("/x\ny"), "invalid cookie Path"),
+            (
+                Cookie::new("a", "v").domain("evil.test; Path=/"),
+                "invalid cookie Domain",
+            ),
+            (Cookie::new("a", "v").domain("."), "invalid cookie Domain"),
+            (
+                Cookie::new("a", "v").same_site(SameSite::None),
+                "SameSite=None cookies must be Secure",
+            ),
+            (
+                Cookie::new("__Secure-a", "v"),
+                "__Secure- cookies must be Secure",
+            ),
+            (
+                Cookie::new("__Host-a", "v").path("/"),
+                "__Host- cookies must be Secure, have Path=/ and no Domain",
+            ),
+            (
+                Cookie::new("__Host-a", "v").secure(true).path("/app"),
+                "__Host- cookies must be Secure, have Path=/ and no Domain",
+            ),
+            (
+                Cookie::new("__Host-a", "v")
+                    .secure(true)
+                    .path("/")
+                    .domain("example.test"),
+                "__Host- cookies must be Secure, have Path=/ and no Domain",
+            ),
+        ];
+        for (cookie, message) in invalid {
+            assert_eq!(
+                cookie.to_header().unwrap_err().to_string(),
+                message,
+                "{:?}",
+                cookie
+            );
+        }
+
+        let valid = [
+            Cookie::new("a", "v").secure(true).same_site(SameSite::None),
+            Cookie::new("__Secure-a", "v")
+                .secure(true)
+                .domain("example.test"),
+            Cookie::new("__Host-a", "v").secure(true).path("/"),
+        ];
+        for cookie in valid {
+            assert!(cookie.to_header().is_ok(), "{:?}", cookie);
+        }
+    }
+
+    #[test]
+    fn percent_encodes_values() {
+        for value in [
+            "plain",
+            "a b;c,d\"e\\f",
+            "100%",
+            "%41",
+            "한글 ✓",
+            "",
+            "x\r\ny",
+        ] {
+            let header = Cookie::new("v", value).to_header().unwrap();
+            let encoded = header.strip_prefix("v=").unwrap();
+            assert!(
+                encoded.bytes().all(|b| is_cookie_octet(b) || b == b'%'),
+                "{}",
+                encoded
+            );
+            assert_eq!(
+                parse(&header).get("v").as_deref(),
+                Some(value),
+                "{}",
+                header
+            );
+        }
+        assert_eq!(percent_encode("a b"), "a%20b");
+        // 잘못된 %XX는 그대로 둔다.
+        assert_eq!(percent_decode("50%-off%2"), "50%-off%2");
+        assert_eq!(percent_decode("%ZZ%41"), "%ZZA");
+    }
+
+    #[test]
+    fn signed_cookies_detect_tampering() {
+        let key = CookieKey::new(&[7; 32]).unwrap();
+        let cookie = key.sign(Cookie::new("user", "alice"));
+        assert!(cookie.value().starts_with("alice."));
+        let header = cookie.to_header().unwrap();
+        assert_eq!(
+            parse(&header).signed("user", &key).as_deref(),
+            Some("alice")
+        );
+
+        let tag = cookie.value().strip_prefix("alice.").unwrap();
+        assert_eq!(key.verify("user", &format!("admin.{}", tag)), None);
+        assert_eq!(key.verify("user", "alice"), None);
+        assert_eq!(key.verify("user", &format!("alice.{}x", tag)), None);
+        // 같은 값이라도 다른 쿠키 이름으로는 쓸 수 없다.
+        assert_eq!(key.verify("role", cookie.value()), None);
+        // 다른 키로 서명한 쿠키도 받지 않는다.
+        let other = CookieKey::new(&[8; 32]).unwrap();
+        assert_eq!(other.verify("user", cookie.value()), None);
+    }
+
+    #[test]
+    fn private_cookies_are_encrypted() {
+        let key = CookieKey::generate();
+        let cookie = key.encrypt(Cookie::new("cart", "apples=3; pears=1"));
+        assert!(!cookie.value().contains("apples"));
+        // 같은 값도 매번 다르게 암호화된다.
+        assert_ne!(
+            key.encrypt(Cookie::new("cart", "x")).value(),
+            key.encrypt(Cookie::new("cart", "x")).value()
+        );
+        let header = cookie.to_header().unwrap();
+        assert_eq!(
+            parse(&header).private("cart", &key).as_deref(),
+            Some("apples=3; pears=1")
+        );
+
+        let mut tampered = URL_SAFE_NO_PAD.decode(cookie.value()).unwrap();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert_eq!(key.decrypt("cart", &URL_SAFE_NO_PAD.encode(tampered)), None);
+        assert_eq!(key.decrypt("wishlist", cookie.value()), None);
+        assert_eq!(key.decrypt("cart", "short"), None);
+        assert_eq!(key.decrypt("cart", "not base64!"), None);
+        assert_eq!(CookieKey::generate().decrypt("cart", cookie.value()), None);
+    }
+
+    #[test]
+    fn keys_must_be_long_enough() {
+        assert!(matches!(
+            CookieKey::new(&[0; 31]),
+            Err(CookieError::KeyTooShort)
+        ));
+        assert_eq!(format!("{:?}", CookieKey::generate()), "CookieKey(..)");
+    }
+}
@@ -0,0 +1,46 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+// HTTP 헤더에 쓰는 날짜 형식(IMF-fixdate). 항상 GMT로 쓴다. (RFC 9110 5.6.7)
+// "Sun, 06 Nov 1994 08:49:37 GMT"
+pub fn format(time: SystemTime) -> String {
+    // 1970년 이전 시각은 쓸 일이 없으므로 1970년으로 맞춘다.
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let days = secs / 86_400;
+    let secs_of_day = secs % 86_400;
+    let (year, month, day) = civil_from_days(days);
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        // 1970년 1월 1일은 목요일이다.
+        DAYS[(days % 7) as usize],
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+// 1970년 1월 1일부터 지난 날 수를 (년, 월, 일)로 바꾼다.
+// 3월부터 시작하는 400년 주기로 계산하면 윤년 처리가 간단해진다. (Howard Hinnant의 civil_from_days)
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
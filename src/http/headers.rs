@@ -0,0 +1,167 @@
+use super::ParseError;
+use crate::config::is_token;
+use std::convert::TryFrom;
+use std::io::{Result as IoResult, Write};
+
+// 요청 헤더들. QueryString처럼 이름과 값 모두 요청을 읽은 버퍼를 가리키는 슬라이스다.
+// 헤더 이름은 대소문자를 구분하지 않고, 같은 이름이 여러 번 나올 수 있기 때문에 HashMap 대신 Vec에 순서대로 담는다.
+#[derive(Debug, Default, Clone)]
+pub struct Headers<'buf> {
+    data: Vec<(&'buf str, &'buf str)>,
+}
+
+impl<'buf> Headers<'buf> {
+    // 같은 이름의 헤더가 여러 개라면 첫번째 값을 리턴한다.
+    pub fn get(&self, name: &str) -> Option<&'buf str> {
+        self.data
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| *value)
+    }
+
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'buf str> + 'a {
+        self.data
+            .iter()
+            .filter(move |(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| *value)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&'buf str, &'buf str)> + '_ {
+        self.data.iter().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+// 요청을 다시 쓸 때 헤더 줄들을 쓴다. 본문의 길이는 실제로 보내는 본문에 맞게 따로 적어야 하므로
+// Content-Length와 Transfer-Encoding은 건너뛴다.
+pub(crate) fn write_without_framing<'a>(
+    writer: &mut impl Write,
+    headers: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> IoResult<()> {
+    for (name, value) in headers {
+        let framing = ["Content-Length", "Transfer-Encoding"]
+            .iter()
+            .any(|framing| framing.eq_ignore_ascii_case(name));
+        if !framing {
+            write!(writer, "{}: {}\r\n", name, value)?;
+        }
+    }
+    Ok(())
+}
+
+// Host: localhost:4000\r\n
+// Accept: */*\r\n
+// 요청 줄 다음부터 빈 줄 전까지의 헤더 줄들을 받는다.
+impl<'buf> TryFrom<&'buf str> for Headers<'buf> {
+    type Error = ParseError;
+
+    fn try_from(s: &'buf str) -> Result<Self, Self::Error> {
+        let mut data = Vec::new();
+        for line in s.split("\r\n") {
+            // 헤더는 빈 줄로 끝난다.
+            if line.is_empty() {
+                break;
+            }
+
+            // 이름과 콜론 사이에는 공백이 올 수 없고, 값 앞뒤의 공백은 무시한다.
+            let (name, value) = line.split_once(':').ok_or(ParseError::InvalidHeader)?;
+            // 값에 남은 LF나 CR은 프록시가 요청을 다시 쓸 때 줄바꿈이 되어 헤더를 하나 더 끼워 넣을 수 있다.
+            // 탭을 뺀 제어 문자는 모두 거절한다. (RFC 9110 5.5)
+            if !is_token(name) || value.bytes().any(|b| b != b'\t' && b.is_ascii_control()) {
+                return Err(ParseError::InvalidHeader);
+            }
+            data.push((name, value.trim_matches([' ', '\t'])));
+        }
+
+        Ok(Headers { data })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::Request;
+
+    #[test]
+    fn parses_headers_in_order() {
+        let headers =
+            Headers::try_from("Host: localhost\r\nX-A:\t1 \r\nx-a: 2\r\nEmpty:\r\n\r\n").unwrap();
+        assert_eq!(headers.len(), 4);
+        assert_eq!(headers.get("host"), Some("localhost"));
+        assert_eq!(headers.get_all("X-A").collect::<Vec<_>>(), ["1", "2"]);
+        assert_eq!(headers.get("empty"), Some(""));
+        // 값 안의 탭과 UTF-8 글자는 그대로 둔다.
+        let headers = Headers::try_from("X-Name: a\tb é\r\n").unwrap();
+        assert_eq!(headers.get("X-Name"), Some("a\tb é"));
+    }
+
+    #[test]
+    fn rejects_control_bytes_in_values() {
+        for line in [
+            "X: a\nTransfer-Encoding: chunked",
+            "X: a\rTransfer-Encoding: chunked",
+            "X: a\0b",
+            "X: a\x01b",
+            "X: a\x1bb",
+            "X: a\x7fb",
+        ] {
+            let head = format!("{}\r\n\r\n", line);
+            assert!(
+                matches!(
+                    Headers::try_from(head.as_str()),
+                    Err(ParseError::InvalidHeader)
+                ),
+                "accepted {:?}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_names_that_are_not_tokens() {
+        for line in [
+            ": value",
+            " X: value",
+            "X : value",
+            "X\t: value",
+            "X Y: value",
+            "X\nY: value",
+            "X\0: value",
+            "(X): value",
+            "X/Y: value",
+            "Ünicode: value",
+            "no colon",
+        ] {
+            let head = format!("{}\r\n\r\n", line);
+            assert!(
+                matches!(
+                    Headers::try_from(head.as_str()),
+                    Err(ParseError::InvalidHeader)
+                ),
+                "accepted {:?}",
+                line
+            );
+        }
+    }
+
+    // 헤더를 하나 더 끼워 넣으려는 요청은 핸들러나 프록시까지 가지 않는다.
+    #[test]
+    fn smuggled_header_is_a_bad_request() {
+        let head = b"POST / HTTP/1.1\r\nHost: a\r\nX: a\nTransfer-Encoding: chunked\r\n\r\n";
+        assert!(matches!(
+            Request::try_from(&head[..]),
+            Err(ParseError::InvalidHeader)
+        ));
+    }
+}
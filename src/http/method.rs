@@ -1,6 +1,7 @@
 use std::str::FromStr;
 
-#[derive(Debug)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone)]
 pub enum Method {
     GET,
     DELETE,
@@ -15,6 +16,23 @@ pub enum Method {
 
 // FromStr은 생명주기를 가지지 않기에 간단한 enum은 파싱할 수 있지만, 참조나 구조체 그리고 참조가 포함된
 // enum은 파싱할수 없다.
+impl Method {
+    // 요청 줄에 쓰는 이름. 요청을 다시 보낼 때 쓴다.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::GET => "GET",
+            Self::DELETE => "DELETE",
+            Self::POST => "POST",
+            Self::PUT => "PUT",
+            Self::HEAD => "HEAD",
+            Self::CONNECT => "CONNECT",
+            Self::OPTIONS => "OPTIONS",
+            Self::TRACE => "TRACE",
+            Self::PATCH => "PATCH",
+        }
+    }
+}
+
 impl FromStr for Method {
     type Err = MethodError;
 
@@ -1,12 +1,30 @@
+pub use body::Body;
+pub use client::Client;
+pub use cookie::{Cookie, Cookies};
+pub use headers::Headers;
 pub use method::Method;
-pub use query_string::{QueryString, Value as QueryStringValue};
+pub use query_string::QueryString;
+// 서버 안에서는 쓰지 않지만 쿼리 값을 꺼내 쓰는 코드가 이름으로 쓸 수 있게 내보낸다.
+#[allow(unused_imports)]
+pub use query_string::Value as QueryStringValue;
 pub use request::ParseError;
 pub use request::Request;
+pub use request_builder::RequestBuilder;
 pub use response::Response;
+pub use response::ResponseError;
 pub use status_code::StatusCode;
 
+pub mod body;
+pub mod chunked;
+pub mod client;
+pub mod cookie;
+pub mod date;
+pub mod headers;
 pub mod method;
+pub mod negotiate;
+pub mod path;
 pub mod query_string;
 pub mod request;
+pub mod request_builder;
 pub mod response;
 pub mod status_code;
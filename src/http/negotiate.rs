@@ -0,0 +1,300 @@
+// Accept, Accept-Language, Accept-Charset, Accept-Encoding 헤더로 내용 협상을 한다. (RFC 9110 12.5)
+//
+// 헤더는 "text/html, application/json;q=0.9, */*;q=0.1" 처럼 값마다 q값(0~1)을 붙인 목록이다.
+// 서버가 줄 수 있는 것마다 가장 구체적으로 맞는 항목의 q값을 찾아서, q값이 가장 높은 것을 고른다.
+// q값이 같으면 서버가 나열한 순서대로 앞에 있는 것을 고르고, q=0은 받을 수 없다는 뜻이다.
+
+// 헤더 목록의 항목 하나
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Preference<'buf> {
+    value: &'buf str,
+    // q 앞에 있는 파라미터들. Accept의 미디어 타입 파라미터(level=1 등)만 의미가 있다.
+    params: Vec<(&'buf str, &'buf str)>,
+    // 비교하기 쉽게 1000배 한 정수로 저장한다. 소수점 아래 세 자리까지만 쓸 수 있기 때문이다.
+    quality: u16,
+}
+
+impl<'buf> Preference<'buf> {
+    pub fn value(&self) -> &'buf str {
+        self.value
+    }
+
+    pub fn params(&self) -> &[(&'buf str, &'buf str)] {
+        &self.params
+    }
+
+    pub fn quality(&self) -> f32 {
+        self.quality as f32 / 1000.0
+    }
+}
+
+// 쉼표로 나뉜 항목들을 읽는다. q값을 해석할 수 없는 항목은 버린다.
+pub(super) fn parse(value: &str) -> Vec<Preference<'_>> {
+    value
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            let value = parts.next()?.trim();
+            if value.is_empty() {
+                return None;
+            }
+            let mut params = Vec::new();
+            let mut quality = 1000;
+            for param in parts {
+                let (name, value) = match param.split_once('=') {
+                    Some((name, value)) => (name.trim(), value.trim().trim_matches('"')),
+                    None => continue,
+                };
+                if name.eq_ignore_ascii_case("q") {
+                    quality = parse_quality(value)?;
+                    // q 뒤에 오는 것은 확장 파라미터라서 미디어 타입 비교에 쓰지 않는다.
+                    break;
+                }
+                params.push((name, value));
+            }
+            Some(Preference {
+                value,
+                params,
+                quality,
+            })
+        })
+        .collect()
+}
+
+fn parse_quality(value: &str) -> Option<u16> {
+    let quality: f32 = value.parse().ok()?;
+    if !(0.0..=1.0).contains(&quality) {
+        return None;
+    }
+    Some((quality * 1000.0).round() as u16)
+}
+
+// 협상할 헤더의 종류마다 항목이 어떤 값에 맞는지, 얼마나 구체적으로 맞는지가 다르다.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(super) enum Kind {
+    MediaType,
+    Language,
+    Charset,
+    Encoding,
+}
+
+impl Kind {
+    // 항목이 available에 맞으면 구체적인 정도를 리턴한다. 숫자가 클수록 구체적이다.
+    fn specificity(self, preference: &Preference, available: &str) -> Option<usize> {
+        let range = preference.value;
+        match self {
+            Self::MediaType => media_range_specificity(preference, available),
+            // "en"은 "en-US"에도 맞는다. 부분 태그가 많을수록 구체적이다. (RFC 4647 3.3.1)
+            Self::Language => {
+                if range == "*" {
+                    return Some(0);
+                }
+                let tag = available.as_bytes();
+                let matches = tag.len() >= range.len()
+                    && tag[..range.len()].eq_ignore_ascii_case(range.as_bytes())
+                    && (tag.len() == range.len() || tag[range.len()] == b'-');
+                matches.then(|| range.split('-').count())
+            }
+            Self::Charset => match range {
+                "*" => Some(0),
+                range if range.eq_ignore_ascii_case(available) => Some(1),
+                _ => None,
+            },
+            Self::Encoding => match range {
+                "*" => Some(0),
+                range if encoding_name(range).eq_ignore_ascii_case(encoding_name(available)) => {
+                    Some(1)
+                }
+                _ => None,
+            },
+        }
+    }
+}
+
+// x-gzip, x-compress는 예전 이름이지만 아직 보내는 클라이언트가 있다.
+fn encoding_name(token: &str) -> &str {
+    if token.eq_ignore_ascii_case("x-gzip") {
+        "gzip"
+    } else if token.eq_ignore_ascii_case("x-compress") {
+        "compress"
+    } else {
+        token
+    }
+}
+
+// */* < text/* < text/html < text/html;level=1 순서로 구체적이다.
+// 범위에 파라미터가 있으면 available에도 같은 파라미터가 모두 있어야 맞는다.
+fn media_range_specificity(preference: &Preference, available: &str) -> Option<usize> {
+    let (range_type, range_subtype) = preference.value.split_once('/')?;
+    let mut available_parts = available.split(';');
+    let (available_type, available_subtype) = available_parts.next()?.trim().split_once('/')?;
+    let available_params: Vec<(&str, &str)> = available_parts
+        .filter_map(|param| {
+            let (name, value) = param.split_once('=')?;
+            Some((name.trim(), value.trim().trim_matches('"')))
+        })
+        .collect();
+
+    match (range_type.trim(), range_subtype.trim()) {
+        ("*", "*") => Some(0),
+        (range_type, "*") if range_type.eq_ignore_ascii_case(available_type) => Some(1),
+        (range_type, range_subtype)
+            if range_type.eq_ignore_ascii_case(available_type)
+                && range_subtype.eq_ignore_ascii_case(available_subtype) =>
+        {
+            let params_match = preference.params.iter().all(|(name, value)| {
+                available_params
+                    .iter()
+                    .any(|(n, v)| n.eq_ignore_ascii_case(name) && v.eq_ignore_ascii_case(value))
+            });
+            params_match.then_some(2 + preference.params.len())
+        }
+        _ => None,
+    }
+}
+
+// header가 None이면 클라이언트가 무엇이든 받는다는 뜻이므로 available의 첫번째 것을 고른다.
+pub(super) fn negotiate<'a>(
+    kind: Kind,
+    header: Option<&str>,
+    available: &[&'a str],
+) -> Option<&'a str> {
+    let header = match header {
+        Some(header) => header,
+        None => return available.first().copied(),
+    };
+    let preferences = parse(header);
+
+    let mut best: Option<(&'a str, u16)> = None;
+    for &candidate in available {
+        let quality = preferences
+            .iter()
+            .filter_map(|preference| {
+                let specificity = kind.specificity(preference, candidate)?;
+                Some((specificity, preference.quality))
+            })
+            // 가장 구체적인 항목의 q값을 쓴다. 구체적인 정도가 같다면 앞에 나온 항목을 쓴다.
+            .fold(
+                None,
+                |best: Option<(usize, u16)>, (specificity, quality)| match best {
+                    Some((best_specificity, _)) if best_specificity >= specificity => best,
+                    _ => Some((specificity, quality)),
+                },
+            )
+            .map(|(_, quality)| quality);
+        // identity는 목록에서 따로 빼지 않았다면 언제나 받을 수 있다. (RFC 9110 12.5.3)
+        let quality = match quality {
+            None if kind == Kind::Encoding && candidate.eq_ignore_ascii_case("identity") => 1000,
+            quality => quality.unwrap_or(0),
+        };
+        if quality > 0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+            best = Some((candidate, quality));
+        }
+    }
+    best.map(|(candidate, _)| candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn media(header: &str, available: &[&'static str]) -> Option<&'static str> {
+        negotiate(Kind::MediaType, Some(header), available)
+    }
+
+    #[test]
+    fn parses_values_params_and_quality() {
+        let preferences = parse("text/html;level=1;q=0.5;ext=x, , en;q=abc, */*;Q=0");
+        assert_eq!(preferences.len(), 2);
+        assert_eq!(preferences[0].value(), "text/html");
+        // q 뒤의 파라미터는 버린다.
+        assert_eq!(preferences[0].params(), &[("level", "1")]);
+        assert_eq!(preferences[0].quality(), 0.5);
+        assert_eq!(preferences[1].value(), "*/*");
+        assert_eq!(preferences[1].quality(), 0.0);
+        assert!(parse("a;q=1.5, b;q=-1").is_empty());
+    }
+
+    #[test]
+    fn most_specific_range_wins() {
+        let header = "*/*;q=0.1, text/*;q=0.3, text/html;q=0.5, text/html;level=1;q=0.9";
+        let available = ["image/png", "text/plain", "text/html", "text/html;level=1"];
+        assert_eq!(media(header, &available), Some("text/html;level=1"));
+        assert_eq!(media(header, &available[..3]), Some("text/html"));
+        assert_eq!(media(header, &available[..2]), Some("text/plain"));
+        assert_eq!(media(header, &available[..1]), Some("image/png"));
+        // level=2는 level=1 항목에 맞지 않으므로 text/html의 q값을 쓴다.
+        assert_eq!(
+            media(header, &["text/plain", "text/html;level=2"]),
+            Some("text/html;level=2")
+        );
+        // 같은 q값이면 서버가 앞에 둔 것을 고른다.
+        assert_eq!(
+            media("text/*", &["text/plain", "text/html"]),
+            Some("text/plain")
+        );
+    }
+
+    #[test]
+    fn zero_quality_excludes() {
+        assert_eq!(
+            media("text/html;q=0, */*", &["text/html", "application/json"]),
+            Some("application/json")
+        );
+        assert_eq!(media("text/*;q=0", &["text/html", "text/plain"]), None);
+        // 더 구체적인 항목이 q=0을 덮어쓴다.
+        assert_eq!(
+            media("text/*;q=0, text/plain", &["text/html", "text/plain"]),
+            Some("text/plain")
+        );
+        assert_eq!(media("application/json", &["text/html"]), None);
+    }
+
+    #[test]
+    fn language_prefixes() {
+        let language =
+            |header, available: &[&'static str]| negotiate(Kind::Language, Some(header), available);
+        assert_eq!(language("en", &["ko", "en-US"]), Some("en-US"));
+        assert_eq!(language("EN-us", &["ko", "en-US"]), Some("en-US"));
+        // 접두사는 부분 태그 단위로만 맞는다.
+        assert_eq!(language("en", &["eng"]), None);
+        assert_eq!(language("en-US", &["en"]), None);
+        assert_eq!(
+            language("en;q=0.5, en-GB;q=0.9, *;q=0.1", &["en-US", "en-GB", "ko"]),
+            Some("en-GB")
+        );
+        assert_eq!(language("en, *;q=0.1", &["ko"]), Some("ko"));
+        assert_eq!(language("ko-KR, ko;q=0", &["ko"]), None);
+    }
+
+    #[test]
+    fn identity_is_acceptable_unless_excluded() {
+        let encoding =
+            |header, available: &[&'static str]| negotiate(Kind::Encoding, Some(header), available);
+        assert_eq!(encoding("gzip", &["br", "identity"]), Some("identity"));
+        assert_eq!(
+            encoding("gzip;q=0.5", &["gzip", "identity"]),
+            Some("identity")
+        );
+        assert_eq!(
+            encoding("gzip, identity;q=0.5", &["gzip", "identity"]),
+            Some("gzip")
+        );
+        assert_eq!(encoding("br, identity;q=0", &["identity"]), None);
+        assert_eq!(encoding("gzip, *;q=0", &["br", "identity"]), None);
+        assert_eq!(encoding("x-gzip", &["gzip", "identity"]), Some("gzip"));
+        assert_eq!(encoding("", &["gzip", "identity"]), Some("identity"));
+    }
+
+    #[test]
+    fn missing_header_accepts_the_first() {
+        assert_eq!(
+            negotiate(Kind::Charset, None, &["utf-8", "iso-8859-1"]),
+            Some("utf-8")
+        );
+        assert_eq!(
+            negotiate(Kind::Charset, Some("ISO-8859-1"), &["utf-8", "iso-8859-1"]),
+            Some("iso-8859-1")
+        );
+    }
+}
@@ -0,0 +1,133 @@
+// 경로를 비교하기 전에 %XX를 풀고, 빈 부분과 ".", ".."를 정리한다.
+// 경로 접두사로 규칙을 거는 미들웨어가 "/x/../admin", "//admin", "/%61dmin" 같은 경로에 속지 않게 한다.
+pub fn normalize(path: &str) -> String {
+    let decoded = percent_decode(path);
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in decoded.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    format!("/{}", segments.join("/"))
+}
+
+// 정리한 path가 prefix이거나 그 아래에 있으면 true. "/admin"은 "/admin/x"에는 맞지만 "/administrator"에는 맞지 않는다.
+pub fn has_prefix(path: &str, prefix: &str) -> bool {
+    prefix == "/"
+        || path == prefix
+        || path
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+// normalize()처럼 빈 부분과 ".", ".."를 정리하지만 %XX는 풀지 않고, 끝의 /도 남긴다.
+// 경로를 다른 서버에 넘길 때 쓴다. "%2e%2e"처럼 인코딩된 ".."도 ".."로 본다.
+// normalize()와 부분의 개수가 같으므로 같은 접두사를 부분 단위로 떼어낼 수 있다.
+pub fn clean(path: &str) -> String {
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in path.split('/') {
+        match percent_decode(segment).as_str() {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+    let mut cleaned = format!("/{}", segments.join("/"));
+    if path.ends_with('/') && !segments.is_empty() {
+        cleaned.push('/');
+    }
+    cleaned
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = s
+            .get(i + 1..i + 3)
+            .filter(|hex| bytes[i] == b'%' && hex.bytes().all(|b| b.is_ascii_hexdigit()));
+        match hex {
+            Some(hex) => {
+                decoded.push(u8::from_str_radix(hex, 16).unwrap());
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// 받은 경로를 다시 보낼 수 있게 인코딩한다. 올바른 %XX와 경로에 쓸 수 있는 문자는 그대로 두고,
+// 공백, 한글 같은 ASCII가 아닌 문자, 제어 문자, 뒤에 16진수가 없는 %는 %XX로 바꾼다. (RFC 3986 3.3)
+// 이미 인코딩된 경로는 바뀌지 않으므로 여러 번 불러도 된다.
+pub fn encode(path: &str) -> String {
+    encode_with(path, |b| is_pchar(b) || b == b'/')
+}
+
+// encode()와 같지만 쿼리 문자열에 쓸 수 있는 ?도 남긴다. &, =, +도 그대로 두므로 이름과 값의 구분은 바뀌지 않는다.
+pub fn encode_query(query: &str) -> String {
+    encode_with(query, |b| is_pchar(b) || b == b'/' || b == b'?')
+}
+
+// 쿼리의 이름이나 값 하나를 인코딩한다. 영문자, 숫자와 - . _ ~ 말고는 모두 %XX로 바꾸고, %도 %25로 바꾼다.
+// "a&b=c"가 이름이나 값을 나누지 않도록 할 때 쓴다.
+pub fn encode_component(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match is_unreserved(b) {
+            true => encoded.push(b as char),
+            false => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
+
+fn encode_with(s: &str, allowed: impl Fn(u8) -> bool) -> String {
+    let bytes = s.as_bytes();
+    let mut encoded = String::with_capacity(s.len());
+    for (i, &b) in bytes.iter().enumerate() {
+        let escaped = b == b'%'
+            && bytes
+                .get(i + 1..i + 3)
+                .is_some_and(|hex| hex.iter().all(u8::is_ascii_hexdigit));
+        match escaped || (b != b'%' && allowed(b)) {
+            true => encoded.push(b as char),
+            false => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
+
+fn is_unreserved(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~')
+}
+
+// unreserved, sub-delims, ':', '@'
+fn is_pchar(b: u8) -> bool {
+    is_unreserved(b)
+        || matches!(
+            b,
+            b'!' | b'$'
+                | b'&'
+                | b'\''
+                | b'('
+                | b')'
+                | b'*'
+                | b'+'
+                | b','
+                | b';'
+                | b'='
+                | b':'
+                | b'@'
+        )
+}
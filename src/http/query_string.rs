@@ -5,12 +5,12 @@ use std::collections::HashMap;
 // a=1&b=2&c&d=&e===&d=7&d=abc
 // c는 비어 있는 값으로 삽입할 것이고, d는 기호가 있지만 여전히 값은 없다.
 // 하지만 뒤를 보면 더 값이 있는 것으로 보아 d는 값이 array가 되길 원한다.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct QueryString<'buf> {
     data: HashMap<&'buf str, Value<'buf>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Value<'buf> {
     Single(&'buf str),
     // Mutiple은 어레이를 감싸야 하는데 문제는 일반적인 어레이는 길이의 값을 정해야 하지만 우리는 모른다.
@@ -22,7 +22,7 @@ pub enum Value<'buf> {
 // QueryString 구현 블록은 수명을 지정해줘야 한다. 왜냐하면 QueryString이
 // 수명에 대해 제네릭하기 때문이다.
 impl<'buf> QueryString<'buf> {
-    pub fn get(&self, key: &str) -> Option<&Value> {
+    pub fn get(&self, key: &str) -> Option<&Value<'_>> {
         self.data.get(key)
     }
 }
@@ -35,12 +35,10 @@ impl<'buf> From<&'buf str> for QueryString<'buf> {
         // split()은 문자열 슬라이스에서 우리가 넣어준 패턴으로 구분된 모든 하위 문자열을 반복하는
         // 이터레이터를 리턴하는 일을 한다.
         for sub_str in s.split('&') {
-            let mut key = sub_str;
-            let mut val = "";
             if let Some(i) = sub_str.find('=') {
                 // =는 1바이트 이므로 아래와 같이 해도 안전하다.
-                key = &sub_str[..i];
-                val = &sub_str[i + 1..];
+                let key = &sub_str[..i];
+                let val = &sub_str[i + 1..];
 
                 data.entry(key)
                     .and_modify(|existing: &mut Value| match existing {
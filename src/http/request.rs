@@ -1,7 +1,16 @@
+use super::chunked::ChunkedWriter;
+use super::headers;
+use super::negotiate::{self, Kind, Preference};
+use super::path;
+use super::Body;
+use super::Cookies;
+use super::Headers;
 use super::QueryString;
 use super::{method::MethodError, Method};
 use std::convert::TryFrom;
 use std::error::Error;
+use std::io::{self, ErrorKind, Read, Result as IoResult, Write};
+use std::net::SocketAddr;
 use std::str;
 use std::str::Utf8Error;
 // Result는 이미 모든 범위에서 import 되기에 여기에서는 as를 사용해 별칭을 만든다.
@@ -17,18 +26,34 @@ use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
 // Request의 수명은 buffer의 수명과 같다.
 // Request를 제네릭하게 바꿔주었기에 나머지도 모두 바꿔줘야 한다.
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Request<'buf> {
+    // 요청 줄에 적힌 그대로의 경로. 쿼리 문자열까지 포함한다.
+    target: &'buf str,
     path: &'buf str,
     // Request에 Debug를 구현했으면 그 아래도 Debug를 구현해야 하는데, 구현안해주면 에러표시가 나온다.
     query_string: Option<QueryString<'buf>>,
     method: Method,
+    headers: Headers<'buf>,
+    // 요청을 보낸 클라이언트의 주소. 서버가 연결을 받을 때 알게 되므로 요청을 읽은 뒤에 채운다.
+    remote_addr: Option<SocketAddr>,
+    // TLS 연결로 받은 요청인지. remote_addr처럼 서버가 채운다.
+    secure: bool,
+    // 본문이 없는 요청(Content-Length도 Transfer-Encoding도 없는 요청)이면 None이다.
+    body: Option<Body<'buf>>,
+    // 인증 미들웨어가 확인한 사용자 이름. 요청을 읽은 직후에는 항상 None이다.
+    principal: Option<String>,
 }
 
 // 러스트 규약에 따르면 게터의 이름은 필드 앞에 get 이라는 단어를 쓰지 않고 필드 위에 써야 한다.
 impl<'buf> Request<'buf> {
     pub fn path(&self) -> &str {
-        &self.path
+        self.path
+    }
+
+    // "/search?name=abc" 처럼 쿼리 문자열이 붙은 원래 경로. 요청을 다른 주소로 그대로 넘길 때 쓴다.
+    pub fn target(&self) -> &str {
+        self.target
     }
 
     pub fn method(&self) -> &Method {
@@ -36,11 +61,152 @@ impl<'buf> Request<'buf> {
     }
 
     // 호출자는 Option에는 관심이 없다. 오히려 Option이 감싸고 있는 것에만 관심이 있다.
-    pub fn query_string(&self) -> Option<&QueryString> {
+    pub fn query_string(&self) -> Option<&QueryString<'_>> {
         // as_ref() : Converts from &Option<T> to Option<&T>.
         // 이런식으로 메서드를 구현하면 훨씬 더 유연하다.
         self.query_string.as_ref()
     }
+
+    pub fn headers(&self) -> &Headers<'buf> {
+        &self.headers
+    }
+
+    // 헤더 하나만 필요할 때 쓰는 지름길. 이름은 대소문자를 구분하지 않는다.
+    pub fn header(&self, name: &str) -> Option<&'buf str> {
+        self.headers.get(name)
+    }
+
+    // 프록시 뒤에 있다면 프록시의 주소다.
+    pub fn remote_addr(&self) -> Option<SocketAddr> {
+        self.remote_addr
+    }
+
+    pub fn set_remote_addr(&mut self, remote_addr: SocketAddr) {
+        self.remote_addr = Some(remote_addr);
+    }
+
+    pub fn is_secure(&self) -> bool {
+        self.secure
+    }
+
+    pub fn set_secure(&mut self, secure: bool) {
+        self.secure = secure;
+    }
+
+    // 본문은 연결에서 바로 읽으므로 한 번만 읽을 수 있다.
+    pub fn body(&self) -> Option<Body<'buf>> {
+        self.body
+    }
+
+    pub fn set_body(&mut self, body: Body<'buf>) {
+        self.body = Some(body);
+    }
+
+    // 인증된 요청이면 사용자 이름(Basic) 또는 토큰 이름(Bearer)
+    pub fn principal(&self) -> Option<&str> {
+        self.principal.as_deref()
+    }
+
+    // 인증한 사용자를 붙인 요청을 만든다. 헤더는 버퍼를 가리키는 슬라이스라서 복사해도 비싸지 않다.
+    pub fn with_principal(&self, principal: impl Into<String>) -> Self {
+        Self {
+            principal: Some(principal.into()),
+            ..self.clone()
+        }
+    }
+
+    // Cookie 헤더의 이름=값 목록. 헤더가 여러 개면 모두 합친다.
+    pub fn cookies(&self) -> Cookies<'buf> {
+        let mut cookies = Cookies::default();
+        for header in self.headers.get_all("Cookie") {
+            cookies.parse(header);
+        }
+        cookies
+    }
+
+    // Accept 같은 목록 헤더를 q값이 높은 순서로 돌려준다. 같은 이름의 헤더가 여러 개면 모두 합친다.
+    pub fn preferences(&self, name: &str) -> Vec<Preference<'buf>> {
+        let mut preferences: Vec<Preference<'buf>> = self
+            .headers
+            .get_all(name)
+            .flat_map(negotiate::parse)
+            .collect();
+        // sort_by는 안정 정렬이라 q값이 같으면 헤더에 나온 순서가 유지된다.
+        preferences.sort_by(|a, b| b.quality().total_cmp(&a.quality()));
+        preferences
+    }
+
+    // Accept를 보고 available 중에서 보낼 미디어 타입을 고른다. 받을 수 있는 게 없으면 None이다.
+    // request.negotiate(&["application/json", "text/html"]) 처럼 서버가 선호하는 순서로 나열한다.
+    pub fn negotiate<'a>(&self, available: &[&'a str]) -> Option<&'a str> {
+        self.negotiate_with(Kind::MediaType, "Accept", available)
+    }
+
+    // Accept-Language. "en"은 "en-US" 같은 하위 태그에도 맞는다.
+    pub fn negotiate_language<'a>(&self, available: &[&'a str]) -> Option<&'a str> {
+        self.negotiate_with(Kind::Language, "Accept-Language", available)
+    }
+
+    pub fn negotiate_charset<'a>(&self, available: &[&'a str]) -> Option<&'a str> {
+        self.negotiate_with(Kind::Charset, "Accept-Charset", available)
+    }
+
+    // Accept-Encoding. 압축하지 않고 보낼 수 있다면 available에 "identity"도 넣어야 한다.
+    pub fn negotiate_encoding<'a>(&self, available: &[&'a str]) -> Option<&'a str> {
+        self.negotiate_with(Kind::Encoding, "Accept-Encoding", available)
+    }
+
+    fn negotiate_with<'a>(&self, kind: Kind, name: &str, available: &[&'a str]) -> Option<&'a str> {
+        let values: Vec<&str> = self.headers.get_all(name).collect();
+        let header = match values.len() {
+            0 => None,
+            _ => Some(values.join(",")),
+        };
+        negotiate::negotiate(kind, header.as_deref(), available)
+    }
+
+    // 받은 요청을 HTTP/1.1로 다시 쓴다. 경로와 쿼리 문자열은 보낼 수 없는 문자만 %XX로 바꾼다.
+    // 헤더는 받은 순서대로 쓰지만 Content-Length와 Transfer-Encoding은 본문에 맞게 새로 적는다.
+    // 길이를 아는 본문은 그만큼 그대로 보내고, 모르는 본문은 조각으로 나눠서 보낸다.
+    // 본문은 연결에서 읽어 오므로 한 번만 쓸 수 있다.
+    pub fn write_to(&self, writer: &mut impl Write) -> IoResult<()> {
+        let target = match self.target.split_once('?') {
+            Some((target_path, query)) => {
+                format!(
+                    "{}?{}",
+                    path::encode(target_path),
+                    path::encode_query(query)
+                )
+            }
+            None => path::encode(self.target),
+        };
+        write!(writer, "{} {} HTTP/1.1\r\n", self.method.as_str(), target)?;
+        headers::write_without_framing(writer, self.headers.iter())?;
+
+        let mut body = match self.body {
+            Some(body) => body,
+            None => return writer.write_all(b"\r\n"),
+        };
+        match body.length() {
+            Some(length) => {
+                write!(writer, "Content-Length: {}\r\n\r\n", length)?;
+                let copied = io::copy(&mut body.take(length), writer)?;
+                if copied < length {
+                    return Err(io::Error::new(
+                        ErrorKind::UnexpectedEof,
+                        "request body is shorter than its length",
+                    ));
+                }
+                Ok(())
+            }
+            None => {
+                writer.write_all(b"Transfer-Encoding: chunked\r\n\r\n")?;
+                let mut chunked = ChunkedWriter::new(writer);
+                io::copy(&mut body, &mut chunked)?;
+                chunked.finish().map(|_| ())
+            }
+        }
+    }
 }
 
 // impl<'buf> Request<'buf> {
@@ -97,8 +263,9 @@ impl<'buf> TryFrom<&'buf [u8]> for Request<'buf> {
         // // // 컴파일러는 스마트하게 path변수의 수명이 우리가 get_next_word() 함수에 주는 파라미터 수명과 같아야 한다고 추론한다.
         let (method, request) = get_next_word(request).ok_or(ParseError::InvalidRequest)?;
         let (mut path, request) = get_next_word(request).ok_or(ParseError::InvalidRequest)?;
-        // protocol 다음은 무시할 것이기 때문에 _로 처리한다.
-        let (protocol, _) = get_next_word(request).ok_or(ParseError::InvalidRequest)?;
+        // protocol 다음에는 \r 뒤에 \n이 남아 있고, 그 다음 줄부터 헤더가 시작된다.
+        let (protocol, request) = get_next_word(request).ok_or(ParseError::InvalidRequest)?;
+        let headers = Headers::try_from(request.strip_prefix('\n').unwrap_or(""))?;
 
         if protocol != "HTTP/1.1" {
             return Err(ParseError::InvalidProtocol);
@@ -106,6 +273,7 @@ impl<'buf> TryFrom<&'buf [u8]> for Request<'buf> {
         // 여기서는 에러가 type Error = ParseError;이렇게 해놨기에 아래에서 ParseError에 MethodError를 추가해야 한다.
         let method: Method = method.parse()?;
 
+        let target = path;
         let mut query_string = None;
 
         // find는 option을 리턴한다. 왜냐하면 문자열에 매칭되는게 없을 수도 있기 때문이다.
@@ -138,9 +306,15 @@ impl<'buf> TryFrom<&'buf [u8]> for Request<'buf> {
         }
 
         Ok(Self {
+            target,
             path,
             query_string,
             method,
+            headers,
+            remote_addr: None,
+            secure: false,
+            body: None,
+            principal: None,
         })
     }
 }
@@ -159,8 +333,9 @@ fn get_next_word(request: &str) -> Option<(&str, &str)> {
     //     }
     // }
 
-    // 반복자만이 아니라 인덱스까지 받아야 하므로 enumerate를 추가한다.
-    for (i, c) in request.chars().enumerate() {
+    // 반복자만이 아니라 인덱스까지 받아야 하므로 char_indices를 사용한다. enumerate는 글자 순서를 주기 때문에
+    // 멀티바이트 문자가 앞에 있으면 바이트 인덱스와 어긋난다.
+    for (i, c) in request.char_indices() {
         // 공백을 표현하려고 할때 " " 이렇게 쌍따옴표를 써서는 안된다.
         if c == ' ' || c == '\r' {
             // 공백 앞에 있는 모든 문자를 저장, 두번째는 공백을 제외한 다음문자를 추가하기 위해서 i + 1를 해준다.
@@ -176,11 +351,13 @@ fn get_next_word(request: &str) -> Option<(&str, &str)> {
     None
 }
 
+#[allow(clippy::enum_variant_names)]
 pub enum ParseError {
     InvalidRequest,
     InvalidEncoding,
     InvalidProtocol,
     InvalidMethod,
+    InvalidHeader,
 }
 
 impl ParseError {
@@ -192,6 +369,7 @@ impl ParseError {
             Self::InvalidEncoding => "InvalidEncoding",
             Self::InvalidProtocol => "InvalidProtocol",
             Self::InvalidMethod => "InvalidMethod",
+            Self::InvalidHeader => "InvalidHeader",
         }
     }
 }
@@ -245,3 +423,105 @@ impl Error for ParseError {}
 //         unimplemented!()
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    fn written(request: &Request) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        request.write_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn writes_back_what_was_parsed() {
+        let raw = b"GET /a/b?x=1&y=2 HTTP/1.1\r\nHost: example.test\r\nAccept: */*\r\nAccept: text/html\r\n\r\n";
+        let request = Request::try_from(&raw[..]).unwrap();
+        let bytes = written(&request);
+        assert_eq!(bytes, raw);
+
+        let parsed = Request::try_from(&bytes[..]).unwrap();
+        assert_eq!(parsed.method().as_str(), "GET");
+        assert_eq!(parsed.target(), request.target());
+        assert_eq!(parsed.path(), "/a/b");
+        let headers: Vec<(&str, &str)> = parsed.headers().iter().collect();
+        let expected: Vec<(&str, &str)> = request.headers().iter().collect();
+        assert_eq!(headers, expected);
+    }
+
+    #[test]
+    fn re_encodes_the_target() {
+        let raw = "GET /a%20b/ü/\"x\"?q=한글&z=%zz HTTP/1.1\r\nHost: h\r\n\r\n";
+        let request = Request::try_from(raw.as_bytes()).unwrap();
+        let bytes = written(&request);
+        let parsed = Request::try_from(&bytes[..]).unwrap();
+        assert_eq!(
+            parsed.target(),
+            "/a%20b/%C3%BC/%22x%22?q=%ED%95%9C%EA%B8%80&z=%25zz"
+        );
+        assert_eq!(
+            path::normalize(parsed.path()),
+            path::normalize(request.path())
+        );
+    }
+
+    #[test]
+    fn body_with_known_length() {
+        let raw = b"POST /upload HTTP/1.1\r\nHost: h\r\nContent-Length: 99\r\nTransfer-Encoding: gzip\r\n\r\n";
+        let mut request = Request::try_from(&raw[..]).unwrap();
+        let body = RefCell::new(&b"hello world, and more"[..]);
+        request.set_body(Body::new(&body, Some(11)));
+        let bytes = written(&request);
+        assert_eq!(
+            bytes,
+            b"POST /upload HTTP/1.1\r\nHost: h\r\nContent-Length: 11\r\n\r\nhello world"
+        );
+        let parsed = Request::try_from(&bytes[..]).unwrap();
+        let lengths: Vec<&str> = parsed.headers().get_all("Content-Length").collect();
+        assert_eq!(lengths, ["11"]);
+    }
+
+    #[test]
+    fn body_with_unknown_length_is_chunked() {
+        let raw = b"POST /upload HTTP/1.1\r\nHost: h\r\nTransfer-Encoding: chunked\r\n\r\n";
+        let mut request = Request::try_from(&raw[..]).unwrap();
+        let body = RefCell::new(&b"hello world"[..]);
+        request.set_body(Body::new(&body, None));
+        let bytes = written(&request);
+        assert_eq!(
+            bytes,
+            &b"POST /upload HTTP/1.1\r\nHost: h\r\nTransfer-Encoding: chunked\r\n\r\nb\r\nhello world\r\n0\r\n\r\n"[..]
+        );
+        let parsed = Request::try_from(&bytes[..]).unwrap();
+        assert_eq!(parsed.header("Transfer-Encoding"), Some("chunked"));
+    }
+
+    #[test]
+    fn short_body_is_an_error() {
+        let raw = b"PUT / HTTP/1.1\r\nHost: h\r\n\r\n";
+        let mut request = Request::try_from(&raw[..]).unwrap();
+        let body = RefCell::new(&b"ab"[..]);
+        request.set_body(Body::new(&body, Some(5)));
+        let error = request.write_to(&mut Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn builder_output_round_trips_through_write_to() {
+        use crate::http::RequestBuilder;
+
+        let builder = RequestBuilder::new(Method::PATCH, "/items/7?fields=a b")
+            .query("x", "1&2")
+            .header("Host", "example.test")
+            .header("Content-Type", "text/plain")
+            .body("payload");
+        let bytes = builder.to_bytes().unwrap();
+        let end = bytes.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+        let mut request = Request::try_from(&bytes[..end]).unwrap();
+        let body = RefCell::new(&bytes[end..]);
+        request.set_body(Body::new(&body, Some((bytes.len() - end) as u64)));
+        assert_eq!(written(&request), bytes);
+    }
+}
@@ -0,0 +1,336 @@
+use super::headers;
+use super::path;
+use super::Method;
+use std::io::{Error, ErrorKind, Result as IoResult, Write};
+
+// 값을 모두 가지고 있는 요청. 서버가 받은 Request는 버퍼를 빌리고 있으므로
+// 다른 서버에 넘길 요청이나 시험에 쓸 요청을 만들 때는 이걸 쓴다.
+// 쓴 바이트는 Request::try_from()으로 다시 읽을 수 있다.
+//
+// let request = RequestBuilder::new(Method::POST, "/users?page=1")
+//     .query("name", "홍 길동")
+//     .header("Host", "localhost:4000")
+//     .header("Content-Type", "application/json")
+//     .body(r#"{"name":"abc"}"#);
+// request.write_to(&mut stream)?;
+#[derive(Debug, Clone)]
+pub struct RequestBuilder {
+    method: Method,
+    // 인코딩한 경로
+    path: String,
+    // 인코딩한 쿼리 문자열. ?는 빼고 가지고 있다.
+    query: Option<String>,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl RequestBuilder {
+    // target은 "/경로[?쿼리]" 꼴이다. 프록시에 보낼 때는 "http://호스트/경로" 꼴도 되고,
+    // OPTIONS는 서버 전체를 뜻하는 "*"도 된다. 그 밖의 target은 write_to()가 거절한다.
+    // 보낼 수 없는 문자는 %XX로 바꾸고, 이미 있는 %XX는 그대로 둔다.
+    // 프래그먼트(#)는 서버에 보내지 않으므로 버린다.
+    pub fn new(method: Method, target: &str) -> Self {
+        let target = target.split('#').next().unwrap_or_default();
+        let (raw_path, query) = match target.split_once('?') {
+            Some((raw_path, query)) => (raw_path, Some(path::encode_query(query))),
+            None => (target, None),
+        };
+        let encoded = match raw_path {
+            "" => "/".to_string(),
+            raw_path => path::encode(raw_path),
+        };
+        Self {
+            method,
+            path: encoded,
+            query,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    // 쿼리 문자열 끝에 name=value를 붙인다. 이름과 값은 &, =가 들어 있어도 되도록 따로 인코딩한다.
+    pub fn query(mut self, name: &str, value: &str) -> Self {
+        let pair = format!(
+            "{}={}",
+            path::encode_component(name),
+            path::encode_component(value)
+        );
+        match &mut self.query {
+            Some(query) if !query.is_empty() => {
+                query.push('&');
+                query.push_str(&pair);
+            }
+            query => *query = Some(pair),
+        }
+        self
+    }
+
+    // 같은 이름을 여러 번 넣으면 모두 보낸다.
+    // Content-Length와 Transfer-Encoding은 본문으로 정하므로 넣어도 무시한다.
+    pub fn header(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.headers.push((name.to_string(), value.into()));
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    // 요청 줄에 쓰는 경로와 쿼리 문자열
+    pub fn target(&self) -> String {
+        match &self.query {
+            Some(query) => format!("{}?{}", self.path, query),
+            None => self.path.clone(),
+        }
+    }
+
+    pub fn headers(&self) -> impl Iterator<Item = (&str, &str)> + '_ {
+        self.headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> IoResult<()> {
+        self.write_with(writer, &[])
+    }
+
+    // Vec에 쓰는 건 target이나 헤더가 잘못되었을 때만 실패한다.
+    pub fn to_bytes(&self) -> IoResult<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.write_to(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    // 요청 줄에 쓸 수 있는 target인지. (RFC 9112 3.2)
+    fn has_valid_target(&self) -> bool {
+        if self.path.starts_with('/') {
+            return true;
+        }
+        if self.path == "*" {
+            return matches!(self.method, Method::OPTIONS) && self.query.is_none();
+        }
+        // absolute-form. 스킴 뒤에 호스트가 있어야 한다.
+        match self.path.split_once("://") {
+            Some((scheme, rest)) => {
+                scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                    && scheme
+                        .bytes()
+                        .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'-' | b'.'))
+                    && !rest.is_empty()
+                    && !rest.starts_with('/')
+            }
+            None => false,
+        }
+    }
+
+    // 헤더 줄을 끊거나 새 헤더를 끼워 넣을 수 있는 이름과 값이 없으면 true
+    pub(crate) fn has_valid_headers(&self) -> bool {
+        self.headers.iter().all(|(name, value)| {
+            !name.is_empty()
+                && name.bytes().all(|b| b.is_ascii_graphic() && b != b':')
+                && !value.bytes().any(|b| b == b'\r' || b == b'\n' || b == 0)
+        })
+    }
+
+    // extra는 넣은 헤더 뒤, Content-Length 앞에 쓴다. 클라이언트가 User-Agent나 Connection을 붙일 때 쓴다.
+    pub(crate) fn write_with(
+        &self,
+        writer: &mut impl Write,
+        extra: &[(&str, &str)],
+    ) -> IoResult<()> {
+        if !self.has_valid_target() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "invalid request target",
+            ));
+        }
+        if !self.has_valid_headers() {
+            return Err(Error::new(ErrorKind::InvalidInput, "invalid header"));
+        }
+        write!(
+            writer,
+            "{} {} HTTP/1.1\r\n",
+            self.method.as_str(),
+            self.target()
+        )?;
+        headers::write_without_framing(writer, self.headers().chain(extra.iter().copied()))?;
+        // 본문을 보내는 메서드는 빈 본문이라도 길이를 적는다.
+        if !self.body.is_empty()
+            || matches!(self.method, Method::POST | Method::PUT | Method::PATCH)
+        {
+            write!(writer, "Content-Length: {}\r\n", self.body.len())?;
+        }
+        writer.write_all(b"\r\n")?;
+        writer.write_all(&self.body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{QueryStringValue, Request};
+    use std::convert::TryFrom;
+
+    fn round_trip(builder: &RequestBuilder) -> (Vec<u8>, String) {
+        let bytes = builder.to_bytes().unwrap();
+        let request = Request::try_from(&bytes[..]).unwrap();
+        assert_eq!(request.method().as_str(), builder.method().as_str());
+        assert_eq!(request.target(), builder.target());
+        let target = request.target().to_string();
+        (bytes, target)
+    }
+
+    #[test]
+    fn writes_a_request_that_parses_back() {
+        let builder = RequestBuilder::new(Method::POST, "/users?page=1")
+            .query("name", "홍 길동")
+            .header("Host", "localhost:4000")
+            .header("Accept", "text/html")
+            .header("Accept", "application/json")
+            .body(r#"{"name":"abc"}"#);
+        let (bytes, target) = round_trip(&builder);
+        assert_eq!(target, "/users?page=1&name=%ED%99%8D%20%EA%B8%B8%EB%8F%99");
+
+        let request = Request::try_from(&bytes[..]).unwrap();
+        assert_eq!(request.path(), "/users");
+        let accept: Vec<&str> = request.headers().get_all("Accept").collect();
+        assert_eq!(accept, ["text/html", "application/json"]);
+        assert_eq!(request.header("Content-Length"), Some("14"));
+        assert!(bytes.ends_with(br#"{"name":"abc"}"#));
+    }
+
+    #[test]
+    fn encodes_path_and_query() {
+        let cases = [
+            ("/a b/ü", "/a%20b/%C3%BC"),
+            ("/already%20encoded", "/already%20encoded"),
+            ("/100%", "/100%25"),
+            ("/x?q=a b&c=<d>", "/x?q=a%20b&c=%3Cd%3E"),
+            ("/x?a=1#fragment", "/x?a=1"),
+            ("", "/"),
+        ];
+        for (target, expected) in cases {
+            let builder = RequestBuilder::new(Method::GET, target);
+            assert_eq!(builder.target(), expected);
+            round_trip(&builder);
+            // 인코딩한 target을 다시 넣어도 그대로다.
+            assert_eq!(
+                RequestBuilder::new(Method::GET, expected).target(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn query_components_are_escaped() {
+        let builder = RequestBuilder::new(Method::GET, "/search")
+            .query("a&b", "c=d+e")
+            .query("empty", "");
+        let (bytes, target) = round_trip(&builder);
+        assert_eq!(target, "/search?a%26b=c%3Dd%2Be&empty=");
+        let request = Request::try_from(&bytes[..]).unwrap();
+        let query = request.query_string().unwrap();
+        assert!(matches!(
+            query.get("a%26b"),
+            Some(QueryStringValue::Single("c%3Dd%2Be"))
+        ));
+        assert!(matches!(
+            query.get("empty"),
+            Some(QueryStringValue::Single(""))
+        ));
+    }
+
+    #[test]
+    fn random_targets_round_trip() {
+        let chars: Vec<char> = "ab/%?&=+# 가~-._!$'()*,;:@\"<>{}|\\^`\t[]Z9"
+            .chars()
+            .collect();
+        let mut seed: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut next = |n: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % n as u64) as usize
+        };
+        for _ in 0..2000 {
+            let mut target = "/".to_string();
+            for _ in 0..next(12) {
+                target.push(chars[next(chars.len())]);
+            }
+            let mut builder = RequestBuilder::new(Method::GET, &target);
+            for _ in 0..next(3) {
+                let name: String = (0..1 + next(4)).map(|_| chars[next(chars.len())]).collect();
+                let value: String = (0..next(4)).map(|_| chars[next(chars.len())]).collect();
+                builder = builder.query(&name, &value);
+            }
+            let (_, encoded) = round_trip(&builder);
+            assert!(encoded.bytes().all(|b| b.is_ascii_graphic()), "{}", encoded);
+            assert_eq!(RequestBuilder::new(Method::GET, &encoded).target(), encoded);
+        }
+    }
+
+    #[test]
+    fn framing_headers_follow_the_body() {
+        let builder = RequestBuilder::new(Method::PUT, "/file")
+            .header("Content-Length", "999")
+            .header("Transfer-Encoding", "chunked")
+            .body("abc");
+        let (bytes, _) = round_trip(&builder);
+        let request = Request::try_from(&bytes[..]).unwrap();
+        let lengths: Vec<&str> = request.headers().get_all("Content-Length").collect();
+        assert_eq!(lengths, ["3"]);
+        assert!(!request.headers().contains("Transfer-Encoding"));
+
+        // 본문을 보내는 메서드는 빈 본문이라도 길이를 적는다.
+        let bytes = RequestBuilder::new(Method::POST, "/").to_bytes().unwrap();
+        let request = Request::try_from(&bytes[..]).unwrap();
+        assert_eq!(request.header("Content-Length"), Some("0"));
+        let bytes = RequestBuilder::new(Method::GET, "/").to_bytes().unwrap();
+        let request = Request::try_from(&bytes[..]).unwrap();
+        assert_eq!(request.header("Content-Length"), None);
+    }
+
+    #[test]
+    fn request_target_forms() {
+        let options = RequestBuilder::new(Method::OPTIONS, "*");
+        assert_eq!(round_trip(&options).1, "*");
+        let absolute = RequestBuilder::new(Method::GET, "http://example.test:8080/a b?x=1");
+        assert_eq!(
+            round_trip(&absolute).1,
+            "http://example.test:8080/a%20b?x=1"
+        );
+
+        for (method, target) in [
+            (Method::GET, "users"),
+            (Method::GET, "*"),
+            (Method::OPTIONS, "*?x=1"),
+            (Method::GET, "example.test:443"),
+            (Method::GET, "http:///path"),
+        ] {
+            let error = RequestBuilder::new(method, target).to_bytes().unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidInput, "{}", target);
+        }
+    }
+
+    #[test]
+    fn rejects_headers_that_break_the_request() {
+        for (name, value) in [
+            ("X-Evil", "a\r\nInjected: 1"),
+            ("X-Evil", "a\nb"),
+            ("Bad Name", "1"),
+            ("Bad:Name", "1"),
+            ("", "1"),
+        ] {
+            let error = RequestBuilder::new(Method::GET, "/")
+                .header(name, value)
+                .to_bytes()
+                .unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidInput);
+        }
+    }
+}
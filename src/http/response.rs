@@ -1,17 +1,137 @@
-use super::StatusCode;
-use std::io::{Result as IoResult, Write};
-use std::net::TcpStream;
+use super::chunked::ChunkedReader;
+use super::cookie::CookieError;
+use super::{Cookie, Headers, StatusCode};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use std::io::{BufRead, BufWriter, ErrorKind, Read, Result as IoResult, Write};
+use std::str::{self, Utf8Error};
+
+// 헤더를 보낸 뒤 연결에 직접 본문을 써 나가는 함수. 미리 길이를 알 수 없거나 끝나지 않는 본문에 쓴다.
+pub type StreamBody = Box<dyn FnOnce(&mut dyn Write) -> IoResult<()> + Send>;
 
-#[derive(Debug)]
 pub struct Response {
     status_code: StatusCode,
-    // 응답에 본문이 없을 경우를 처리하기 위해 String을 Option으로 감싼다.
-    body: Option<String>,
+    // 같은 이름의 헤더가 여러 번 나올 수 있고(Set-Cookie 등) 순서도 유지하고 싶기 때문에 HashMap 대신 Vec을 쓴다.
+    headers: Vec<(String, String)>,
+    // 응답에 본문이 없을 경우를 처리하기 위해 Option으로 감싼다.
+    // 이미지 같은 파일은 유효한 utf-8이 아니기 때문에 String이 아니라 바이트로 저장한다.
+    body: Option<Vec<u8>>,
+    // 있으면 body 대신 이걸로 본문을 보내고, 본문이 끝나면 연결을 닫는다.
+    stream: Option<StreamBody>,
+}
+
+impl Debug for Response {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Response")
+            .field("status_code", &self.status_code)
+            .field("headers", &self.headers)
+            .field("body", &self.body)
+            .field("stream", &self.stream.is_some())
+            .finish()
+    }
 }
 
 impl Response {
     pub fn new(status_code: StatusCode, body: Option<String>) -> Self {
-        Response { status_code, body }
+        Response {
+            status_code,
+            headers: Vec::new(),
+            body: body.map(String::into_bytes),
+            stream: None,
+        }
+    }
+
+    pub fn from_bytes(status_code: StatusCode, body: Vec<u8>) -> Self {
+        Response {
+            status_code,
+            headers: Vec::new(),
+            body: Some(body),
+            stream: None,
+        }
+    }
+
+    // 헤더를 먼저 보내고 writer가 리턴할 때까지 연결을 열어 둔다. writer가 쓰는 대로 클라이언트에게 전달되도록
+    // 필요할 때마다 flush()를 호출해야 한다. Content-Length 없이 연결을 닫아서 본문의 끝을 알린다.
+    pub fn stream(
+        status_code: StatusCode,
+        writer: impl FnOnce(&mut dyn Write) -> IoResult<()> + Send + 'static,
+    ) -> Self {
+        Response {
+            status_code,
+            headers: Vec::new(),
+            body: None,
+            stream: Some(Box::new(writer)),
+        }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        self.status_code
+    }
+
+    // 스트리밍 응답이면 None이다.
+    pub fn body(&self) -> Option<&[u8]> {
+        self.body.as_deref()
+    }
+
+    // 압축처럼 본문을 바꾸는 미들웨어가 쓴다. Content-Length는 보낼 때 다시 계산된다.
+    pub fn set_body(&mut self, body: Vec<u8>) {
+        self.body = Some(body);
+        self.stream = None;
+    }
+
+    pub fn is_stream(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    // 헤더 이름은 대소문자를 구분하지 않는다.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn headers(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    // 같은 이름의 헤더가 이미 있다면 값을 바꾸고, 없다면 추가한다.
+    pub fn set_header(&mut self, name: &str, value: impl Into<String>) {
+        let value = value.into();
+        match self
+            .headers
+            .iter_mut()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        {
+            Some((_, existing)) => *existing = value,
+            None => self.headers.push((name.to_string(), value)),
+        }
+    }
+
+    // 같은 이름의 헤더가 있어도 덮어쓰지 않고 하나 더 추가한다.
+    pub fn append_header(&mut self, name: &str, value: impl Into<String>) {
+        self.headers.push((name.to_string(), value.into()));
+    }
+
+    // 쿠키마다 Set-Cookie 헤더를 하나씩 추가한다. 쿠키가 잘못되었으면 헤더를 추가하지 않는다.
+    pub fn set_cookie(&mut self, cookie: &Cookie) -> Result<(), CookieError> {
+        let header = cookie.to_header()?;
+        self.append_header("Set-Cookie", header);
+        Ok(())
+    }
+
+    pub fn remove_header(&mut self, name: &str) {
+        self.headers
+            .retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+    }
+
+    // Response::new(...).with_header(...) 처럼 이어서 쓸 수 있게 self를 받아서 다시 돌려준다.
+    pub fn with_header(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.set_header(name, value);
+        self
     }
 
     // dyn : dynamic dispatch에서 온 것이다.
@@ -36,23 +156,342 @@ impl Response {
     // 많은 바이너리가 생성된다. 함수 하나가 여러번 구현되기 때문이다. 그래서 일부 임베디드 시스템에서는 문제가 된다.
     // 제일 좋은 것은 정확하게 타입을 넣어서 만드는게 좋다.
 
-    pub fn send(&self, stream: &mut impl Write) -> IoResult<()> {
-        let body = match &self.body {
+    pub fn send(self, stream: &mut impl Write) -> IoResult<()> {
+        self.send_with(stream, true)
+    }
+
+    // HEAD 요청에 대한 응답. 헤더는 GET과 똑같이(Content-Length도 본문의 길이로) 보내고 본문만 보내지 않는다.
+    pub fn send_head(self, stream: &mut impl Write) -> IoResult<()> {
+        self.send_with(stream, false)
+    }
+
+    // 보낼 본문의 길이. 프록시가 받은 HEAD 응답처럼 본문 없이 길이만 알려주는 경우에는 적힌 값을 그대로 쓴다.
+    pub(crate) fn content_length(&self, with_body: bool) -> String {
+        match (&self.body, self.header("Content-Length")) {
+            (None, Some(length)) if !with_body => length.to_string(),
+            (body, _) => body.as_ref().map_or(0, Vec::len).to_string(),
+        }
+    }
+
+    fn send_with(self, stream: &mut impl Write, with_body: bool) -> IoResult<()> {
+        let body: &[u8] = match &self.body {
             Some(b) => b,
-            None => " ",
+            None => &[],
         };
+        // write!를 여러 번 하면 그때마다 작은 패킷이 나갈 수 있으므로 버퍼에 모았다가 한번에 보낸다.
+        let mut stream = BufWriter::new(stream);
 
         write!(
             stream,
-            "HTTP/1.1 {} {}\r\nContent-Length: {}\r\n\r\n {}",
+            "HTTP/1.1 {} {}\r\n",
             self.status_code,
-            self.status_code.reason_phrase(),
-            body.len(),
-            body
-        )
+            self.status_code.reason_phrase()
+        )?;
+        for (name, value) in &self.headers {
+            // 본문 길이는 실제로 보내는 바이트 수와 항상 같아야 하기 때문에 직접 계산한다.
+            // 스트림 본문은 연결을 닫아서 끝을 알리므로 Connection도 아래에서 직접 쓴다.
+            // 본문이 없는 응답에는 Transfer-Encoding도 붙이면 안 된다. (RFC 9112 6.1)
+            if name.eq_ignore_ascii_case("Content-Length")
+                || (self.stream.is_some() && name.eq_ignore_ascii_case("Connection"))
+                || (!self.status_code.allows_body()
+                    && name.eq_ignore_ascii_case("Transfer-Encoding"))
+            {
+                continue;
+            }
+            write!(stream, "{}: {}\r\n", name, value)?;
+        }
+        if let Some(writer) = self.stream {
+            write!(stream, "Connection: close\r\n\r\n")?;
+            stream.flush()?;
+            if !with_body || !self.status_code.allows_body() {
+                return Ok(());
+            }
+            // 버퍼를 거치지 않고 연결에 바로 쓰게 한다. 그래야 writer가 flush()할 때 바로 전달되고,
+            // 쓰다가 실패한 바이트가 버퍼에 남아서 나중에 다시 보내지는 일도 없다.
+            return writer(stream.get_mut());
+        }
+        // 101 Switching Protocols 같은 1xx 응답과 204 응답에는 Content-Length도 본문도 붙이면 안 된다.
+        if !self.status_code.allows_body() {
+            write!(stream, "\r\n")?;
+            return stream.flush();
+        }
+        write!(
+            stream,
+            "Content-Length: {}\r\n\r\n",
+            self.content_length(with_body)
+        )?;
+        if with_body {
+            stream.write_all(body)?;
+        }
+        stream.flush()
+    }
+}
+
+// HTTP/1.1 200 OK\r\n
+// Content-Type: text/plain\r\n
+// Content-Length: 5\r\n
+// \r\n
+// hello
+//
+// send()가 쓴 바이트를 다시 Response로 읽는다. 바이트에는 응답 하나만 온전히 들어 있어야 한다.
+// 본문의 끝은 Transfer-Encoding, Content-Length 순서로 보고, 둘 다 없으면 바이트의 끝까지가 본문이다.
+// chunked 본문은 풀어서 담고 Transfer-Encoding 헤더는 뺀다. Content-Length는 그대로 둔다.
+impl TryFrom<&[u8]> for Response {
+    type Error = ResponseError;
+
+    fn try_from(buf: &[u8]) -> Result<Self, Self::Error> {
+        parse(buf, false)
+    }
+}
+
+impl Response {
+    // HEAD 요청에 대한 응답(send_head()가 쓴 바이트)을 읽는다. Content-Length가 있어도 본문은 없다.
+    pub fn try_from_head_response(buf: &[u8]) -> Result<Self, ResponseError> {
+        parse(buf, true)
+    }
+}
+
+fn parse(buf: &[u8], head_request: bool) -> Result<Response, ResponseError> {
+    let end = buf
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or(ResponseError::Incomplete)?;
+    let head = ResponseHead::parse(str::from_utf8(&buf[..end])?)?;
+    let mut rest = &buf[end + 4..];
+
+    let body = match head.framing(head_request)? {
+        Framing::None => None,
+        Framing::Length(length) => {
+            let length = usize::try_from(length).map_err(|_| ResponseError::Incomplete)?;
+            if rest.len() < length {
+                return Err(ResponseError::Incomplete);
+            }
+            let (body, after) = rest.split_at(length);
+            rest = after;
+            Some(body.to_vec())
+        }
+        Framing::Chunked => {
+            let mut body = Vec::new();
+            let mut reader = ChunkedReader::new(&mut rest);
+            reader.read_to_end(&mut body).map_err(|e| match e.kind() {
+                ErrorKind::UnexpectedEof => ResponseError::Incomplete,
+                _ => ResponseError::InvalidBody,
+            })?;
+            Some(body)
+        }
+        Framing::Close => Some(std::mem::take(&mut rest).to_vec()),
+    };
+    if !rest.is_empty() {
+        return Err(ResponseError::TrailingData);
     }
+
+    let mut response = head.into_response()?;
+    response.body = body;
+    Ok(response)
+}
+
+// 상태 줄과 헤더만 읽은 응답. 연결에서 응답을 읽는 쪽(클라이언트, 프록시)은 머리를 먼저 읽고
+// framing()을 보고 본문을 따로 읽는다.
+pub(crate) struct ResponseHead {
+    // HTTP/1.x의 x
+    pub(crate) version: u8,
+    pub(crate) code: u16,
+    pub(crate) headers: Vec<(String, String)>,
 }
 
+// 응답 본문이 어디서 끝나는지 (RFC 9112 6.3)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Framing {
+    None,
+    Length(u64),
+    Chunked,
+    // 연결이 닫힐 때까지가 본문이다. 본문 뒤에 연결을 다시 쓸 수 없다.
+    Close,
+}
+
+impl ResponseHead {
+    // 끝의 빈 줄은 있어도 되고 없어도 된다.
+    pub(crate) fn parse(head: &str) -> Result<Self, ResponseError> {
+        let (line, fields) = head.split_once("\r\n").unwrap_or((head, ""));
+        let mut parts = line.splitn(3, ' ');
+        let version = match parts.next() {
+            Some("HTTP/1.1") => 1,
+            Some("HTTP/1.0") => 0,
+            Some("") | None => return Err(ResponseError::InvalidStatusLine),
+            Some(_) => return Err(ResponseError::InvalidProtocol),
+        };
+        let code = parts.next().ok_or(ResponseError::InvalidStatusLine)?;
+        if code.len() != 3 || !code.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(ResponseError::InvalidStatusCode);
+        }
+        let code = code.parse().map_err(|_| ResponseError::InvalidStatusCode)?;
+        let headers = Headers::try_from(fields)
+            .map_err(|_| ResponseError::InvalidHeader)?
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        Ok(Self {
+            version,
+            code,
+            headers,
+        })
+    }
+
+    pub(crate) fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.headers
+            .iter()
+            .filter(move |(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    // 우리가 모르는 코드는 같은 종류의 x00 코드로 바꾼다. (299 -> 200)
+    pub(crate) fn status_code(&self) -> Result<StatusCode, ResponseError> {
+        StatusCode::from_u16_or_class(self.code).ok_or(ResponseError::InvalidStatusCode)
+    }
+
+    // HEAD 요청에 대한 응답과 1xx, 204, 304 응답에는 본문이 없다.
+    pub(crate) fn framing(&self, head_request: bool) -> Result<Framing, ResponseError> {
+        if head_request || !self.status_code()?.allows_body() {
+            return Ok(Framing::None);
+        }
+        if let Some(coding) = self.get_all("Transfer-Encoding").last() {
+            let chunked = coding
+                .rsplit(',')
+                .next()
+                .is_some_and(|last| last.trim().eq_ignore_ascii_case("chunked"));
+            // chunked로 끝나지 않으면 연결이 닫힐 때까지가 본문이다.
+            return Ok(if chunked {
+                Framing::Chunked
+            } else {
+                Framing::Close
+            });
+        }
+
+        let mut length = None;
+        for value in self
+            .get_all("Content-Length")
+            .flat_map(|value| value.split(','))
+        {
+            let value = value.trim();
+            if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(ResponseError::InvalidBody);
+            }
+            let value: u64 = value.parse().map_err(|_| ResponseError::InvalidBody)?;
+            // 값이 다른 Content-Length가 여러 개면 어디까지가 본문인지 알 수 없다.
+            if length.is_some_and(|length| length != value) {
+                return Err(ResponseError::InvalidBody);
+            }
+            length = Some(value);
+        }
+        Ok(length.map_or(Framing::Close, Framing::Length))
+    }
+
+    // HTTP/1.1은 Connection: close가 없으면, HTTP/1.0은 Connection: keep-alive가 있으면 연결을 이어서 쓸 수 있다.
+    pub(crate) fn is_persistent(&self) -> bool {
+        let has = |token: &str| {
+            self.get_all("Connection")
+                .flat_map(|value| value.split(','))
+                .any(|value| value.trim().eq_ignore_ascii_case(token))
+        };
+        match self.version {
+            1 => !has("close"),
+            _ => has("keep-alive"),
+        }
+    }
+
+    // 본문이 없는 Response. 본문은 풀어서 담을 것이므로 Transfer-Encoding은 뺀다.
+    pub(crate) fn into_response(self) -> Result<Response, ResponseError> {
+        let mut response = Response::new(self.status_code()?, None);
+        response.headers = self
+            .headers
+            .into_iter()
+            .filter(|(name, _)| !name.eq_ignore_ascii_case("Transfer-Encoding"))
+            .collect();
+        Ok(response)
+    }
+}
+
+// 빈 줄까지 읽어서 상태 줄과 헤더를 돌려준다. 빈 줄은 빼고 돌려준다.
+// 줄바꿈 없이 끝없이 이어지는 응답에 메모리를 다 쓰지 않도록 max 바이트까지만 읽는다.
+pub(crate) fn read_head(reader: &mut impl BufRead, max: usize) -> IoResult<String> {
+    let mut head = Vec::new();
+    loop {
+        let start = head.len();
+        let limit = (max + 1 - start) as u64;
+        let len = reader.by_ref().take(limit).read_until(b'\n', &mut head)?;
+        if len == 0 {
+            return Err(std::io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "connection closed before the response",
+            ));
+        }
+        if head.len() > max {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                "response head is too large",
+            ));
+        }
+        if !head.ends_with(b"\n") {
+            continue;
+        }
+        let line = &head[start..];
+        if line == b"\r\n" || line == b"\n" {
+            head.truncate(start);
+            return String::from_utf8(head).map_err(|_| {
+                std::io::Error::new(ErrorKind::InvalidData, "response head is not UTF-8")
+            });
+        }
+    }
+}
+
+pub enum ResponseError {
+    InvalidEncoding,
+    InvalidStatusLine,
+    InvalidProtocol,
+    InvalidStatusCode,
+    InvalidHeader,
+    InvalidBody,
+    // 머리나 본문이 끝나기 전에 바이트가 끝났다.
+    Incomplete,
+    // 응답이 끝난 뒤에도 바이트가 남았다.
+    TrailingData,
+}
+
+impl ResponseError {
+    fn message(&self) -> &str {
+        match self {
+            Self::InvalidEncoding => "InvalidEncoding",
+            Self::InvalidStatusLine => "InvalidStatusLine",
+            Self::InvalidProtocol => "InvalidProtocol",
+            Self::InvalidStatusCode => "InvalidStatusCode",
+            Self::InvalidHeader => "InvalidHeader",
+            Self::InvalidBody => "InvalidBody",
+            Self::Incomplete => "Incomplete",
+            Self::TrailingData => "TrailingData",
+        }
+    }
+}
+
+impl From<Utf8Error> for ResponseError {
+    fn from(_: Utf8Error) -> Self {
+        Self::InvalidEncoding
+    }
+}
+
+impl Display for ResponseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl Debug for ResponseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl Error for ResponseError {}
+
 // 전에는 Formatter에 기록했는데 이제는 할당이 필요하지 않다.
 // 왜냐하면 웹사이트를 위해 main.js 파일을 제공해야 할 수 있으니까
 
@@ -72,3 +511,321 @@ impl Response {
 //         )
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::chunked::ChunkedWriter;
+
+    fn sent(response: Response) -> Vec<u8> {
+        let mut buf = Vec::new();
+        response.send(&mut buf).unwrap();
+        buf
+    }
+
+    fn headers(response: &Response) -> Vec<(String, String)> {
+        response
+            .headers()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    // 보낸 헤더가 순서대로 모두 있고, 그 밖에는 send()가 붙인 Content-Length만 있어야 한다.
+    fn assert_headers(parsed: &Response, expected: &[(&str, &str)]) {
+        let parsed: Vec<(String, String)> = headers(parsed)
+            .into_iter()
+            .filter(|(name, _)| name != "Content-Length")
+            .collect();
+        let expected: Vec<(String, String)> = expected
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        assert_eq!(parsed, expected);
+    }
+
+    #[test]
+    fn ok_with_body() {
+        let response = Response::new(StatusCode::Ok, Some("hello".to_string()))
+            .with_header("Content-Type", "text/plain");
+        let parsed = Response::try_from(&sent(response)[..]).unwrap();
+        assert_eq!(parsed.status_code(), StatusCode::Ok);
+        assert_eq!(parsed.header("Content-Length"), Some("5"));
+        assert_eq!(parsed.body(), Some(&b"hello"[..]));
+        assert_headers(&parsed, &[("Content-Type", "text/plain")]);
+    }
+
+    #[test]
+    fn binary_and_empty_bodies() {
+        let body: Vec<u8> = (0..=255).collect();
+        let parsed =
+            Response::try_from(&sent(Response::from_bytes(StatusCode::Ok, body.clone()))[..])
+                .unwrap();
+        assert_eq!(parsed.body(), Some(&body[..]));
+
+        let parsed =
+            Response::try_from(&sent(Response::new(StatusCode::NotFound, None))[..]).unwrap();
+        assert_eq!(parsed.status_code(), StatusCode::NotFound);
+        assert_eq!(parsed.header("Content-Length"), Some("0"));
+        assert_eq!(parsed.body(), Some(&b""[..]));
+    }
+
+    #[test]
+    fn responses_without_body() {
+        for status in [
+            StatusCode::NoContent,
+            StatusCode::NotModified,
+            StatusCode::Continue,
+            StatusCode::SwitchingProtocols,
+        ] {
+            // 본문을 넣어도 보내지 않는다.
+            let response =
+                Response::new(status, Some("ignored".to_string())).with_header("ETag", "\"abc\"");
+            let buf = sent(response);
+            let parsed = Response::try_from(&buf[..]).unwrap();
+            assert_eq!(parsed.status_code(), status);
+            assert_eq!(parsed.body(), None);
+            assert_eq!(parsed.header("Content-Length"), None);
+            assert_headers(&parsed, &[("ETag", "\"abc\"")]);
+        }
+    }
+
+    #[test]
+    fn head_response() {
+        let response = Response::new(StatusCode::Ok, Some("hello".to_string()))
+            .with_header("Content-Type", "text/plain");
+        let mut buf = Vec::new();
+        response.send_head(&mut buf).unwrap();
+        let parsed = Response::try_from_head_response(&buf).unwrap();
+        assert_eq!(parsed.status_code(), StatusCode::Ok);
+        // 길이는 GET과 같게 알려주지만 본문은 없다.
+        assert_eq!(parsed.header("Content-Length"), Some("5"));
+        assert_eq!(parsed.body(), None);
+        assert_headers(&parsed, &[("Content-Type", "text/plain")]);
+        // HEAD 응답인 줄 모르고 읽으면 본문이 모자란다.
+        assert!(matches!(
+            Response::try_from(&buf[..]),
+            Err(ResponseError::Incomplete)
+        ));
+    }
+
+    #[test]
+    fn repeated_headers_keep_their_order() {
+        let mut response = Response::new(StatusCode::Ok, None);
+        response.append_header("Set-Cookie", "a=1");
+        response.append_header("Vary", "Accept");
+        response.append_header("Set-Cookie", "b=2; Path=/");
+        let parsed = Response::try_from(&sent(response)[..]).unwrap();
+        assert_headers(
+            &parsed,
+            &[
+                ("Set-Cookie", "a=1"),
+                ("Vary", "Accept"),
+                ("Set-Cookie", "b=2; Path=/"),
+            ],
+        );
+    }
+
+    #[test]
+    fn chunked_body() {
+        let mut response = Response::stream(StatusCode::Ok, |stream| {
+            let mut chunked = ChunkedWriter::new(stream);
+            chunked.write_all(b"hello ")?;
+            chunked.write_all(b"world")?;
+            chunked.finish().map(|_| ())
+        });
+        response.set_header("Transfer-Encoding", "chunked");
+        response.set_header("Content-Type", "text/plain");
+        let parsed = Response::try_from(&sent(response)[..]).unwrap();
+        assert_eq!(parsed.status_code(), StatusCode::Ok);
+        assert_eq!(parsed.body(), Some(&b"hello world"[..]));
+        // 본문을 풀어서 담으므로 Transfer-Encoding은 빠진다.
+        assert_headers(
+            &parsed,
+            &[("Content-Type", "text/plain"), ("Connection", "close")],
+        );
+    }
+
+    #[test]
+    fn stream_body_until_close() {
+        let response = Response::stream(StatusCode::Ok, |stream| stream.write_all(b"data: 1\n\n"));
+        let parsed = Response::try_from(&sent(response)[..]).unwrap();
+        assert_eq!(parsed.body(), Some(&b"data: 1\n\n"[..]));
+        assert_eq!(parsed.header("Connection"), Some("close"));
+    }
+
+    #[derive(Clone, Copy, Debug)]
+    enum Framing {
+        Length,
+        Chunked,
+        Close,
+    }
+
+    // 상태 코드, 헤더 묶음, 본문, 본문의 끝을 알리는 방법을 모두 조합해서 보낸 그대로 읽히는지 본다.
+    #[test]
+    fn generated_round_trips() {
+        let statuses: Vec<StatusCode> = (100..600).filter_map(StatusCode::from_u16).collect();
+        let header_sets: [&[(&str, &str)]; 4] = [
+            &[],
+            &[("Content-Type", "text/plain; charset=utf-8")],
+            &[
+                ("Set-Cookie", "a=1"),
+                ("Cache-Control", "no-cache"),
+                ("Set-Cookie", "b=2; Path=/"),
+            ],
+            &[("X-Empty", ""), ("X-Spaces", "a  b"), ("ETag", "W/\"x\"")],
+        ];
+        let bodies: [Vec<u8>; 5] = [
+            Vec::new(),
+            b"hello".to_vec(),
+            b"line\r\n\r\nHTTP/1.1 200 OK\r\n".to_vec(),
+            (0..=255).collect(),
+            (0..70_000u32).map(|i| (i % 251) as u8).collect(),
+        ];
+
+        for &status in &statuses {
+            for headers in header_sets {
+                for body in &bodies {
+                    for framing in [Framing::Length, Framing::Chunked, Framing::Close] {
+                        let context = format!("{status:?} {headers:?} {} {framing:?}", body.len());
+                        let mut response = match framing {
+                            Framing::Length => Response::from_bytes(status, body.clone()),
+                            Framing::Chunked => {
+                                let body = body.clone();
+                                let mut response = Response::stream(status, move |stream| {
+                                    let mut chunked = ChunkedWriter::new(stream);
+                                    // 여러 조각으로 나눠 보낸다.
+                                    for part in body.chunks(4096) {
+                                        chunked.write_all(part)?;
+                                    }
+                                    chunked.finish().map(|_| ())
+                                });
+                                response.set_header("Transfer-Encoding", "chunked");
+                                response
+                            }
+                            Framing::Close => {
+                                let body = body.clone();
+                                Response::stream(status, move |stream| stream.write_all(&body))
+                            }
+                        };
+                        for (name, value) in headers {
+                            response.append_header(name, *value);
+                        }
+
+                        let parsed = Response::try_from(&sent(response)[..])
+                            .unwrap_or_else(|e| panic!("{context}: {e}"));
+                        assert_eq!(parsed.status_code(), status, "{context}");
+                        let mut expected = headers.to_vec();
+                        if !status.allows_body() {
+                            // 본문이 없는 응답이면 writer는 불리지 않고 헤더만 간다.
+                            assert_eq!(parsed.body(), None, "{context}");
+                        } else {
+                            assert_eq!(parsed.body(), Some(&body[..]), "{context}");
+                            match framing {
+                                Framing::Length => assert_eq!(
+                                    parsed.header("Content-Length"),
+                                    Some(body.len().to_string().as_str()),
+                                    "{context}"
+                                ),
+                                Framing::Chunked | Framing::Close => {
+                                    assert_eq!(parsed.header("Content-Length"), None, "{context}")
+                                }
+                            }
+                        }
+                        if !matches!(framing, Framing::Length) {
+                            expected.push(("Connection", "close"));
+                        }
+                        let parsed_headers = headers_of(&parsed);
+                        assert_eq!(parsed_headers, expected, "{context}");
+                    }
+                }
+            }
+        }
+    }
+
+    fn headers_of(response: &Response) -> Vec<(&str, &str)> {
+        response
+            .headers()
+            .filter(|(name, _)| *name != "Content-Length")
+            .collect()
+    }
+
+    #[test]
+    fn unknown_codes_fall_back_to_their_class() {
+        let parsed =
+            Response::try_from(&b"HTTP/1.1 299 Whatever\r\nContent-Length: 0\r\n\r\n"[..]).unwrap();
+        assert_eq!(parsed.status_code(), StatusCode::Ok);
+    }
+
+    fn error(buf: &[u8]) -> ResponseError {
+        Response::try_from(buf).unwrap_err()
+    }
+
+    #[test]
+    fn malformed_responses() {
+        assert!(matches!(
+            error(b"HTTP/1.1 200 OK\r\nX-Name: \xff\r\n\r\n"),
+            ResponseError::InvalidEncoding
+        ));
+        assert!(matches!(
+            error(b"\r\n\r\n"),
+            ResponseError::InvalidStatusLine
+        ));
+        assert!(matches!(
+            error(b"HTTP/1.1\r\n\r\n"),
+            ResponseError::InvalidStatusLine
+        ));
+        assert!(matches!(
+            error(b"HTTP/2 200 OK\r\n\r\n"),
+            ResponseError::InvalidProtocol
+        ));
+        assert!(matches!(
+            error(b"HTTP/1.1 2x0 OK\r\n\r\n"),
+            ResponseError::InvalidStatusCode
+        ));
+        assert!(matches!(
+            error(b"HTTP/1.1 999 Nope\r\n\r\n"),
+            ResponseError::InvalidStatusCode
+        ));
+        assert!(matches!(
+            error(b"HTTP/1.1 200 OK\r\nNo colon here\r\n\r\n"),
+            ResponseError::InvalidHeader
+        ));
+        assert!(matches!(
+            error(b"HTTP/1.1 200 OK\r\nContent-Length: abc\r\n\r\n"),
+            ResponseError::InvalidBody
+        ));
+        assert!(matches!(
+            error(b"HTTP/1.1 200 OK\r\nContent-Length: 1\r\nContent-Length: 2\r\n\r\nab"),
+            ResponseError::InvalidBody
+        ));
+        assert!(matches!(
+            error(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\nab\r\n0\r\n\r\n"),
+            ResponseError::InvalidBody
+        ));
+    }
+
+    #[test]
+    fn truncated_and_trailing_bytes() {
+        let full = sent(Response::new(StatusCode::Ok, Some("hello".to_string())));
+        // 머리의 빈 줄 앞에서 자르거나 본문 가운데에서 자르면 모자란다.
+        for len in [0, 10, full.len() - 1] {
+            assert!(matches!(error(&full[..len]), ResponseError::Incomplete));
+        }
+        assert!(matches!(
+            error(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhel"),
+            ResponseError::Incomplete
+        ));
+        assert!(matches!(
+            error(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nhi\r\n"),
+            ResponseError::Incomplete
+        ));
+
+        let mut extra = full.clone();
+        extra.extend_from_slice(b"HTTP/1.1 200 OK\r\n");
+        assert!(matches!(error(&extra), ResponseError::TrailingData));
+        assert!(matches!(
+            error(b"HTTP/1.1 204 No Content\r\n\r\nbody"),
+            ResponseError::TrailingData
+        ));
+    }
+}
@@ -3,21 +3,191 @@ use std::fmt::{Display, Formatter, Result as FmtResult};
 
 // 배리언트에 추가 데이터를 감사고 있지 않는한 간단한 enum은 하나의 숫자로 표현된다.
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum StatusCode {
+    Continue = 100,
+    SwitchingProtocols = 101,
     Ok = 200,
+    Created = 201,
+    Accepted = 202,
+    NonAuthoritativeInformation = 203,
+    NoContent = 204,
+    ResetContent = 205,
+    PartialContent = 206,
+    MultipleChoices = 300,
+    MovedPermanently = 301,
+    Found = 302,
+    SeeOther = 303,
+    NotModified = 304,
+    TemporaryRedirect = 307,
+    PermanentRedirect = 308,
     BadRequest = 400,
+    Unauthorized = 401,
+    PaymentRequired = 402,
+    Forbidden = 403,
     NotFound = 404,
+    MethodNotAllowed = 405,
+    NotAcceptable = 406,
+    ProxyAuthenticationRequired = 407,
+    RequestTimeout = 408,
+    Conflict = 409,
+    Gone = 410,
+    LengthRequired = 411,
+    PreconditionFailed = 412,
+    ContentTooLarge = 413,
+    UriTooLong = 414,
+    UnsupportedMediaType = 415,
+    RangeNotSatisfiable = 416,
+    ExpectationFailed = 417,
+    MisdirectedRequest = 421,
+    UnprocessableContent = 422,
+    UpgradeRequired = 426,
+    PreconditionRequired = 428,
+    TooManyRequests = 429,
+    RequestHeaderFieldsTooLarge = 431,
+    UnavailableForLegalReasons = 451,
+    InternalServerError = 500,
+    NotImplemented = 501,
+    BadGateway = 502,
+    ServiceUnavailable = 503,
+    GatewayTimeout = 504,
+    HttpVersionNotSupported = 505,
 }
 
 impl StatusCode {
     pub fn reason_phrase(&self) -> &str {
         match self {
+            Self::Continue => "Continue",
+            Self::SwitchingProtocols => "Switching Protocols",
             Self::Ok => "OK",
-            Self::BadRequest => "BadRequest",
-            Self::NotFound => "NotFound",
+            Self::Created => "Created",
+            Self::Accepted => "Accepted",
+            Self::NonAuthoritativeInformation => "Non-Authoritative Information",
+            Self::NoContent => "No Content",
+            Self::ResetContent => "Reset Content",
+            Self::PartialContent => "Partial Content",
+            Self::MultipleChoices => "Multiple Choices",
+            Self::MovedPermanently => "Moved Permanently",
+            Self::Found => "Found",
+            Self::SeeOther => "See Other",
+            Self::NotModified => "Not Modified",
+            Self::TemporaryRedirect => "Temporary Redirect",
+            Self::PermanentRedirect => "Permanent Redirect",
+            Self::BadRequest => "Bad Request",
+            Self::Unauthorized => "Unauthorized",
+            Self::PaymentRequired => "Payment Required",
+            Self::Forbidden => "Forbidden",
+            Self::NotFound => "Not Found",
+            Self::MethodNotAllowed => "Method Not Allowed",
+            Self::NotAcceptable => "Not Acceptable",
+            Self::ProxyAuthenticationRequired => "Proxy Authentication Required",
+            Self::RequestTimeout => "Request Timeout",
+            Self::Conflict => "Conflict",
+            Self::Gone => "Gone",
+            Self::LengthRequired => "Length Required",
+            Self::PreconditionFailed => "Precondition Failed",
+            Self::ContentTooLarge => "Content Too Large",
+            Self::UriTooLong => "URI Too Long",
+            Self::UnsupportedMediaType => "Unsupported Media Type",
+            Self::RangeNotSatisfiable => "Range Not Satisfiable",
+            Self::ExpectationFailed => "Expectation Failed",
+            Self::MisdirectedRequest => "Misdirected Request",
+            Self::UnprocessableContent => "Unprocessable Content",
+            Self::UpgradeRequired => "Upgrade Required",
+            Self::PreconditionRequired => "Precondition Required",
+            Self::TooManyRequests => "Too Many Requests",
+            Self::RequestHeaderFieldsTooLarge => "Request Header Fields Too Large",
+            Self::UnavailableForLegalReasons => "Unavailable For Legal Reasons",
+            Self::InternalServerError => "Internal Server Error",
+            Self::NotImplemented => "Not Implemented",
+            Self::BadGateway => "Bad Gateway",
+            Self::ServiceUnavailable => "Service Unavailable",
+            Self::GatewayTimeout => "Gateway Timeout",
+            Self::HttpVersionNotSupported => "HTTP Version Not Supported",
         }
     }
+
+    // 설정 파일처럼 숫자로 주어진 상태 코드를 enum으로 바꾼다. 우리가 모르는 코드라면 None을 리턴한다.
+    pub fn from_u16(code: u16) -> Option<Self> {
+        match code {
+            100 => Some(Self::Continue),
+            101 => Some(Self::SwitchingProtocols),
+            200 => Some(Self::Ok),
+            201 => Some(Self::Created),
+            202 => Some(Self::Accepted),
+            203 => Some(Self::NonAuthoritativeInformation),
+            204 => Some(Self::NoContent),
+            205 => Some(Self::ResetContent),
+            206 => Some(Self::PartialContent),
+            300 => Some(Self::MultipleChoices),
+            301 => Some(Self::MovedPermanently),
+            302 => Some(Self::Found),
+            303 => Some(Self::SeeOther),
+            304 => Some(Self::NotModified),
+            307 => Some(Self::TemporaryRedirect),
+            308 => Some(Self::PermanentRedirect),
+            400 => Some(Self::BadRequest),
+            401 => Some(Self::Unauthorized),
+            402 => Some(Self::PaymentRequired),
+            403 => Some(Self::Forbidden),
+            404 => Some(Self::NotFound),
+            405 => Some(Self::MethodNotAllowed),
+            406 => Some(Self::NotAcceptable),
+            407 => Some(Self::ProxyAuthenticationRequired),
+            408 => Some(Self::RequestTimeout),
+            409 => Some(Self::Conflict),
+            410 => Some(Self::Gone),
+            411 => Some(Self::LengthRequired),
+            412 => Some(Self::PreconditionFailed),
+            413 => Some(Self::ContentTooLarge),
+            414 => Some(Self::UriTooLong),
+            415 => Some(Self::UnsupportedMediaType),
+            416 => Some(Self::RangeNotSatisfiable),
+            417 => Some(Self::ExpectationFailed),
+            421 => Some(Self::MisdirectedRequest),
+            422 => Some(Self::UnprocessableContent),
+            426 => Some(Self::UpgradeRequired),
+            428 => Some(Self::PreconditionRequired),
+            429 => Some(Self::TooManyRequests),
+            431 => Some(Self::RequestHeaderFieldsTooLarge),
+            451 => Some(Self::UnavailableForLegalReasons),
+            500 => Some(Self::InternalServerError),
+            501 => Some(Self::NotImplemented),
+            502 => Some(Self::BadGateway),
+            503 => Some(Self::ServiceUnavailable),
+            504 => Some(Self::GatewayTimeout),
+            505 => Some(Self::HttpVersionNotSupported),
+            _ => None,
+        }
+    }
+
+    // 1xx 응답은 본문 없이 헤더만 보낸다.
+    pub fn is_informational(&self) -> bool {
+        matches!(*self as u16, 100..=199)
+    }
+
+    // 1xx와 204 응답은 본문을 가질 수 없고 Content-Length도 붙이면 안 된다. (RFC 9110 8.6)
+    // 304 응답도 본문이 없다. 캐시에 있는 본문을 그대로 쓰라는 뜻이다.
+    pub fn allows_body(&self) -> bool {
+        !self.is_informational() && *self != Self::NoContent && *self != Self::NotModified
+    }
+
+    // 우리가 모르는 코드는 같은 종류의 x00 코드로 다룬다. (RFC 9110 15) 프록시가 받은 응답에 쓴다.
+    pub fn from_u16_or_class(code: u16) -> Option<Self> {
+        Self::from_u16(code).or_else(|| Self::from_u16(code / 100 * 100))
+    }
+
+    // Location으로 다른 주소를 알려주는 응답. 300과 304는 3xx지만 여기에 들지 않는다.
+    pub fn is_redirect(&self) -> bool {
+        matches!(
+            self,
+            Self::MovedPermanently
+                | Self::Found
+                | Self::SeeOther
+                | Self::TemporaryRedirect
+                | Self::PermanentRedirect
+        )
+    }
 }
 
 impl Display for StatusCode {
@@ -0,0 +1,182 @@
+use crate::http::{Method, Request, Response, StatusCode};
+use crate::server::Handler;
+use crate::virtual_hosts::host_name;
+use crate::website_handler::WebsiteHandler;
+
+// 평문 HTTP로 들어온 모든 요청을 같은 주소의 https:// URL로 보낸다.
+// GET, HEAD는 301로, 그 외의 메서드는 메서드와 본문이 유지되도록 308로 응답한다.
+//
+// well_known()으로 디렉터리를 주면 /.well-known/ 아래의 경로는 리다이렉트하지 않고 그 디렉터리에서 파일을 보여준다.
+// 인증서를 발급받을 때 ACME(HTTP-01) 검증 요청은 반드시 평문 HTTP로 응답해야 하기 때문이다.
+pub struct HttpsRedirect {
+    // HTTPS 리스너가 443이 아닌 포트에 있다면 그 포트를 URL에 붙인다.
+    port: Option<u16>,
+    well_known: Option<WebsiteHandler>,
+}
+
+impl HttpsRedirect {
+    pub fn new() -> Self {
+        Self {
+            port: None,
+            well_known: None,
+        }
+    }
+
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = if port == 443 { None } else { Some(port) };
+        self
+    }
+
+    // certbot의 --webroot처럼 root 아래의 .well-known 디렉터리에서 파일을 찾는다.
+    // 예를 들어 /.well-known/acme-challenge/abc 요청은 root/.well-known/acme-challenge/abc 파일로 응답한다.
+    pub fn well_known(mut self, root: String) -> Self {
+        self.well_known = Some(WebsiteHandler::new(root));
+        self
+    }
+
+    fn location(&self, host: &str, request: &Request) -> String {
+        match self.port {
+            Some(port) => format!("https://{}:{}{}", host, port, request.target()),
+            None => format!("https://{}{}", host, request.target()),
+        }
+    }
+}
+
+impl Default for HttpsRedirect {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Handler for HttpsRedirect {
+    fn handle_request(&self, request: &Request) -> Response {
+        if let Some(well_known) = &self.well_known {
+            if request.path().starts_with("/.well-known/") {
+                return well_known.handle_request(request);
+            }
+        }
+
+        // 포트는 평문 리스너의 것이므로 떼어내고 이름만 쓴다.
+        let host = match request.header("Host").and_then(host_name) {
+            Some(host) => host,
+            None => return Response::new(StatusCode::BadRequest, None),
+        };
+        // 요청 대상이 "*"나 절대 URL이면 host 뒤에 그대로 붙일 수 없다.
+        if !request.target().starts_with('/') {
+            return Response::new(StatusCode::BadRequest, None);
+        }
+
+        let status = match request.method() {
+            Method::GET | Method::HEAD => StatusCode::MovedPermanently,
+            _ => StatusCode::PermanentRedirect,
+        };
+        Response::new(status, None).with_header("Location", self.location(&host, request))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // 테스트마다 새로 만드는 디렉터리. 값이 사라질 때 지운다.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            static NEXT: AtomicUsize = AtomicUsize::new(0);
+            let path = std::env::temp_dir().join(format!(
+                "http_server-redirect-{}-{}",
+                std::process::id(),
+                NEXT.fetch_add(1, Ordering::Relaxed)
+            ));
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn request(redirect: &HttpsRedirect, method: &str, target: &str) -> Response {
+        let raw = format!(
+            "{} {} HTTP/1.1\r\nHost: Example.Test:8080\r\n\r\n",
+            method, target
+        );
+        redirect.handle_request(&Request::try_from(raw.as_bytes()).unwrap())
+    }
+
+    #[test]
+    fn safe_methods_get_301_and_others_308() {
+        let redirect = HttpsRedirect::new();
+        for (method, status) in [
+            ("GET", StatusCode::MovedPermanently),
+            ("HEAD", StatusCode::MovedPermanently),
+            ("POST", StatusCode::PermanentRedirect),
+            ("PUT", StatusCode::PermanentRedirect),
+            ("DELETE", StatusCode::PermanentRedirect),
+        ] {
+            let response = request(&redirect, method, "/");
+            assert_eq!(response.status_code(), status, "{}", method);
+            assert_eq!(response.header("Location"), Some("https://example.test/"));
+        }
+    }
+
+    #[test]
+    fn location_keeps_path_and_query() {
+        let response = request(&HttpsRedirect::new(), "GET", "/a/b?x=1&y=%20");
+        assert_eq!(
+            response.header("Location"),
+            Some("https://example.test/a/b?x=1&y=%20")
+        );
+        // 평문 리스너의 포트 대신 HTTPS 포트를 붙이고, 443이면 적지 않는다.
+        let response = request(&HttpsRedirect::new().port(8443), "GET", "/a?x=1");
+        assert_eq!(
+            response.header("Location"),
+            Some("https://example.test:8443/a?x=1")
+        );
+        let response = request(&HttpsRedirect::new().port(443), "GET", "/a");
+        assert_eq!(response.header("Location"), Some("https://example.test/a"));
+    }
+
+    #[test]
+    fn well_known_is_served_instead_of_redirected() {
+        let dir = TempDir::new();
+        fs::create_dir_all(dir.0.join(".well-known/acme-challenge")).unwrap();
+        fs::write(dir.0.join(".well-known/acme-challenge/token"), "proof").unwrap();
+        let redirect = HttpsRedirect::new().well_known(dir.0.to_string_lossy().into_owned());
+
+        let response = request(&redirect, "GET", "/.well-known/acme-challenge/token");
+        assert_eq!(response.status_code(), StatusCode::Ok);
+        assert_eq!(response.body(), Some(&b"proof"[..]));
+        assert_eq!(response.header("Location"), None);
+        let response = request(&redirect, "GET", "/.well-known/acme-challenge/missing");
+        assert_eq!(response.status_code(), StatusCode::NotFound);
+        // .well-known 밖의 경로는 그대로 리다이렉트한다.
+        let response = request(&redirect, "GET", "/index.html");
+        assert_eq!(response.status_code(), StatusCode::MovedPermanently);
+
+        // 디렉터리를 주지 않았으면 .well-known도 리다이렉트한다.
+        let response = request(
+            &HttpsRedirect::new(),
+            "GET",
+            "/.well-known/acme-challenge/token",
+        );
+        assert_eq!(response.status_code(), StatusCode::MovedPermanently);
+    }
+
+    #[test]
+    fn missing_host_or_non_path_target_is_bad_request() {
+        let raw = "GET / HTTP/1.1\r\n\r\n";
+        let response =
+            HttpsRedirect::new().handle_request(&Request::try_from(raw.as_bytes()).unwrap());
+        assert_eq!(response.status_code(), StatusCode::BadRequest);
+        let response = request(&HttpsRedirect::new(), "OPTIONS", "*");
+        assert_eq!(response.status_code(), StatusCode::BadRequest);
+    }
+}
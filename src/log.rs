@@ -0,0 +1,124 @@
+use std::fmt::{Arguments, Display, Formatter, Result as FmtResult};
+use std::fs::{File, OpenOptions};
+use std::io::{Result as IoResult, Write};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Mutex;
+
+// 로그 레벨. 숫자가 클수록 더 자세한 로그까지 출력한다.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Off = 0,
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+}
+
+// 모든 스레드가 같은 레벨을 봐야 하기 때문에 전역 원자 변수에 저장한다.
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(level: Level) -> bool {
+    level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+// 파일이 지정되면 모든 레벨의 로그를 그 파일 끝에 이어서 쓴다. 지정되지 않으면 터미널로 출력한다.
+static FILE: Mutex<Option<File>> = Mutex::new(None);
+
+pub fn set_file(path: &str) -> IoResult<()> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    *FILE.lock().unwrap() = Some(file);
+    Ok(())
+}
+
+// 다시 터미널로 출력한다.
+pub fn close_file() {
+    *FILE.lock().unwrap() = None;
+}
+
+// 매크로가 호출하는 함수. 직접 부르기보다는 error!, info! 같은 매크로를 사용한다.
+pub fn write(level: Level, args: Arguments) {
+    if !enabled(level) {
+        return;
+    }
+
+    if let Some(file) = FILE.lock().unwrap().as_mut() {
+        let _ = writeln!(file, "[{}] {}", level, args);
+        return;
+    }
+
+    // 에러와 경고는 표준 에러로, 나머지는 표준 출력으로 보낸다.
+    if level <= Level::Warn {
+        eprintln!("{}", args);
+    } else {
+        println!("{}", args);
+    }
+}
+
+impl Level {
+    fn as_str(&self) -> &str {
+        match self {
+            Self::Off => "off",
+            Self::Error => "error",
+            Self::Warn => "warn",
+            Self::Info => "info",
+            Self::Debug => "debug",
+        }
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "error" => Ok(Self::Error),
+            "warn" => Ok(Self::Warn),
+            "info" => Ok(Self::Info),
+            "debug" => Ok(Self::Debug),
+            _ => Err(format!(
+                "unknown log level {} (expected off, error, warn, info or debug)",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => {
+        $crate::log::write($crate::log::Level::Error, format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        $crate::log::write($crate::log::Level::Warn, format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::log::write($crate::log::Level::Info, format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::log::write($crate::log::Level::Debug, format_args!($($arg)*))
+    };
+}
@@ -2,19 +2,221 @@
 // !의 속성이 그 안에 선언된 아이템에 적용될 것이라는 의미이다.
 // 따라서 main 모듈안에 선언되었기에 전체 모듈과 서브 모듈에 적용이 된다.
 // !을 안붇이면 그 뒤에 있는 식에만 속성이 적용된다는 의미이다.
-use server::Server;
+use cli::{Command, Options};
+use config::{Config, ListenerConfig};
+use reload::{reload_on_sighup, Reloadable};
+use server::shutdown_on_signals;
 use std::env;
-use website_handler::WebsiteHandler;
+use std::process;
 
+// 매크로는 선언된 뒤에만 사용할 수 있기 때문에 log 모듈을 가장 먼저 선언한다.
+#[macro_use]
+mod log;
+
+mod auth;
+mod cli;
+mod compression;
+mod config;
+mod cors;
 mod http;
+mod https_redirect;
+mod proxy;
+mod rate_limit;
+mod reload;
 mod server;
+mod session;
+mod sse;
+mod virtual_hosts;
 mod website_handler;
+mod websocket;
+
 fn main() {
-    // env! : 컴파일링 할때 환경 변수를 읽는데 사용한다.
-    // CARGO_MANIFEST_DIR 이라고 하면 Cargo.toml 파일이 있는 위치를 기준으로 한다.
-    let default_path = format!("{}/public", env!("CARGO_MANIFEST_DIR"));
-    let public_path = env::var("PUBLIC_PATH").unwrap_or(default_path);
-    println!("public path : {}", public_path);
-    let server = Server::new("127.0.0.1:4000".to_string());
-    server.run(WebsiteHandler::new(public_path));
+    // 첫번째 인자는 프로그램 이름이므로 건너뛴다.
+    let options = match cli::parse(env::args().skip(1), |key| env::var(key).ok()) {
+        Ok(Command::Run(options)) => options,
+        Ok(Command::Help) => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        Ok(Command::Version) => {
+            println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+            return;
+        }
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, cli::USAGE);
+            process::exit(2);
+        }
+    };
+
+    let config = match load_config(&options) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = apply_log_config(&config) {
+        eprintln!("error: {}", e);
+        process::exit(2);
+    }
+
+    info!("public path : {}", config.site.root);
+    let server = match config.server() {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(2);
+        }
+    };
+    // Ctrl+C나 배포 도구가 보내는 SIGTERM을 받으면 처리 중인 요청을 마무리하고 run()에서 빠져나온다.
+    if let Err(e) = shutdown_on_signals(server.shutdown_handle()) {
+        error!("Failed to install signal handlers : {}", e);
+    }
+
+    // SIGHUP을 받으면 설정 파일을 다시 읽어서 경로, 루트, 헤더 등을 새 요청부터 적용한다.
+    // 새 설정이 잘못되었다면 로그만 남기고 예전 설정을 그대로 쓴다.
+    // 핸들러를 새로 만들므로 RateLimit 버킷과 프록시 헬스 체크는 처음부터 다시 시작한다.
+    let handler = Reloadable::new(config.handler());
+    let reloader = handler.handle();
+    let mut current = config;
+    let reload = move || match load_config(&options) {
+        Ok(config) => {
+            for key in current.restart_required(&config) {
+                warn!("{} changed, restart the server to apply it", key);
+            }
+            if let Err(e) = apply_log_config(&config) {
+                error!("{}", e);
+            }
+            reloader.replace(config.handler());
+            info!("Configuration reloaded");
+            current = config;
+        }
+        Err(e) => error!(
+            "Failed to reload configuration, keeping the previous one : {}",
+            e
+        ),
+    };
+    if let Err(e) = reload_on_sighup(reload) {
+        error!("Failed to install signal handlers : {}", e);
+    }
+
+    if let Err(e) = server.run(handler) {
+        error!("{}", e);
+        process::exit(1);
+    }
+}
+
+fn apply_log_config(config: &Config) -> Result<(), String> {
+    log::set_level(config.log.level);
+    match &config.log.file {
+        Some(file) => {
+            log::set_file(file).map_err(|e| format!("cannot open log file {}: {}", file, e))
+        }
+        None => {
+            log::close_file();
+            Ok(())
+        }
+    }
+}
+
+// 설정 파일이 있으면 읽고, 명령행 인자와 환경 변수로 주어진 값으로 덮어쓴다.
+fn load_config(options: &Options) -> Result<Config, config::ConfigError> {
+    let mut config = match &options.config {
+        Some(path) => Config::load(path)?,
+        // env! : 컴파일링 할때 환경 변수를 읽는데 사용한다.
+        // CARGO_MANIFEST_DIR 이라고 하면 Cargo.toml 파일이 있는 위치를 기준으로 한다.
+        None => Config::new(format!("{}/public", env!("CARGO_MANIFEST_DIR"))),
+    };
+
+    if let Some(addr) = options.addr() {
+        config.listeners = vec![ListenerConfig {
+            bind: addr,
+            tls: None,
+            https_redirect: None,
+        }];
+    }
+    if let Some(root) = &options.root {
+        config.site.root = root.clone();
+    }
+    if let Some(threads) = options.threads {
+        config.threads = threads;
+    }
+    if let Some(level) = options.log {
+        config.log.level = level;
+    }
+
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // 설정 파일 < 환경 변수 < 명령행 인자 순서로 덮어쓰는지 본다.
+    #[test]
+    fn options_override_the_config_file() {
+        let dir = std::env::temp_dir().join(format!("http_server-cli-{}", process::id()));
+        fs::create_dir_all(dir.join("site")).unwrap();
+        let path = dir.join("server.toml");
+        fs::write(
+            &path,
+            "[server]\nthreads = 4\n\n[[listeners]]\nbind = \"127.0.0.1:4100\"\n\n\
+             [[listeners]]\nbind = \"127.0.0.1:4200\"\n\n[site]\nroot = \"site\"\n\n\
+             [log]\nlevel = \"warn\"\n",
+        )
+        .unwrap();
+        let path = path.to_string_lossy().into_owned();
+
+        let load = |args: &[&str], env: &[(&str, &str)]| {
+            let args = args.iter().map(|arg| arg.to_string());
+            let options = match cli::parse(args, |name| {
+                env.iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| value.to_string())
+            }) {
+                Ok(Command::Run(options)) => options,
+                other => panic!("{:?}", other),
+            };
+            load_config(&options).unwrap()
+        };
+        let binds = |config: &Config| {
+            config
+                .listeners
+                .iter()
+                .map(|l| l.bind.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let config = load(&["-c", &path], &[]);
+        assert_eq!(config.threads, 4);
+        assert_eq!(binds(&config), ["127.0.0.1:4100", "127.0.0.1:4200"]);
+        assert_eq!(config.site.root, dir.join("site").to_string_lossy());
+        assert_eq!(config.log.level, log::Level::Warn);
+
+        let env = [
+            ("HTTP_CONFIG", path.as_str()),
+            ("HTTP_THREADS", "8"),
+            ("HTTP_PORT", "5000"),
+            ("HTTP_LOG", "error"),
+        ];
+        let config = load(&[], &env);
+        assert_eq!(config.threads, 8);
+        // 포트만 주어도 설정 파일의 리스너를 모두 바꾼다.
+        assert_eq!(binds(&config), ["127.0.0.1:5000"]);
+        assert_eq!(config.site.root, dir.join("site").to_string_lossy());
+        assert_eq!(config.log.level, log::Level::Error);
+
+        let config = load(
+            &["-t", "2", "-b", "0.0.0.0", "-r", "cli-root", "-l", "debug"],
+            &env,
+        );
+        assert_eq!(config.threads, 2);
+        assert_eq!(binds(&config), ["0.0.0.0:5000"]);
+        assert_eq!(config.site.root, "cli-root");
+        assert_eq!(config.log.level, log::Level::Debug);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
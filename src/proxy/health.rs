@@ -0,0 +1,169 @@
+use super::pool::Pool;
+use crate::http::Client;
+use std::sync::{Arc, Weak};
+use std::thread;
+use std::time::Duration;
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(10);
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+
+// 능동 검사. interval마다 서버마다 path로 GET 요청을 보내서 2xx, 3xx로 응답하지 않는 서버를 뺀다.
+// 서버마다 연결을 이어서 쓰므로 검사할 때마다 새로 연결하지 않는다.
+// 빠진 서버도 계속 검사해서 다시 응답하면 넣는다.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthCheck {
+    path: String,
+    interval: Duration,
+    timeout: Duration,
+}
+
+impl HealthCheck {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            interval: DEFAULT_INTERVAL,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    // 연결하고 응답을 받을 때까지 기다리는 시간
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+// 검사하는 스레드를 띄운다. 스레드는 Pool을 약하게 가지고 있어서,
+// 설정을 다시 읽어서 핸들러가 바뀌면 다음 검사 때 끝난다.
+pub(super) fn spawn(pool: &Arc<Pool>, check: HealthCheck) {
+    let pool = Arc::downgrade(pool);
+    let spawned = thread::Builder::new()
+        .name("health-check".to_string())
+        .spawn(move || run(pool, check));
+    if let Err(e) = spawned {
+        error!("Failed to start the health check : {}", e);
+    }
+}
+
+fn run(pool: Weak<Pool>, check: HealthCheck) {
+    let client = Client::new()
+        .connect_timeout(check.timeout)
+        .timeout(check.timeout);
+    while let Some(pool) = pool.upgrade() {
+        for server in pool.servers() {
+            let url = format!("http://{}{}", server.addr(), check.path);
+            let healthy = match client.get(&url) {
+                Ok(response) if (200..400).contains(&(response.status_code() as u16)) => true,
+                Ok(response) => {
+                    debug!(
+                        "Health check of {} returned {}",
+                        server.addr(),
+                        response.status_code()
+                    );
+                    false
+                }
+                Err(e) => {
+                    debug!("Health check of {} failed : {}", server.addr(), e);
+                    false
+                }
+            };
+            server.set_healthy(healthy);
+        }
+        drop(pool);
+        thread::sleep(check.interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::Request;
+    use crate::proxy::Upstream;
+    use std::convert::TryFrom;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Instant;
+
+    // 검사 경로에 healthy가 켜져 있으면 200, 아니면 503으로 응답하는 업스트림
+    fn spawn_upstream(healthy: Arc<AtomicBool>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { return };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap_or(0) > 0 && line != "\r\n" {
+                    line.clear();
+                }
+                let status = match healthy.load(Ordering::SeqCst) {
+                    true => "200 OK",
+                    false => "503 Service Unavailable",
+                };
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                );
+            }
+        });
+        addr
+    }
+
+    fn available(pool: &Arc<Pool>) -> bool {
+        let request = Request::try_from(&b"GET / HTTP/1.1\r\nHost: example.test\r\n\r\n"[..]);
+        pool.pick(&request.unwrap(), &[]).is_some()
+    }
+
+    // 검사하는 스레드가 결과를 바꿀 때까지 기다린다.
+    fn wait_until_available(pool: &Arc<Pool>, expected: bool) {
+        let started = Instant::now();
+        while available(pool) != expected {
+            assert!(
+                started.elapsed() < Duration::from_secs(5),
+                "server never became {}",
+                if expected { "available" } else { "unavailable" }
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn marks_servers_down_and_back_up() {
+        let healthy = Arc::new(AtomicBool::new(true));
+        let check = HealthCheck::new("/health")
+            .interval(Duration::from_millis(20))
+            .timeout(Duration::from_secs(1));
+        let upstream = Upstream::new(spawn_upstream(Arc::clone(&healthy)));
+        let pool = Arc::new(Pool::new(upstream));
+        spawn(&pool, check);
+        assert!(available(&pool));
+
+        healthy.store(false, Ordering::SeqCst);
+        wait_until_available(&pool, false);
+        healthy.store(true, Ordering::SeqCst);
+        wait_until_available(&pool, true);
+    }
+
+    #[test]
+    fn stops_when_the_pool_is_dropped() {
+        let check = HealthCheck::new("/health").interval(Duration::from_millis(20));
+        let healthy = Arc::new(AtomicBool::new(true));
+        let pool = Arc::new(Pool::new(Upstream::new(spawn_upstream(healthy))));
+        let weak = Arc::downgrade(&pool);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        thread::spawn(move || {
+            run(weak, check);
+            let _ = sender.send(());
+        });
+
+        drop(pool);
+        receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    }
+}
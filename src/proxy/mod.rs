@@ -0,0 +1,968 @@
+// 경로 접두사마다 요청을 다른 HTTP/1.1 서버(업스트림)에 넘기고, 받은 응답을 클라이언트에게 돌려준다. (리버스 프록시)
+//
+// 업스트림은 서버 여러 개로 이루어질 수 있고, 요청마다 balance에 따라 한 서버를 고른다. (pool.rs)
+// 서버에는 요청마다 새로 연결하고 응답을 다 받으면 닫는다. Host는 서버의 주소로 바꾸고,
+// 클라이언트의 주소와 스킴은 X-Forwarded-For, X-Forwarded-Proto, Forwarded 헤더로 알려준다.
+// 서버에 연결할 수 없으면 다른 서버에 다시 연결해 본다. 응답이 이상하면 502 Bad Gateway,
+// 제때 응답하지 않으면 504 Gateway Timeout, 쓸 수 있는 서버가 하나도 없으면 503 Service Unavailable로 응답한다.
+//
+// let handler = ProxyHandler::new(WebsiteHandler::new(root))
+//     .route("/api", Upstream::new("127.0.0.1:3000").strip_prefix(true))
+//     .route(
+//         "/app",
+//         Upstream::new("127.0.0.1:4000")
+//             .server("127.0.0.1:4001")
+//             .balance(Balance::LeastConnections)
+//             .health_check(HealthCheck::new("/health")),
+//     );
+mod health;
+mod pool;
+
+pub use health::HealthCheck;
+pub use pool::{Balance, HashKey};
+
+use crate::config::is_token;
+use crate::http::chunked::{ChunkedReader, ChunkedWriter};
+use crate::http::path::{clean, has_prefix, normalize};
+use crate::http::response::{read_head, Framing, ResponseHead};
+use crate::http::{Headers, Method, ParseError, Request, Response, StatusCode};
+use crate::server::Handler;
+use std::io::{self, BufReader, BufWriter, Error, ErrorKind, Read, Result as IoResult, Write};
+use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+use pool::{Lease, Pool};
+
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+const DEFAULT_MAX_FAILS: u32 = 3;
+const DEFAULT_FAIL_TIMEOUT: Duration = Duration::from_secs(10);
+// Content-Length가 이 크기 이하면 모두 받은 뒤에 보통 응답으로 돌려준다. 압축 미들웨어와 HTTP/2에서도 쓸 수 있다.
+// 더 크거나 chunked처럼 끝을 미리 알 수 없으면 머리만 받고 본문은 받는 대로 흘려보낸다.
+// SSE나 롱 폴링은 본문이 언제 끝날지 모르므로 기다렸다가 보내면 안 된다. (HTTP/2에서는 HTTP/1.1로 다시 요청하게 된다)
+const BUFFER_LIMIT: usize = 1024 * 1024;
+// 업스트림 응답의 상태 줄과 헤더를 합친 최대 크기
+const MAX_RESPONSE_HEAD: usize = 64 * 1024;
+
+// 연결 하나에만 의미가 있는 헤더들. 다음 연결로 넘기지 않는다. (RFC 9110 7.6.1)
+// Connection 헤더에 적힌 이름들도 마찬가지다.
+const HOP_BY_HOP: &[&str] = &[
+    "Connection",
+    "Keep-Alive",
+    "Proxy-Connection",
+    "Proxy-Authenticate",
+    "Proxy-Authorization",
+    "TE",
+    "Trailer",
+    "Transfer-Encoding",
+    "Upgrade",
+];
+
+pub struct ProxyHandler<H> {
+    inner: H,
+    // (경로 접두사, 업스트림). 여러 접두사가 맞으면 가장 긴 것을 쓴다. 맞는 게 없으면 inner가 처리한다.
+    routes: Vec<(String, Arc<Pool>)>,
+}
+
+// 요청을 넘길 서버들
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Upstream {
+    // "127.0.0.1:3000", "localhost:8080" 처럼 호스트와 포트
+    servers: Vec<String>,
+    balance: Balance,
+    health_check: Option<HealthCheck>,
+    max_fails: u32,
+    fail_timeout: Duration,
+    strip_prefix: bool,
+    preserve_host: bool,
+    connect_timeout: Duration,
+    timeout: Duration,
+}
+
+impl Upstream {
+    pub fn new(addr: impl Into<String>) -> Self {
+        Self {
+            servers: vec![addr.into()],
+            balance: Balance::RoundRobin,
+            health_check: None,
+            max_fails: DEFAULT_MAX_FAILS,
+            fail_timeout: DEFAULT_FAIL_TIMEOUT,
+            strip_prefix: false,
+            preserve_host: false,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    // 같은 요청을 처리할 수 있는 서버를 하나 더 둔다.
+    pub fn server(mut self, addr: impl Into<String>) -> Self {
+        self.servers.push(addr.into());
+        self
+    }
+
+    pub fn balance(mut self, balance: Balance) -> Self {
+        self.balance = balance;
+        self
+    }
+
+    pub fn health_check(mut self, health_check: HealthCheck) -> Self {
+        self.health_check = Some(health_check);
+        self
+    }
+
+    // 요청을 넘기다가 연달아 max_fails번 실패한 서버는 fail_timeout 동안 고르지 않는다. 0이면 빼지 않는다.
+    pub fn max_fails(mut self, max_fails: u32) -> Self {
+        self.max_fails = max_fails;
+        self
+    }
+
+    pub fn fail_timeout(mut self, fail_timeout: Duration) -> Self {
+        self.fail_timeout = fail_timeout;
+        self
+    }
+
+    // 켜면 경로에서 접두사를 떼고 넘긴다. "/api"로 연결했다면 "/api/users"가 "/users"가 된다.
+    pub fn strip_prefix(mut self, strip_prefix: bool) -> Self {
+        self.strip_prefix = strip_prefix;
+        self
+    }
+
+    // 켜면 Host를 업스트림 주소로 바꾸지 않고 클라이언트가 보낸 그대로 넘긴다.
+    pub fn preserve_host(mut self, preserve_host: bool) -> Self {
+        self.preserve_host = preserve_host;
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    // 업스트림에 쓰거나 업스트림에서 읽을 때 한 번에 기다리는 시간
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl<H: Handler> ProxyHandler<H> {
+    pub fn new(inner: H) -> Self {
+        Self {
+            inner,
+            routes: Vec::new(),
+        }
+    }
+
+    // prefix와 그 아래의 경로를 upstream으로 넘긴다. 능동 검사를 설정했다면 검사하는 스레드를 띄운다.
+    pub fn route(mut self, prefix: &str, upstream: Upstream) -> Self {
+        let health_check = upstream.health_check.clone();
+        let pool = Arc::new(Pool::new(upstream));
+        if let Some(health_check) = health_check {
+            health::spawn(&pool, health_check);
+        }
+        self.routes.push((normalize(prefix), pool));
+        self
+    }
+
+    fn find_route(&self, path: &str) -> Option<&(String, Arc<Pool>)> {
+        let path = normalize(path);
+        self.routes
+            .iter()
+            .filter(|(prefix, _)| has_prefix(&path, prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+    }
+}
+
+impl<H: Handler> Handler for ProxyHandler<H> {
+    fn handle_request(&self, request: &Request) -> Response {
+        let (prefix, pool) = match self.find_route(request.path()) {
+            Some(route) => route,
+            None => return self.inner.handle_request(request),
+        };
+        // 터널을 여는 CONNECT는 리버스 프록시가 할 일이 아니다.
+        if matches!(request.method(), Method::CONNECT) {
+            return Response::new(StatusCode::MethodNotAllowed, None);
+        }
+
+        let upstream = &pool.upstream;
+        let target = upstream_target(request, prefix, upstream.strip_prefix);
+        let mut tried = Vec::new();
+        let mut last_error = None;
+        while let Some(lease) = pool.pick(request, &tried) {
+            let index = lease.index();
+            tried.push(index);
+            // 연결하기 전에는 본문을 읽지 않았으므로 다른 서버로 다시 보내도 된다.
+            let stream = match connect(lease.addr(), upstream.connect_timeout) {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!("Failed to connect to {} : {}", lease.addr(), e);
+                    pool.failed(index);
+                    last_error = Some(e);
+                    continue;
+                }
+            };
+
+            let addr = lease.addr().to_string();
+            return match forward(request, upstream, stream, &target, lease) {
+                Ok(response) => {
+                    pool.succeeded(index);
+                    response
+                }
+                Err(failure) => {
+                    let e = match failure {
+                        Failure::Upstream(e) => {
+                            pool.failed(index);
+                            e
+                        }
+                        Failure::Body(e) => e,
+                    };
+                    warn!("Failed to proxy {} to {} : {}", request.path(), addr, e);
+                    error_response(&e)
+                }
+            };
+        }
+
+        match last_error {
+            Some(e) => error_response(&e),
+            None => {
+                warn!("No upstream server is available for {}", request.path());
+                Response::new(StatusCode::ServiceUnavailable, None)
+            }
+        }
+    }
+
+    fn handle_bad_request(&self, e: &ParseError) -> Response {
+        self.inner.handle_bad_request(e)
+    }
+
+    // WebSocket 경로는 프록시 경로보다 먼저 처리되므로 안쪽 핸들러의 검사만 거친다.
+    fn accept_upgrade<'buf>(&self, request: &Request<'buf>) -> Result<Request<'buf>, Response> {
+        self.inner.accept_upgrade(request)
+    }
+}
+
+// 요청을 넘기지 못한 까닭
+enum Failure {
+    // 서버가 응답하지 않거나 응답이 이상하다.
+    Upstream(Error),
+    // 클라이언트의 본문을 보내다가 실패했고 서버의 응답도 없다.
+    // 클라이언트가 끊었을 수도 있으므로 서버를 빼는 데 세지 않는다.
+    Body(Error),
+}
+
+fn error_response(e: &Error) -> Response {
+    let status = match e.kind() {
+        ErrorKind::TimedOut | ErrorKind::WouldBlock => StatusCode::GatewayTimeout,
+        _ => StatusCode::BadGateway,
+    };
+    Response::new(status, None)
+}
+
+// 업스트림에 보낼 경로와 쿼리 문자열. %XX는 클라이언트가 보낸 그대로 두고 "."와 ".."만 정리한다.
+fn upstream_target(request: &Request, prefix: &str, strip_prefix: bool) -> String {
+    let (path, query) = match request.target().split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (request.target(), None),
+    };
+    let mut target = clean(path);
+    if strip_prefix {
+        // 접두사는 %XX를 푼 경로와 비교했으므로 글자가 아니라 부분의 개수만큼 떼어낸다.
+        let count = prefix
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .count();
+        target = match target.match_indices('/').nth(count) {
+            Some((i, _)) => target[i..].to_string(),
+            None => "/".to_string(),
+        };
+    }
+    if let Some(query) = query {
+        target.push('?');
+        target.push_str(query);
+    }
+    target
+}
+
+fn forward(
+    request: &Request,
+    upstream: &Upstream,
+    stream: TcpStream,
+    target: &str,
+    lease: Lease,
+) -> Result<Response, Failure> {
+    stream
+        .set_read_timeout(Some(upstream.timeout))
+        .and_then(|_| stream.set_write_timeout(Some(upstream.timeout)))
+        .map_err(Failure::Upstream)?;
+
+    let mut writer = BufWriter::new(&stream);
+    write_head(&mut writer, request, upstream, lease.addr(), target).map_err(Failure::Upstream)?;
+    let sent = send_body(&mut writer, request);
+    if sent.is_err() {
+        // 본문을 다 보내지 못했다. 업스트림이 본문을 더 기다리지 않도록 쓰는 쪽을 닫고,
+        // 업스트림이 먼저 응답을 보냈다면(413 등) 그 응답을 돌려준다.
+        let _ = stream.shutdown(Shutdown::Write);
+    }
+    drop(writer);
+
+    match read_response(BufReader::new(stream), request, lease) {
+        Ok(response) => Ok(response),
+        Err(e) => match sent {
+            Ok(()) => Err(Failure::Upstream(e)),
+            Err(sent) => Err(Failure::Body(sent)),
+        },
+    }
+}
+
+fn connect(addr: &str, timeout: Duration) -> IoResult<TcpStream> {
+    let mut last_error = Error::new(ErrorKind::NotFound, format!("{} did not resolve", addr));
+    for addr in addr.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+fn write_head(
+    writer: &mut impl Write,
+    request: &Request,
+    upstream: &Upstream,
+    addr: &str,
+    target: &str,
+) -> IoResult<()> {
+    let headers = request.headers();
+    write!(
+        writer,
+        "{} {} HTTP/1.1\r\n",
+        request.method().as_str(),
+        target
+    )?;
+
+    let connection = connection_tokens(headers.get_all("Connection"));
+    for (name, value) in headers.iter() {
+        // 아래에서 다시 만드는 헤더들. 100 Continue는 서버가 이미 클라이언트에게 보냈다.
+        let replaced = [
+            "Host",
+            "Content-Length",
+            "Expect",
+            "X-Forwarded-For",
+            "X-Forwarded-Proto",
+            "X-Forwarded-Host",
+            "Forwarded",
+            "Via",
+        ]
+        .iter()
+        .any(|replaced| replaced.eq_ignore_ascii_case(name));
+        if replaced || is_hop_by_hop(name, &connection) {
+            continue;
+        }
+        write!(writer, "{}: {}\r\n", name, value)?;
+    }
+
+    let host = request.header("Host");
+    match host.filter(|_| upstream.preserve_host) {
+        Some(host) => write!(writer, "Host: {}\r\n", host)?,
+        None => write!(writer, "Host: {}\r\n", addr)?,
+    }
+
+    // 앞에 다른 프록시가 있었다면 그 프록시가 적은 값 뒤에 이어서 적는다.
+    let proto = if request.is_secure() { "https" } else { "http" };
+    let client = request.remote_addr().map(|addr| addr.ip().to_canonical());
+    if let Some(client) = client {
+        let forwarded_for = joined(headers, "X-Forwarded-For", client.to_string());
+        write!(writer, "X-Forwarded-For: {}\r\n", forwarded_for)?;
+    }
+    write!(writer, "X-Forwarded-Proto: {}\r\n", proto)?;
+    if let Some(host) = host {
+        write!(writer, "X-Forwarded-Host: {}\r\n", host)?;
+    }
+
+    // Forwarded: for=192.0.2.1;proto=https;host=example.test (RFC 7239)
+    let mut element = Vec::new();
+    if let Some(client) = client {
+        let node = match client {
+            std::net::IpAddr::V6(ip) => format!("[{}]", ip),
+            ip => ip.to_string(),
+        };
+        element.push(format!("for={}", forwarded_value(&node)));
+    }
+    element.push(format!("proto={}", proto));
+    if let Some(host) = host {
+        element.push(format!("host={}", forwarded_value(host)));
+    }
+    let forwarded = joined(headers, "Forwarded", element.join(";"));
+    write!(writer, "Forwarded: {}\r\n", forwarded)?;
+    let via = joined(headers, "Via", format!("1.1 {}", env!("CARGO_PKG_NAME")));
+    write!(writer, "Via: {}\r\n", via)?;
+
+    match request.body().map(|body| body.length()) {
+        None => {}
+        Some(Some(length)) => write!(writer, "Content-Length: {}\r\n", length)?,
+        Some(None) => write!(writer, "Transfer-Encoding: chunked\r\n")?,
+    }
+    // 요청마다 연결을 새로 맺으므로 응답이 끝나면 업스트림이 연결을 닫게 한다. 그래야 본문의 끝을 알기도 쉽다.
+    write!(writer, "Connection: close\r\n\r\n")
+}
+
+// 클라이언트가 보내는 본문을 읽는 대로 업스트림에 쓴다.
+fn send_body(writer: &mut BufWriter<&TcpStream>, request: &Request) -> IoResult<()> {
+    if let Some(mut body) = request.body() {
+        match body.length() {
+            Some(length) => {
+                let copied = io::copy(&mut body, writer)?;
+                if copied != length {
+                    return Err(Error::from(ErrorKind::UnexpectedEof));
+                }
+            }
+            None => {
+                let mut chunked = ChunkedWriter::new(&mut *writer);
+                io::copy(&mut body, &mut chunked)?;
+                chunked.finish()?;
+            }
+        }
+    }
+    writer.flush()
+}
+
+// 응답 본문을 흘려보내는 동안에도 처리 중인 요청으로 세도록 lease를 함께 넘긴다.
+fn read_response(
+    mut reader: BufReader<TcpStream>,
+    request: &Request,
+    lease: Lease,
+) -> IoResult<Response> {
+    // 100 Continue 같은 중간 응답은 건너뛴다. 우리는 Upgrade를 넘기지 않으므로 101은 오면 안 된다.
+    let head = loop {
+        let head = ResponseHead::parse(&read_head(&mut reader, MAX_RESPONSE_HEAD)?)
+            .map_err(|e| invalid(&e.to_string()))?;
+        match head.code {
+            101 => return Err(invalid("unexpected 101 Switching Protocols")),
+            100..=199 => continue,
+            _ => break head,
+        }
+    };
+    let status = head
+        .status_code()
+        .map_err(|_| invalid(&format!("unknown status code {}", head.code)))?;
+    let head_request = matches!(request.method(), Method::HEAD);
+    let framing = head
+        .framing(head_request)
+        .map_err(|e| invalid(&e.to_string()))?;
+
+    let connection = connection_tokens(head.get_all("Connection"));
+    let mut response = Response::new(status, None);
+    for (name, value) in &head.headers {
+        // 본문 길이는 보낼 때 다시 계산한다. HEAD 응답은 본문이 없으니 업스트림이 알려준 길이를 그대로 쓴다.
+        let length = name.eq_ignore_ascii_case("Content-Length") && !head_request;
+        if length || is_hop_by_hop(name, &connection) {
+            continue;
+        }
+        response.append_header(name, value.as_str());
+    }
+
+    let mut body: Box<dyn Read + Send> = match framing {
+        Framing::None => return Ok(response),
+        Framing::Length(length) if length <= BUFFER_LIMIT as u64 => {
+            let mut buffered = Vec::with_capacity(length as usize);
+            reader.take(length).read_to_end(&mut buffered)?;
+            if buffered.len() as u64 != length {
+                return Err(Error::from(ErrorKind::UnexpectedEof));
+            }
+            response.set_body(buffered);
+            return Ok(response);
+        }
+        Framing::Length(length) => Box::new(reader.take(length)),
+        Framing::Chunked => Box::new(ChunkedReader::new(reader)),
+        Framing::Close => Box::new(reader),
+    };
+
+    let mut streamed = Response::stream(status, move |out| {
+        let _lease = lease;
+        // 이벤트 하나가 버퍼에 머물지 않도록 읽은 만큼 바로 보낸다.
+        let mut buffer = [0; 16 * 1024];
+        loop {
+            let len = match body.read(&mut buffer) {
+                Ok(0) => return out.flush(),
+                Ok(len) => len,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            out.write_all(&buffer[..len])?;
+            out.flush()?;
+        }
+    });
+    for (name, value) in response.headers() {
+        streamed.append_header(name, value);
+    }
+    Ok(streamed)
+}
+
+// Connection 헤더에 적힌 헤더 이름들
+fn connection_tokens<'a>(values: impl Iterator<Item = &'a str>) -> Vec<&'a str> {
+    values
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+        .collect()
+}
+
+fn is_hop_by_hop(name: &str, connection: &[&str]) -> bool {
+    HOP_BY_HOP
+        .iter()
+        .chain(connection)
+        .any(|hop| hop.eq_ignore_ascii_case(name))
+}
+
+// 같은 이름의 헤더 값들 뒤에 value를 붙인 목록
+fn joined(headers: &Headers, name: &str, value: String) -> String {
+    let mut values: Vec<&str> = headers.get_all(name).collect();
+    values.push(&value);
+    values.join(", ")
+}
+
+// Forwarded의 값은 토큰이 아니면 따옴표로 감싼다. "[2001:db8::1]", "example.test:8080"
+fn forwarded_value(value: &str) -> String {
+    if is_token(value) {
+        value.to_string()
+    } else {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::testing::TestServer;
+    use crate::server::Server;
+    use std::cell::RefCell;
+    use std::convert::TryFrom;
+    use std::io::BufRead;
+    use std::net::TcpListener;
+    use std::sync::mpsc::{self, Receiver};
+    use std::thread;
+
+    struct NotFound;
+
+    impl Handler for NotFound {
+        fn handle_request(&self, _: &Request) -> Response {
+            Response::new(StatusCode::NotFound, None)
+        }
+    }
+
+    // 업스트림이 받은 요청. 본문은 chunked였다면 푼 것이다.
+    struct Received {
+        head: String,
+        body: Vec<u8>,
+    }
+
+    impl Received {
+        fn headers(&self, name: &str) -> Vec<&str> {
+            self.head
+                .split("\r\n")
+                .skip(1)
+                .filter_map(|line| line.split_once(": "))
+                .filter(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value)
+                .collect()
+        }
+
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers(name).first().copied()
+        }
+
+        fn request_line(&self) -> &str {
+            self.head.split("\r\n").next().unwrap()
+        }
+    }
+
+    // 127.0.0.1의 빈 포트에서 요청을 받아서 respond로 응답하는 업스트림. 받은 요청은 채널로 보낸다.
+    fn spawn_upstream(
+        respond: impl Fn(&Received, &mut TcpStream) + Send + 'static,
+    ) -> (String, Receiver<Received>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => return,
+                };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut head = String::new();
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                        break;
+                    }
+                    head.push_str(&line);
+                }
+                let mut received = Received {
+                    head,
+                    body: Vec::new(),
+                };
+                if let Some(length) = received.header("Content-Length") {
+                    let length = length.parse().unwrap();
+                    (&mut reader)
+                        .take(length)
+                        .read_to_end(&mut received.body)
+                        .unwrap();
+                } else if received.header("Transfer-Encoding") == Some("chunked") {
+                    ChunkedReader::new(&mut reader)
+                        .read_to_end(&mut received.body)
+                        .unwrap();
+                }
+                respond(&received, &mut stream);
+                drop(stream);
+                if sender.send(received).is_err() {
+                    return;
+                }
+            }
+        });
+        (addr, receiver)
+    }
+
+    fn ok(body: &'static str) -> impl Fn(&Received, &mut TcpStream) + Send + 'static {
+        move |_, stream| {
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+        }
+    }
+
+    fn proxy(prefix: &str, upstream: Upstream) -> ProxyHandler<NotFound> {
+        ProxyHandler::new(NotFound).route(prefix, upstream)
+    }
+
+    fn send(proxy: &ProxyHandler<NotFound>, raw: &str) -> Response {
+        let mut request = Request::try_from(raw.as_bytes()).unwrap();
+        request.set_remote_addr("192.0.2.7:5000".parse().unwrap());
+        proxy.handle_request(&request)
+    }
+
+    // 스트림 응답도 보통 응답처럼 비교할 수 있도록 보낸 바이트를 다시 읽는다.
+    fn sent(response: Response) -> Response {
+        let mut bytes = Vec::new();
+        response.send(&mut bytes).unwrap();
+        Response::try_from(&bytes[..]).unwrap()
+    }
+
+    #[test]
+    fn rewrites_host_and_strips_prefix() {
+        let (addr, received) = spawn_upstream(ok("hello"));
+        let proxy = proxy("/api", Upstream::new(addr.clone()).strip_prefix(true));
+        let response = send(
+            &proxy,
+            "GET /api/users?page=2 HTTP/1.1\r\nHost: example.test\r\n\r\n",
+        );
+        assert_eq!(response.status_code(), StatusCode::Ok);
+        assert_eq!(response.body(), Some(&b"hello"[..]));
+
+        let received = received.recv().unwrap();
+        assert_eq!(received.request_line(), "GET /users?page=2 HTTP/1.1");
+        assert_eq!(received.headers("Host"), [addr.as_str()]);
+        assert_eq!(received.header("X-Forwarded-Host"), Some("example.test"));
+    }
+
+    #[test]
+    fn preserves_host_when_asked() {
+        let (addr, received) = spawn_upstream(ok(""));
+        let proxy = proxy("/", Upstream::new(addr).preserve_host(true));
+        send(&proxy, "GET / HTTP/1.1\r\nHost: example.test\r\n\r\n");
+        let received = received.recv().unwrap();
+        assert_eq!(received.headers("Host"), ["example.test"]);
+    }
+
+    #[test]
+    fn appends_forwarded_headers() {
+        let (addr, received) = spawn_upstream(ok(""));
+        let proxy = proxy("/", Upstream::new(addr));
+        let raw = "GET / HTTP/1.1\r\n\
+                   Host: example.test:8443\r\n\
+                   X-Forwarded-For: 10.0.0.1\r\n\
+                   X-Forwarded-Proto: http\r\n\
+                   X-Forwarded-Host: spoofed.test\r\n\
+                   Forwarded: for=10.0.0.1\r\n\
+                   Via: 1.1 edge\r\n\r\n";
+        let mut request = Request::try_from(raw.as_bytes()).unwrap();
+        request.set_remote_addr("[2001:db8::7]:5000".parse().unwrap());
+        request.set_secure(true);
+        proxy.handle_request(&request);
+
+        let received = received.recv().unwrap();
+        assert_eq!(
+            received.headers("X-Forwarded-For"),
+            ["10.0.0.1, 2001:db8::7"]
+        );
+        // Proto와 Host는 이어 붙이지 않고 이 연결의 값으로 바꾼다.
+        assert_eq!(received.headers("X-Forwarded-Proto"), ["https"]);
+        assert_eq!(received.headers("X-Forwarded-Host"), ["example.test:8443"]);
+        assert_eq!(
+            received.headers("Forwarded"),
+            ["for=10.0.0.1, for=\"[2001:db8::7]\";proto=https;host=\"example.test:8443\""]
+        );
+        assert_eq!(
+            received.headers("Via"),
+            [format!("1.1 edge, 1.1 {}", env!("CARGO_PKG_NAME")).as_str()]
+        );
+    }
+
+    #[test]
+    fn strips_hop_by_hop_headers() {
+        let (addr, received) = spawn_upstream(|_, stream| {
+            stream
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\n\
+                      Connection: X-Internal, close\r\n\
+                      X-Internal: secret\r\n\
+                      Keep-Alive: timeout=5\r\n\
+                      Proxy-Authenticate: Basic\r\n\
+                      X-Public: 1\r\n\
+                      Content-Length: 0\r\n\r\n",
+                )
+                .unwrap();
+        });
+        let proxy = proxy("/", Upstream::new(addr));
+        let response = send(
+            &proxy,
+            "GET / HTTP/1.1\r\n\
+             Host: example.test\r\n\
+             Connection: keep-alive, X-Session\r\n\
+             X-Session: abc\r\n\
+             Keep-Alive: timeout=5\r\n\
+             Proxy-Authorization: Basic eDp5\r\n\
+             Proxy-Connection: keep-alive\r\n\
+             TE: trailers\r\n\
+             Upgrade: h2c\r\n\
+             X-Kept: yes\r\n\r\n",
+        );
+
+        let received = received.recv().unwrap();
+        for name in [
+            "X-Session",
+            "Keep-Alive",
+            "Proxy-Authorization",
+            "Proxy-Connection",
+            "TE",
+            "Upgrade",
+        ] {
+            assert_eq!(received.header(name), None, "{} was forwarded", name);
+        }
+        assert_eq!(received.headers("Connection"), ["close"]);
+        assert_eq!(received.header("X-Kept"), Some("yes"));
+
+        for name in [
+            "Connection",
+            "X-Internal",
+            "Keep-Alive",
+            "Proxy-Authenticate",
+        ] {
+            assert_eq!(response.header(name), None, "{} was returned", name);
+        }
+        assert_eq!(response.header("X-Public"), Some("1"));
+    }
+
+    #[test]
+    fn streams_request_bodies() {
+        let (addr, received) = spawn_upstream(ok("done"));
+        let proxy = proxy("/", Upstream::new(addr));
+        let body: Vec<u8> = (0..200_000).map(|i| (i % 251) as u8).collect();
+
+        // 길이를 모르는 본문은 chunked로 넘긴다.
+        let mut request = Request::try_from(
+            &b"POST /upload HTTP/1.1\r\nHost: h\r\nTransfer-Encoding: chunked\r\n\r\n"[..],
+        )
+        .unwrap();
+        let reader = RefCell::new(&body[..]);
+        request.set_body(crate::http::Body::new(&reader, None));
+        assert_eq!(proxy.handle_request(&request).status_code(), StatusCode::Ok);
+        let upload = received.recv().unwrap();
+        assert_eq!(upload.header("Transfer-Encoding"), Some("chunked"));
+        assert_eq!(upload.header("Content-Length"), None);
+        assert!(upload.body == body);
+
+        // 길이를 아는 본문은 그 길이 그대로 넘긴다.
+        let mut request = Request::try_from(
+            &b"PUT /upload HTTP/1.1\r\nHost: h\r\nContent-Length: 200000\r\n\r\n"[..],
+        )
+        .unwrap();
+        let reader = RefCell::new(&body[..]);
+        request.set_body(crate::http::Body::new(&reader, Some(body.len() as u64)));
+        assert_eq!(proxy.handle_request(&request).status_code(), StatusCode::Ok);
+        let upload = received.recv().unwrap();
+        assert_eq!(upload.headers("Content-Length"), ["200000"]);
+        assert_eq!(upload.header("Transfer-Encoding"), None);
+        assert!(upload.body == body);
+    }
+
+    #[test]
+    fn streams_large_response_bodies() {
+        let body: Vec<u8> = (0..3 * BUFFER_LIMIT).map(|i| (i % 253) as u8).collect();
+        let expected = body.clone();
+        let (addr, _received) = spawn_upstream(move |_, stream| {
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nX-Big: 1\r\n\r\n")
+                .unwrap();
+            let mut chunked = ChunkedWriter::new(&mut *stream);
+            for chunk in body.chunks(64 * 1024) {
+                chunked.write_all(chunk).unwrap();
+            }
+            chunked.finish().unwrap();
+        });
+        let proxy = proxy("/", Upstream::new(addr));
+        let response = send(&proxy, "GET /big HTTP/1.1\r\nHost: h\r\n\r\n");
+        assert!(response.is_stream());
+        let response = sent(response);
+        assert_eq!(response.header("X-Big"), Some("1"));
+        assert!(response.body() == Some(&expected[..]));
+    }
+
+    #[test]
+    fn streams_through_the_server() {
+        let (addr, received) = spawn_upstream(|received, stream| {
+            // 받은 본문을 그대로 돌려준다.
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n",
+                received.body.len()
+            )
+            .unwrap();
+            stream.write_all(&received.body).unwrap();
+        });
+        let server = TestServer::start(
+            Server::new("127.0.0.1:0".to_string()),
+            proxy("/", Upstream::new(addr)),
+        );
+
+        let mut client = TcpStream::connect(server.addr()).unwrap();
+        client
+            .write_all(b"POST /echo HTTP/1.1\r\nHost: h\r\nTransfer-Encoding: chunked\r\n\r\n")
+            .unwrap();
+        let mut chunked = ChunkedWriter::new(&mut client);
+        for _ in 0..50 {
+            chunked.write_all(&[b'x'; 1000]).unwrap();
+        }
+        chunked.finish().unwrap();
+        let mut bytes = Vec::new();
+        client.read_to_end(&mut bytes).unwrap();
+
+        let response = Response::try_from(&bytes[..]).unwrap();
+        assert_eq!(response.status_code(), StatusCode::Ok);
+        assert!(response.body() == Some(&[b'x'; 50_000][..]));
+        let received = received.recv().unwrap();
+        assert_eq!(received.body.len(), 50_000);
+        assert_eq!(received.header("X-Forwarded-For"), Some("127.0.0.1"));
+    }
+
+    // 업스트림이 본문을 끝내기 전에 첫 이벤트가 클라이언트에게 도착해야 한다.
+    #[test]
+    fn streams_events_before_the_upstream_finishes() {
+        let (next, wait) = mpsc::channel::<()>();
+        let wait = std::sync::Mutex::new(wait);
+        let (addr, _received) = spawn_upstream(move |_, stream| {
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nTransfer-Encoding: chunked\r\n\r\n")
+                .unwrap();
+            let mut chunked = ChunkedWriter::new(&mut *stream);
+            chunked.write_all(b"data: one\n\n").unwrap();
+            // 클라이언트가 첫 이벤트를 받았다고 알려줄 때까지 다음 이벤트를 보내지 않는다.
+            wait.lock().unwrap().recv().unwrap();
+            chunked.write_all(b"data: two\n\n").unwrap();
+            chunked.finish().unwrap();
+        });
+        let server = TestServer::start(
+            Server::new("127.0.0.1:0".to_string()),
+            proxy("/", Upstream::new(addr)),
+        );
+
+        let mut client = TcpStream::connect(server.addr()).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        client
+            .write_all(b"GET /events HTTP/1.1\r\nHost: h\r\n\r\n")
+            .unwrap();
+        let mut bytes = Vec::new();
+        let mut buffer = [0; 1024];
+        while !bytes.ends_with(b"data: one\n\n") {
+            let len = client
+                .read(&mut buffer)
+                .expect("first event did not arrive");
+            assert!(len > 0, "connection closed before the first event");
+            bytes.extend_from_slice(&buffer[..len]);
+        }
+        let head = String::from_utf8_lossy(&bytes).to_string();
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", head);
+        assert!(
+            head.contains("Content-Type: text/event-stream\r\n"),
+            "{}",
+            head
+        );
+        assert!(!head.contains("data: two"));
+
+        next.send(()).unwrap();
+        client.read_to_end(&mut bytes).unwrap();
+        assert!(bytes.ends_with(b"data: one\n\ndata: two\n\n"));
+    }
+
+    #[test]
+    fn small_bodies_are_buffered() {
+        let (addr, _received) = spawn_upstream(|_, stream| {
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello")
+                .unwrap();
+        });
+        let proxy = proxy("/", Upstream::new(addr));
+        let response = send(&proxy, "GET / HTTP/1.1\r\nHost: h\r\n\r\n");
+        assert!(!response.is_stream());
+        assert_eq!(response.body(), Some(&b"hello"[..]));
+    }
+
+    #[test]
+    fn refused_upstream_is_bad_gateway() {
+        let addr = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap().to_string()
+        };
+        let proxy = proxy("/", Upstream::new(addr));
+        let response = send(&proxy, "GET / HTTP/1.1\r\nHost: h\r\n\r\n");
+        assert_eq!(response.status_code(), StatusCode::BadGateway);
+    }
+
+    #[test]
+    fn invalid_response_is_bad_gateway() {
+        let (addr, _received) = spawn_upstream(|_, stream| {
+            stream.write_all(b"SMTP ready\r\n\r\n").unwrap();
+        });
+        let proxy = proxy("/", Upstream::new(addr));
+        let response = send(&proxy, "GET / HTTP/1.1\r\nHost: h\r\n\r\n");
+        assert_eq!(response.status_code(), StatusCode::BadGateway);
+    }
+
+    #[test]
+    fn slow_upstream_is_gateway_timeout() {
+        let (addr, _received) = spawn_upstream(|_, _| {
+            thread::sleep(Duration::from_secs(2));
+        });
+        let proxy = proxy("/", Upstream::new(addr).timeout(Duration::from_millis(200)));
+        let response = send(&proxy, "GET / HTTP/1.1\r\nHost: h\r\n\r\n");
+        assert_eq!(response.status_code(), StatusCode::GatewayTimeout);
+    }
+
+    #[test]
+    fn unrouted_requests_go_to_the_inner_handler() {
+        let proxy = proxy("/api", Upstream::new("127.0.0.1:1"));
+        let response = send(&proxy, "GET /apidocs HTTP/1.1\r\nHost: h\r\n\r\n");
+        assert_eq!(response.status_code(), StatusCode::NotFound);
+    }
+}
@@ -0,0 +1,437 @@
+use super::Upstream;
+use crate::http::Request;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+// 서버 하나를 해시 링에 몇 번 올릴지. 많을수록 키가 서버들에 고르게 나뉜다.
+const VIRTUAL_NODES: usize = 160;
+
+// 요청을 받을 서버를 고르는 방법
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Balance {
+    // 차례대로 돌아가며 고른다.
+    RoundRobin,
+    // 지금 처리 중인 요청이 가장 적은 서버를 고른다. 요청마다 걸리는 시간이 많이 다를 때 좋다.
+    LeastConnections,
+    // 키가 같은 요청은 같은 서버로 보낸다. (consistent hashing)
+    // 서버가 빠지거나 돌아와도 그 서버에 가던 키들만 옮겨가고 나머지는 그대로다.
+    Hash(HashKey),
+}
+
+// 해시할 요청의 값
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HashKey {
+    Ip,
+    Path,
+    // 헤더나 쿠키가 없는 요청은 IP 주소로 고른다.
+    Header(String),
+    Cookie(String),
+}
+
+impl std::str::FromStr for Balance {
+    type Err = String;
+
+    // "round_robin", "least_connections", "hash"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "round_robin" => Ok(Self::RoundRobin),
+            "least_connections" => Ok(Self::LeastConnections),
+            "hash" => Ok(Self::Hash(HashKey::Ip)),
+            _ => Err(format!(
+                "unknown balance {}, expected round_robin, least_connections or hash",
+                s
+            )),
+        }
+    }
+}
+
+impl std::str::FromStr for HashKey {
+    type Err = String;
+
+    // "ip", "path", "header:X-User-Id", "cookie:session"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = |name: &str| name.trim().to_string();
+        match s.split_once(':') {
+            None if s.eq_ignore_ascii_case("ip") => Ok(Self::Ip),
+            None if s.eq_ignore_ascii_case("path") => Ok(Self::Path),
+            Some((kind, value)) if kind.eq_ignore_ascii_case("header") && !value.is_empty() => {
+                Ok(Self::Header(name(value)))
+            }
+            Some((kind, value)) if kind.eq_ignore_ascii_case("cookie") && !value.is_empty() => {
+                Ok(Self::Cookie(name(value)))
+            }
+            _ => Err(format!(
+                "unknown hash key {}, expected ip, path, header:<name> or cookie:<name>",
+                s
+            )),
+        }
+    }
+}
+
+// 업스트림 하나의 서버들과 그 상태. 요청을 넘길 때마다 pick()으로 서버를 고른다.
+//
+// 서버는 두 가지 방법으로 빠진다.
+// 능동 검사는 주기적으로 서버에 요청을 보내서 실패하면 빼고, 다시 성공하면 넣는다. (health.rs)
+// 수동 검사는 실제 요청을 넘기다가 연달아 max_fails번 실패하면 fail_timeout 동안 뺀다.
+// 시간이 지나면 다시 요청을 보내 보고, 성공하면 돌아오고 실패하면 다시 fail_timeout 동안 빠진다.
+pub(super) struct Pool {
+    pub(super) upstream: Upstream,
+    servers: Vec<Server>,
+    // (해시 값, 서버 번호). 해시 값 순서로 정렬되어 있다.
+    ring: Vec<(u64, usize)>,
+    next: AtomicUsize,
+}
+
+pub(super) struct Server {
+    addr: String,
+    // 지금 처리 중인 요청 수
+    active: AtomicUsize,
+    // 마지막 능동 검사의 결과. 검사를 하지 않으면 늘 true다.
+    healthy: AtomicBool,
+    passive: Mutex<Passive>,
+}
+
+#[derive(Default)]
+struct Passive {
+    // 연달아 실패한 횟수
+    fails: u32,
+    ejected_until: Option<Instant>,
+}
+
+// 고른 서버. 처리 중인 요청 수를 세기 위한 것이라서 응답을 다 보낼 때까지 가지고 있어야 한다.
+pub(super) struct Lease {
+    pool: Arc<Pool>,
+    index: usize,
+}
+
+impl Pool {
+    pub(super) fn new(upstream: Upstream) -> Self {
+        let servers: Vec<Server> = upstream
+            .servers
+            .iter()
+            .map(|addr| Server {
+                addr: addr.clone(),
+                active: AtomicUsize::new(0),
+                healthy: AtomicBool::new(true),
+                passive: Mutex::new(Passive::default()),
+            })
+            .collect();
+        let mut ring = Vec::new();
+        if matches!(upstream.balance, Balance::Hash(_)) {
+            for (index, server) in servers.iter().enumerate() {
+                for node in 0..VIRTUAL_NODES {
+                    ring.push((hash(format!("{}#{}", server.addr, node).as_bytes()), index));
+                }
+            }
+            ring.sort_unstable();
+        }
+        Self {
+            upstream,
+            servers,
+            ring,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub(super) fn servers(&self) -> &[Server] {
+        &self.servers
+    }
+
+    // tried에 있는 서버와 빠진 서버는 고르지 않는다. 고를 서버가 없으면 None이다.
+    pub(super) fn pick(self: &Arc<Self>, request: &Request, tried: &[usize]) -> Option<Lease> {
+        let now = Instant::now();
+        let usable = |index: &usize| !tried.contains(index) && self.servers[*index].available(now);
+        let count = self.servers.len();
+        let index = match &self.upstream.balance {
+            Balance::RoundRobin => {
+                let start = self.next.fetch_add(1, Ordering::Relaxed);
+                (0..count).map(|i| (start + i) % count).find(usable)
+            }
+            Balance::LeastConnections => {
+                // 처리 중인 요청 수가 같으면 먼저 나온 서버가 골라지므로, 시작점을 돌려서 한 서버에 몰리지 않게 한다.
+                let start = self.next.fetch_add(1, Ordering::Relaxed);
+                (0..count)
+                    .map(|i| (start + i) % count)
+                    .filter(usable)
+                    .min_by_key(|&i| self.servers[i].active.load(Ordering::Relaxed))
+            }
+            Balance::Hash(key) => {
+                // 링에서 키의 해시 값 다음에 있는 서버부터 시계 방향으로 찾는다.
+                let key = hash(hash_key(request, key).as_bytes());
+                let start = self.ring.partition_point(|(hash, _)| *hash < key);
+                (0..self.ring.len())
+                    .map(|i| self.ring[(start + i) % self.ring.len()].1)
+                    .find(usable)
+            }
+        }?;
+        self.servers[index].active.fetch_add(1, Ordering::Relaxed);
+        Some(Lease {
+            pool: self.clone(),
+            index,
+        })
+    }
+
+    pub(super) fn succeeded(&self, index: usize) {
+        let server = &self.servers[index];
+        let mut passive = server.passive.lock().unwrap();
+        if passive.ejected_until.take().is_some() {
+            info!("Upstream server {} is back", server.addr);
+        }
+        passive.fails = 0;
+    }
+
+    pub(super) fn failed(&self, index: usize) {
+        let max_fails = self.upstream.max_fails;
+        if max_fails == 0 {
+            return;
+        }
+        let server = &self.servers[index];
+        let mut passive = server.passive.lock().unwrap();
+        passive.fails = passive.fails.saturating_add(1);
+        if passive.fails >= max_fails {
+            warn!(
+                "Upstream server {} failed {} time(s) in a row, removed for {:?}",
+                server.addr, passive.fails, self.upstream.fail_timeout
+            );
+            passive.ejected_until = Some(Instant::now() + self.upstream.fail_timeout);
+        }
+    }
+}
+
+impl Server {
+    pub(super) fn addr(&self) -> &str {
+        &self.addr
+    }
+
+    pub(super) fn set_healthy(&self, healthy: bool) {
+        if self.healthy.swap(healthy, Ordering::Relaxed) != healthy {
+            match healthy {
+                true => info!("Upstream server {} passed the health check", self.addr),
+                false => warn!("Upstream server {} failed the health check", self.addr),
+            }
+        }
+    }
+
+    fn available(&self, now: Instant) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+            && self
+                .passive
+                .lock()
+                .unwrap()
+                .ejected_until
+                .is_none_or(|until| now >= until)
+    }
+}
+
+impl Lease {
+    pub(super) fn index(&self) -> usize {
+        self.index
+    }
+
+    pub(super) fn addr(&self) -> &str {
+        &self.pool.servers[self.index].addr
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        self.pool.servers[self.index]
+            .active
+            .fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+fn hash_key(request: &Request, key: &HashKey) -> String {
+    let value = match key {
+        HashKey::Ip => None,
+        HashKey::Path => return request.path().to_string(),
+        HashKey::Header(name) => request.header(name).map(str::to_string),
+        HashKey::Cookie(name) => request.cookies().get_raw(name).map(str::to_string),
+    };
+    value.unwrap_or_else(|| match request.remote_addr().map(|addr| addr.ip()) {
+        Some(IpAddr::V6(ip)) => ip.to_canonical().to_string(),
+        Some(ip) => ip.to_string(),
+        None => String::new(),
+    })
+}
+
+// 프로세스를 다시 띄워도 같은 키가 같은 서버로 가도록 고정된 해시를 쓴다. (FNV-1a 뒤에 비트를 섞는다)
+fn hash(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    // splitmix64
+    hash ^= hash >> 30;
+    hash = hash.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    hash ^= hash >> 27;
+    hash = hash.wrapping_mul(0x94d0_49bb_1331_11eb);
+    hash ^ (hash >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{Response, StatusCode};
+    use crate::proxy::ProxyHandler;
+    use crate::server::Handler;
+    use std::convert::TryFrom;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Duration;
+
+    struct NotFound;
+
+    impl Handler for NotFound {
+        fn handle_request(&self, _: &Request) -> Response {
+            Response::new(StatusCode::NotFound, None)
+        }
+    }
+
+    // 127.0.0.1의 빈 포트에서 받은 요청마다 body로 응답하는 업스트림. body가 None이면 응답하지 않고 끊는다.
+    fn spawn_upstream(body: impl Fn() -> Option<String> + Send + 'static) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { return };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap_or(0) > 0 && line != "\r\n" {
+                    line.clear();
+                }
+                if let Some(body) = body() {
+                    let _ = write!(
+                        stream,
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                }
+            }
+        });
+        addr
+    }
+
+    fn named(name: &'static str) -> String {
+        spawn_upstream(move || Some(name.to_string()))
+    }
+
+    fn pick(pool: &Arc<Pool>, path: &str) -> Lease {
+        let raw = format!("GET {} HTTP/1.1\r\nHost: example.test\r\n\r\n", path);
+        let request = Request::try_from(raw.as_bytes()).unwrap();
+        pool.pick(&request, &[]).unwrap()
+    }
+
+    // 응답 본문. 업스트림에 넘기지 못했으면 상태 코드
+    fn send(proxy: &ProxyHandler<NotFound>) -> String {
+        let request = Request::try_from(&b"GET / HTTP/1.1\r\nHost: example.test\r\n\r\n"[..]);
+        let response = proxy.handle_request(&request.unwrap());
+        let mut bytes = Vec::new();
+        response.send(&mut bytes).unwrap();
+        let response = Response::try_from(&bytes[..]).unwrap();
+        match response.body() {
+            Some(body) if response.status_code() == StatusCode::Ok => {
+                String::from_utf8_lossy(body).into_owned()
+            }
+            _ => (response.status_code() as u16).to_string(),
+        }
+    }
+
+    #[test]
+    fn round_robin_takes_turns() {
+        let upstream = Upstream::new(named("a"))
+            .server(named("b"))
+            .server(named("c"));
+        let proxy = ProxyHandler::new(NotFound).route("/", upstream);
+
+        let bodies: Vec<String> = (0..6).map(|_| send(&proxy)).collect();
+        assert_eq!(bodies, ["a", "b", "c", "a", "b", "c"]);
+    }
+
+    #[test]
+    fn least_connections_picks_the_idlest_server() {
+        let upstream = Upstream::new(named("a"))
+            .server(named("b"))
+            .server(named("c"))
+            .balance(Balance::LeastConnections);
+        let pool = Arc::new(Pool::new(upstream));
+        let pick = || pick(&pool, "/");
+
+        let first = pick();
+        let second = pick();
+        let third = pick();
+        assert_eq!([first.index(), second.index(), third.index()], [0, 1, 2]);
+        // 두번째 서버만 처리 중인 요청이 없다.
+        drop(second);
+        assert_eq!(pick().index(), 1);
+        let _busy = pick();
+        drop(first);
+        assert_eq!(pick().index(), 0);
+        drop(third);
+    }
+
+    #[test]
+    fn hashing_keeps_keys_when_a_server_is_removed() {
+        let (a, b, c) = (named("a"), named("b"), named("c"));
+        let balance = Balance::Hash(HashKey::Path);
+        let three = Arc::new(Pool::new(
+            Upstream::new(a.clone())
+                .server(b.clone())
+                .server(c.clone())
+                .balance(balance.clone()),
+        ));
+        let two = Arc::new(Pool::new(Upstream::new(a).server(c).balance(balance)));
+        let addr = |pool: &Arc<Pool>, path: &str| pick(pool, path).addr().to_string();
+
+        let mut moved = 0;
+        for key in 0..300 {
+            let path = format!("/users/{}", key);
+            let before = addr(&three, &path);
+            assert_eq!(addr(&three, &path), before);
+            let after = addr(&two, &path);
+            if before == b {
+                moved += 1;
+            } else {
+                assert_eq!(after, before, "{} moved", path);
+            }
+        }
+        // 빠진 서버에 가던 키만 옮겨간다. 서버마다 대략 1/3씩 나뉜다.
+        assert!((50..150).contains(&moved), "{}", moved);
+    }
+
+    #[test]
+    fn ejects_failing_servers_and_takes_them_back() {
+        let healthy = Arc::new(AtomicBool::new(false));
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let flaky = spawn_upstream({
+            let healthy = Arc::clone(&healthy);
+            let accepted = Arc::clone(&accepted);
+            move || {
+                accepted.fetch_add(1, Ordering::SeqCst);
+                healthy.load(Ordering::SeqCst).then(|| "flaky".to_string())
+            }
+        });
+        let fail_timeout = Duration::from_millis(300);
+        let upstream = Upstream::new(flaky)
+            .server(named("live"))
+            .max_fails(2)
+            .fail_timeout(fail_timeout);
+        let proxy = ProxyHandler::new(NotFound).route("/", upstream);
+
+        // 두번 연달아 실패하면 fail_timeout 동안 빠진다.
+        let bodies: Vec<String> = (0..6).map(|_| send(&proxy)).collect();
+        assert_eq!(bodies, ["502", "live", "502", "live", "live", "live"]);
+        assert_eq!(accepted.load(Ordering::SeqCst), 2);
+
+        // 시간이 지나면 다시 보내 보고, 성공하면 돌아온다.
+        healthy.store(true, Ordering::SeqCst);
+        thread::sleep(fail_timeout + Duration::from_millis(50));
+        let bodies: Vec<String> = (0..4).map(|_| send(&proxy)).collect();
+        assert_eq!(bodies, ["flaky", "live", "flaky", "live"]);
+    }
+}
@@ -0,0 +1,321 @@
+use crate::http::path::{has_prefix, normalize};
+use crate::http::{ParseError, Request, Response, StatusCode};
+use crate::server::Handler;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// 버킷이 이만큼 늘어날 때마다 한번씩 다시 가득 찬 버킷을 지운다. max_clients가 더 작으면 그만큼마다 지운다.
+const SWEEP_THRESHOLD: usize = 1024;
+const DEFAULT_MAX_CLIENTS: usize = 100_000;
+
+// 클라이언트마다 토큰 버킷을 두고 요청 하나에 토큰 하나를 쓴다.
+// 버킷에는 requests개까지 토큰이 들어가고 period 동안 requests개가 다시 채워진다.
+// 즉 잠깐 동안은 requests개를 한번에 보낼 수 있고, 길게 보면 period마다 requests개를 넘지 못한다.
+// 토큰이 없으면 안쪽 핸들러를 부르지 않고 429 Too Many Requests로 응답한다.
+// 제한을 건 경로에 맞지 않는 요청은 그대로 안쪽 핸들러로 넘긴다.
+//
+// let handler = RateLimit::new(handler)
+//     .limit("/", Limit::new(100, Duration::from_secs(60)))
+//     .limit("/login", Limit::new(5, Duration::from_secs(60)));
+pub struct RateLimit<H> {
+    inner: H,
+    // (경로 접두사, 제한). 여러 접두사가 맞으면 가장 긴 것 하나만 적용한다.
+    limits: Vec<(String, Limit)>,
+    max_clients: usize,
+    buckets: Mutex<Buckets>,
+}
+
+// 경로 하나에 적용하는 제한
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Limit {
+    requests: u32,
+    period: Duration,
+    key: Key,
+}
+
+// 어떤 요청들을 같은 클라이언트로 볼지
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Key {
+    // 연결의 IP 주소. IPv6는 한 사용자가 /64 대역을 통째로 받는 경우가 많아서 앞의 64비트만 본다.
+    Ip,
+    // API 키 같은 헤더의 값. 헤더가 없는 요청은 IP 주소로 나눈다.
+    // 클라이언트가 요청마다 값을 바꾸면 매번 새 버킷을 받으므로, 앞에 있는 믿을 수 있는 프록시나
+    // 인증이 값을 정해 주는 헤더(검증한 API 키 등)에만 써야 한다. 그렇지 않으면 Ip를 쓴다.
+    Header(String),
+}
+
+impl Limit {
+    pub fn new(requests: u32, period: Duration) -> Self {
+        Self {
+            requests: requests.max(1),
+            period,
+            key: Key::Ip,
+        }
+    }
+
+    pub fn key(mut self, key: Key) -> Self {
+        self.key = key;
+        self
+    }
+
+    // 토큰 하나가 다시 채워지는 데 걸리는 시간
+    fn interval(&self) -> Duration {
+        self.period / self.requests
+    }
+}
+
+impl std::str::FromStr for Key {
+    type Err = String;
+
+    // "ip" 또는 "header:X-Api-Key"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s.eq_ignore_ascii_case("ip") => Ok(Self::Ip),
+            Some((kind, name)) if kind.eq_ignore_ascii_case("header") && !name.is_empty() => {
+                Ok(Self::Header(name.trim().to_string()))
+            }
+            _ => Err(format!("unknown key {}, expected ip or header:<name>", s)),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Buckets {
+    // (제한의 순서, 클라이언트) -> 버킷
+    data: HashMap<(usize, String), Bucket>,
+    next_sweep: usize,
+}
+
+// 토큰 개수 대신 "버킷이 가득 차는 시각"을 저장한다. 토큰을 쓸 때마다 그 시각이 interval만큼 뒤로 밀린다.
+// 그 시각이 지났다면 버킷이 가득 찬 것이라서 새 버킷과 다를 게 없으므로 지워도 된다. (GCRA)
+struct Bucket {
+    full_at: Instant,
+}
+
+// 요청 하나를 검사한 결과
+struct Decision {
+    allowed: bool,
+    remaining: u32,
+    // 버킷이 다시 가득 찰 때까지 남은 시간
+    reset: Duration,
+    // 토큰이 하나 생길 때까지 남은 시간. 거절했을 때만 의미가 있다.
+    retry_after: Duration,
+}
+
+impl<H: Handler> RateLimit<H> {
+    pub fn new(inner: H) -> Self {
+        Self {
+            inner,
+            limits: Vec::new(),
+            max_clients: DEFAULT_MAX_CLIENTS,
+            buckets: Mutex::new(Buckets::default()),
+        }
+    }
+
+    // prefix와 그 아래의 경로에 이 제한을 적용한다. 제한마다 버킷을 따로 쓴다.
+    pub fn limit(mut self, prefix: &str, limit: Limit) -> Self {
+        self.limits.push((normalize(prefix), limit));
+        self
+    }
+
+    // 기억하는 클라이언트 수의 상한. 넘으면 버킷이 곧 가득 찰 클라이언트부터 절반을 잊는다.
+    pub fn max_clients(mut self, max_clients: usize) -> Self {
+        self.max_clients = max_clients.max(1);
+        self
+    }
+
+    fn find_limit(&self, path: &str) -> Option<(usize, &Limit)> {
+        let path = normalize(path);
+        self.limits
+            .iter()
+            .enumerate()
+            .filter(|(_, (prefix, _))| has_prefix(&path, prefix))
+            .max_by_key(|(_, (prefix, _))| prefix.len())
+            .map(|(i, (_, limit))| (i, limit))
+    }
+
+    fn check(&self, index: usize, limit: &Limit, client: String) -> Decision {
+        let now = Instant::now();
+        let interval = limit.interval();
+        let capacity = interval * limit.requests;
+        let mut buckets = self.buckets.lock().unwrap();
+        buckets.evict(now, self.max_clients);
+
+        let bucket = buckets
+            .data
+            .entry((index, client))
+            .or_insert(Bucket { full_at: now });
+        let full_at = bucket.full_at.max(now);
+        // 토큰을 하나 쓰면 가득 차는 시각이 이만큼이 된다. 지금부터 capacity보다 멀면 토큰이 없는 것이다.
+        let next_full_at = full_at + interval;
+        let allowed = next_full_at - now <= capacity;
+        if allowed {
+            bucket.full_at = next_full_at;
+        }
+
+        let reset = bucket.full_at.max(now) - now;
+        let used = reset.as_nanos().div_ceil(interval.as_nanos().max(1)) as u32;
+        Decision {
+            allowed,
+            remaining: limit.requests.saturating_sub(used),
+            reset,
+            retry_after: (next_full_at - now).saturating_sub(capacity),
+        }
+    }
+}
+
+impl Buckets {
+    fn evict(&mut self, now: Instant, max_clients: usize) {
+        let threshold = SWEEP_THRESHOLD.min(max_clients);
+        if self.data.len() < self.next_sweep.max(threshold) && self.data.len() < max_clients {
+            return;
+        }
+        self.data.retain(|_, bucket| bucket.full_at > now);
+        // 모두 활발한 클라이언트라서 지울 게 없다면, 버킷이 가득 차는 시각이 가까운 절반을 버린다.
+        // 버려진 클라이언트는 제한이 처음부터 다시 시작되지만 메모리는 max_clients를 넘지 않는다.
+        if self.data.len() >= max_clients {
+            let mut full_at: Vec<Instant> =
+                self.data.values().map(|bucket| bucket.full_at).collect();
+            let middle = full_at.len() / 2;
+            let (_, median, _) = full_at.select_nth_unstable(middle);
+            let median = *median;
+            self.data.retain(|_, bucket| bucket.full_at > median);
+        }
+        self.next_sweep = self.data.len() + threshold;
+    }
+}
+
+impl<H: Handler> Handler for RateLimit<H> {
+    fn handle_request(&self, request: &Request) -> Response {
+        let (index, limit) = match self.find_limit(request.path()) {
+            Some(found) => found,
+            None => return self.inner.handle_request(request),
+        };
+        let decision = self.check(index, limit, client_key(request, &limit.key));
+
+        let mut response = if decision.allowed {
+            self.inner.handle_request(request)
+        } else {
+            debug!("Rate limit exceeded for {}", request.path());
+            too_many_requests(&decision)
+        };
+        add_headers(&mut response, limit, &decision);
+        response
+    }
+
+    fn handle_bad_request(&self, e: &ParseError) -> Response {
+        self.inner.handle_bad_request(e)
+    }
+
+    // WebSocket 연결도 업그레이드 요청 하나로 센다. 연결 뒤에 주고받는 메시지는 세지 않는다.
+    fn accept_upgrade<'buf>(&self, request: &Request<'buf>) -> Result<Request<'buf>, Response> {
+        if let Some((index, limit)) = self.find_limit(request.path()) {
+            let decision = self.check(index, limit, client_key(request, &limit.key));
+            if !decision.allowed {
+                debug!("Rate limit exceeded for {}", request.path());
+                let mut response = too_many_requests(&decision);
+                add_headers(&mut response, limit, &decision);
+                return Err(response);
+            }
+        }
+        self.inner.accept_upgrade(request)
+    }
+}
+
+fn too_many_requests(decision: &Decision) -> Response {
+    let mut response = Response::new(StatusCode::TooManyRequests, None);
+    response.set_header(
+        "Retry-After",
+        ceil_secs(decision.retry_after).max(1).to_string(),
+    );
+    response
+}
+
+// IETF RateLimit 헤더 초안의 형식이다. 클라이언트가 한도에 닿기 전에 속도를 줄일 수 있게 한다.
+fn add_headers(response: &mut Response, limit: &Limit, decision: &Decision) {
+    response.set_header(
+        "RateLimit-Policy",
+        format!("{};w={}", limit.requests, limit.period.as_secs()),
+    );
+    response.set_header("RateLimit-Limit", limit.requests.to_string());
+    response.set_header("RateLimit-Remaining", decision.remaining.to_string());
+    response.set_header("RateLimit-Reset", ceil_secs(decision.reset).to_string());
+}
+
+fn client_key(request: &Request, key: &Key) -> String {
+    if let Key::Header(name) = key {
+        if let Some(value) = request.header(name) {
+            return format!("{}:{}", name, value);
+        }
+    }
+    match request.remote_addr().map(|addr| addr.ip()) {
+        Some(IpAddr::V6(ip)) => match ip.to_ipv4_mapped() {
+            // 듀얼 스택 소켓은 IPv4 클라이언트를 ::ffff:1.2.3.4로 보여준다.
+            Some(ip) => ip.to_string(),
+            None => {
+                let segments = ip.segments();
+                format!(
+                    "{:x}:{:x}:{:x}:{:x}::/64",
+                    segments[0], segments[1], segments[2], segments[3]
+                )
+            }
+        },
+        Some(ip) => ip.to_string(),
+        None => "unknown".to_string(),
+    }
+}
+
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_millis().div_ceil(1000) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    struct Ok200;
+
+    impl Handler for Ok200 {
+        fn handle_request(&self, _: &Request) -> Response {
+            Response::new(StatusCode::Ok, None)
+        }
+    }
+
+    fn get(handler: &RateLimit<Ok200>, api_key: &str) -> Response {
+        let raw = format!("GET /api HTTP/1.1\r\nX-Api-Key: {}\r\n\r\n", api_key);
+        let mut request = Request::try_from(raw.as_bytes()).unwrap();
+        request.set_remote_addr("127.0.0.1:5000".parse().unwrap());
+        handler.handle_request(&request)
+    }
+
+    #[test]
+    fn limits_each_client() {
+        let handler = RateLimit::new(Ok200).limit(
+            "/api",
+            Limit::new(2, Duration::from_secs(60)).key(Key::Header("X-Api-Key".to_string())),
+        );
+        assert_eq!(get(&handler, "a").status_code(), StatusCode::Ok);
+        assert_eq!(get(&handler, "a").status_code(), StatusCode::Ok);
+        let response = get(&handler, "a");
+        assert_eq!(response.status_code(), StatusCode::TooManyRequests);
+        assert!(response.header("Retry-After").is_some());
+        assert_eq!(get(&handler, "b").status_code(), StatusCode::Ok);
+    }
+
+    #[test]
+    fn max_clients_below_sweep_threshold_is_enforced() {
+        let handler = RateLimit::new(Ok200)
+            .limit(
+                "/api",
+                Limit::new(5, Duration::from_secs(60)).key(Key::Header("X-Api-Key".to_string())),
+            )
+            .max_clients(8);
+        for i in 0..100 {
+            get(&handler, &i.to_string());
+            assert!(handler.buckets.lock().unwrap().data.len() <= 8);
+        }
+    }
+}
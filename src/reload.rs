@@ -0,0 +1,161 @@
+use crate::http::{ParseError, Request, Response};
+use crate::server::Handler;
+use std::io::Result as IoResult;
+use std::sync::{Arc, RwLock};
+
+// 실행 중에 다른 핸들러로 바꿔 끼울 수 있는 핸들러.
+// 요청이 들어올 때마다 현재 핸들러의 Arc를 복제해서 쓰기 때문에, 교체가 일어나도 이미 처리 중인 요청은
+// 예전 핸들러로 끝까지 처리되고 새 요청부터 새 핸들러를 쓴다. 예전 핸들러는 마지막 요청이 끝날 때 drop된다.
+//
+// 교체할 때마다 미들웨어 체인을 통째로 새로 만들기 때문에 핸들러 안의 상태는 이어지지 않는다.
+// RateLimit의 버킷은 비어서 다시 시작하고, 프록시의 헬스 체크 스레드는 새 풀에 대해 새로 뜬다.
+// 예전 헬스 체크 스레드는 Weak로 풀을 가리키므로 예전 핸들러가 drop되면 다음 검사 때 스스로 끝난다.
+pub struct Reloadable<H> {
+    current: Arc<RwLock<Arc<H>>>,
+}
+
+// 핸들러를 교체할 때 쓰는 핸들. 시그널 처리 스레드로 넘길 수 있다.
+pub struct ReloadHandle<H> {
+    current: Arc<RwLock<Arc<H>>>,
+}
+
+impl<H> Reloadable<H> {
+    pub fn new(handler: H) -> Self {
+        Self {
+            current: Arc::new(RwLock::new(Arc::new(handler))),
+        }
+    }
+
+    pub fn handle(&self) -> ReloadHandle<H> {
+        ReloadHandle {
+            current: Arc::clone(&self.current),
+        }
+    }
+
+    // 읽기 락은 Arc를 복제하는 동안만 잡는다. 요청을 처리하는 동안 락을 잡고 있으면 교체가 그만큼 늦어진다.
+    fn current(&self) -> Arc<H> {
+        Arc::clone(&self.current.read().unwrap())
+    }
+}
+
+impl<H> ReloadHandle<H> {
+    pub fn replace(&self, handler: H) {
+        *self.current.write().unwrap() = Arc::new(handler);
+    }
+}
+
+impl<H: Handler> Handler for Reloadable<H> {
+    fn handle_request(&self, request: &Request) -> Response {
+        self.current().handle_request(request)
+    }
+
+    fn handle_bad_request(&self, e: &ParseError) -> Response {
+        self.current().handle_bad_request(e)
+    }
+
+    fn accept_upgrade<'buf>(&self, request: &Request<'buf>) -> Result<Request<'buf>, Response> {
+        self.current().accept_upgrade(request)
+    }
+}
+
+// SIGHUP을 받을 때마다 reload를 호출한다. 설정을 다시 읽는 일은 호출자가 넘겨준 함수가 한다.
+#[cfg(unix)]
+pub fn reload_on_sighup(mut reload: impl FnMut() + Send + 'static) -> IoResult<()> {
+    use signal_hook::consts::SIGHUP;
+    use signal_hook::iterator::Signals;
+    use std::thread;
+
+    let mut signals = Signals::new([SIGHUP])?;
+    thread::spawn(move || {
+        for _ in signals.forever() {
+            info!("Received SIGHUP, reloading configuration");
+            reload();
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn reload_on_sighup(_reload: impl FnMut() + Send + 'static) -> IoResult<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::StatusCode;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc::{channel, Receiver, Sender};
+    use std::sync::Mutex;
+    use std::thread;
+
+    // /slow 요청은 release를 받을 때까지 끝나지 않는다. 응답 본문은 핸들러 이름이다.
+    struct Named {
+        name: &'static str,
+        started: Mutex<Sender<()>>,
+        release: Mutex<Receiver<()>>,
+        dropped: Arc<AtomicBool>,
+    }
+
+    impl Handler for Named {
+        fn handle_request(&self, request: &Request) -> Response {
+            if request.path() == "/slow" {
+                self.started.lock().unwrap().send(()).unwrap();
+                self.release.lock().unwrap().recv().unwrap();
+            }
+            Response::new(StatusCode::Ok, Some(self.name.to_string()))
+        }
+    }
+
+    impl Drop for Named {
+        fn drop(&mut self) {
+            self.dropped.store(true, Ordering::SeqCst);
+        }
+    }
+
+    fn named(name: &'static str) -> (Named, Receiver<()>, Sender<()>, Arc<AtomicBool>) {
+        let (started, on_started) = channel();
+        let (release, on_release) = channel();
+        let dropped = Arc::new(AtomicBool::new(false));
+        let handler = Named {
+            name,
+            started: Mutex::new(started),
+            release: Mutex::new(on_release),
+            dropped: dropped.clone(),
+        };
+        (handler, on_started, release, dropped)
+    }
+
+    fn get(handler: &impl Handler, path: &str) -> String {
+        let raw = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+        let request = Request::try_from(raw.as_bytes()).unwrap();
+        let response = handler.handle_request(&request);
+        String::from_utf8(response.body().unwrap().to_vec()).unwrap()
+    }
+
+    #[test]
+    fn in_flight_requests_keep_the_old_handler() {
+        let (old, started, release, old_dropped) = named("old");
+        let handler = Arc::new(Reloadable::new(old));
+        let reloader = handler.handle();
+
+        let in_flight = thread::spawn({
+            let handler = handler.clone();
+            move || get(&*handler, "/slow")
+        });
+        started.recv().unwrap();
+
+        let (new, _, _, new_dropped) = named("new");
+        reloader.replace(new);
+        assert_eq!(get(&*handler, "/"), "new");
+        // 처리 중인 요청이 예전 핸들러를 붙잡고 있다.
+        assert!(!old_dropped.load(Ordering::SeqCst));
+
+        release.send(()).unwrap();
+        assert_eq!(in_flight.join().unwrap(), "old");
+        assert!(old_dropped.load(Ordering::SeqCst));
+        assert!(!new_dropped.load(Ordering::SeqCst));
+        assert_eq!(get(&*handler, "/"), "new");
+    }
+}
@@ -0,0 +1,85 @@
+use crate::http::chunked::ChunkedReader;
+use crate::http::Request;
+use std::io::{BufReader, Read, Result as IoResult, Write};
+
+// HTTP/1.1 요청 본문이 어디서 끝나는지 (RFC 9112 6.3)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(super) enum Framing {
+    // Content-Length도 Transfer-Encoding도 없으면 본문이 없는 요청이다.
+    None,
+    Length(u64),
+    Chunked,
+}
+
+// 둘 다 있거나 값이 이상한 요청은 앞에 있는 프록시와 본문의 끝을 다르게 볼 수 있으므로(request smuggling) 거절한다.
+pub(super) fn framing(request: &Request) -> Result<Framing, &'static str> {
+    let headers = request.headers();
+    if headers.contains("Transfer-Encoding") {
+        if headers.contains("Content-Length") {
+            return Err("both Transfer-Encoding and Content-Length are present");
+        }
+        let mut codings = headers
+            .get_all("Transfer-Encoding")
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|coding| !coding.is_empty());
+        // 다른 코딩(gzip 등)은 지원하지 않는다. chunked 하나만 받는다.
+        return match (codings.next(), codings.next()) {
+            (Some(coding), None) if coding.eq_ignore_ascii_case("chunked") => Ok(Framing::Chunked),
+            _ => Err("unsupported Transfer-Encoding"),
+        };
+    }
+
+    let mut length = None;
+    for value in headers
+        .get_all("Content-Length")
+        .flat_map(|value| value.split(','))
+    {
+        let value = value.trim();
+        if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+            return Err("invalid Content-Length");
+        }
+        let value: u64 = value.parse().map_err(|_| "invalid Content-Length")?;
+        // "Content-Length: 5, 5" 처럼 같은 값이 여러 번 나오는 것은 괜찮다.
+        if length.is_some_and(|length| length != value) {
+            return Err("conflicting Content-Length values");
+        }
+        length = Some(value);
+    }
+    Ok(length.map_or(Framing::None, Framing::Length))
+}
+
+// 본문을 읽는 쪽. stream은 요청 머리 뒤에 함께 읽힌 바이트를 앞에 붙인 연결이다.
+pub(super) fn reader<'a, S: Read + Write + 'a>(
+    stream: S,
+    framing: Framing,
+    expect_continue: bool,
+) -> Box<dyn Read + 'a> {
+    let stream = ExpectContinue {
+        inner: stream,
+        pending: expect_continue,
+    };
+    match framing {
+        Framing::None => Box::new(stream.take(0)),
+        Framing::Length(length) => Box::new(stream.take(length)),
+        Framing::Chunked => Box::new(ChunkedReader::new(BufReader::new(stream))),
+    }
+}
+
+// "Expect: 100-continue"를 보낸 클라이언트는 서버가 허락할 때까지 본문을 보내지 않고 기다린다.
+// 핸들러가 본문을 처음 읽으려고 할 때 100 Continue를 보낸다. 본문을 읽지 않고 응답하면 보내지 않는다.
+struct ExpectContinue<S> {
+    inner: S,
+    pending: bool,
+}
+
+impl<S: Read + Write> Read for ExpectContinue<S> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        if self.pending {
+            self.pending = false;
+            self.inner.write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+            self.inner.flush()?;
+        }
+        self.inner.read(buf)
+    }
+}
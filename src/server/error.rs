@@ -0,0 +1,51 @@
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use std::io;
+
+// 서버를 실행하다가 더 이상 계속할 수 없는 경우의 오류들이다.
+// 연결 하나가 실패하는 것처럼 복구 가능한 오류는 여기에 포함되지 않고 로그만 남긴다.
+pub enum ServerError {
+    // 주소는 올바르지만 그 주소에 리스너를 열지 못한 경우 (포트가 이미 사용 중이거나 권한이 없는 경우)
+    Bind { addr: String, source: io::Error },
+    // 주소 형식이 잘못되었거나 설정 값이 올바르지 않은 경우
+    Config(String),
+    // accept()가 다시 시도해도 소용없는 오류를 리턴한 경우
+    Accept(io::Error),
+}
+
+impl ServerError {
+    fn kind(&self) -> &str {
+        match self {
+            Self::Bind { .. } => "BindError",
+            Self::Config(_) => "ConfigError",
+            Self::Accept(_) => "AcceptError",
+        }
+    }
+}
+
+impl Display for ServerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Bind { addr, source } => {
+                write!(f, "{}: failed to bind {}: {}", self.kind(), addr, source)
+            }
+            Self::Config(message) => write!(f, "{}: {}", self.kind(), message),
+            Self::Accept(source) => write!(f, "{}: {}", self.kind(), source),
+        }
+    }
+}
+
+impl Debug for ServerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}", self)
+    }
+}
+
+impl Error for ServerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Bind { source, .. } | Self::Accept(source) => Some(source),
+            Self::Config(_) => None,
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use std::io::{ErrorKind, Read, Result as IoResult, Write};
+
+// 모든 HTTP/2 연결은 클라이언트가 이 24바이트를 보내면서 시작한다.
+pub const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+pub const HEADER_LEN: usize = 9;
+// SETTINGS_MAX_FRAME_SIZE의 기본값이자 최솟값
+pub const DEFAULT_MAX_FRAME_SIZE: u32 = 16_384;
+pub const MAX_MAX_FRAME_SIZE: u32 = (1 << 24) - 1;
+pub const DEFAULT_WINDOW_SIZE: u32 = 65_535;
+pub const MAX_WINDOW_SIZE: i64 = (1 << 31) - 1;
+
+// 플래그는 프레임 종류마다 의미가 다르지만 비트 위치가 겹치지 않아서 상수 하나로 쓴다.
+pub const END_STREAM: u8 = 0x1;
+pub const ACK: u8 = 0x1;
+pub const END_HEADERS: u8 = 0x4;
+pub const PADDED: u8 = 0x8;
+pub const PRIORITY: u8 = 0x20;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FrameType {
+    Data,
+    Headers,
+    Priority,
+    RstStream,
+    Settings,
+    PushPromise,
+    Ping,
+    GoAway,
+    WindowUpdate,
+    Continuation,
+    // 모르는 종류의 프레임은 무시해야 한다.
+    Unknown(u8),
+}
+
+impl FrameType {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0x0 => Self::Data,
+            0x1 => Self::Headers,
+            0x2 => Self::Priority,
+            0x3 => Self::RstStream,
+            0x4 => Self::Settings,
+            0x5 => Self::PushPromise,
+            0x6 => Self::Ping,
+            0x7 => Self::GoAway,
+            0x8 => Self::WindowUpdate,
+            0x9 => Self::Continuation,
+            other => Self::Unknown(other),
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            Self::Data => 0x0,
+            Self::Headers => 0x1,
+            Self::Priority => 0x2,
+            Self::RstStream => 0x3,
+            Self::Settings => 0x4,
+            Self::PushPromise => 0x5,
+            Self::Ping => 0x6,
+            Self::GoAway => 0x7,
+            Self::WindowUpdate => 0x8,
+            Self::Continuation => 0x9,
+            Self::Unknown(other) => other,
+        }
+    }
+}
+
+// RST_STREAM과 GOAWAY에 담아 보내는 오류 코드 (RFC 9113 7장)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ErrorCode {
+    NoError = 0x0,
+    ProtocolError = 0x1,
+    InternalError = 0x2,
+    FlowControlError = 0x3,
+    StreamClosed = 0x5,
+    FrameSizeError = 0x6,
+    RefusedStream = 0x7,
+    Cancel = 0x8,
+    CompressionError = 0x9,
+    EnhanceYourCalm = 0xb,
+    Http11Required = 0xd,
+}
+
+// SETTINGS 프레임의 항목 번호
+pub const SETTINGS_HEADER_TABLE_SIZE: u16 = 0x1;
+pub const SETTINGS_ENABLE_PUSH: u16 = 0x2;
+pub const SETTINGS_MAX_CONCURRENT_STREAMS: u16 = 0x3;
+pub const SETTINGS_INITIAL_WINDOW_SIZE: u16 = 0x4;
+pub const SETTINGS_MAX_FRAME_SIZE: u16 = 0x5;
+pub const SETTINGS_MAX_HEADER_LIST_SIZE: u16 = 0x6;
+
+#[derive(Debug)]
+pub struct Frame {
+    pub kind: FrameType,
+    pub flags: u8,
+    pub stream_id: u32,
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    pub fn has(&self, flag: u8) -> bool {
+        self.flags & flag != 0
+    }
+}
+
+pub enum ReadFrame {
+    Frame(Frame),
+    // 프레임 경계에서 연결이 닫혔다.
+    Closed,
+    // 프레임이 max_frame_size보다 크다. 페이로드는 읽지 않았으므로 연결을 끊어야 한다.
+    TooLarge,
+}
+
+// 9바이트 헤더: 길이(24비트), 종류(8비트), 플래그(8비트), 예약 비트 1개와 스트림 번호(31비트)
+pub fn read_frame(stream: &mut impl Read, max_frame_size: u32) -> IoResult<ReadFrame> {
+    let mut header = [0; HEADER_LEN];
+    let mut filled = 0;
+    while filled < HEADER_LEN {
+        match stream.read(&mut header[filled..]) {
+            Ok(0) if filled == 0 => return Ok(ReadFrame::Closed),
+            Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+            Ok(len) => filled += len,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    let len = u32::from_be_bytes([0, header[0], header[1], header[2]]);
+    if len > max_frame_size {
+        return Ok(ReadFrame::TooLarge);
+    }
+    let mut payload = vec![0; len as usize];
+    stream.read_exact(&mut payload)?;
+
+    Ok(ReadFrame::Frame(Frame {
+        kind: FrameType::from_u8(header[3]),
+        flags: header[4],
+        stream_id: u32::from_be_bytes([header[5], header[6], header[7], header[8]]) & 0x7fff_ffff,
+        payload,
+    }))
+}
+
+pub fn write_frame(
+    stream: &mut impl Write,
+    kind: FrameType,
+    flags: u8,
+    stream_id: u32,
+    payload: &[u8],
+) -> IoResult<()> {
+    let len = (payload.len() as u32).to_be_bytes();
+    let id = stream_id.to_be_bytes();
+    let header = [
+        len[1],
+        len[2],
+        len[3],
+        kind.to_u8(),
+        flags,
+        id[0],
+        id[1],
+        id[2],
+        id[3],
+    ];
+    stream.write_all(&header)?;
+    stream.write_all(payload)
+}
+
+pub fn settings_payload(settings: &[(u16, u32)]) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(settings.len() * 6);
+    for (id, value) in settings {
+        payload.extend_from_slice(&id.to_be_bytes());
+        payload.extend_from_slice(&value.to_be_bytes());
+    }
+    payload
+}
+
+pub fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+// PADDED 플래그가 있으면 첫 바이트가 패딩 길이다. 패딩을 떼어낸 나머지를 리턴한다.
+pub fn strip_padding(frame: &Frame) -> Option<&[u8]> {
+    if !frame.has(PADDED) {
+        return Some(&frame.payload);
+    }
+    let (&pad, rest) = frame.payload.split_first()?;
+    rest.len().checked_sub(pad as usize).map(|end| &rest[..end])
+}
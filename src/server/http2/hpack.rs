@@ -0,0 +1,292 @@
+use super::huffman;
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+// RFC 7541 부록 A의 정적 테이블. 인덱스는 1부터 시작한다.
+const STATIC_TABLE: [(&str, &str); 61] = [
+    (":authority", ""),
+    (":method", "GET"),
+    (":method", "POST"),
+    (":path", "/"),
+    (":path", "/index.html"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "200"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "304"),
+    (":status", "400"),
+    (":status", "404"),
+    (":status", "500"),
+    ("accept-charset", ""),
+    ("accept-encoding", "gzip, deflate"),
+    ("accept-language", ""),
+    ("accept-ranges", ""),
+    ("accept", ""),
+    ("access-control-allow-origin", ""),
+    ("age", ""),
+    ("allow", ""),
+    ("authorization", ""),
+    ("cache-control", ""),
+    ("content-disposition", ""),
+    ("content-encoding", ""),
+    ("content-language", ""),
+    ("content-length", ""),
+    ("content-location", ""),
+    ("content-range", ""),
+    ("content-type", ""),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("expect", ""),
+    ("expires", ""),
+    ("from", ""),
+    ("host", ""),
+    ("if-match", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("if-range", ""),
+    ("if-unmodified-since", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("max-forwards", ""),
+    ("proxy-authenticate", ""),
+    ("proxy-authorization", ""),
+    ("range", ""),
+    ("referer", ""),
+    ("refresh", ""),
+    ("retry-after", ""),
+    ("server", ""),
+    ("set-cookie", ""),
+    ("strict-transport-security", ""),
+    ("transfer-encoding", ""),
+    ("user-agent", ""),
+    ("vary", ""),
+    ("via", ""),
+    ("www-authenticate", ""),
+];
+
+// 헤더 블록을 풀다가 만난 오류. 디코더 상태가 어긋났을 수 있으므로 연결 전체를 COMPRESSION_ERROR로 끊어야 한다.
+#[derive(Debug)]
+pub struct HpackError(&'static str);
+
+impl Display for HpackError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}", self.0)
+    }
+}
+
+// 클라이언트가 보낸 헤더 블록을 푸는 디코더. 동적 테이블은 연결 하나 동안 유지된다.
+pub struct Decoder {
+    // 맨 앞이 가장 최근에 추가된 항목이다. (인덱스 62)
+    table: VecDeque<(String, String)>,
+    size: usize,
+    max_size: usize,
+    // SETTINGS_HEADER_TABLE_SIZE로 알려준 값. 클라이언트는 이보다 큰 테이블을 쓸 수 없다.
+    limit: usize,
+}
+
+impl Decoder {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            table: VecDeque::new(),
+            size: 0,
+            max_size: limit,
+            limit,
+        }
+    }
+
+    // max_list_size는 이름과 값 길이에 항목마다 32바이트를 더한 합의 상한이다. (SETTINGS_MAX_HEADER_LIST_SIZE)
+    // 넘으면 None을 리턴하지만, 동적 테이블이 어긋나지 않도록 블록은 끝까지 푼다.
+    pub fn decode(
+        &mut self,
+        mut block: &[u8],
+        max_list_size: usize,
+    ) -> Result<Option<Vec<(String, String)>>, HpackError> {
+        let mut headers = Vec::new();
+        let mut list_size = 0;
+        let mut too_large = false;
+        let mut first = true;
+
+        while let Some(&byte) = block.first() {
+            let header = if byte & 0x80 != 0 {
+                // 1xxxxxxx: 테이블에 있는 헤더 그대로
+                let index = read_integer(&mut block, 7)?;
+                let (name, value) = self.get(index)?;
+                (name.to_string(), value.to_string())
+            } else if byte & 0x40 != 0 {
+                // 01xxxxxx: 리터럴, 동적 테이블에 추가
+                let header = self.read_literal(&mut block, 6)?;
+                self.insert(header.clone());
+                header
+            } else if byte & 0x20 != 0 {
+                // 001xxxxx: 동적 테이블 크기 변경. 블록의 맨 앞에만 올 수 있다.
+                if !first {
+                    return Err(HpackError("table size update after a header"));
+                }
+                let max_size = read_integer(&mut block, 5)?;
+                if max_size > self.limit {
+                    return Err(HpackError("table size update exceeds the limit"));
+                }
+                self.max_size = max_size;
+                self.evict(0);
+                continue;
+            } else {
+                // 0000xxxx: 리터럴, 테이블에 추가하지 않음 / 0001xxxx: 중간 프록시도 테이블에 추가하면 안 되는 리터럴
+                self.read_literal(&mut block, 4)?
+            };
+            first = false;
+
+            list_size += header.0.len() + header.1.len() + 32;
+            if list_size > max_list_size {
+                too_large = true;
+            }
+            if !too_large {
+                headers.push(header);
+            }
+        }
+
+        Ok(if too_large { None } else { Some(headers) })
+    }
+
+    fn get(&self, index: usize) -> Result<(&str, &str), HpackError> {
+        match index {
+            0 => Err(HpackError("index 0 is not allowed")),
+            1..=61 => Ok(STATIC_TABLE[index - 1]),
+            _ => self
+                .table
+                .get(index - 62)
+                .map(|(name, value)| (name.as_str(), value.as_str()))
+                .ok_or(HpackError("index out of range")),
+        }
+    }
+
+    fn read_literal(&self, block: &mut &[u8], prefix: u8) -> Result<(String, String), HpackError> {
+        let index = read_integer(block, prefix)?;
+        let name = if index == 0 {
+            read_string(block)?
+        } else {
+            self.get(index)?.0.to_string()
+        };
+        let value = read_string(block)?;
+        Ok((name, value))
+    }
+
+    fn insert(&mut self, header: (String, String)) {
+        let size = header.0.len() + header.1.len() + 32;
+        // 테이블보다 큰 항목은 테이블을 비우기만 하고 추가되지 않는다.
+        self.evict(size);
+        if size <= self.max_size {
+            self.size += size;
+            self.table.push_front(header);
+        }
+    }
+
+    // extra 바이트를 넣을 자리가 생길 때까지 오래된 항목부터 뺀다.
+    fn evict(&mut self, extra: usize) {
+        while self.size + extra > self.max_size {
+            match self.table.pop_back() {
+                Some((name, value)) => self.size -= name.len() + value.len() + 32,
+                None => break,
+            }
+        }
+    }
+}
+
+// 응답 헤더를 만드는 인코더. 동적 테이블은 쓰지 않기 때문에 상태가 없고,
+// 클라이언트가 SETTINGS_HEADER_TABLE_SIZE를 어떻게 바꾸든 신경 쓸 필요가 없다.
+pub fn encode(headers: &[(&str, &str)]) -> Vec<u8> {
+    let mut block = Vec::new();
+    for &(name, value) in headers {
+        if let Some(index) = STATIC_TABLE
+            .iter()
+            .position(|&entry| entry == (name, value))
+        {
+            write_integer(&mut block, 0x80, 7, index + 1);
+            continue;
+        }
+
+        // 0000xxxx: 동적 테이블에 추가하지 않는 리터럴
+        match STATIC_TABLE.iter().position(|&(n, _)| n == name) {
+            Some(index) => write_integer(&mut block, 0, 4, index + 1),
+            None => {
+                block.push(0);
+                write_string(&mut block, name.as_bytes());
+            }
+        }
+        write_string(&mut block, value.as_bytes());
+    }
+    block
+}
+
+// 앞의 prefix 비트에 값이 들어 있고, 다 차면 다음 바이트들에 7비트씩 이어진다. (RFC 7541 5.1)
+fn read_integer(block: &mut &[u8], prefix: u8) -> Result<usize, HpackError> {
+    let (&first, rest) = block.split_first().ok_or(HpackError("truncated integer"))?;
+    *block = rest;
+
+    let max = (1usize << prefix) - 1;
+    let mut value = first as usize & max;
+    if value < max {
+        return Ok(value);
+    }
+
+    let mut shift = 0;
+    loop {
+        let (&byte, rest) = block.split_first().ok_or(HpackError("truncated integer"))?;
+        *block = rest;
+        // 헤더 블록에서 2^28을 넘는 값은 정상적인 용도가 없다.
+        if shift > 21 {
+            return Err(HpackError("integer is too large"));
+        }
+        value += ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+}
+
+fn write_integer(block: &mut Vec<u8>, flags: u8, prefix: u8, mut value: usize) {
+    let max = (1usize << prefix) - 1;
+    if value < max {
+        block.push(flags | value as u8);
+        return;
+    }
+    block.push(flags | max as u8);
+    value -= max;
+    while value >= 0x80 {
+        block.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    block.push(value as u8);
+}
+
+fn read_string(block: &mut &[u8]) -> Result<String, HpackError> {
+    let huffman = block.first().ok_or(HpackError("truncated string"))? & 0x80 != 0;
+    let len = read_integer(block, 7)?;
+    if block.len() < len {
+        return Err(HpackError("truncated string"));
+    }
+    let (bytes, rest) = block.split_at(len);
+    *block = rest;
+
+    let bytes = if huffman {
+        huffman::decode(bytes).ok_or(HpackError("invalid huffman code"))?
+    } else {
+        bytes.to_vec()
+    };
+    String::from_utf8(bytes).map_err(|_| HpackError("header is not valid UTF-8"))
+}
+
+// 허프만 코드가 더 짧을 때만 쓴다.
+fn write_string(block: &mut Vec<u8>, bytes: &[u8]) {
+    let huffman_len = huffman::encoded_len(bytes);
+    if huffman_len < bytes.len() {
+        write_integer(block, 0x80, 7, huffman_len);
+        block.extend_from_slice(&huffman::encode(bytes));
+    } else {
+        write_integer(block, 0, 7, bytes.len());
+        block.extend_from_slice(bytes);
+    }
+}
@@ -0,0 +1,1053 @@
+// HTTP/2 (RFC 9113) 서버 쪽 구현.
+//
+// 연결 하나는 작업자 스레드 하나가 맡는다. 여러 스트림의 프레임이 섞여서 들어오지만 요청은 준비된 순서대로
+// 하나씩 핸들러에 넘기고, 응답을 보내는 동안 흐름 제어 창이 모자라면 그 자리에서 프레임을 더 읽어서
+// WINDOW_UPDATE를 기다린다. 핸들러는 HTTP/1.1과 똑같은 Request와 Response를 주고받는다.
+//
+// 지금 구현에는 두 가지 한계가 있다.
+// - 핸들러를 연결의 스레드에서 차례로 부르므로, 느린 핸들러 하나가 같은 연결의 다른 스트림을 모두 막는다.
+//   프레임 단위의 head-of-line blocking은 없어지지만 핸들러 단위로는 남아 있다.
+// - 스트리밍 응답(Response::stream)은 보내지 않고 HTTP_1_1_REQUIRED로 스트림을 끊는다. SSE와,
+//   Content-Length가 작지 않은 프록시 응답이 여기에 해당한다. 클라이언트는 HTTP/1.1로 다시 요청해야 한다.
+use super::timed::Receiving;
+use super::{ConnectionSettings, Handler};
+use crate::config::is_token;
+use crate::http::{Body, Request, Response, StatusCode};
+use frame::{
+    read_frame, settings_payload, strip_padding, u32_at, write_frame, ErrorCode, Frame, FrameType,
+    ReadFrame, ACK, DEFAULT_MAX_FRAME_SIZE, DEFAULT_WINDOW_SIZE, END_HEADERS, END_STREAM,
+    MAX_MAX_FRAME_SIZE, MAX_WINDOW_SIZE, PREFACE, PRIORITY,
+};
+use hpack::Decoder;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::io::{self, Cursor, ErrorKind, Read, Write};
+use std::net::SocketAddr;
+
+mod frame;
+mod hpack;
+mod huffman;
+
+// 동시에 열어둘 수 있는 스트림 개수. 넘으면 새 스트림을 REFUSED_STREAM으로 거절한다.
+const MAX_CONCURRENT_STREAMS: u32 = 100;
+// 클라이언트가 HPACK 동적 테이블로 쓸 수 있는 크기 (기본값)
+const HEADER_TABLE_SIZE: usize = 4096;
+// 요청 본문을 모아 둘 최대 크기. 넘으면 413 Content Too Large로 응답한다.
+const MAX_REQUEST_BODY: usize = 16 * 1024 * 1024;
+// 연결 하나에서 핸들러에 넘기기 전까지 모아 둘 수 있는 본문의 합. 연결의 흐름 제어 창을 이만큼 열어두고,
+// 모아 둔 본문이 빠져나가는 만큼만 다시 열어준다.
+const MAX_BUFFERED_BODY: usize = 2 * MAX_REQUEST_BODY;
+
+// stream은 이미 TLS 핸드셰이크(ALPN h2)를 마쳤거나, 평문 연결에서 클라이언트가 프리페이스를 보낸 상태여야 한다.
+// 프리페이스는 아직 읽지 않은 것으로 보고 여기서 읽는다.
+pub(super) fn serve<S: Read + Write>(
+    stream: &mut S,
+    remote_addr: SocketAddr,
+    handler: &dyn Handler,
+    settings: ConnectionSettings,
+    receiving: &Receiving,
+) {
+    let mut connection = Connection::new(stream, remote_addr, handler, settings, receiving);
+    match connection.run() {
+        Ok(()) => connection.go_away(ErrorCode::NoError),
+        // 한동안 아무 요청도 없으면 정상적으로 연결을 닫는다.
+        Err(Error::Io(e)) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+            connection.go_away(ErrorCode::NoError)
+        }
+        Err(Error::Io(e)) => debug!("HTTP/2 connection closed : {}", e),
+        Err(Error::Connection(code, reason)) => {
+            warn!("HTTP/2 connection error ({:?}) : {}", code, reason);
+            connection.go_away(code);
+        }
+    }
+}
+
+enum Error {
+    Io(io::Error),
+    // 연결 전체를 GOAWAY로 끊어야 하는 오류
+    Connection(ErrorCode, &'static str),
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+fn protocol_error(reason: &'static str) -> Error {
+    Error::Connection(ErrorCode::ProtocolError, reason)
+}
+
+struct Stream {
+    headers: Vec<(String, String)>,
+    // 헤더가 SETTINGS_MAX_HEADER_LIST_SIZE를 넘었다. 431로 응답한다.
+    too_large: bool,
+    // 클라이언트가 END_STREAM을 보냈다.
+    remote_closed: bool,
+    send_window: i64,
+    // 여러 스트림의 DATA 프레임이 섞여서 오므로 HTTP/1.1처럼 연결에서 바로 읽게 할 수 없다.
+    // END_STREAM까지 모았다가 핸들러에 넘긴다.
+    body: Vec<u8>,
+    body_too_large: bool,
+}
+
+struct Connection<'a, S> {
+    stream: &'a mut S,
+    remote_addr: SocketAddr,
+    handler: &'a dyn Handler,
+    settings: ConnectionSettings,
+    // 본문을 다 받지 못한 스트림이 있는 동안에는 연결 전체에 최소 수신 속도를 적용한다.
+    receiving: &'a Receiving,
+    decoder: Decoder,
+    streams: HashMap<u32, Stream>,
+    // 요청을 다 받아서 핸들러에 넘길 차례를 기다리는 스트림들
+    ready: VecDeque<u32>,
+    last_stream_id: u32,
+    // 클라이언트가 보낸 SETTINGS 값들
+    peer_settings_received: bool,
+    peer_initial_window: i64,
+    peer_max_frame_size: u32,
+    send_window: i64,
+    // 클라이언트가 더 보낼 수 있는 DATA 크기 (연결 전체)
+    recv_window: i64,
+    // 클라이언트가 GOAWAY를 보냈다. 남은 응답만 보내고 연결을 닫는다.
+    going_away: bool,
+    // 작은 프레임을 하나씩 보내지 않도록 모았다가 한번에 쓴다.
+    out: Vec<u8>,
+}
+
+impl<'a, S: Read + Write> Connection<'a, S> {
+    fn new(
+        stream: &'a mut S,
+        remote_addr: SocketAddr,
+        handler: &'a dyn Handler,
+        settings: ConnectionSettings,
+        receiving: &'a Receiving,
+    ) -> Self {
+        Self {
+            stream,
+            remote_addr,
+            handler,
+            settings,
+            receiving,
+            decoder: Decoder::new(HEADER_TABLE_SIZE),
+            streams: HashMap::new(),
+            ready: VecDeque::new(),
+            last_stream_id: 0,
+            peer_settings_received: false,
+            peer_initial_window: DEFAULT_WINDOW_SIZE as i64,
+            peer_max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            send_window: DEFAULT_WINDOW_SIZE as i64,
+            recv_window: MAX_BUFFERED_BODY as i64,
+            going_away: false,
+            out: Vec::new(),
+        }
+    }
+
+    fn run(&mut self) -> Result<(), Error> {
+        let mut preface = [0; PREFACE.len()];
+        self.stream.read_exact(&mut preface)?;
+        if preface != PREFACE {
+            return Err(protocol_error("invalid connection preface"));
+        }
+
+        let settings = settings_payload(&[
+            (
+                frame::SETTINGS_MAX_CONCURRENT_STREAMS,
+                MAX_CONCURRENT_STREAMS,
+            ),
+            (
+                frame::SETTINGS_MAX_HEADER_LIST_SIZE,
+                self.settings.max_request_bytes as u32,
+            ),
+        ]);
+        self.send(FrameType::Settings, 0, 0, &settings);
+        let increment = (MAX_BUFFERED_BODY - DEFAULT_WINDOW_SIZE as usize) as u32;
+        self.send(FrameType::WindowUpdate, 0, 0, &increment.to_be_bytes());
+
+        loop {
+            while let Some(id) = self.ready.pop_front() {
+                self.respond(id)?;
+            }
+            if self.going_away && self.streams.is_empty() {
+                return Ok(());
+            }
+            self.flush()?;
+            if !self.read_and_process()? {
+                return Ok(());
+            }
+        }
+    }
+
+    fn send(&mut self, kind: FrameType, flags: u8, stream_id: u32, payload: &[u8]) {
+        // Vec에 쓰는 건 실패하지 않는다.
+        let _ = write_frame(&mut self.out, kind, flags, stream_id, payload);
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.out.is_empty() {
+            self.stream.write_all(&self.out)?;
+            self.out.clear();
+        }
+        self.stream.flush()
+    }
+
+    fn go_away(&mut self, code: ErrorCode) {
+        let mut payload = self.last_stream_id.to_be_bytes().to_vec();
+        payload.extend_from_slice(&(code as u32).to_be_bytes());
+        self.send(FrameType::GoAway, 0, 0, &payload);
+        if let Err(e) = self.flush() {
+            debug!("Failed to send GOAWAY : {}", e);
+        }
+    }
+
+    // 스트림 하나만 끊는다. 연결은 계속 쓴다.
+    fn reset(&mut self, id: u32, code: ErrorCode) {
+        self.send(FrameType::RstStream, 0, id, &(code as u32).to_be_bytes());
+        self.remove_stream(id);
+    }
+
+    fn remove_stream(&mut self, id: u32) {
+        if let Some(stream) = self.streams.remove(&id) {
+            self.release(stream.body.len());
+        }
+        self.ready.retain(|&ready| ready != id);
+    }
+
+    // 모아 둔 본문이 빠져나간 만큼 연결의 흐름 제어 창을 다시 열어준다.
+    fn release(&mut self, len: usize) {
+        if len > 0 {
+            self.recv_window += len as i64;
+            self.send(FrameType::WindowUpdate, 0, 0, &(len as u32).to_be_bytes());
+        }
+    }
+
+    // 프레임 하나를 읽어서 처리한다. 클라이언트가 연결을 닫았으면 false를 리턴한다.
+    fn read_and_process(&mut self) -> Result<bool, Error> {
+        self.receiving
+            .set(self.streams.values().any(|stream| !stream.remote_closed));
+        let frame = match read_frame(self.stream, DEFAULT_MAX_FRAME_SIZE)? {
+            ReadFrame::Frame(frame) => frame,
+            ReadFrame::Closed => return Ok(false),
+            ReadFrame::TooLarge => {
+                return Err(Error::Connection(
+                    ErrorCode::FrameSizeError,
+                    "frame is larger than SETTINGS_MAX_FRAME_SIZE",
+                ))
+            }
+        };
+
+        // 프리페이스 바로 다음 프레임은 SETTINGS 여야 한다.
+        if !self.peer_settings_received && frame.kind != FrameType::Settings {
+            return Err(protocol_error("expected SETTINGS after the preface"));
+        }
+
+        match frame.kind {
+            FrameType::Data => self.on_data(frame)?,
+            FrameType::Headers => self.on_headers(frame)?,
+            FrameType::Priority => {
+                if frame.stream_id == 0 {
+                    return Err(protocol_error("PRIORITY on stream 0"));
+                }
+                if frame.payload.len() != 5 {
+                    self.reset(frame.stream_id, ErrorCode::FrameSizeError);
+                }
+            }
+            FrameType::RstStream => {
+                if frame.stream_id == 0 || frame.stream_id > self.last_stream_id {
+                    return Err(protocol_error("RST_STREAM on an idle stream"));
+                }
+                if frame.payload.len() != 4 {
+                    return Err(Error::Connection(
+                        ErrorCode::FrameSizeError,
+                        "RST_STREAM must be 4 bytes",
+                    ));
+                }
+                self.remove_stream(frame.stream_id);
+            }
+            FrameType::Settings => self.on_settings(frame)?,
+            FrameType::PushPromise => return Err(protocol_error("clients cannot push")),
+            FrameType::Ping => {
+                if frame.stream_id != 0 {
+                    return Err(protocol_error("PING on a stream"));
+                }
+                if frame.payload.len() != 8 {
+                    return Err(Error::Connection(
+                        ErrorCode::FrameSizeError,
+                        "PING must be 8 bytes",
+                    ));
+                }
+                if !frame.has(ACK) {
+                    self.send(FrameType::Ping, ACK, 0, &frame.payload);
+                }
+            }
+            FrameType::GoAway => {
+                if frame.stream_id != 0 {
+                    return Err(protocol_error("GOAWAY on a stream"));
+                }
+                self.going_away = true;
+            }
+            FrameType::WindowUpdate => self.on_window_update(frame)?,
+            // CONTINUATION은 on_headers()에서 HEADERS에 이어서 읽는다. 여기까지 왔다면 순서가 틀린 것이다.
+            FrameType::Continuation => return Err(protocol_error("unexpected CONTINUATION")),
+            FrameType::Unknown(_) => {}
+        }
+
+        Ok(true)
+    }
+
+    fn on_settings(&mut self, frame: Frame) -> Result<(), Error> {
+        if frame.stream_id != 0 {
+            return Err(protocol_error("SETTINGS on a stream"));
+        }
+        if frame.has(ACK) {
+            if !frame.payload.is_empty() {
+                return Err(Error::Connection(
+                    ErrorCode::FrameSizeError,
+                    "SETTINGS ack with a payload",
+                ));
+            }
+            return Ok(());
+        }
+        if !frame.payload.len().is_multiple_of(6) {
+            return Err(Error::Connection(
+                ErrorCode::FrameSizeError,
+                "SETTINGS length must be a multiple of 6",
+            ));
+        }
+
+        for setting in frame.payload.chunks(6) {
+            let id = u16::from_be_bytes([setting[0], setting[1]]);
+            let value = u32_at(setting, 2);
+            match id {
+                frame::SETTINGS_ENABLE_PUSH if value > 1 => {
+                    return Err(protocol_error("invalid SETTINGS_ENABLE_PUSH"));
+                }
+                frame::SETTINGS_INITIAL_WINDOW_SIZE => {
+                    if value as i64 > MAX_WINDOW_SIZE {
+                        return Err(Error::Connection(
+                            ErrorCode::FlowControlError,
+                            "SETTINGS_INITIAL_WINDOW_SIZE is too large",
+                        ));
+                    }
+                    // 이미 열린 스트림의 창도 차이만큼 바뀐다.
+                    let delta = value as i64 - self.peer_initial_window;
+                    for stream in self.streams.values_mut() {
+                        stream.send_window += delta;
+                        if stream.send_window > MAX_WINDOW_SIZE {
+                            return Err(Error::Connection(
+                                ErrorCode::FlowControlError,
+                                "stream window overflow",
+                            ));
+                        }
+                    }
+                    self.peer_initial_window = value as i64;
+                }
+                frame::SETTINGS_MAX_FRAME_SIZE => {
+                    if !(DEFAULT_MAX_FRAME_SIZE..=MAX_MAX_FRAME_SIZE).contains(&value) {
+                        return Err(protocol_error("invalid SETTINGS_MAX_FRAME_SIZE"));
+                    }
+                    self.peer_max_frame_size = value;
+                }
+                // 응답 헤더에 동적 테이블을 쓰지 않으므로 HEADER_TABLE_SIZE는 상관없고,
+                // 푸시는 하지 않으며 나머지는 알 필요가 없는 값들이다.
+                _ => {}
+            }
+        }
+
+        self.peer_settings_received = true;
+        self.send(FrameType::Settings, ACK, 0, &[]);
+        Ok(())
+    }
+
+    fn on_window_update(&mut self, frame: Frame) -> Result<(), Error> {
+        if frame.payload.len() != 4 {
+            return Err(Error::Connection(
+                ErrorCode::FrameSizeError,
+                "WINDOW_UPDATE must be 4 bytes",
+            ));
+        }
+        let increment = (u32_at(&frame.payload, 0) & 0x7fff_ffff) as i64;
+
+        if frame.stream_id == 0 {
+            if increment == 0 {
+                return Err(protocol_error("WINDOW_UPDATE with zero increment"));
+            }
+            self.send_window += increment;
+            if self.send_window > MAX_WINDOW_SIZE {
+                return Err(Error::Connection(
+                    ErrorCode::FlowControlError,
+                    "connection window overflow",
+                ));
+            }
+            return Ok(());
+        }
+
+        if frame.stream_id > self.last_stream_id {
+            return Err(protocol_error("WINDOW_UPDATE on an idle stream"));
+        }
+        if increment == 0 {
+            self.reset(frame.stream_id, ErrorCode::ProtocolError);
+            return Ok(());
+        }
+        // 이미 닫힌 스트림에 대한 WINDOW_UPDATE는 늦게 도착한 것일 수 있으니 무시한다.
+        if let Some(stream) = self.streams.get_mut(&frame.stream_id) {
+            stream.send_window += increment;
+            if stream.send_window > MAX_WINDOW_SIZE {
+                self.reset(frame.stream_id, ErrorCode::FlowControlError);
+            }
+        }
+        Ok(())
+    }
+
+    fn on_data(&mut self, frame: Frame) -> Result<(), Error> {
+        let id = frame.stream_id;
+        if id == 0 {
+            return Err(protocol_error("DATA on stream 0"));
+        }
+        if id > self.last_stream_id {
+            return Err(protocol_error("DATA on an idle stream"));
+        }
+        let data = match strip_padding(&frame) {
+            Some(data) => data,
+            None => return Err(protocol_error("invalid padding")),
+        };
+
+        // 패딩도 창을 차지한다.
+        let len = frame.payload.len();
+        if len as i64 > self.recv_window {
+            return Err(Error::Connection(
+                ErrorCode::FlowControlError,
+                "DATA is larger than the flow control window",
+            ));
+        }
+        self.recv_window -= len as i64;
+
+        // 연결 창은 모아 둔 본문만큼만 닫아 두고, 패딩이나 버리는 데이터는 바로 돌려준다.
+        let mut released = len;
+        match self.streams.get_mut(&id) {
+            // 413 응답을 기다리는 중이다. 남은 본문은 버린다.
+            Some(stream) if stream.body_too_large => {}
+            Some(stream) if !stream.remote_closed => {
+                stream.remote_closed = frame.has(END_STREAM);
+                if stream.body.len() + data.len() > MAX_REQUEST_BODY {
+                    // 너무 큰 본문은 더 받지 않는다. 413으로 응답하고 RST_STREAM으로 나머지를 보내지 않게 한다.
+                    stream.body_too_large = true;
+                    released += std::mem::take(&mut stream.body).len();
+                    self.ready.push_back(id);
+                } else {
+                    stream.body.extend_from_slice(data);
+                    released -= data.len();
+                    if stream.remote_closed {
+                        self.ready.push_back(id);
+                    } else if len > 0 {
+                        // 스트림 창은 바로 열어준다. 모아 둘 수 있는 양은 연결 창이 정한다.
+                        self.send(FrameType::WindowUpdate, 0, id, &(len as u32).to_be_bytes());
+                    }
+                }
+            }
+            _ => self.reset(id, ErrorCode::StreamClosed),
+        }
+        self.release(released);
+
+        // 모든 스트림이 본문을 다 받기 전에 연결 창이 닫히면 어느 요청도 끝나지 않는다.
+        // 가장 많이 모은 스트림을 거절해서 자리를 만든다. 핸들러에 넘기지 않았으니 클라이언트가 다시 보내도 된다.
+        if self.recv_window == 0 && self.ready.is_empty() {
+            let largest = self
+                .streams
+                .iter()
+                .filter(|(_, stream)| !stream.body.is_empty())
+                .max_by_key(|(_, stream)| stream.body.len())
+                .map(|(&id, _)| id);
+            if let Some(id) = largest {
+                self.reset(id, ErrorCode::RefusedStream);
+            }
+        }
+        Ok(())
+    }
+
+    fn on_headers(&mut self, frame: Frame) -> Result<(), Error> {
+        let id = frame.stream_id;
+        // 클라이언트가 여는 스트림은 홀수 번호다.
+        if id == 0 || id.is_multiple_of(2) {
+            return Err(protocol_error("invalid stream id for HEADERS"));
+        }
+
+        let mut fragment = strip_padding(&frame).ok_or(protocol_error("invalid padding"))?;
+        if frame.has(PRIORITY) {
+            if fragment.len() < 5 {
+                return Err(Error::Connection(
+                    ErrorCode::FrameSizeError,
+                    "HEADERS priority is truncated",
+                ));
+            }
+            fragment = &fragment[5..];
+        }
+        let block = self.read_header_block(id, fragment.to_vec(), frame.has(END_HEADERS))?;
+
+        // 헤더 블록은 스트림을 거절하더라도 꼭 풀어야 한다. 그래야 동적 테이블이 클라이언트와 어긋나지 않는다.
+        let decoded = self
+            .decoder
+            .decode(&block, self.settings.max_request_bytes)
+            .map_err(|e| {
+                warn!("HPACK error : {}", e);
+                Error::Connection(ErrorCode::CompressionError, "invalid header block")
+            })?;
+        let end_stream = frame.has(END_STREAM);
+
+        // 이미 열린 스트림에 다시 온 HEADERS는 본문 뒤의 트레일러다. 내용은 쓰지 않는다.
+        if let Some(stream) = self.streams.get_mut(&id) {
+            if stream.remote_closed {
+                return Err(Error::Connection(
+                    ErrorCode::StreamClosed,
+                    "HEADERS after END_STREAM",
+                ));
+            }
+            if !end_stream {
+                self.reset(id, ErrorCode::ProtocolError);
+                return Ok(());
+            }
+            stream.remote_closed = true;
+            self.ready.push_back(id);
+            return Ok(());
+        }
+
+        if id <= self.last_stream_id {
+            return Err(Error::Connection(
+                ErrorCode::StreamClosed,
+                "HEADERS on a closed stream",
+            ));
+        }
+        self.last_stream_id = id;
+        if self.going_away {
+            return Ok(());
+        }
+        if self.streams.len() >= MAX_CONCURRENT_STREAMS as usize {
+            self.reset(id, ErrorCode::RefusedStream);
+            return Ok(());
+        }
+
+        self.streams.insert(
+            id,
+            Stream {
+                too_large: decoded.is_none(),
+                headers: decoded.unwrap_or_default(),
+                remote_closed: end_stream,
+                send_window: self.peer_initial_window,
+                body: Vec::new(),
+                body_too_large: false,
+            },
+        );
+        if end_stream {
+            self.ready.push_back(id);
+        }
+        Ok(())
+    }
+
+    // END_HEADERS가 올 때까지 같은 스트림의 CONTINUATION 프레임을 이어 붙인다. 그 사이에 다른 프레임이 오면 안 된다.
+    fn read_header_block(
+        &mut self,
+        id: u32,
+        mut block: Vec<u8>,
+        mut end_headers: bool,
+    ) -> Result<Vec<u8>, Error> {
+        // 압축된 블록이 풀린 헤더 목록의 최대 크기보다 훨씬 클 이유는 없다.
+        let max_block = self.settings.max_request_bytes * 2;
+        while !end_headers {
+            let frame = match read_frame(self.stream, DEFAULT_MAX_FRAME_SIZE)? {
+                ReadFrame::Frame(frame) => frame,
+                ReadFrame::Closed => return Err(io::Error::from(ErrorKind::UnexpectedEof).into()),
+                ReadFrame::TooLarge => {
+                    return Err(Error::Connection(
+                        ErrorCode::FrameSizeError,
+                        "frame is larger than SETTINGS_MAX_FRAME_SIZE",
+                    ))
+                }
+            };
+            if frame.kind != FrameType::Continuation || frame.stream_id != id {
+                return Err(protocol_error("expected CONTINUATION"));
+            }
+            block.extend_from_slice(&frame.payload);
+            if block.len() > max_block {
+                return Err(Error::Connection(
+                    ErrorCode::EnhanceYourCalm,
+                    "header block is too large",
+                ));
+            }
+            end_headers = frame.has(END_HEADERS);
+        }
+        Ok(block)
+    }
+
+    fn respond(&mut self, id: u32) -> Result<(), Error> {
+        let (headers, too_large, body, body_too_large, remote_closed) =
+            match self.streams.get_mut(&id) {
+                Some(stream) => (
+                    std::mem::take(&mut stream.headers),
+                    stream.too_large,
+                    std::mem::take(&mut stream.body),
+                    stream.body_too_large,
+                    stream.remote_closed,
+                ),
+                None => return Ok(()),
+            };
+        // 본문은 이제 핸들러 몫이므로 그만큼 클라이언트가 더 보낼 수 있다.
+        self.release(body.len());
+        if too_large {
+            let response = Response::new(StatusCode::RequestHeaderFieldsTooLarge, None);
+            return self.send_response(id, &response, false);
+        }
+        if body_too_large {
+            let response = Response::new(StatusCode::ContentTooLarge, None);
+            self.send_response(id, &response, false)?;
+            // 응답은 끝났지만 클라이언트는 아직 본문을 보내는 중이다. NO_ERROR로 그만 보내게 한다. (RFC 9113 8.1)
+            if !remote_closed {
+                let code = ErrorCode::NoError as u32;
+                self.send(FrameType::RstStream, 0, id, &code.to_be_bytes());
+            }
+            return Ok(());
+        }
+
+        let head = match request_head(&headers) {
+            Ok(head) => head,
+            Err(reason) => {
+                debug!("Malformed HTTP/2 request : {}", reason);
+                self.reset(id, ErrorCode::ProtocolError);
+                return Ok(());
+            }
+        };
+        debug!("Receiced a request: {}", String::from_utf8_lossy(&head));
+        let response = match Request::try_from(&head[..]) {
+            Ok(mut request) => {
+                request.set_remote_addr(self.remote_addr);
+                request.set_secure(self.settings.secure);
+                let length = body.len() as u64;
+                let reader = RefCell::new(Cursor::new(body));
+                // 본문의 길이를 이미 알고 있으므로 HTTP/1.1의 Content-Length 본문처럼 넘긴다.
+                if length > 0 || request.headers().contains("content-length") {
+                    request.set_body(Body::new(&reader, Some(length)));
+                }
+                self.handler.handle_request(&request)
+            }
+            Err(e) => self.handler.handle_bad_request(&e),
+        };
+        // 스트리밍 응답은 연결 하나를 끝까지 차지해야 하므로 HTTP/1.1로 다시 요청하게 한다.
+        if response.is_stream() {
+            self.reset(id, ErrorCode::Http11Required);
+            return Ok(());
+        }
+        let head_request = headers
+            .iter()
+            .any(|(name, value)| name == ":method" && value == "HEAD");
+        self.send_response(id, &response, head_request)
+    }
+
+    fn send_response(
+        &mut self,
+        id: u32,
+        response: &Response,
+        head_request: bool,
+    ) -> Result<(), Error> {
+        let status = response.status_code().to_string();
+        let body = response.body().unwrap_or(&[]);
+        let content_length = response.content_length(!head_request);
+        let names: Vec<String> = response
+            .headers()
+            .map(|(name, _)| name.to_ascii_lowercase())
+            .collect();
+
+        let mut fields = vec![(":status", status.as_str())];
+        for (name, (_, value)) in names.iter().zip(response.headers()) {
+            // HTTP/2에서는 연결에 관한 헤더를 보내면 안 되고, 본문 길이는 직접 계산한다.
+            if is_connection_specific(name) || name == "content-length" {
+                continue;
+            }
+            fields.push((name, value));
+        }
+        if response.status_code().allows_body() {
+            fields.push(("content-length", content_length.as_str()));
+        }
+        let block = hpack::encode(&fields);
+
+        let body = if head_request || !response.status_code().allows_body() {
+            &[][..]
+        } else {
+            body
+        };
+        self.send_headers(id, &block, body.is_empty());
+
+        let mut sent = 0;
+        while sent < body.len() {
+            let window = match self.send_capacity(id)? {
+                Some(window) => window,
+                // 보내는 도중에 클라이언트가 스트림을 취소했다.
+                None => return Ok(()),
+            };
+            let len = (body.len() - sent)
+                .min(window)
+                .min(self.peer_max_frame_size as usize);
+            let end = sent + len;
+            let flags = if end == body.len() { END_STREAM } else { 0 };
+            self.send(FrameType::Data, flags, id, &body[sent..end]);
+            self.send_window -= len as i64;
+            if let Some(stream) = self.streams.get_mut(&id) {
+                stream.send_window -= len as i64;
+            }
+            sent = end;
+        }
+
+        self.flush()?;
+        // 양쪽 모두 END_STREAM을 보냈으므로 스트림이 닫혔다.
+        self.remove_stream(id);
+        Ok(())
+    }
+
+    // 헤더 블록이 프레임 하나에 들어가지 않으면 CONTINUATION으로 나눠서 보낸다.
+    fn send_headers(&mut self, id: u32, block: &[u8], end_stream: bool) {
+        let max = self.peer_max_frame_size as usize;
+        let mut chunks = block.chunks(max).peekable();
+        let mut kind = FrameType::Headers;
+        let mut flags = if end_stream { END_STREAM } else { 0 };
+        // 빈 블록도 HEADERS 프레임 하나는 보내야 한다.
+        if block.is_empty() {
+            self.send(kind, flags | END_HEADERS, id, &[]);
+            return;
+        }
+        while let Some(chunk) = chunks.next() {
+            if chunks.peek().is_none() {
+                flags |= END_HEADERS;
+            }
+            self.send(kind, flags, id, chunk);
+            kind = FrameType::Continuation;
+            flags = 0;
+        }
+    }
+
+    // 연결과 스트림 양쪽의 흐름 제어 창에 여유가 생길 때까지 프레임을 읽는다.
+    // 그 사이에 스트림이 취소되면 None을 리턴한다.
+    fn send_capacity(&mut self, id: u32) -> Result<Option<usize>, Error> {
+        loop {
+            let stream_window = match self.streams.get(&id) {
+                Some(stream) => stream.send_window,
+                None => return Ok(None),
+            };
+            let window = stream_window.min(self.send_window);
+            if window > 0 {
+                return Ok(Some(window as usize));
+            }
+            self.flush()?;
+            if !self.read_and_process()? {
+                return Err(io::Error::from(ErrorKind::UnexpectedEof).into());
+            }
+        }
+    }
+}
+
+// HTTP/2 요청 헤더를 HTTP/1.1 요청 머리로 바꿔서, Request::try_from()으로 같은 Request를 만들 수 있게 한다.
+// :authority는 Host 헤더가 되고, 나눠서 온 cookie 헤더는 다시 하나로 합친다. (RFC 9113 8.2.3)
+fn request_head(headers: &[(String, String)]) -> Result<Vec<u8>, &'static str> {
+    let mut method = None;
+    let mut path = None;
+    let mut scheme = None;
+    let mut authority = None;
+    let mut regular = Vec::new();
+    let mut cookies = Vec::new();
+
+    for (name, value) in headers {
+        if value.contains(['\r', '\n', '\0']) {
+            return Err("header value contains a line break");
+        }
+        if let Some(pseudo) = name.strip_prefix(':') {
+            // 가상 헤더는 일반 헤더보다 앞에 와야 하고 한 번씩만 올 수 있다.
+            if !regular.is_empty() || !cookies.is_empty() {
+                return Err("pseudo-header after a regular header");
+            }
+            let slot = match pseudo {
+                "method" => &mut method,
+                "path" => &mut path,
+                "scheme" => &mut scheme,
+                "authority" => &mut authority,
+                _ => return Err("unknown pseudo-header"),
+            };
+            if slot.replace(value.as_str()).is_some() {
+                return Err("duplicate pseudo-header");
+            }
+            continue;
+        }
+
+        if !is_token(name) || name.bytes().any(|b| b.is_ascii_uppercase()) {
+            return Err("invalid header name");
+        }
+        if is_connection_specific(name) || (name == "te" && value != "trailers") {
+            return Err("connection-specific header");
+        }
+        if name == "cookie" {
+            cookies.push(value.as_str());
+        } else {
+            regular.push((name.as_str(), value.as_str()));
+        }
+    }
+
+    let method = method.ok_or("missing :method")?;
+    let path = path.ok_or("missing :path")?;
+    if scheme.is_none() || path.is_empty() || path.contains(' ') {
+        return Err("missing :scheme or invalid :path");
+    }
+
+    let mut head = format!("{} {} HTTP/1.1\r\n", method, path);
+    if let Some(authority) = authority {
+        if !regular.iter().any(|(name, _)| *name == "host") {
+            head.push_str(&format!("host: {}\r\n", authority));
+        }
+    }
+    for (name, value) in regular {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    if !cookies.is_empty() {
+        head.push_str(&format!("cookie: {}\r\n", cookies.join("; ")));
+    }
+    head.push_str("\r\n");
+    Ok(head.into_bytes())
+}
+
+fn is_connection_specific(name: &str) -> bool {
+    matches!(
+        name,
+        "connection" | "keep-alive" | "proxy-connection" | "transfer-encoding" | "upgrade"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const MIB: usize = 1024 * 1024;
+
+    // 미리 적어 둔 클라이언트 프레임을 읽고, 서버가 쓴 프레임을 모아 둔다.
+    struct Pipe {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for Pipe {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Pipe {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // 흐름 제어 창을 보지 않고 적어 둔 대로 보내는 클라이언트
+    struct Client(Vec<u8>);
+
+    impl Client {
+        fn new() -> Self {
+            let mut client = Client(PREFACE.to_vec());
+            client.frame(FrameType::Settings, 0, 0, &[]);
+            client
+        }
+
+        fn frame(&mut self, kind: FrameType, flags: u8, id: u32, payload: &[u8]) {
+            write_frame(&mut self.0, kind, flags, id, payload).unwrap();
+        }
+
+        fn post(&mut self, id: u32) {
+            let block = hpack::encode(&[
+                (":method", "POST"),
+                (":scheme", "http"),
+                (":authority", "localhost"),
+                (":path", "/"),
+            ]);
+            self.frame(FrameType::Headers, END_HEADERS, id, &block);
+        }
+
+        fn data(&mut self, id: u32, mut len: usize, end_stream: bool) {
+            let chunk = [b'x'; DEFAULT_MAX_FRAME_SIZE as usize];
+            while len > 0 {
+                let size = len.min(chunk.len());
+                len -= size;
+                let flags = if end_stream && len == 0 {
+                    END_STREAM
+                } else {
+                    0
+                };
+                self.frame(FrameType::Data, flags, id, &chunk[..size]);
+            }
+        }
+    }
+
+    struct BodyLength(AtomicUsize);
+
+    impl Handler for BodyLength {
+        fn handle_request(&self, request: &Request) -> Response {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            let mut body = Vec::new();
+            if let Some(mut reader) = request.body() {
+                reader.read_to_end(&mut body).unwrap();
+            }
+            Response::new(StatusCode::Ok, Some(body.len().to_string()))
+        }
+    }
+
+    // 서버가 보낸 프레임들과 핸들러가 불린 횟수
+    fn serve_client(client: Client) -> (Vec<Frame>, usize) {
+        let mut pipe = Pipe {
+            input: Cursor::new(client.0),
+            output: Vec::new(),
+        };
+        let handler = BodyLength(AtomicUsize::new(0));
+        let settings = ConnectionSettings {
+            read_timeout: None,
+            write_timeout: None,
+            header_timeout: None,
+            min_send_rate: None,
+            min_recv_rate: None,
+            max_request_bytes: 8192,
+            http2: true,
+            secure: false,
+        };
+        serve(
+            &mut pipe,
+            "192.0.2.1:1234".parse().unwrap(),
+            &handler,
+            settings,
+            &Receiving::default(),
+        );
+
+        let mut output = &pipe.output[..];
+        let mut frames = Vec::new();
+        while let ReadFrame::Frame(frame) = read_frame(&mut output, MAX_MAX_FRAME_SIZE).unwrap() {
+            frames.push(frame);
+        }
+        (frames, handler.0.into_inner())
+    }
+
+    fn error_code(frame: &Frame) -> u32 {
+        let at = if frame.kind == FrameType::GoAway {
+            4
+        } else {
+            0
+        };
+        u32_at(&frame.payload, at)
+    }
+
+    fn resets(frames: &[Frame]) -> Vec<(u32, u32)> {
+        frames
+            .iter()
+            .filter(|frame| frame.kind == FrameType::RstStream)
+            .map(|frame| (frame.stream_id, error_code(frame)))
+            .collect()
+    }
+
+    fn window_updates(frames: &[Frame], id: u32) -> Vec<usize> {
+        frames
+            .iter()
+            .filter(|frame| frame.kind == FrameType::WindowUpdate && frame.stream_id == id)
+            .map(|frame| u32_at(&frame.payload, 0) as usize)
+            .collect()
+    }
+
+    fn status(frames: &[Frame], id: u32) -> Option<String> {
+        let frame = frames
+            .iter()
+            .find(|frame| frame.kind == FrameType::Headers && frame.stream_id == id)?;
+        let headers = Decoder::new(HEADER_TABLE_SIZE)
+            .decode(&frame.payload, usize::MAX)
+            .unwrap()
+            .unwrap();
+        headers
+            .into_iter()
+            .find(|(name, _)| name == ":status")
+            .map(|(_, value)| value)
+    }
+
+    #[test]
+    fn buffered_body_reopens_the_window_when_handled() {
+        let mut client = Client::new();
+        client.post(1);
+        client.data(1, 100_000, true);
+        let (frames, handled) = serve_client(client);
+
+        assert_eq!(handled, 1);
+        assert_eq!(status(&frames, 1).as_deref(), Some("200"));
+        let data = frames
+            .iter()
+            .find(|frame| frame.kind == FrameType::Data && frame.stream_id == 1)
+            .unwrap();
+        assert_eq!(data.payload, b"100000");
+        // 처음에 연 연결 창을 빼면, 핸들러에 넘긴 본문만큼 한번에 돌려준다.
+        let updates = window_updates(&frames, 0);
+        assert_eq!(
+            updates,
+            [MAX_BUFFERED_BODY - DEFAULT_WINDOW_SIZE as usize, 100_000]
+        );
+    }
+
+    #[test]
+    fn oversized_body_is_answered_and_reset() {
+        let mut client = Client::new();
+        client.post(1);
+        client.data(1, MAX_REQUEST_BODY + MIB, true);
+        let (frames, handled) = serve_client(client);
+
+        assert_eq!(handled, 0);
+        assert_eq!(status(&frames, 1).as_deref(), Some("413"));
+        let resets = resets(&frames);
+        assert_eq!(resets[0], (1, ErrorCode::NoError as u32));
+        // 413 이후에 도착한 프레임은 닫힌 스트림으로 처리한다.
+        assert!(resets[1..]
+            .iter()
+            .all(|&reset| reset == (1, ErrorCode::StreamClosed as u32)));
+        // 스트림 창은 한도까지만 열어주고, 연결 창은 버린 본문까지 모두 돌려준다.
+        let stream_window: usize = window_updates(&frames, 1).iter().sum();
+        assert!(stream_window + DEFAULT_WINDOW_SIZE as usize <= MAX_REQUEST_BODY + MIB);
+        assert!(stream_window >= MAX_REQUEST_BODY - DEFAULT_WINDOW_SIZE as usize);
+        let released: usize = window_updates(&frames, 0)[1..].iter().sum();
+        assert_eq!(released, MAX_REQUEST_BODY + MIB);
+    }
+
+    #[test]
+    fn full_connection_window_refuses_the_largest_stream() {
+        let mut client = Client::new();
+        for id in [1, 3, 5] {
+            client.post(id);
+        }
+        client.data(1, 15 * MIB, false);
+        client.data(3, 14 * MIB, false);
+        client.data(5, MAX_BUFFERED_BODY - 29 * MIB, false);
+        // 자리가 생겼으니 다른 스트림은 끝까지 받는다.
+        client.data(3, MIB, true);
+        let (frames, handled) = serve_client(client);
+
+        assert_eq!(resets(&frames), [(1, ErrorCode::RefusedStream as u32)]);
+        assert_eq!(handled, 1);
+        assert_eq!(status(&frames, 3).as_deref(), Some("200"));
+        assert_eq!(window_updates(&frames, 0)[1..], [15 * MIB, 15 * MIB]);
+    }
+
+    #[test]
+    fn data_beyond_the_connection_window_is_an_error() {
+        let mut client = Client::new();
+        client.post(1);
+        client.post(3);
+        client.data(1, MAX_REQUEST_BODY, false);
+        client.data(3, MAX_BUFFERED_BODY - MAX_REQUEST_BODY - 100, false);
+        client.data(3, 200, false);
+        let (frames, handled) = serve_client(client);
+
+        assert_eq!(handled, 0);
+        let go_away = frames.last().unwrap();
+        assert_eq!(go_away.kind, FrameType::GoAway);
+        assert_eq!(error_code(go_away), ErrorCode::FlowControlError as u32);
+    }
+}
@@ -0,0 +1,209 @@
+use super::metrics::Metrics;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+
+// 동시에 열어 둘 연결의 개수를 서버 전체와 클라이언트 IP마다 제한한다.
+// 작업자 스레드를 기다리는 연결도 소켓과 메모리를 쓰기 때문에 accept() 하자마자 센다.
+pub(crate) struct ConnectionLimiter {
+    max: Option<usize>,
+    max_per_ip: Option<usize>,
+    state: Mutex<State>,
+    metrics: Metrics,
+}
+
+#[derive(Default)]
+struct State {
+    active: usize,
+    // 연결이 하나라도 열려 있는 IP만 들어 있다.
+    per_ip: HashMap<IpAddr, usize>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Rejection {
+    TooManyConnections,
+    TooManyFromClient,
+}
+
+impl Display for Rejection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::TooManyConnections => write!(f, "too many connections"),
+            Self::TooManyFromClient => write!(f, "too many connections from this client"),
+        }
+    }
+}
+
+impl ConnectionLimiter {
+    pub(crate) fn new(max: Option<usize>, max_per_ip: Option<usize>, metrics: Metrics) -> Self {
+        Self {
+            max,
+            max_per_ip,
+            state: Mutex::new(State::default()),
+            metrics,
+        }
+    }
+
+    // 제한 안이면 연결을 세고 허가증을 돌려준다. 허가증이 drop 되면 연결이 닫힌 것으로 센다.
+    pub(crate) fn acquire(self: &Arc<Self>, ip: IpAddr) -> Result<Permit, Rejection> {
+        // 듀얼 스택 소켓은 IPv4 클라이언트를 ::ffff:1.2.3.4로 보여준다.
+        let ip = ip.to_canonical();
+        let mut state = self.state.lock().unwrap();
+        let rejection = if self.max.is_some_and(|max| state.active >= max) {
+            Some(Rejection::TooManyConnections)
+        } else if self
+            .max_per_ip
+            .is_some_and(|max| state.per_ip.get(&ip).is_some_and(|count| *count >= max))
+        {
+            Some(Rejection::TooManyFromClient)
+        } else {
+            None
+        };
+        if let Some(rejection) = rejection {
+            self.metrics
+                .rejected(rejection == Rejection::TooManyFromClient);
+            return Err(rejection);
+        }
+
+        state.active += 1;
+        if self.max_per_ip.is_some() {
+            *state.per_ip.entry(ip).or_insert(0) += 1;
+        }
+        self.metrics.connection_opened();
+        Ok(Permit {
+            limiter: Arc::clone(self),
+            ip,
+        })
+    }
+
+    fn release(&self, ip: IpAddr) {
+        let mut state = self.state.lock().unwrap();
+        state.active -= 1;
+        if let Some(count) = state.per_ip.get_mut(&ip) {
+            *count -= 1;
+            if *count == 0 {
+                state.per_ip.remove(&ip);
+            }
+        }
+        self.metrics.connection_closed();
+    }
+}
+
+pub(crate) struct Permit {
+    limiter: Arc<ConnectionLimiter>,
+    ip: IpAddr,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.limiter.release(self.ip);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::http::{Request, Response, StatusCode};
+    use crate::server::metrics::MetricsSnapshot;
+    use crate::server::testing::TestServer;
+    use crate::server::{Handler, Server};
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    struct Hello;
+
+    impl Handler for Hello {
+        fn handle_request(&self, _: &Request) -> Response {
+            Response::new(StatusCode::Ok, Some("hello".to_string()))
+        }
+    }
+
+    fn limited_server(max: Option<usize>, max_per_ip: Option<usize>) -> TestServer {
+        let server = Server::new("127.0.0.1:0".to_string())
+            .max_connections(max)
+            .max_connections_per_ip(max_per_ip);
+        TestServer::start(server, Hello)
+    }
+
+    // 연결은 accept 루프가 받아야 세어지므로 카운터가 바뀔 때까지 기다린다.
+    fn wait_for(server: &TestServer, done: impl Fn(&MetricsSnapshot) -> bool) -> MetricsSnapshot {
+        let started = Instant::now();
+        loop {
+            let snapshot = server.metrics().snapshot();
+            if done(&snapshot) || started.elapsed() > Duration::from_secs(5) {
+                return snapshot;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn get(server: &TestServer) -> String {
+        let mut stream = TcpStream::connect(server.addr()).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let _ = stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response);
+        response
+    }
+
+    #[test]
+    fn rejects_connections_over_the_per_ip_limit() {
+        let server = limited_server(None, Some(1));
+        let idle = TcpStream::connect(server.addr()).unwrap();
+        wait_for(&server, |s| s.active == 1);
+
+        let response = get(&server);
+        assert!(response.starts_with("HTTP/1.1 503"), "{}", response);
+        assert!(response.contains("Retry-After: 1\r\n"), "{}", response);
+        assert!(response.contains("Connection: close\r\n"), "{}", response);
+        let snapshot = server.metrics().snapshot();
+        assert_eq!(snapshot.rejected_per_ip, 1);
+        assert_eq!(snapshot.rejected, 0);
+
+        // 열려 있던 연결이 닫히면 다시 받는다.
+        drop(idle);
+        wait_for(&server, |s| s.active == 0);
+        assert!(get(&server).starts_with("HTTP/1.1 200"));
+    }
+
+    #[test]
+    fn rejects_connections_over_the_global_limit() {
+        let server = limited_server(Some(1), Some(2));
+        let idle = TcpStream::connect(server.addr()).unwrap();
+        wait_for(&server, |s| s.active == 1);
+
+        let response = get(&server);
+        assert!(response.starts_with("HTTP/1.1 503"), "{}", response);
+        let snapshot = server.metrics().snapshot();
+        assert_eq!(snapshot.rejected, 1);
+        assert_eq!(snapshot.rejected_per_ip, 0);
+
+        drop(idle);
+        wait_for(&server, |s| s.active == 0);
+        assert!(get(&server).starts_with("HTTP/1.1 200"));
+    }
+
+    #[test]
+    fn counts_accepted_and_active_connections() {
+        let server = limited_server(None, None);
+        let first = TcpStream::connect(server.addr()).unwrap();
+        let second = TcpStream::connect(server.addr()).unwrap();
+        let snapshot = wait_for(&server, |s| s.active == 2);
+        assert_eq!((snapshot.accepted, snapshot.active), (2, 2));
+
+        drop(first);
+        drop(second);
+        let snapshot = wait_for(&server, |s| s.active == 0);
+        assert_eq!(
+            snapshot,
+            MetricsSnapshot {
+                accepted: 2,
+                ..MetricsSnapshot::default()
+            }
+        );
+    }
+}
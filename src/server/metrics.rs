@@ -0,0 +1,81 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+// 서버가 받고, 거절하고, 끊은 연결의 개수. Clone 해서 다른 스레드에서 읽을 수 있다.
+#[derive(Clone, Default)]
+pub struct Metrics {
+    inner: Arc<Counters>,
+}
+
+#[derive(Default)]
+struct Counters {
+    accepted: AtomicU64,
+    active: AtomicU64,
+    rejected: AtomicU64,
+    rejected_per_ip: AtomicU64,
+    timed_out: AtomicU64,
+}
+
+// 어느 한 순간의 값들
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    pub accepted: u64,
+    // 처리 중이거나 작업자 스레드를 기다리는 연결
+    pub active: u64,
+    // 서버 전체의 동시 연결 수 제한에 걸려서 거절한 연결
+    pub rejected: u64,
+    // 클라이언트 하나의 동시 연결 수 제한에 걸려서 거절한 연결
+    pub rejected_per_ip: u64,
+    // 타임아웃, 요청 머리 기한, 최소 송수신 속도에 걸려서 끊은 연결
+    pub timed_out: u64,
+}
+
+impl Metrics {
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let inner = &self.inner;
+        MetricsSnapshot {
+            accepted: inner.accepted.load(Ordering::Relaxed),
+            active: inner.active.load(Ordering::Relaxed),
+            rejected: inner.rejected.load(Ordering::Relaxed),
+            rejected_per_ip: inner.rejected_per_ip.load(Ordering::Relaxed),
+            timed_out: inner.timed_out.load(Ordering::Relaxed),
+        }
+    }
+
+    pub(crate) fn connection_opened(&self) {
+        self.inner.accepted.fetch_add(1, Ordering::Relaxed);
+        self.inner.active.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn connection_closed(&self) {
+        self.inner.active.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn rejected(&self, per_ip: bool) {
+        let counter = if per_ip {
+            &self.inner.rejected_per_ip
+        } else {
+            &self.inner.rejected
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn timed_out(&self) {
+        self.inner.timed_out.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Display for MetricsSnapshot {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "{} accepted, {} active, {} rejected ({} per client), {} timed out",
+            self.accepted,
+            self.active,
+            self.rejected + self.rejected_per_ip,
+            self.rejected_per_ip,
+            self.timed_out
+        )
+    }
+}
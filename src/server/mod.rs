@@ -0,0 +1,802 @@
+// 우리가 트레이트로부터 함수를 호출하기 전에 트레이트를 우리 범위로 가져와야 한다.
+
+use std::convert::TryFrom;
+
+// 여기서 crate 키워드를 사용한다는 것은 전체 크레이트의 루트를 의미한다.
+use crate::http::{Body, Method, ParseError, Request, Response, StatusCode};
+use crate::websocket::{self, WebSocketHandler};
+use limits::ConnectionLimiter;
+use rewind::Rewind;
+use rustls::{ServerConfig, ServerConnection, StreamOwned};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{ErrorKind, Read, Result as IoResult, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use timed::{HeadDeadline, Receiving, TimedStream};
+
+pub use error::ServerError;
+pub use metrics::Metrics;
+pub use pool::ThreadPool;
+pub use shutdown::{shutdown_on_signals, ShutdownHandle};
+pub use tls::TlsConfig;
+
+mod body;
+mod error;
+mod http2;
+mod limits;
+mod metrics;
+mod pool;
+mod rewind;
+mod shutdown;
+#[cfg(test)]
+pub(crate) mod testing;
+mod timed;
+mod tls;
+
+// 종료 요청 후 처리 중인 요청이 끝나길 기다리는 기본 시간
+const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(30);
+// 요청 줄과 헤더를 합친 최대 크기의 기본값
+const DEFAULT_MAX_REQUEST_BYTES: usize = 8 * 1024;
+// Read, Write 트레이트는 Rust에서 IO 연산의 중심에 있다.
+// 여러 작업자 스레드가 하나의 핸들러를 함께 사용하기 때문에 &mut self 대신 &self를 받고,
+// 스레드 간에 공유할 수 있도록 Send + Sync 여야 한다. 바뀌는 상태가 필요하면 Mutex 등으로 감싸면 된다.
+pub trait Handler: Send + Sync {
+    fn handle_request(&self, request: &Request) -> Response;
+    // Handler 구현자는 이것을 구현하길 원하지 않을 수 있다. 왜냐하면 상당히 제네릭 하기 때문이다.
+    fn handle_bad_request(&self, e: &ParseError) -> Response {
+        warn!("Failed to parse request: {}", e);
+        Response::new(StatusCode::BadRequest, None)
+    }
+
+    // WebSocket 경로로 온 요청은 handle_request()를 거치지 않고 연결째 WebSocketHandler에게 넘어간다.
+    // 그 전에 불러서 Err로 응답을 돌려주면 업그레이드하지 않고 그 응답을 보낸다. Ok로 돌려준 요청(인증한 사용자 등)을
+    // WebSocketHandler가 받는다. 다른 핸들러를 감싸는 미들웨어는 자기 검사를 한 뒤 꼭 안쪽으로 넘겨야 한다.
+    // Auth, RateLimit은 요청과 똑같이 검사하고, Cors와 Compression은 응답을 고칠 뿐이라 그냥 넘긴다.
+    fn accept_upgrade<'buf>(&self, request: &Request<'buf>) -> Result<Request<'buf>, Response> {
+        Ok(request.clone())
+    }
+}
+
+// Box<dyn Handler>도 Handler로 쓸 수 있게 한다. 설정에 따라 서로 다른 핸들러 타입을 골라야 할 때 필요하다.
+impl<H: Handler + ?Sized> Handler for Box<H> {
+    fn handle_request(&self, request: &Request) -> Response {
+        (**self).handle_request(request)
+    }
+
+    fn handle_bad_request(&self, e: &ParseError) -> Response {
+        (**self).handle_bad_request(e)
+    }
+
+    fn accept_upgrade<'buf>(&self, request: &Request<'buf>) -> Result<Request<'buf>, Response> {
+        (**self).accept_upgrade(request)
+    }
+}
+
+// 경로마다 등록된 WebSocket 핸들러
+type WebSockets = HashMap<String, Arc<dyn WebSocketHandler>>;
+
+pub struct Server {
+    endpoints: Vec<Endpoint>,
+    // bind()를 먼저 호출했다면 여기에 리스너들이 들어 있고, run()은 이걸 그대로 사용한다.
+    listeners: Vec<Listener>,
+    threads: usize,
+    settings: ConnectionSettings,
+    // 동시에 열어 둘 연결 수. None이면 제한하지 않는다.
+    max_connections: Option<usize>,
+    max_connections_per_ip: Option<usize>,
+    shutdown: ShutdownHandle,
+    metrics: Metrics,
+    websockets: WebSockets,
+}
+
+// 열어야 할 주소 하나. tls가 있으면 이 주소로 들어온 연결은 HTTPS로 처리한다.
+// handler가 있으면 run()에 넘긴 핸들러 대신 이 핸들러가 요청을 처리한다.
+struct Endpoint {
+    addr: String,
+    tls: Option<TlsConfig>,
+    handler: Option<Arc<dyn Handler>>,
+}
+
+struct Listener {
+    socket: TcpListener,
+    tls: Option<Arc<ServerConfig>>,
+    handler: Option<Arc<dyn Handler>>,
+}
+
+impl Endpoint {
+    fn plain(addr: String) -> Self {
+        Self {
+            addr,
+            tls: None,
+            handler: None,
+        }
+    }
+}
+
+// 연결 하나를 처리할 때 적용되는 제한들. 작업자 스레드마다 복사해서 넘긴다.
+#[derive(Copy, Clone, Debug)]
+struct ConnectionSettings {
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    // 연결을 받은 뒤 요청 머리(TLS 핸드셰이크 포함)를 다 받을 때까지 기다리는 시간
+    header_timeout: Option<Duration>,
+    // 응답을 이보다 느리게(초당 바이트) 읽어가는 클라이언트는 끊는다.
+    min_send_rate: Option<u64>,
+    // 요청 본문을 이보다 느리게 보내는 클라이언트는 끊는다.
+    min_recv_rate: Option<u64>,
+    max_request_bytes: usize,
+    // TLS에서는 ALPN으로 h2를 협상하고, 평문에서는 클라이언트가 HTTP/2 프리페이스로 시작하면(prior knowledge) HTTP/2로 처리한다.
+    http2: bool,
+    // TLS 연결인지. 연결마다 정해지므로 서버 설정에서는 항상 false다.
+    secure: bool,
+}
+
+// 우리가 항상 array에 원소가 몇 개나 있을지 항상 알아야 한다면 그것은 힘든일이다.
+// // 그럴때 이렇게 참조로 하게 되면
+// // fn arr(a: [u8;5]){} 이런식으로 크기를 안 정해줘도 된다.
+// //  그리고 아래와 같은 참조를 슬라이스라고 부른다. 문자열 슬라이스는 u8 슬라이스에 비해 추가적인 기능이 있는 래퍼에 불과함
+// fn arr(a: &[u8]) {}
+
+impl Server {
+    pub fn new(addr: String) -> Self {
+        Self {
+            endpoints: vec![Endpoint::plain(addr)],
+            listeners: Vec::new(),
+            threads: 1,
+            settings: ConnectionSettings {
+                read_timeout: None,
+                write_timeout: None,
+                header_timeout: None,
+                min_send_rate: None,
+                min_recv_rate: None,
+                max_request_bytes: DEFAULT_MAX_REQUEST_BYTES,
+                http2: true,
+                secure: false,
+            },
+            max_connections: None,
+            max_connections_per_ip: None,
+            shutdown: ShutdownHandle::new(DEFAULT_GRACE_PERIOD),
+            metrics: Metrics::default(),
+            websockets: HashMap::new(),
+        }
+    }
+
+    // 첫번째 리스너부터 HTTPS로 여는 서버
+    pub fn new_tls(addr: String, tls: TlsConfig) -> Self {
+        let mut server = Self::new(addr);
+        server.endpoints[0].tls = Some(tls);
+        server
+    }
+
+    // 첫번째 리스너가 자기만의 핸들러를 쓰는 서버
+    pub fn new_with(addr: String, handler: impl Handler + 'static) -> Self {
+        let mut server = Self::new(addr);
+        server.endpoints[0].handler = Some(Arc::new(handler));
+        server
+    }
+
+    // 다른 주소에서도 연결을 받는다. 모든 리스너는 같은 핸들러와 작업자 스레드를 함께 쓴다.
+    pub fn listen(mut self, addr: String) -> Self {
+        self.endpoints.push(Endpoint::plain(addr));
+        self
+    }
+
+    // run()에 넘긴 핸들러가 아니라 따로 준 핸들러로 요청을 처리하는 리스너를 추가한다.
+    // HTTPS로 리다이렉트만 하는 80번 포트처럼 사이트와 다른 일을 하는 리스너에 쓴다.
+    pub fn listen_with(mut self, addr: String, handler: impl Handler + 'static) -> Self {
+        self.endpoints.push(Endpoint {
+            handler: Some(Arc::new(handler)),
+            ..Endpoint::plain(addr)
+        });
+        self
+    }
+
+    // 이 주소로 들어온 연결은 TLS로 암호화한다. listen()과 함께 써서 HTTP와 HTTPS를 동시에 서비스할 수 있다.
+    pub fn listen_tls(mut self, addr: String, tls: TlsConfig) -> Self {
+        self.endpoints.push(Endpoint {
+            tls: Some(tls),
+            ..Endpoint::plain(addr)
+        });
+        self
+    }
+
+    // 클라이언트가 이 시간 동안 아무것도 보내지 않으면 연결을 끊는다. None이면 무한정 기다린다.
+    pub fn read_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.settings.read_timeout = timeout;
+        self
+    }
+
+    pub fn write_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.settings.write_timeout = timeout;
+        self
+    }
+
+    // 한 바이트씩 아주 천천히 보내서 연결을 붙잡아 두는 클라이언트를 막는다.
+    // 읽기 타임아웃과 달리 연결을 받은 때부터 요청 머리를 다 받을 때까지의 전체 시간이다.
+    pub fn header_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.settings.header_timeout = timeout;
+        self
+    }
+
+    // 응답을 보내는 동안 클라이언트가 초당 이만큼도 읽어가지 않으면 연결을 끊는다.
+    pub fn min_send_rate(mut self, bytes_per_second: Option<u64>) -> Self {
+        self.settings.min_send_rate = bytes_per_second;
+        self
+    }
+
+    // 요청 본문을 받는 동안 클라이언트가 초당 이만큼도 보내지 않으면 연결을 끊는다.
+    // 요청 머리를 다 받은 뒤에는 header_timeout이 적용되지 않으므로 본문을 천천히 보내는 클라이언트는 이걸로 막는다.
+    pub fn min_recv_rate(mut self, bytes_per_second: Option<u64>) -> Self {
+        self.settings.min_recv_rate = bytes_per_second;
+        self
+    }
+
+    // 동시에 열어 둘 연결 수. 넘으면 새 연결에 503 Service Unavailable로 응답하고 바로 닫는다.
+    pub fn max_connections(mut self, max: Option<usize>) -> Self {
+        self.max_connections = max;
+        self
+    }
+
+    // 클라이언트 IP 하나가 동시에 열어 둘 수 있는 연결 수
+    pub fn max_connections_per_ip(mut self, max: Option<usize>) -> Self {
+        self.max_connections_per_ip = max;
+        self
+    }
+
+    // 요청 줄과 헤더가 이 크기를 넘으면 431 Request Header Fields Too Large로 응답한다.
+    pub fn max_request_bytes(mut self, max_request_bytes: usize) -> Self {
+        self.settings.max_request_bytes = max_request_bytes;
+        self
+    }
+
+    // HTTP/2를 쓸지 정한다. 기본값은 true다. 끄면 ALPN에서 h2를 빼고 HTTP/1.1만 쓴다.
+    pub fn http2(mut self, enabled: bool) -> Self {
+        self.settings.http2 = enabled;
+        self
+    }
+
+    // 이 경로로 들어온 업그레이드 요청은 run()에 넘긴 핸들러 대신 WebSocket 핸들러가 처리한다.
+    // 업그레이드하기 전에 run()에 넘긴 핸들러의 accept_upgrade()를 거치므로 인증과 요청 수 제한은 그대로 적용된다.
+    // listen_with()로 따로 핸들러를 준 리스너에는 적용되지 않고, HTTP/2 연결에서는 업그레이드할 수 없다.
+    pub fn websocket(mut self, path: &str, handler: impl WebSocketHandler + 'static) -> Self {
+        self.websockets.insert(path.to_string(), Arc::new(handler));
+        self
+    }
+
+    // 연결을 동시에 처리할 작업자 스레드 개수. 기본값은 1개로, 연결을 하나씩 순서대로 처리한다.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    // 종료 요청을 받은 뒤 처리 중인 요청을 얼마나 기다려 줄지 정한다. 이 시간이 지나면 연결을 강제로 끊는다.
+    pub fn grace_period(self, grace_period: Duration) -> Self {
+        self.shutdown.set_grace_period(grace_period);
+        self
+    }
+
+    // run()은 self를 가져가기 때문에 그 전에 핸들을 받아 두어야 다른 곳에서 서버를 멈출 수 있다.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    // 받고 거절한 연결의 개수를 읽을 수 있는 핸들. run() 전에 받아 두어야 한다.
+    pub fn metrics(&self) -> Metrics {
+        self.metrics.clone()
+    }
+
+    // 서버를 실행하기 전에 리스너를 먼저 연다. 포트를 0으로 주면 운영체제가 빈 포트를 골라주는데,
+    // 리턴되는 주소를 보면 실제로 어떤 포트가 열렸는지 알 수 있다.
+    // 리스너가 여러 개라면 첫번째 리스너의 주소를 리턴한다. 나머지는 local_addrs()로 알 수 있다.
+    pub fn bind(&mut self) -> Result<SocketAddr, ServerError> {
+        if self.endpoints.is_empty() {
+            return Err(ServerError::Config("no listen address given".to_string()));
+        }
+
+        if self.listeners.is_empty() {
+            for endpoint in &self.endpoints {
+                let tls = match &endpoint.tls {
+                    Some(tls) => Some(tls.server_config(self.settings.http2)?),
+                    None => None,
+                };
+                let socket = bind_listener(&endpoint.addr)?;
+                let local_addr = socket.local_addr().map_err(ServerError::Accept)?;
+                self.shutdown.add_local_addr(local_addr);
+                self.listeners.push(Listener {
+                    socket,
+                    tls,
+                    handler: endpoint.handler.clone(),
+                });
+            }
+        }
+
+        self.listeners[0]
+            .socket
+            .local_addr()
+            .map_err(ServerError::Accept)
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.listeners.first()?.socket.local_addr().ok()
+    }
+
+    pub fn local_addrs(&self) -> Vec<SocketAddr> {
+        self.listeners
+            .iter()
+            .filter_map(|listener| listener.socket.local_addr().ok())
+            .collect()
+    }
+
+    pub fn run(mut self, handler: impl Handler + 'static) -> Result<(), ServerError> {
+        // ? 연산자는 Result가 Err라면 그 오류를 그대로 호출자에게 리턴한다.
+        // 예전처럼 unwrap()으로 프로그램을 종료하지 않고, 어떻게 처리할지는 호출자가 정하게 한다.
+        self.bind()?;
+        for listener in &self.listeners {
+            if let Ok(local_addr) = listener.socket.local_addr() {
+                let scheme = if listener.tls.is_some() {
+                    "https"
+                } else {
+                    "http"
+                };
+                info!(
+                    "Listening on {}://{} with {} worker(s)",
+                    scheme, local_addr, self.threads
+                );
+            }
+        }
+        let listeners = std::mem::take(&mut self.listeners);
+        // 다른 언어처럼 break를 써서 반복문에서 나갈수 있고, continue를 써서 반복문의 다음 반복으로 넘어갈수 있다.
+        // 안쪽 loop의 본문에서 바깥쪽 loop를 break 하려면 '레이블'을 이용해 loop에 주석을 달 수 있다.
+        //
+        // 'outer: loop {
+        //     loop{
+        //         break 'outer; 또는
+        //         continue 'outer;
+        //     }
+        // }
+        //
+
+        // 작업자 스레드들이 핸들러를 함께 쓰기 때문에 Arc로 감싸서 각 작업에 복제해 넘긴다.
+        let handler: Arc<dyn Handler> = Arc::new(handler);
+        let websockets = Arc::new(std::mem::take(&mut self.websockets));
+        let pool = ThreadPool::new(self.threads);
+        let limiter = Arc::new(ConnectionLimiter::new(
+            self.max_connections,
+            self.max_connections_per_ip,
+            self.metrics.clone(),
+        ));
+
+        // 리스너마다 accept() 루프를 별도의 스레드에서 돌린다. scope를 쓰면 모든 루프가 끝날 때까지
+        // 기다려주기 때문에 self나 pool 같은 지역 변수를 빌려서 쓸 수 있다.
+        let result = thread::scope(|scope| {
+            let loops: Vec<_> = listeners
+                .iter()
+                .map(|listener| {
+                    scope.spawn(|| {
+                        self.accept_loop(listener, &handler, &websockets, &pool, &limiter)
+                    })
+                })
+                .collect();
+
+            // 첫번째로 발생한 치명적인 오류를 리턴한다.
+            loops
+                .into_iter()
+                .map(|accept_loop| accept_loop.join().unwrap_or(Ok(())))
+                .fold(Ok(()), Result::and)
+        });
+
+        info!("Shutting down, waiting for in-flight requests");
+        if !self
+            .shutdown
+            .wait_for_connections(self.shutdown.grace_period())
+        {
+            warn!("Grace period elapsed, closing remaining connections");
+            self.shutdown.close_connections();
+        }
+        // 풀이 drop 되면서 작업자 스레드가 모두 끝나길 기다린다.
+        drop(pool);
+        info!("Connections : {}", self.metrics.snapshot());
+
+        result
+    }
+
+    fn accept_loop(
+        &self,
+        listener: &Listener,
+        handler: &Arc<dyn Handler>,
+        websockets: &Arc<WebSockets>,
+        pool: &ThreadPool,
+        limiter: &Arc<ConnectionLimiter>,
+    ) -> Result<(), ServerError> {
+        // 종료 요청이 들어오면 더 이상 새 연결을 받지 않고 루프를 빠져나온다.
+        while !self.shutdown.is_shutdown() {
+            // 이것은 복구 가능한 오류로써 연결 하나에 실패하면 다음 연결을 시도해야 한다.
+            // 아래 처럼 작성할 수 있지만, enum을 활용하는 좋은 예제는 아니다.
+            // let res = listener.accept();
+            // if res.is_err() {
+            //     continue;
+            // }
+
+            // let (stream, addr) = res.unwrap();
+            // 실행하려는 코드가 단일 구문이라면 중괄호를 쓰지 않고 바로 적을 수 있다.
+            match listener.socket.accept() {
+                // 종료 요청 뒤에 받은 연결도 버리지 않는다. shutdown()이 깨우려고 만든 연결은 아무것도 보내지 않고
+                // 닫히므로 응답 없이 끝나고, 그보다 먼저 줄을 서 있던 클라이언트는 응답을 받는다.
+                Ok((stream, remote_addr)) => {
+                    // 허가증도 가드처럼 작업자가 처리를 끝낼 때 drop 되어야 연결 수가 줄어든다.
+                    let permit = match limiter.acquire(remote_addr.ip()) {
+                        Ok(permit) => permit,
+                        Err(rejection) => {
+                            debug!("Rejected connection from {} : {}", remote_addr, rejection);
+                            reject(stream, listener.tls.is_some());
+                            continue;
+                        }
+                    };
+                    // 가드가 살아 있는 동안은 처리 중인 연결로 취급된다.
+                    // 가드를 작업 안으로 옮겨서 작업자가 처리를 끝낼 때 drop 되도록 한다.
+                    let guard = self.shutdown.track(&stream);
+                    let handler = Arc::clone(listener.handler.as_ref().unwrap_or(handler));
+                    // 자기만의 핸들러를 가진 리스너는 사이트와 다른 일을 하므로 WebSocket 경로도 쓰지 않는다.
+                    let websockets = match listener.handler {
+                        Some(_) => None,
+                        None => Some(Arc::clone(websockets)),
+                    };
+                    let settings = self.settings;
+                    let tls = listener.tls.clone();
+                    let metrics = self.metrics.clone();
+                    pool.execute(move || {
+                        let routes = Routes {
+                            handler: handler.as_ref(),
+                            websockets: websockets.as_deref(),
+                        };
+                        serve_connection(stream, remote_addr, tls, routes, settings, metrics);
+                        drop(guard);
+                        drop(permit);
+                    });
+                }
+                // _ => 이하 사례에 대해 신겨읏지 않는다면 여기에 _을 넣으면 만능 패턴 역할을 해서 대응해
+                // 수작업으로 매칭시 키지 않은 모든 요소를 잡아 줄 것이다.
+                Err(e) if is_transient(&e) => error!("ERR {}", e),
+                // 그 외의 오류는 다시 시도해도 계속 실패할 가능성이 높기 때문에 서버 전체를 멈춘다.
+                Err(e) => {
+                    self.shutdown.shutdown();
+                    return Err(ServerError::Accept(e));
+                }
+            }
+
+            // enum 뿐만 아니라 일반 switch 구문으로도 활용할 수 있다.
+            // match "abcd" {
+            //     "abd" => {},
+            //     "a" | "b" => {},
+            //     _ => {}
+            // }
+        }
+
+        Ok(())
+    }
+}
+
+// 제한에 걸린 연결은 작업자 스레드에 넘기지 않고 바로 닫는다. accept 루프가 막히지 않도록 기다리지 않고 한번만 써 본다.
+// TLS 리스너에서는 핸드셰이크 전이라 응답을 보낼 수 없으므로 그냥 닫는다.
+fn reject(mut stream: TcpStream, tls: bool) {
+    if tls || stream.set_nonblocking(true).is_err() {
+        return;
+    }
+    let response = Response::new(StatusCode::ServiceUnavailable, None)
+        .with_header("Retry-After", "1")
+        .with_header("Connection", "close");
+    let _ = response.send(&mut stream);
+}
+
+fn bind_listener(addr: &str) -> Result<TcpListener, ServerError> {
+    // 주소를 해석할 수 없다면 설정 문제이고, 해석은 되지만 열 수 없다면 바인딩 문제다.
+    let addrs: Vec<SocketAddr> = addr
+        .to_socket_addrs()
+        .map_err(|e| ServerError::Config(format!("invalid address {}: {}", addr, e)))?
+        .collect();
+    if addrs.is_empty() {
+        return Err(ServerError::Config(format!(
+            "address {} did not resolve to anything",
+            addr
+        )));
+    }
+
+    TcpListener::bind(&addrs[..]).map_err(|source| ServerError::Bind {
+        addr: addr.to_string(),
+        source,
+    })
+}
+
+// 클라이언트가 연결 도중 끊었거나, 파일 디스크립터가 잠시 부족한 것처럼 다음 연결에서는 괜찮을 수 있는 오류들
+fn is_transient(e: &std::io::Error) -> bool {
+    match e.kind() {
+        ErrorKind::ConnectionAborted
+        | ErrorKind::ConnectionReset
+        | ErrorKind::Interrupted
+        | ErrorKind::WouldBlock
+        | ErrorKind::TimedOut => true,
+        // EMFILE(24), ENFILE(23): 열린 파일이 너무 많다. 연결이 닫히면 다시 받을 수 있다.
+        _ => matches!(e.raw_os_error(), Some(23) | Some(24)),
+    }
+}
+
+// 연결 하나를 처리할 때 요청을 넘길 곳들
+#[derive(Copy, Clone)]
+struct Routes<'a> {
+    handler: &'a dyn Handler,
+    websockets: Option<&'a WebSockets>,
+}
+
+fn serve_connection(
+    stream: TcpStream,
+    remote_addr: SocketAddr,
+    tls: Option<Arc<ServerConfig>>,
+    routes: Routes,
+    settings: ConnectionSettings,
+    metrics: Metrics,
+) {
+    // 타임아웃을 걸어두지 않으면 아무것도 보내지 않는 클라이언트 하나가 작업자 스레드를 영원히 붙잡게 된다.
+    // TLS 스트림으로 감싸기 전에 소켓을 감싸므로 핸드셰이크에도 같은 타임아웃과 기한이 적용된다.
+    if let Err(e) = stream.set_write_timeout(settings.write_timeout) {
+        error!("Failed to set timeouts : {}", e);
+        return;
+    }
+    let deadline = HeadDeadline::new(settings.header_timeout);
+    let receiving = Receiving::default();
+    let mut stream = TimedStream::new(
+        stream,
+        settings.read_timeout,
+        deadline.clone(),
+        receiving.clone(),
+        settings.min_send_rate,
+        settings.min_recv_rate,
+        metrics,
+    );
+    let timers = (&deadline, &receiving);
+
+    let config = match tls {
+        Some(config) => config,
+        None => return handle_connection(&mut stream, remote_addr, timers, routes, settings),
+    };
+
+    let connection = match ServerConnection::new(config) {
+        Ok(connection) => connection,
+        Err(e) => {
+            error!("Failed to start TLS session : {}", e);
+            return;
+        }
+    };
+    let mut stream = StreamOwned::new(connection, stream);
+    let settings = ConnectionSettings {
+        secure: true,
+        ..settings
+    };
+
+    // 어떤 프로토콜을 쓸지는 ALPN으로 정해지므로 요청을 읽기 전에 핸드셰이크를 끝낸다.
+    while stream.conn.is_handshaking() {
+        if let Err(e) = stream.conn.complete_io(&mut stream.sock) {
+            debug!("TLS handshake failed : {}", e);
+            return;
+        }
+    }
+    if stream.conn.alpn_protocol() == Some(b"h2") {
+        // HTTP/2 연결은 요청 사이에 쉬는 동안에도 열려 있으므로 이후로는 읽기 타임아웃만 적용한다.
+        deadline.finish();
+        http2::serve(
+            &mut stream,
+            remote_addr,
+            routes.handler,
+            settings,
+            &receiving,
+        );
+    } else {
+        // TLS 위에서는 ALPN으로만 HTTP/2를 고른다. 프리페이스로 시작하는 연결은 평문에서만 받는다.
+        let settings = ConnectionSettings {
+            http2: false,
+            ..settings
+        };
+        handle_connection(&mut stream, remote_addr, timers, routes, settings);
+    }
+    // 응답을 다 보냈다는 걸 알려야 클라이언트가 연결이 잘린 것과 구분할 수 있다.
+    stream.conn.send_close_notify();
+    if let Err(e) = stream.flush() {
+        debug!("Failed to close TLS session : {}", e);
+    }
+}
+
+// 평문 TCP 연결과 TLS 연결 모두 Read + Write 이므로 같은 코드로 요청을 처리한다.
+fn handle_connection<S: Read + Write>(
+    stream: &mut S,
+    remote_addr: SocketAddr,
+    (deadline, receiving): (&HeadDeadline, &Receiving),
+    routes: Routes,
+    settings: ConnectionSettings,
+) {
+    // let a = [1, 2, 3, 4, 5, 5, 5, 5, 5]; // array의 구체적인 타입은 항상 그 안에 있는 값의 타입과
+    // 거기 포함된 값의 개수를 더한 것. 왜냐하면 컴파일일러가 array가 얼마나 큰지 알아야 하기 때문
+    // 만약 Ok에서 Result 사용하길 원치 안으면 _로 무시할 수 있다.
+    // enum 요소에 매칭할때 그것들의 값을 연결할 수 있다.
+
+    // traits에서 가능을 사용하려면 먼저 그걸 우리의 범위로 가져와야 한다.
+    // stream.read();
+
+    // arr(&a[1..3]);
+    let handler = routes.handler;
+    let head = read_request_head(stream, settings.max_request_bytes);
+    // 응답을 만들고 보내는 데 걸리는 시간이나 WebSocket 연결에는 기한을 두지 않는다.
+    deadline.finish();
+    let (buffer, rest) = match head {
+        // 연결을 받자마자 아무것도 보내지 않고 닫은 경우(shutdown이 깨우려고 만든 연결 등)는 응답할 필요가 없다.
+        Ok(Head::Closed) => return,
+        Ok(Head::TooLarge) => {
+            let response = Response::new(StatusCode::RequestHeaderFieldsTooLarge, None)
+                .with_header("Connection", "close");
+            if let Err(e) = response.send(stream) {
+                error!("Failed to send response : {}", e)
+            }
+            return;
+        }
+        Ok(Head::Complete(buffer, rest)) => {
+            // HTTP/2 프리페이스("PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n")도 빈 줄이 있어서 요청 머리처럼 읽힌다.
+            if settings.http2 && buffer.starts_with(b"PRI * HTTP/2.0\r\n") {
+                let prefix = [buffer, rest].concat();
+                http2::serve(
+                    &mut Rewind::new(prefix, stream),
+                    remote_addr,
+                    handler,
+                    settings,
+                    receiving,
+                );
+                return;
+            }
+            (buffer, rest)
+        }
+        // 느린 클라이언트를 끊은 것은 서버의 문제가 아니므로 오류로 남기지 않는다.
+        Err(e) if e.kind() == ErrorKind::TimedOut => {
+            debug!("Closing connection from {} : {}", remote_addr, e);
+            return;
+        }
+        Err(e) => {
+            error!("Failed to read from connection : {}", e);
+            return;
+        }
+    };
+
+    // 우리는 버퍼를 실제 텍스트로 변환해서 그걸 화면에 프린트하고 필요하다면 디버깅을 해야 한다.
+    // from_utf8() 이라는 함수는 바이트가 담긴 버퍼를 파라미터로 예상하고 그 바이트는 유효한 utf-8이어야 한다.
+    // 이것은 Result를 반환하는데 왜냐하면 여기에 유효하지 않은 utf-8 바이트가 포함되어 있을 수 있기 때문이다.
+    // from_utf8_lossy는 유효하지 않은 바이트까지 포함해서 변환시킨다.
+    debug!("Receiced a request: {}", String::from_utf8_lossy(&buffer));
+    // Request::try_from(&buffer as &[u8]); 아래와 동일하다. 우리는 벡터 전체가 담긴 슬라이스를 원하기에
+    // 하단과 상단 경계선을 생략하고 그냥 .. 라고만 적으면 전체가 담긴 바이트 슬라이스가 생성된다.
+    let mut head_request = false;
+    let response = match Request::try_from(&buffer[..]) {
+        Ok(mut request) => {
+            request.set_remote_addr(remote_addr);
+            request.set_secure(settings.secure);
+            // 라이프 타임을 주게 되면 위 try_from에서 이미 버퍼는 사용이 끝났기에
+            // 다음과 같이 수정하더라도 문제가 없기에 다음은 허용이 된다.
+            // buffer[1] = 0;
+            // 하지만 아래와 같이 다시 이를 할당한다면 버퍼사용이 끝나지 않았기에 위의 수정은 허용되지 않는다.
+            // let a = request;
+            // dbg!(request);
+            // 아래는 NotFound 이므로 반환할 것이 아무것도 없으므로 None을 반환한다.
+            // let response = Response::new(
+            //     StatusCode::Ok,
+            //     Some("<h1>It works!!!</h1>".to_string()),
+            // );
+            // let response = Response::new(StatusCode::NotFound, None);
+            // write!(stream, "HTTP/1.1 404 Not Found\r\n\r\n");
+            // response.send(&mut stream);
+            // 업그레이드한 뒤에는 연결을 WebSocket 핸들러가 끝까지 쓴다. 머리 뒤에 함께 읽힌 프레임도 넘겨준다.
+            if let Some(ws) = routes.websockets.and_then(|ws| ws.get(request.path())) {
+                // 업그레이드하기 전에 핸들러 체인의 인증과 요청 수 제한을 먼저 거친다.
+                match handler.accept_upgrade(&request) {
+                    Ok(request) => {
+                        websocket::upgrade(&mut Rewind::new(rest, stream), &request, ws.as_ref());
+                        return;
+                    }
+                    Err(response) => response,
+                }
+            } else {
+                head_request = matches!(request.method(), Method::HEAD);
+                match body::framing(&request) {
+                    Ok(framing) => {
+                        let expect_continue = request
+                            .header("Expect")
+                            .is_some_and(|value| value.eq_ignore_ascii_case("100-continue"));
+                        // 본문은 핸들러가 읽는 만큼만 연결에서 읽는다. 핸들러가 리턴하면 빌린 연결을 돌려받아서 응답을 보낸다.
+                        let reader = RefCell::new(body::reader(
+                            Rewind::new(rest, &mut *stream),
+                            framing,
+                            expect_continue,
+                        ));
+                        // 본문이 있으면 그걸 읽는 동안 최소 수신 속도를 지키게 한다.
+                        receiving.set(!matches!(framing, body::Framing::None));
+                        match framing {
+                            body::Framing::None => {}
+                            body::Framing::Length(length) => {
+                                request.set_body(Body::new(&reader, Some(length)))
+                            }
+                            body::Framing::Chunked => request.set_body(Body::new(&reader, None)),
+                        }
+                        handler.handle_request(&request)
+                    }
+                    Err(reason) => {
+                        debug!(
+                            "Invalid request body framing from {} : {}",
+                            remote_addr, reason
+                        );
+                        handler.handle_bad_request(&ParseError::InvalidHeader)
+                    }
+                }
+            }
+        }
+        Err(e) => {
+            // println!("Failed to parse a request {}", e);
+            handler.handle_bad_request(&e)
+        }
+    };
+
+    // 요청 하나에 응답하고 나면 연결을 닫는다. 클라이언트가 연결을 다시 쓰려다가 닫힌 연결에 요청을 보내지 않게 알린다.
+    let response = response.with_header("Connection", "close");
+    let sent = if head_request {
+        response.send_head(stream)
+    } else {
+        response.send(stream)
+    };
+    if let Err(e) = sent {
+        error!("Failed to send response : {}", e)
+    }
+
+    // let res: &Result<Request, _> = &buffer[..].try_into();
+    // 바이트 슬라이스에 대한 TryInto 구현체는 많을 수 있으니 그냥은 실행안되고
+}
+
+enum Head {
+    // 요청 머리와, 머리 뒤에 함께 읽힌 바이트들
+    Complete(Vec<u8>, Vec<u8>),
+    Closed,
+    TooLarge,
+}
+
+// read는 우리를 기다리고 있는 소켓에서 바이트르를 모두 읽어낸다. 그리고 그걸 buffer 안에 복사한다
+// 하지만 한번의 read로 요청 전체가 들어온다는 보장은 없기 때문에 헤더가 끝나는 빈 줄(\r\n\r\n)이 보일 때까지
+// 계속 읽어서 벡터에 이어 붙인다. 최대 크기를 넘으면 더 읽지 않는다.
+fn read_request_head(stream: &mut impl Read, max_bytes: usize) -> IoResult<Head> {
+    let mut head = Vec::new();
+    let mut chunk = [0; 1024]; // 요소들의 값이 똑같은 어레이를 생성하기 위한 구문
+                               // 러스트에서는 사용하기 전에 모든 메모리를 초기화 하는 방식으로 메모리 손상을 방지한다.
+
+    loop {
+        let len = stream.read(&mut chunk)?;
+        if len == 0 {
+            return Ok(if head.is_empty() {
+                Head::Closed
+            } else {
+                Head::Complete(head, Vec::new())
+            });
+        }
+
+        // 빈 줄이 이전 조각과 이번 조각에 걸쳐 있을 수 있으므로 3바이트 앞에서부터 찾는다.
+        let search_from = head.len().saturating_sub(3);
+        head.extend_from_slice(&chunk[..len]);
+        if let Some(i) = find_head_end(&head[search_from..]) {
+            let rest = head.split_off(search_from + i);
+            return Ok(Head::Complete(head, rest));
+        }
+        if head.len() > max_bytes {
+            return Ok(Head::TooLarge);
+        }
+    }
+}
+
+// 빈 줄이 끝나는 위치(본문이 시작하는 위치)를 리턴한다.
+fn find_head_end(buf: &[u8]) -> Option<usize> {
+    buf.windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .map(|i| i + 4)
+}
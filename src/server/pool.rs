@@ -0,0 +1,68 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+// 정해진 개수의 작업자 스레드가 채널에서 연결 처리 작업을 하나씩 꺼내서 실행한다.
+// 연결마다 스레드를 새로 만들면 요청이 몰릴 때 스레드가 끝없이 늘어날 수 있기 때문이다.
+pub struct ThreadPool {
+    workers: Vec<JoinHandle<()>>,
+    // drop 될 때 송신자를 먼저 닫아야 작업자들이 recv()에서 빠져나오기 때문에 Option으로 감싼다.
+    sender: Option<Sender<Job>>,
+}
+
+impl ThreadPool {
+    pub fn new(size: usize) -> Self {
+        let size = size.max(1);
+        let (sender, receiver) = mpsc::channel::<Job>();
+        // 수신자는 하나뿐이라 여러 작업자가 나눠 쓰려면 Arc<Mutex>로 감싸야 한다.
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        let workers = (0..size)
+            .map(|id| {
+                let receiver = Arc::clone(&receiver);
+                thread::Builder::new()
+                    .name(format!("worker-{}", id))
+                    .spawn(move || work(receiver))
+                    .expect("failed to spawn worker thread")
+            })
+            .collect();
+
+        Self {
+            workers,
+            sender: Some(sender),
+        }
+    }
+
+    pub fn execute(&self, job: impl FnOnce() + Send + 'static) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(Box::new(job));
+        }
+    }
+}
+
+fn work(receiver: Arc<Mutex<Receiver<Job>>>) {
+    loop {
+        // 락은 작업을 꺼내는 동안만 잡고, 작업을 실행하기 전에 풀어준다.
+        let job = match receiver.lock() {
+            Ok(receiver) => receiver.recv(),
+            Err(_) => return,
+        };
+        match job {
+            Ok(job) => job(),
+            // 송신자가 닫혔다면 더 이상 받을 작업이 없다.
+            Err(_) => return,
+        }
+    }
+}
+
+// 풀이 drop 되면 큐에 남은 작업까지 모두 처리한 뒤 작업자 스레드가 끝나길 기다린다.
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
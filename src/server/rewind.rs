@@ -0,0 +1,41 @@
+use std::io::{Read, Result as IoResult, Write};
+
+// 요청 머리를 읽다가 함께 읽혀버린 바이트를 스트림 앞에 다시 붙여준다.
+// 읽을 때는 남은 바이트를 먼저 돌려주고, 다 읽으면 원래 스트림에서 읽는다. 쓰기는 그대로 넘긴다.
+pub(crate) struct Rewind<S> {
+    prefix: Vec<u8>,
+    pos: usize,
+    inner: S,
+}
+
+impl<S> Rewind<S> {
+    pub(crate) fn new(prefix: Vec<u8>, inner: S) -> Self {
+        Self {
+            prefix,
+            pos: 0,
+            inner,
+        }
+    }
+}
+
+impl<S: Read> Read for Rewind<S> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        if self.pos < self.prefix.len() {
+            let len = buf.len().min(self.prefix.len() - self.pos);
+            buf[..len].copy_from_slice(&self.prefix[self.pos..self.pos + len]);
+            self.pos += len;
+            return Ok(len);
+        }
+        self.inner.read(buf)
+    }
+}
+
+impl<S: Write> Write for Rewind<S> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.flush()
+    }
+}
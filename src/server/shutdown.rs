@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+use std::io::Result as IoResult;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// 서버를 멈추게 하는 핸들이다. Clone 해서 시그널 처리 스레드나 테스트 코드로 넘길 수 있다.
+// 내부 상태는 Arc로 공유되기 때문에 어느 복제본에서 shutdown()을 호출해도 같은 서버가 멈춘다.
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    requested: AtomicBool,
+    grace_period: Mutex<Duration>,
+    // accept()에서 막혀 있는 루프를 깨우려면 리스너들이 실제로 바인딩된 주소를 알아야 한다.
+    local_addrs: Mutex<Vec<SocketAddr>>,
+    // 처리 중인 연결들. 유예 시간이 지나면 여기 있는 스트림을 강제로 닫는다.
+    connections: Mutex<HashMap<u64, TcpStream>>,
+    drained: Condvar,
+    next_id: AtomicU64,
+}
+
+impl ShutdownHandle {
+    pub(crate) fn new(grace_period: Duration) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                requested: AtomicBool::new(false),
+                grace_period: Mutex::new(grace_period),
+                local_addrs: Mutex::new(Vec::new()),
+                connections: Mutex::new(HashMap::new()),
+                drained: Condvar::new(),
+                next_id: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    // 새 연결 받기를 멈추고, 처리 중인 요청은 유예 시간 안에 끝나도록 기다린다.
+    // 여러 번 호출해도 처음 한 번만 효과가 있다.
+    pub fn shutdown(&self) {
+        if self.inner.requested.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        // accept()는 새 연결이 들어올 때까지 돌아오지 않기 때문에 우리 자신에게 연결해서 루프를 깨운다.
+        // 이 연결보다 먼저 들어와 있던 클라이언트가 깨우는 연결 대신 받힐 수 있으므로, accept 루프는 받은 연결을
+        // 버리지 않고 처리한다. 유예 시간을 기다렸다가 남은 연결을 끊는 건 Server::run()이 한다.
+        for addr in self.inner.local_addrs.lock().unwrap().iter() {
+            let _ = TcpStream::connect_timeout(&wake_addr(*addr), Duration::from_secs(1));
+        }
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        self.inner.requested.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn grace_period(&self) -> Duration {
+        *self.inner.grace_period.lock().unwrap()
+    }
+
+    pub(crate) fn set_grace_period(&self, grace_period: Duration) {
+        *self.inner.grace_period.lock().unwrap() = grace_period;
+    }
+
+    pub(crate) fn add_local_addr(&self, addr: SocketAddr) {
+        self.inner.local_addrs.lock().unwrap().push(addr);
+    }
+
+    // 연결을 처리 중인 목록에 등록한다. 돌려받은 가드가 drop 되면 목록에서 빠진다.
+    pub(crate) fn track(&self, stream: &TcpStream) -> ConnectionGuard {
+        let id = self.inner.next_id.fetch_add(1, Ordering::SeqCst);
+        if let Ok(clone) = stream.try_clone() {
+            self.inner.connections.lock().unwrap().insert(id, clone);
+        }
+
+        ConnectionGuard {
+            handle: self.clone(),
+            id,
+        }
+    }
+
+    // 처리 중인 연결이 모두 끝날 때까지 최대 timeout 만큼 기다린다. 모두 끝났으면 true를 리턴한다.
+    pub(crate) fn wait_for_connections(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut connections = self.inner.connections.lock().unwrap();
+        while !connections.is_empty() {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            connections = self
+                .inner
+                .drained
+                .wait_timeout(connections, deadline - now)
+                .unwrap()
+                .0;
+        }
+
+        true
+    }
+
+    pub(crate) fn close_connections(&self) {
+        for stream in self.inner.connections.lock().unwrap().values() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+pub(crate) struct ConnectionGuard {
+    handle: ShutdownHandle,
+    id: u64,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let mut connections = self.handle.inner.connections.lock().unwrap();
+        connections.remove(&self.id);
+        if connections.is_empty() {
+            self.handle.inner.drained.notify_all();
+        }
+    }
+}
+
+// 0.0.0.0 같은 주소로 바인딩되어 있으면 거기로는 연결할 수 없으니 루프백 주소로 바꿔준다.
+fn wake_addr(addr: SocketAddr) -> SocketAddr {
+    match addr.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => {
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), addr.port())
+        }
+        IpAddr::V6(ip) if ip.is_unspecified() => {
+            SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), addr.port())
+        }
+        _ => addr,
+    }
+}
+
+// SIGINT(Ctrl+C)나 SIGTERM을 받으면 서버를 멈춘다. 두 번째 시그널을 받으면 기다리지 않고 바로 종료한다.
+#[cfg(unix)]
+pub fn shutdown_on_signals(handle: ShutdownHandle) -> IoResult<()> {
+    use signal_hook::consts::{SIGINT, SIGTERM};
+    use signal_hook::iterator::Signals;
+
+    let mut signals = Signals::new([SIGINT, SIGTERM])?;
+    thread::spawn(move || {
+        for signal in signals.forever() {
+            if handle.is_shutdown() {
+                warn!("Received signal {} again, exiting immediately", signal);
+                std::process::exit(1);
+            }
+            info!("Received signal {}, shutting down", signal);
+            handle.shutdown();
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn shutdown_on_signals(_handle: ShutdownHandle) -> IoResult<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::http::{Request, Response, StatusCode};
+    use crate::server::{Handler, Server};
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::sync::mpsc::{self, Receiver, Sender};
+    use std::sync::Mutex;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    // 요청을 받았다고 알린 뒤 delay만큼 있다가 응답한다.
+    struct Slow {
+        delay: Duration,
+        started: Mutex<Sender<()>>,
+    }
+
+    impl Handler for Slow {
+        fn handle_request(&self, _: &Request) -> Response {
+            let _ = self.started.lock().unwrap().send(());
+            thread::sleep(self.delay);
+            Response::new(StatusCode::Ok, Some("done".to_string()))
+        }
+    }
+
+    struct Running {
+        addr: std::net::SocketAddr,
+        shutdown: super::ShutdownHandle,
+        started: Receiver<()>,
+        stopped: Receiver<()>,
+    }
+
+    // run()이 리턴하면 stopped로 알린다.
+    fn run(server: Server, delay: Duration) -> Running {
+        let mut server = server;
+        let addr = server.bind().unwrap();
+        let shutdown = server.shutdown_handle();
+        let (started_sender, started) = mpsc::channel();
+        let (stopped_sender, stopped) = mpsc::channel();
+        let handler = Slow {
+            delay,
+            started: Mutex::new(started_sender),
+        };
+        thread::spawn(move || {
+            server.run(handler).unwrap();
+            let _ = stopped_sender.send(());
+        });
+        Running {
+            addr,
+            shutdown,
+            started,
+            stopped,
+        }
+    }
+
+    fn grace_period(grace_period: Duration) -> Server {
+        Server::new("127.0.0.1:0".to_string()).grace_period(grace_period)
+    }
+
+    fn send_request(addr: std::net::SocketAddr) -> TcpStream {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        stream
+    }
+
+    fn read_response(stream: &mut TcpStream) -> String {
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response);
+        response
+    }
+
+    #[test]
+    fn in_flight_requests_finish_within_the_grace_period() {
+        let server = run(
+            grace_period(Duration::from_secs(5)),
+            Duration::from_millis(300),
+        );
+        let mut client = send_request(server.addr);
+        server.started.recv().unwrap();
+
+        let started = Instant::now();
+        server.shutdown.shutdown();
+        let response = read_response(&mut client);
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.ends_with("done"), "{}", response);
+
+        // 남은 연결이 없으므로 유예 시간을 다 기다리지 않고 끝난다.
+        server.stopped.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(2));
+        // 더 이상 연결을 받지 않는다.
+        assert!(TcpStream::connect(server.addr).is_err());
+    }
+
+    #[test]
+    fn slow_requests_are_cut_off_after_the_grace_period() {
+        let server = run(
+            grace_period(Duration::from_millis(200)),
+            Duration::from_secs(1),
+        );
+        let mut client = send_request(server.addr);
+        server.started.recv().unwrap();
+
+        let started = Instant::now();
+        server.shutdown.shutdown();
+        // 연결을 끊어도 핸들러가 끝날 때까지는 작업자 스레드를 기다린다.
+        server.stopped.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(read_response(&mut client), "");
+        assert!(started.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn connections_waiting_for_a_worker_are_served() {
+        // 작업자가 하나뿐이라서 나중에 온 연결들은 작업자를 기다린다.
+        let server = run(
+            grace_period(Duration::from_secs(5)).threads(1),
+            Duration::from_millis(100),
+        );
+        let mut first = send_request(server.addr);
+        server.started.recv().unwrap();
+        // 작업자가 첫 요청을 처리하는 동안 들어온 연결들
+        let mut waiting: Vec<TcpStream> = (0..3).map(|_| send_request(server.addr)).collect();
+        thread::sleep(Duration::from_millis(20));
+
+        server.shutdown.shutdown();
+        assert!(read_response(&mut first).starts_with("HTTP/1.1 200 OK"));
+        for client in &mut waiting {
+            let response = read_response(client);
+            assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        }
+        server.stopped.recv_timeout(Duration::from_secs(5)).unwrap();
+    }
+}
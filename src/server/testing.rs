@@ -0,0 +1,55 @@
+use super::metrics::Metrics;
+use super::{Handler, Server, ShutdownHandle};
+use std::net::SocketAddr;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// 테스트에서 빈 포트로 띄운 서버. 값이 사라질 때 서버를 멈추고 끝날 때까지 기다린다.
+//
+// let server = TestServer::start(Server::new("127.0.0.1:0".to_string()), handler);
+// let url = format!("http://{}/", server.addr());
+pub(crate) struct TestServer {
+    addrs: Vec<SocketAddr>,
+    shutdown: ShutdownHandle,
+    metrics: Metrics,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl TestServer {
+    pub(crate) fn start(server: Server, handler: impl Handler + 'static) -> Self {
+        let mut server = server.grace_period(Duration::from_millis(200));
+        server.bind().expect("failed to bind the test server");
+        let addrs = server.local_addrs();
+        let shutdown = server.shutdown_handle();
+        let metrics = server.metrics();
+        let thread = thread::spawn(move || server.run(handler).expect("test server failed"));
+        Self {
+            addrs,
+            shutdown,
+            metrics,
+            thread: Some(thread),
+        }
+    }
+
+    pub(crate) fn addr(&self) -> SocketAddr {
+        self.addrs[0]
+    }
+
+    // listen()이나 listen_tls()로 추가한 리스너의 주소. 추가한 순서대로 있다.
+    pub(crate) fn addrs(&self) -> &[SocketAddr] {
+        &self.addrs
+    }
+
+    pub(crate) fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.shutdown.shutdown();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
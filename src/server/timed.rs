@@ -0,0 +1,303 @@
+use super::metrics::Metrics;
+use std::cell::Cell;
+use std::io::{Error, ErrorKind, Read, Result as IoResult, Write};
+use std::net::TcpStream;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+// 주고받는 속도를 재기 전에 봐주는 시간. 연결 초반에는 TCP 창이 작아서 느릴 수 있다.
+const RATE_GRACE_PERIOD: Duration = Duration::from_secs(5);
+// write() 한 번에 소켓에 넘기는 최대 크기. 본문 전체를 한번에 넘기면 쓰기 타임아웃이 지나서야 돌아오므로
+// 속도를 자주 잴 수 있도록 나눠서 쓴다.
+const MAX_WRITE: usize = 64 * 1024;
+
+// 소켓을 감싸서 느린 클라이언트(slowloris)를 끊는다.
+//
+// 읽기 타임아웃은 read() 한 번을 기다리는 시간이라서, 몇 초마다 한 바이트씩 보내는 클라이언트는 막지 못한다.
+// 그래서 요청 머리를 다 받아야 하는 기한을 따로 두고, 기한이 가까워지면 읽기 타임아웃을 그만큼 줄인다.
+// 응답을 아주 천천히 읽어가는 클라이언트는 write()에서 막혀 있던 시간에 비해 보낸 바이트가 너무 적으면 끊는다.
+// SSE처럼 가끔씩만 쓰는 응답은 write()가 바로 끝나므로 속도 제한에 걸리지 않는다.
+// 본문도 마찬가지로, 받는 중인 동안 read()에서 기다린 시간에 비해 받은 바이트가 너무 적으면 끊는다.
+pub(crate) struct TimedStream {
+    stream: TcpStream,
+    read_timeout: Option<Duration>,
+    // 지금 소켓에 걸려 있는 읽기 타임아웃. 바뀔 때만 소켓에 다시 건다.
+    applied: Option<Duration>,
+    deadline: HeadDeadline,
+    // 초당 최소 바이트 수
+    min_send_rate: Option<u64>,
+    sent: u64,
+    // write()에서 막혀 있었던 시간의 합
+    blocked: Duration,
+    receiving: Receiving,
+    min_recv_rate: Option<u64>,
+    received: u64,
+    // 본문을 받는 동안 read()에서 기다린 시간의 합
+    waited: Duration,
+    metrics: Metrics,
+    timed_out: bool,
+}
+
+// 요청 머리를 다 읽어야 하는 시각. 연결을 처리하는 쪽에서 머리를 다 읽었으면 finish()로 기한을 없앤다.
+// TLS 스트림 안쪽에 있는 TimedStream에 닿기 어려우므로 같은 값을 나눠 가진다.
+#[derive(Clone)]
+pub(crate) struct HeadDeadline(Rc<Cell<Option<Instant>>>);
+
+impl HeadDeadline {
+    pub(crate) fn new(timeout: Option<Duration>) -> Self {
+        // 더할 수 없을 만큼 긴 시간은 기한이 없는 것과 같다.
+        Self(Rc::new(Cell::new(
+            timeout.and_then(|timeout| Instant::now().checked_add(timeout)),
+        )))
+    }
+
+    pub(crate) fn finish(&self) {
+        self.0.set(None);
+    }
+}
+
+// 요청 본문을 받는 중인지. 켜져 있는 동안만 받는 속도를 잰다.
+// 요청 머리는 HeadDeadline이 맡고, HTTP/2에서 요청 사이에 쉬는 시간에는 읽기 타임아웃만 적용한다.
+#[derive(Clone, Default)]
+pub(crate) struct Receiving(Rc<Cell<bool>>);
+
+impl Receiving {
+    pub(crate) fn set(&self, receiving: bool) {
+        self.0.set(receiving);
+    }
+}
+
+impl TimedStream {
+    pub(crate) fn new(
+        stream: TcpStream,
+        read_timeout: Option<Duration>,
+        deadline: HeadDeadline,
+        receiving: Receiving,
+        min_send_rate: Option<u64>,
+        min_recv_rate: Option<u64>,
+        metrics: Metrics,
+    ) -> Self {
+        Self {
+            stream,
+            read_timeout,
+            // 소켓을 처음 만들었을 때는 타임아웃이 없다.
+            applied: None,
+            deadline,
+            min_send_rate,
+            sent: 0,
+            blocked: Duration::ZERO,
+            receiving,
+            min_recv_rate,
+            received: 0,
+            waited: Duration::ZERO,
+            metrics,
+            timed_out: false,
+        }
+    }
+
+    // 같은 연결을 여러 번 세지 않도록 처음 한 번만 센다.
+    fn timeout(&mut self, message: String) -> Error {
+        if !self.timed_out {
+            self.timed_out = true;
+            self.metrics.timed_out();
+        }
+        Error::new(ErrorKind::TimedOut, message)
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> IoResult<()> {
+        if self.applied != timeout {
+            self.stream.set_read_timeout(timeout)?;
+            self.applied = timeout;
+        }
+        Ok(())
+    }
+}
+
+impl Read for TimedStream {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        let deadline = self.deadline.0.get();
+        let timeout = match deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    return Err(self.timeout("request head was not received in time".to_string()));
+                }
+                Some(self.read_timeout.map_or(remaining, |t| t.min(remaining)))
+            }
+            None => self.read_timeout,
+        };
+        self.set_read_timeout(timeout)?;
+
+        let started = Instant::now();
+        let len = match self.stream.read(buf) {
+            Ok(len) => len,
+            // 유닉스에서는 타임아웃이 지나면 WouldBlock이 나온다.
+            Err(e) if is_timeout(&e) => {
+                let message = match deadline {
+                    Some(deadline) if Instant::now() >= deadline => {
+                        "request head was not received in time".to_string()
+                    }
+                    _ => format!("read timed out : {}", e),
+                };
+                return Err(self.timeout(message));
+            }
+            Err(e) => return Err(e),
+        };
+        // 읽기 타임아웃 바로 전에 한 바이트씩 보내면 타임아웃으로는 끊을 수 없다. (slow POST)
+        if let Some(rate) = self.min_recv_rate.filter(|_| self.receiving.0.get()) {
+            self.waited += started.elapsed();
+            self.received += len as u64;
+            if self.waited > RATE_GRACE_PERIOD
+                && (self.received as f64) < rate as f64 * self.waited.as_secs_f64()
+            {
+                return Err(self.timeout(format!(
+                    "client is sending slower than {} bytes per second",
+                    rate
+                )));
+            }
+        }
+        Ok(len)
+    }
+}
+
+impl Write for TimedStream {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        let started = Instant::now();
+        let result = self.stream.write(&buf[..buf.len().min(MAX_WRITE)]);
+        self.blocked += started.elapsed();
+
+        let len = match result {
+            Ok(len) => len,
+            Err(e) if is_timeout(&e) => {
+                return Err(self.timeout(format!("write timed out : {}", e)))
+            }
+            Err(e) => return Err(e),
+        };
+        self.sent += len as u64;
+        if let Some(rate) = self.min_send_rate {
+            if self.blocked > RATE_GRACE_PERIOD
+                && (self.sent as f64) < rate as f64 * self.blocked.as_secs_f64()
+            {
+                return Err(self.timeout(format!(
+                    "client is reading slower than {} bytes per second",
+                    rate
+                )));
+            }
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.stream.flush()
+    }
+}
+
+fn is_timeout(e: &Error) -> bool {
+    matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::http::{Request, Response, StatusCode};
+    use crate::server::testing::TestServer;
+    use crate::server::{Handler, Server};
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    // 본문을 끝까지 읽고 그 길이를 돌려준다. 읽다가 실패하면 408로 응답한다.
+    struct ReadBody;
+
+    impl Handler for ReadBody {
+        fn handle_request(&self, request: &Request) -> Response {
+            let mut body = Vec::new();
+            match request.body().map(|mut b| b.read_to_end(&mut body)) {
+                Some(Err(_)) => Response::new(StatusCode::RequestTimeout, None),
+                _ => Response::new(StatusCode::Ok, Some(body.len().to_string())),
+            }
+        }
+    }
+
+    // 클라이언트가 data를 interval마다 한 바이트씩 보낸다. 서버가 끊으면 그만 보낸다.
+    fn trickle(stream: &TcpStream, data: &'static [u8], interval: Duration) {
+        let mut stream = stream.try_clone().unwrap();
+        thread::spawn(move || {
+            for byte in data {
+                if stream.write_all(&[*byte]).is_err() {
+                    return;
+                }
+                thread::sleep(interval);
+            }
+        });
+    }
+
+    fn read_until_closed(stream: &mut TcpStream) -> String {
+        stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+        let mut response = Vec::new();
+        let _ = stream.read_to_end(&mut response);
+        String::from_utf8_lossy(&response).into_owned()
+    }
+
+    #[test]
+    fn closes_connections_that_miss_the_head_deadline() {
+        let server = Server::new("127.0.0.1:0".to_string())
+            .read_timeout(Some(Duration::from_secs(5)))
+            .header_timeout(Some(Duration::from_millis(300)));
+        let server = TestServer::start(server, ReadBody);
+
+        // 읽기 타임아웃보다 자주 보내지만 머리는 기한 안에 끝나지 않는다.
+        let mut stream = TcpStream::connect(server.addr()).unwrap();
+        let started = Instant::now();
+        trickle(
+            &stream,
+            b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n",
+            Duration::from_millis(50),
+        );
+        let response = read_until_closed(&mut stream);
+
+        assert_eq!(response, "");
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(server.metrics().snapshot().timed_out, 1);
+    }
+
+    #[test]
+    fn closes_connections_that_send_the_body_too_slowly() {
+        let server = Server::new("127.0.0.1:0".to_string())
+            .read_timeout(Some(Duration::from_secs(2)))
+            .min_recv_rate(Some(1000));
+        let server = TestServer::start(server, ReadBody);
+
+        let mut stream = TcpStream::connect(server.addr()).unwrap();
+        stream
+            .write_all(b"POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 100\r\n\r\n")
+            .unwrap();
+        // 초당 10바이트. 다 보내려면 10초가 걸린다.
+        let started = Instant::now();
+        trickle(&stream, &[b'a'; 100], Duration::from_millis(100));
+        let response = read_until_closed(&mut stream);
+
+        assert!(response.starts_with("HTTP/1.1 408"), "{}", response);
+        // 속도는 처음 몇 초를 봐준 뒤부터 잰다.
+        assert!(started.elapsed() < Duration::from_secs(8));
+        assert_eq!(server.metrics().snapshot().timed_out, 1);
+    }
+
+    #[test]
+    fn bodies_sent_quickly_are_accepted() {
+        let server = Server::new("127.0.0.1:0".to_string()).min_recv_rate(Some(1000));
+        let server = TestServer::start(server, ReadBody);
+
+        let mut stream = TcpStream::connect(server.addr()).unwrap();
+        stream
+            .write_all(b"POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\nhello")
+            .unwrap();
+        let response = read_until_closed(&mut stream);
+
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(response.ends_with("\r\n\r\n5"), "{}", response);
+        assert_eq!(server.metrics().snapshot().timed_out, 0);
+    }
+}
@@ -0,0 +1,407 @@
+use super::ServerError;
+use rustls::crypto::ring::default_provider;
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use rustls::ServerConfig;
+use rustls_pki_types::pem::PemObject;
+use rustls_pki_types::{CertificateDer, PrivateKeyDer};
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::sync::Arc;
+
+// HTTPS 리스너에 쓸 인증서들. 기본 인증서 하나와, SNI로 전달된 서버 이름에 따라 골라 쓸 인증서들을 가진다.
+//
+// let tls = TlsConfig::new("certs/site.pem", "certs/site.key")?
+//     .sni(&["blog.example.test"], "certs/blog.pem", "certs/blog.key")?;
+// let server = Server::new("0.0.0.0:80".to_string()).listen_tls("0.0.0.0:443".to_string(), tls);
+pub struct TlsConfig {
+    default: Arc<CertifiedKey>,
+    exact: HashMap<String, Arc<CertifiedKey>>,
+    // (".example.test", 인증서). 긴 접미사가 먼저 오도록 정렬해 둔다.
+    wildcards: Vec<(String, Arc<CertifiedKey>)>,
+    alpn: Vec<Vec<u8>>,
+}
+
+impl TlsConfig {
+    // SNI를 보내지 않았거나 맞는 이름이 없는 클라이언트에게 보여줄 인증서
+    pub fn new(cert_path: &str, key_path: &str) -> Result<Self, ServerError> {
+        Ok(Self {
+            default: load_certified_key(cert_path, key_path)?,
+            exact: HashMap::new(),
+            wildcards: Vec::new(),
+            alpn: vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+        })
+    }
+
+    // names에는 "example.test" 같은 이름이나 "*.example.test" 같은 와일드카드를 넣을 수 있다.
+    pub fn sni<S: AsRef<str>>(
+        mut self,
+        names: &[S],
+        cert_path: &str,
+        key_path: &str,
+    ) -> Result<Self, ServerError> {
+        let key = load_certified_key(cert_path, key_path)?;
+        for name in names {
+            let name = name.as_ref().to_ascii_lowercase();
+            match name.strip_prefix('*') {
+                Some(suffix) => self.wildcards.push((suffix.to_string(), Arc::clone(&key))),
+                None => {
+                    self.exact.insert(name, Arc::clone(&key));
+                }
+            }
+        }
+        self.wildcards
+            .sort_by_key(|(suffix, _)| std::cmp::Reverse(suffix.len()));
+        Ok(self)
+    }
+
+    // ALPN으로 협상할 프로토콜 목록. 앞에 있을수록 우선한다. 기본값은 h2, http/1.1 이다.
+    pub fn alpn<S: AsRef<str>>(mut self, protocols: &[S]) -> Self {
+        self.alpn = protocols
+            .iter()
+            .map(|protocol| protocol.as_ref().as_bytes().to_vec())
+            .collect();
+        self
+    }
+
+    // http2가 false면 목록에 h2가 있어도 빼고 협상한다.
+    pub(crate) fn server_config(&self, http2: bool) -> Result<Arc<ServerConfig>, ServerError> {
+        let resolver = SniResolver {
+            default: Arc::clone(&self.default),
+            exact: self.exact.clone(),
+            wildcards: self.wildcards.clone(),
+        };
+
+        let mut config = ServerConfig::builder_with_provider(Arc::new(default_provider()))
+            .with_safe_default_protocol_versions()
+            .map_err(|e| ServerError::Config(format!("invalid TLS settings: {}", e)))?
+            .with_no_client_auth()
+            .with_cert_resolver(Arc::new(resolver));
+        config.alpn_protocols = self
+            .alpn
+            .iter()
+            .filter(|protocol| http2 || protocol.as_slice() != b"h2")
+            .cloned()
+            .collect();
+        Ok(Arc::new(config))
+    }
+}
+
+// PEM 파일에서 인증서 체인과 개인 키를 읽고, 둘이 짝이 맞는지 확인한다.
+fn load_certified_key(cert_path: &str, key_path: &str) -> Result<Arc<CertifiedKey>, ServerError> {
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| {
+            ServerError::Config(format!("cannot read certificate {}: {}", cert_path, e))
+        })?;
+    if certs.is_empty() {
+        return Err(ServerError::Config(format!(
+            "no certificate found in {}",
+            cert_path
+        )));
+    }
+
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .map_err(|e| ServerError::Config(format!("cannot read private key {}: {}", key_path, e)))?;
+
+    let certified_key = CertifiedKey::from_der(certs, key, &default_provider()).map_err(|e| {
+        ServerError::Config(format!(
+            "certificate {} does not match key {}: {}",
+            cert_path, key_path, e
+        ))
+    })?;
+    Ok(Arc::new(certified_key))
+}
+
+// 클라이언트가 보낸 서버 이름(SNI)으로 인증서를 고른다.
+struct SniResolver {
+    default: Arc<CertifiedKey>,
+    exact: HashMap<String, Arc<CertifiedKey>>,
+    wildcards: Vec<(String, Arc<CertifiedKey>)>,
+}
+
+impl ResolvesServerCert for SniResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let name = match client_hello.server_name() {
+            Some(name) => name.to_ascii_lowercase(),
+            None => return Some(Arc::clone(&self.default)),
+        };
+
+        let key = match self.exact.get(&name) {
+            Some(key) => key,
+            None => self
+                .wildcards
+                .iter()
+                .find(|(suffix, _)| name.len() > suffix.len() && name.ends_with(suffix.as_str()))
+                .map(|(_, key)| key)
+                .unwrap_or(&self.default),
+        };
+        Some(Arc::clone(key))
+    }
+}
+
+// ResolvesServerCert는 Debug를 요구하는데 인증서 내용까지 출력할 필요는 없다.
+impl Debug for SniResolver {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("SniResolver")
+            .field("names", &self.exact.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{Request, Response, StatusCode};
+    use crate::server::testing::TestServer;
+    use crate::server::{Handler, Server};
+    use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+    use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+    use rustls::{
+        ClientConfig, ClientConnection, DigitallySignedStruct, SignatureScheme, StreamOwned,
+    };
+    use rustls_pki_types::{ServerName, UnixTime};
+    use std::convert::TryFrom;
+    use std::io::{Read, Write};
+    use std::net::{IpAddr, SocketAddr, TcpStream};
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // 테스트마다 새로 만든 자체 서명 인증서. PEM 파일은 값이 사라질 때 지운다.
+    struct TestCert {
+        der: CertificateDer<'static>,
+        cert_path: PathBuf,
+        key_path: PathBuf,
+    }
+
+    impl TestCert {
+        fn new(names: &[&str]) -> Self {
+            static NEXT: AtomicUsize = AtomicUsize::new(0);
+            let names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+            let generated = rcgen::generate_simple_self_signed(names).unwrap();
+            let prefix = std::env::temp_dir().join(format!(
+                "http_server-tls-{}-{}",
+                std::process::id(),
+                NEXT.fetch_add(1, Ordering::Relaxed)
+            ));
+            let cert_path = prefix.with_extension("pem");
+            let key_path = prefix.with_extension("key");
+            std::fs::write(&cert_path, generated.cert.pem()).unwrap();
+            std::fs::write(&key_path, generated.signing_key.serialize_pem()).unwrap();
+            Self {
+                der: generated.cert.der().clone(),
+                cert_path,
+                key_path,
+            }
+        }
+
+        fn cert(&self) -> &str {
+            self.cert_path.to_str().unwrap()
+        }
+
+        fn key(&self) -> &str {
+            self.key_path.to_str().unwrap()
+        }
+    }
+
+    impl Drop for TestCert {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.cert_path);
+            let _ = std::fs::remove_file(&self.key_path);
+        }
+    }
+
+    // 서버가 보여준 인증서를 직접 비교하므로 클라이언트는 어떤 인증서든 받는다. 서명은 검사한다.
+    #[derive(Debug)]
+    struct AcceptAnyCert(Arc<CryptoProvider>);
+
+    impl ServerCertVerifier for AcceptAnyCert {
+        fn verify_server_cert(
+            &self,
+            _: &CertificateDer<'_>,
+            _: &[CertificateDer<'_>],
+            _: &ServerName<'_>,
+            _: &[u8],
+            _: UnixTime,
+        ) -> Result<ServerCertVerified, rustls::Error> {
+            Ok(ServerCertVerified::assertion())
+        }
+
+        fn verify_tls12_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            verify_tls12_signature(
+                message,
+                cert,
+                dss,
+                &self.0.signature_verification_algorithms,
+            )
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            verify_tls13_signature(
+                message,
+                cert,
+                dss,
+                &self.0.signature_verification_algorithms,
+            )
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+            self.0.signature_verification_algorithms.supported_schemes()
+        }
+    }
+
+    fn client(server_name: &str, alpn: &[&str]) -> ClientConnection {
+        let provider = Arc::new(default_provider());
+        let mut config = ClientConfig::builder_with_provider(Arc::clone(&provider))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(AcceptAnyCert(provider)))
+            .with_no_client_auth();
+        config.alpn_protocols = alpn.iter().map(|p| p.as_bytes().to_vec()).collect();
+        // IP 주소로 연결하면 SNI를 보내지 않는다.
+        let name = match server_name.parse::<IpAddr>() {
+            Ok(ip) => ServerName::IpAddress(ip.into()),
+            Err(_) => ServerName::try_from(server_name.to_string()).unwrap(),
+        };
+        ClientConnection::new(Arc::new(config), name).unwrap()
+    }
+
+    // 핸드셰이크만 하고, 서버가 보여준 인증서와 협상한 프로토콜을 돌려준다.
+    fn handshake(addr: SocketAddr, server_name: &str, alpn: &[&str]) -> (Vec<u8>, Option<Vec<u8>>) {
+        let mut conn = client(server_name, alpn);
+        let mut socket = TcpStream::connect(addr).unwrap();
+        while conn.is_handshaking() {
+            conn.complete_io(&mut socket).unwrap();
+        }
+        let cert = conn.peer_certificates().unwrap()[0].to_vec();
+        (cert, conn.alpn_protocol().map(<[u8]>::to_vec))
+    }
+
+    struct Scheme;
+
+    impl Handler for Scheme {
+        fn handle_request(&self, request: &Request) -> Response {
+            let scheme = if request.is_secure() { "https" } else { "http" };
+            Response::new(StatusCode::Ok, Some(scheme.to_string()))
+        }
+    }
+
+    fn tls_server(server: Server, tls: TlsConfig) -> TestServer {
+        TestServer::start(server.listen_tls("127.0.0.1:0".to_string(), tls), Scheme)
+    }
+
+    fn plain() -> Server {
+        Server::new("127.0.0.1:0".to_string())
+    }
+
+    #[test]
+    fn sni_picks_the_certificate() {
+        let default = TestCert::new(&["default.test"]);
+        let blog = TestCert::new(&["blog.example.test"]);
+        let shop = TestCert::new(&["*.shop.test"]);
+        let tls = TlsConfig::new(default.cert(), default.key())
+            .unwrap()
+            .sni(&["blog.example.test"], blog.cert(), blog.key())
+            .unwrap()
+            .sni(&["*.shop.test"], shop.cert(), shop.key())
+            .unwrap();
+        let server = tls_server(plain(), tls);
+        let addr = server.addrs()[1];
+
+        let cases = [
+            ("blog.example.test", &blog),
+            ("a.shop.test", &shop),
+            ("a.b.shop.test", &shop),
+            // 와일드카드는 앞에 이름이 하나 이상 있어야 맞는다.
+            ("shop.test", &default),
+            ("unknown.test", &default),
+            // SNI를 보내지 않는다.
+            ("127.0.0.1", &default),
+        ];
+        for (name, expected) in cases {
+            let (cert, _) = handshake(addr, name, &["http/1.1"]);
+            assert!(
+                cert == expected.der.to_vec(),
+                "wrong certificate for {}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn alpn_negotiation() {
+        let cert = TestCert::new(&["localhost"]);
+        let tls = || TlsConfig::new(cert.cert(), cert.key()).unwrap();
+
+        let server = tls_server(plain(), tls());
+        let addr = server.addrs()[1];
+        let (_, protocol) = handshake(addr, "localhost", &["h2", "http/1.1"]);
+        assert_eq!(protocol.as_deref(), Some(&b"h2"[..]));
+        let (_, protocol) = handshake(addr, "localhost", &["http/1.1"]);
+        assert_eq!(protocol.as_deref(), Some(&b"http/1.1"[..]));
+        // ALPN을 보내지 않는 클라이언트는 HTTP/1.1로 처리한다.
+        let (_, protocol) = handshake(addr, "localhost", &[]);
+        assert_eq!(protocol, None);
+        drop(server);
+
+        // HTTP/2를 끄면 h2를 협상하지 않는다.
+        let server = tls_server(plain().http2(false), tls());
+        let (_, protocol) = handshake(server.addrs()[1], "localhost", &["h2", "http/1.1"]);
+        assert_eq!(protocol.as_deref(), Some(&b"http/1.1"[..]));
+        drop(server);
+
+        let server = tls_server(plain(), tls().alpn(&["http/1.1"]));
+        let (_, protocol) = handshake(server.addrs()[1], "localhost", &["h2", "http/1.1"]);
+        assert_eq!(protocol.as_deref(), Some(&b"http/1.1"[..]));
+    }
+
+    #[test]
+    fn plain_and_tls_listeners_share_a_server() {
+        let cert = TestCert::new(&["localhost"]);
+        let tls = TlsConfig::new(cert.cert(), cert.key()).unwrap();
+        let server = tls_server(plain(), tls);
+        let request = b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n";
+
+        let mut stream = TcpStream::connect(server.addrs()[0]).unwrap();
+        stream.write_all(request).unwrap();
+        let mut bytes = Vec::new();
+        stream.read_to_end(&mut bytes).unwrap();
+        let response = Response::try_from(&bytes[..]).unwrap();
+        assert_eq!(response.status_code(), StatusCode::Ok);
+        assert_eq!(response.body(), Some(&b"http"[..]));
+
+        let socket = TcpStream::connect(server.addrs()[1]).unwrap();
+        let mut stream = StreamOwned::new(client("localhost", &["http/1.1"]), socket);
+        stream.write_all(request).unwrap();
+        let mut bytes = Vec::new();
+        stream.read_to_end(&mut bytes).unwrap();
+        let response = Response::try_from(&bytes[..]).unwrap();
+        assert_eq!(response.status_code(), StatusCode::Ok);
+        assert_eq!(response.body(), Some(&b"https"[..]));
+    }
+
+    #[test]
+    fn mismatched_key_is_a_config_error() {
+        let a = TestCert::new(&["a.test"]);
+        let b = TestCert::new(&["b.test"]);
+        assert!(matches!(
+            TlsConfig::new(a.cert(), b.key()),
+            Err(ServerError::Config(_))
+        ));
+        assert!(matches!(
+            TlsConfig::new("/nonexistent.pem", a.key()),
+            Err(ServerError::Config(_))
+        ));
+    }
+}
@@ -0,0 +1,323 @@
+use super::{is_valid_id, Record, SessionStore};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Error, ErrorKind, Result as IoResult};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// 만료된 세션 파일을 찾아서 지우는 간격
+const SWEEP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+// 세션 하나를 디렉터리 안의 파일 하나로 저장한다. 서버를 다시 시작해도 세션이 남는다.
+// 파일 이름이 세션 ID이므로 디렉터리는 서버만 읽을 수 있어야 한다.
+//
+// 파일의 첫 줄은 만료 시각(유닉스 시간 초)이고, 그 뒤로 한 줄에 "키=값"이 하나씩 온다.
+// 키와 값의 %, =, 줄바꿈은 %XX로 바꿔서 쓴다.
+pub struct FileStore {
+    dir: PathBuf,
+    last_sweep: Mutex<Instant>,
+    random: SystemRandom,
+}
+
+impl FileStore {
+    pub fn new(dir: impl Into<PathBuf>) -> IoResult<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            last_sweep: Mutex::new(Instant::now()),
+            random: SystemRandom::new(),
+        })
+    }
+
+    // 만료된 세션 파일을 모두 지운다. save()가 SWEEP_INTERVAL마다 부르므로 따로 부르지 않아도 된다.
+    pub fn sweep(&self) -> IoResult<()> {
+        let now = SystemTime::now();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let id = match name.to_str() {
+                Some(id) if is_valid_id(id) => id,
+                _ => continue,
+            };
+            let expired = match self.read(id) {
+                Ok(record) => record.is_expired(now),
+                // 다른 스레드가 이미 지웠다.
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                // 읽을 수 없는 파일은 쓸 수 없는 세션이다.
+                Err(_) => true,
+            };
+            if expired {
+                self.remove(id)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(id)
+    }
+
+    fn read(&self, id: &str) -> IoResult<Record> {
+        let contents = fs::read_to_string(self.path(id))?;
+        parse(&contents).ok_or_else(|| Error::new(ErrorKind::InvalidData, "invalid session file"))
+    }
+
+    fn sweep_if_due(&self) {
+        {
+            let mut last_sweep = self.last_sweep.lock().unwrap();
+            if last_sweep.elapsed() < SWEEP_INTERVAL {
+                return;
+            }
+            *last_sweep = Instant::now();
+        }
+        if let Err(e) = self.sweep() {
+            error!("Failed to remove expired sessions : {}", e);
+        }
+    }
+}
+
+impl SessionStore for FileStore {
+    fn load(&self, id: &str) -> Option<Record> {
+        if !is_valid_id(id) {
+            return None;
+        }
+        match self.read(id) {
+            Ok(record) if !record.is_expired(SystemTime::now()) => Some(record),
+            Ok(_) => {
+                let _ = self.remove(id);
+                None
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => {
+                error!("Failed to read session : {}", e);
+                None
+            }
+        }
+    }
+
+    // 임시 파일에 다 쓴 뒤 이름을 바꿔서, 읽는 쪽이 반쯤 쓴 파일을 보지 않게 한다.
+    // 같은 세션을 동시에 저장하는 요청들이 서로의 임시 파일을 덮어쓰지 않도록 임시 파일 이름은 매번 새로 만든다.
+    fn save(&self, id: &str, record: &Record) -> IoResult<()> {
+        if !is_valid_id(id) {
+            return Err(Error::new(ErrorKind::InvalidInput, "invalid session id"));
+        }
+        let mut suffix = [0; 8];
+        self.random
+            .fill(&mut suffix)
+            .map_err(|_| Error::other("system random number generator failed"))?;
+        let suffix: String = suffix.iter().map(|b| format!("{:02x}", b)).collect();
+        let temp = self.dir.join(format!("{}.{}.tmp", id, suffix));
+        if let Err(e) =
+            fs::write(&temp, serialize(record)).and_then(|_| fs::rename(&temp, self.path(id)))
+        {
+            let _ = fs::remove_file(&temp);
+            return Err(e);
+        }
+        self.sweep_if_due();
+        Ok(())
+    }
+
+    fn remove(&self, id: &str) -> IoResult<()> {
+        if !is_valid_id(id) {
+            return Ok(());
+        }
+        match fs::remove_file(self.path(id)) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn serialize(record: &Record) -> String {
+    let expires = record
+        .expires
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let mut contents = format!("{}\n", expires);
+    for (key, value) in &record.values {
+        contents.push_str(&escape(key));
+        contents.push('=');
+        contents.push_str(&escape(value));
+        contents.push('\n');
+    }
+    contents
+}
+
+fn parse(contents: &str) -> Option<Record> {
+    let mut lines = contents.lines();
+    let expires: u64 = lines.next()?.parse().ok()?;
+    let mut values = HashMap::new();
+    for line in lines {
+        let (key, value) = line.split_once('=')?;
+        values.insert(unescape(key)?, unescape(value)?);
+    }
+    Some(Record {
+        values,
+        expires: UNIX_EPOCH + Duration::from_secs(expires),
+    })
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '%' | '=' | '\r' | '\n' => escaped.push_str(&format!("%{:02X}", c as u8)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut unescaped = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = value.get(i + 1..i + 3)?;
+            unescaped.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            unescaped.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(unescaped).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    // 테스트마다 새로 만드는 디렉터리. 값이 사라질 때 지운다.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            static NEXT: AtomicUsize = AtomicUsize::new(0);
+            Self(std::env::temp_dir().join(format!(
+                "http_server-sessions-{}-{}",
+                std::process::id(),
+                NEXT.fetch_add(1, Ordering::Relaxed)
+            )))
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    const ID: &str = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+
+    fn record(values: &[(&str, &str)], expires_in: Duration) -> Record {
+        Record {
+            values: values
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            // 파일에는 초 단위로 저장되므로 비교할 수 있게 맞춘다.
+            expires: UNIX_EPOCH
+                + Duration::from_secs(
+                    (SystemTime::now() + expires_in)
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs(),
+                ),
+        }
+    }
+
+    #[test]
+    fn round_trips_values_that_need_escaping() {
+        let dir = TempDir::new();
+        let store = FileStore::new(&dir.0).unwrap();
+        let saved = record(
+            &[
+                ("name", "100% = done\nreally\r\n"),
+                ("a=b", "%41"),
+                ("줄\n바꿈", ""),
+            ],
+            Duration::from_secs(60),
+        );
+        store.save(ID, &saved).unwrap();
+        assert_eq!(store.load(ID), Some(saved));
+        // 다시 열어도 남아 있다.
+        let reopened = FileStore::new(&dir.0).unwrap();
+        assert!(reopened.load(ID).is_some());
+
+        store.remove(ID).unwrap();
+        assert_eq!(store.load(ID), None);
+    }
+
+    #[test]
+    fn expired_sessions_are_not_loaded() {
+        let dir = TempDir::new();
+        let store = FileStore::new(&dir.0).unwrap();
+        let mut expired = record(&[("user", "alice")], Duration::ZERO);
+        expired.expires -= Duration::from_secs(1);
+        store.save(ID, &expired).unwrap();
+        assert_eq!(store.load(ID), None);
+        assert!(!store.path(ID).exists());
+
+        store.save(ID, &expired).unwrap();
+        store.sweep().unwrap();
+        assert!(!store.path(ID).exists());
+    }
+
+    #[test]
+    fn rejects_malformed_ids() {
+        let dir = TempDir::new();
+        let store = FileStore::new(&dir.0).unwrap();
+        let saved = record(&[("user", "alice")], Duration::from_secs(60));
+        for id in [
+            "",
+            "short",
+            "../../../../../../../../../../../etc/passwd",
+            &ID[1..],
+            &format!("{}A", ID),
+        ] {
+            assert_eq!(
+                store.save(id, &saved).unwrap_err().kind(),
+                ErrorKind::InvalidInput
+            );
+            assert_eq!(store.load(id), None);
+        }
+        let traversal = format!("{}/", &ID[..42]);
+        assert_eq!(store.load(&traversal), None);
+        assert_eq!(fs::read_dir(&dir.0).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn concurrent_saves_do_not_share_a_temp_file() {
+        let dir = TempDir::new();
+        let store = Arc::new(FileStore::new(&dir.0).unwrap());
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let store = Arc::clone(&store);
+                thread::spawn(move || {
+                    let value = i.to_string().repeat(10_000);
+                    let saved = record(&[("value", &value)], Duration::from_secs(60));
+                    for _ in 0..20 {
+                        store.save(ID, &saved).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        // 마지막으로 이름을 바꾼 파일 하나가 통째로 남고 임시 파일은 남지 않는다.
+        let value = store.load(ID).unwrap().values.remove("value").unwrap();
+        assert_eq!(value.len(), 10_000);
+        assert!(value.bytes().all(|b| b == value.as_bytes()[0]));
+        assert_eq!(fs::read_dir(&dir.0).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,159 @@
+use super::{Record, SessionStore};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Result as IoResult;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+// 프로세스 메모리에 두는 저장소. 서버를 다시 시작하면 모든 세션이 사라진다.
+// capacity개를 넘으면 가장 오래 쓰지 않은 세션부터 지운다. 만료된 세션은 다시 쓰려고 할 때나
+// 가장 오래된 쪽에서 만났을 때 지운다.
+pub struct MemoryStore {
+    capacity: usize,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: HashMap<String, Entry>,
+    // 마지막으로 쓴 순서 -> ID. 맨 앞이 가장 오래 쓰지 않은 세션이다.
+    order: BTreeMap<u64, String>,
+    // 세션을 쓸 때마다 1씩 늘려서 순서로 쓴다.
+    clock: u64,
+}
+
+struct Entry {
+    record: Record,
+    used: u64,
+}
+
+impl MemoryStore {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Inner {
+    // 방금 쓴 세션을 맨 뒤로 옮긴다.
+    fn touch(&mut self, id: &str) {
+        let entry = match self.entries.get_mut(id) {
+            Some(entry) => entry,
+            None => return,
+        };
+        self.order.remove(&entry.used);
+        self.clock += 1;
+        entry.used = self.clock;
+        self.order.insert(self.clock, id.to_string());
+    }
+
+    fn remove(&mut self, id: &str) {
+        if let Some(entry) = self.entries.remove(id) {
+            self.order.remove(&entry.used);
+        }
+    }
+
+    // 가장 오래 쓰지 않은 쪽부터, 자리가 모자라거나 만료된 세션을 지운다.
+    fn evict(&mut self, capacity: usize, now: SystemTime) {
+        while let Some((_, id)) = self.order.first_key_value() {
+            let expired = self.entries[id].record.is_expired(now);
+            if !expired && self.entries.len() <= capacity {
+                break;
+            }
+            let (_, id) = self.order.pop_first().unwrap();
+            self.entries.remove(&id);
+        }
+    }
+}
+
+impl SessionStore for MemoryStore {
+    fn load(&self, id: &str) -> Option<Record> {
+        let mut inner = self.inner.lock().unwrap();
+        let expired = inner.entries.get(id)?.record.is_expired(SystemTime::now());
+        if expired {
+            inner.remove(id);
+            return None;
+        }
+        inner.touch(id);
+        Some(inner.entries[id].record.clone())
+    }
+
+    fn save(&self, id: &str, record: &Record) -> IoResult<()> {
+        let mut inner = self.inner.lock().unwrap();
+        match inner.entries.get_mut(id) {
+            Some(entry) => entry.record = record.clone(),
+            None => {
+                inner.entries.insert(
+                    id.to_string(),
+                    Entry {
+                        record: record.clone(),
+                        used: 0,
+                    },
+                );
+            }
+        }
+        inner.touch(id);
+        inner.evict(self.capacity, SystemTime::now());
+        Ok(())
+    }
+
+    fn remove(&self, id: &str) -> IoResult<()> {
+        self.inner.lock().unwrap().remove(id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    fn record(expires_in: Duration) -> Record {
+        Record {
+            values: HashMap::from([("user".to_string(), "alice".to_string())]),
+            expires: SystemTime::now() + expires_in,
+        }
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used_session() {
+        let store = MemoryStore::new(2);
+        let live = record(Duration::from_secs(60));
+        store.save("a", &live).unwrap();
+        store.save("b", &live).unwrap();
+        // a를 읽었으므로 b가 가장 오래 쓰지 않은 세션이 된다.
+        assert!(store.load("a").is_some());
+        store.save("c", &live).unwrap();
+
+        assert_eq!(store.len(), 2);
+        assert!(store.load("b").is_none());
+        assert!(store.load("a").is_some());
+        assert!(store.load("c").is_some());
+    }
+
+    #[test]
+    fn drops_expired_sessions() {
+        let store = MemoryStore::new(10);
+        store.save("old", &record(Duration::ZERO)).unwrap();
+        assert!(store.load("old").is_none());
+        assert!(store.is_empty());
+
+        // 가장 오래된 쪽에 있는 만료된 세션은 다른 세션을 저장할 때 지운다.
+        store
+            .save("old", &record(Duration::from_millis(20)))
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(30));
+        store.save("new", &record(Duration::from_secs(60))).unwrap();
+        assert_eq!(store.len(), 1);
+    }
+}
@@ -0,0 +1,426 @@
+// 쿠키에는 무작위로 만든 세션 ID만 넣고, 세션에 담는 값은 서버의 저장소에 둔다.
+//
+// 세션은 값을 처음 넣을 때 만들어지고, 그때 Set-Cookie로 ID를 보낸다. 값이 없는 방문자에게는 쿠키를 주지 않는다.
+// 요청이 올 때마다 만료 시각을 idle_timeout 뒤로 미루므로, 그 시간 동안 요청이 없던 세션만 사라진다.
+// 같은 세션으로 동시에 들어온 요청들은 각자 읽은 값을 저장하므로 나중에 끝난 요청의 값이 남는다.
+//
+// struct Admin;
+// impl SessionHandler for Admin {
+//     fn handle_request(&self, request: &Request, session: &mut Session) -> Response {
+//         if request.path() == "/login" && password_matches(request) {
+//             // 권한이 바뀔 때는 ID를 바꿔서 로그인 전에 알려진 ID를 쓰지 못하게 한다.
+//             session.rotate();
+//             session.set("user", "admin");
+//         }
+//         match session.get("user") {
+//             Some(user) => Response::new(StatusCode::Ok, Some(format!("hello {}", user))),
+//             None => Response::new(StatusCode::Unauthorized, None),
+//         }
+//     }
+// }
+// let handler = Sessions::new(MemoryStore::new(10_000), Admin).idle_timeout(Duration::from_secs(1800));
+use crate::http::cookie::SameSite;
+use crate::http::{Cookie, ParseError, Request, Response, StatusCode};
+use crate::server::Handler;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ring::rand::{SecureRandom, SystemRandom};
+use std::collections::HashMap;
+use std::io::Result as IoResult;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+pub mod file;
+pub mod memory;
+
+const DEFAULT_COOKIE_NAME: &str = "sid";
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+// 256비트. 추측해서 맞출 수 없을 만큼 길어야 한다.
+const ID_BYTES: usize = 32;
+// 32바이트를 패딩 없는 base64url로 쓴 길이
+const ID_LEN: usize = 43;
+
+// 저장소에 두는 세션 하나
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub values: HashMap<String, String>,
+    // 이 시각이 지나면 없는 세션으로 본다.
+    pub expires: SystemTime,
+}
+
+impl Record {
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires <= now
+    }
+}
+
+// 세션을 저장하는 곳. 여러 작업자 스레드가 함께 쓴다.
+// 만료된 세션은 load()가 None을 리턴해야 한다. 지우는 시점은 저장소가 정한다.
+pub trait SessionStore: Send + Sync {
+    fn load(&self, id: &str) -> Option<Record>;
+    fn save(&self, id: &str, record: &Record) -> IoResult<()>;
+    fn remove(&self, id: &str) -> IoResult<()>;
+}
+
+impl<S: SessionStore + ?Sized> SessionStore for Box<S> {
+    fn load(&self, id: &str) -> Option<Record> {
+        (**self).load(id)
+    }
+
+    fn save(&self, id: &str, record: &Record) -> IoResult<()> {
+        (**self).save(id, record)
+    }
+
+    fn remove(&self, id: &str) -> IoResult<()> {
+        (**self).remove(id)
+    }
+}
+
+// 저장소를 다른 곳(MemoryStore::len()으로 세션 수를 보는 곳 등)과 함께 쓸 때
+impl<S: SessionStore + ?Sized> SessionStore for Arc<S> {
+    fn load(&self, id: &str) -> Option<Record> {
+        (**self).load(id)
+    }
+
+    fn save(&self, id: &str, record: &Record) -> IoResult<()> {
+        (**self).save(id, record)
+    }
+
+    fn remove(&self, id: &str) -> IoResult<()> {
+        (**self).remove(id)
+    }
+}
+
+// 핸들러에게 넘겨주는 세션. 핸들러가 리턴한 뒤에 바뀐 내용을 저장소에 저장한다.
+#[derive(Debug)]
+pub struct Session {
+    // 저장소에 있던 세션의 ID. 새 세션이면 None이다.
+    id: Option<String>,
+    values: HashMap<String, String>,
+    rotate: bool,
+}
+
+impl Session {
+    fn new(id: Option<String>, values: HashMap<String, String>) -> Self {
+        Self {
+            id,
+            values,
+            rotate: false,
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    pub fn set(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.values.insert(key.into(), value.into());
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        self.values.remove(key)
+    }
+
+    // 값을 모두 지운다. 값이 없는 세션은 저장소에서 지우고 쿠키도 지운다. 로그아웃할 때 쓴다.
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+
+    // 값은 그대로 두고 새 ID로 옮긴다. 로그인처럼 권한이 바뀔 때 불러서 세션 고정 공격을 막는다.
+    pub fn rotate(&mut self) {
+        self.rotate = true;
+    }
+
+    // 이번 요청 전에 저장소에 없던 세션이면 true
+    pub fn is_new(&self) -> bool {
+        self.id.is_none()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+// Sessions로 감싸는 핸들러. Handler와 같지만 요청마다 세션을 함께 받는다.
+pub trait SessionHandler: Send + Sync {
+    fn handle_request(&self, request: &Request, session: &mut Session) -> Response;
+
+    fn handle_bad_request(&self, e: &ParseError) -> Response {
+        warn!("Failed to parse request: {}", e);
+        Response::new(StatusCode::BadRequest, None)
+    }
+}
+
+// 요청의 쿠키로 세션을 찾아서 핸들러에게 넘기고, 응답을 보내기 전에 세션을 저장한다.
+pub struct Sessions<S, H> {
+    store: S,
+    inner: H,
+    cookie_name: String,
+    idle_timeout: Duration,
+    secure: bool,
+    same_site: SameSite,
+    random: SystemRandom,
+}
+
+impl<S: SessionStore, H: SessionHandler> Sessions<S, H> {
+    pub fn new(store: S, inner: H) -> Self {
+        Self {
+            store,
+            inner,
+            cookie_name: DEFAULT_COOKIE_NAME.to_string(),
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            secure: true,
+            same_site: SameSite::Lax,
+            random: SystemRandom::new(),
+        }
+    }
+
+    pub fn cookie_name(mut self, name: impl Into<String>) -> Self {
+        self.cookie_name = name.into();
+        self
+    }
+
+    // 이 시간 동안 요청이 없으면 세션이 만료된다.
+    pub fn idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    // HTTPS 없이 시험할 때만 끈다. 켜져 있으면 브라우저는 HTTP로는 쿠키를 보내지 않는다.
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    pub fn same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = same_site;
+        self
+    }
+
+    fn generate_id(&self) -> String {
+        let mut id = [0; ID_BYTES];
+        self.random
+            .fill(&mut id)
+            .expect("system random number generator failed");
+        URL_SAFE_NO_PAD.encode(id)
+    }
+
+    // 쿠키의 ID로 저장소에서 세션을 찾는다. 모양이 맞지 않는 ID는 저장소에 넘기지 않는다.
+    fn load(&self, request: &Request) -> Session {
+        let record = request
+            .cookies()
+            .get_raw(&self.cookie_name)
+            .filter(|id| is_valid_id(id))
+            .and_then(|id| Some((id, self.store.load(id)?)));
+        match record {
+            Some((id, record)) if !record.is_expired(SystemTime::now()) => {
+                Session::new(Some(id.to_string()), record.values)
+            }
+            _ => Session::new(None, HashMap::new()),
+        }
+    }
+
+    fn save(&self, session: Session, response: &mut Response) {
+        let Session { id, values, rotate } = session;
+
+        if values.is_empty() {
+            if let Some(id) = id {
+                self.remove(&id);
+                self.set_cookie(response, Cookie::removal(self.cookie_name.as_str()));
+            }
+            return;
+        }
+
+        let id = match id {
+            Some(id) if !rotate => id,
+            old => {
+                if let Some(old) = old {
+                    self.remove(&old);
+                }
+                let id = self.generate_id();
+                self.set_cookie(
+                    response,
+                    Cookie::new(self.cookie_name.as_str(), id.as_str()),
+                );
+                id
+            }
+        };
+        let record = Record {
+            values,
+            expires: SystemTime::now() + self.idle_timeout,
+        };
+        if let Err(e) = self.store.save(&id, &record) {
+            error!("Failed to save session : {}", e);
+        }
+    }
+
+    fn remove(&self, id: &str) {
+        if let Err(e) = self.store.remove(id) {
+            error!("Failed to remove session : {}", e);
+        }
+    }
+
+    // 만료는 서버가 관리하므로 쿠키는 브라우저를 닫으면 사라지는 세션 쿠키로 보낸다.
+    fn set_cookie(&self, response: &mut Response, cookie: Cookie) {
+        let cookie = cookie
+            .path("/")
+            .http_only(true)
+            .secure(self.secure)
+            .same_site(self.same_site);
+        if let Err(e) = response.set_cookie(&cookie) {
+            error!("Failed to set session cookie : {}", e);
+        }
+        // 세션 ID가 담긴 응답을 공유 캐시가 다른 사용자에게 주면 안 된다.
+        if response.header("Cache-Control").is_none() {
+            response.set_header("Cache-Control", "no-store");
+        }
+    }
+}
+
+impl<S: SessionStore, H: SessionHandler> Handler for Sessions<S, H> {
+    fn handle_request(&self, request: &Request) -> Response {
+        let mut session = self.load(request);
+        let mut response = self.inner.handle_request(request, &mut session);
+        self.save(session, &mut response);
+        response
+    }
+
+    fn handle_bad_request(&self, e: &ParseError) -> Response {
+        self.inner.handle_bad_request(e)
+    }
+}
+
+// generate_id()가 만든 것과 같은 모양인지 본다. 파일 저장소는 ID를 파일 이름으로 쓰므로 꼭 검사해야 한다.
+pub(crate) fn is_valid_id(id: &str) -> bool {
+    id.len() == ID_LEN
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::memory::MemoryStore;
+    use super::*;
+    use std::convert::TryFrom;
+    use std::thread;
+
+    // "/login"은 ID를 바꾸고 사용자를 넣고, "/logout"은 세션을 비운다. 응답 본문은 세션의 사용자다.
+    struct Login;
+
+    impl SessionHandler for Login {
+        fn handle_request(&self, request: &Request, session: &mut Session) -> Response {
+            match request.path() {
+                "/login" => {
+                    session.rotate();
+                    session.set("user", "alice");
+                }
+                "/visit" => {
+                    let visits = session.get("visits").map_or(0, |v| v.parse().unwrap());
+                    session.set("visits", (visits + 1).to_string());
+                }
+                "/logout" => session.clear(),
+                _ => {}
+            }
+            Response::new(
+                StatusCode::Ok,
+                Some(session.get("user").unwrap_or("").to_string()),
+            )
+        }
+    }
+
+    fn sessions(store: Arc<MemoryStore>) -> Sessions<Arc<MemoryStore>, Login> {
+        Sessions::new(store, Login).secure(false)
+    }
+
+    fn get(handler: &impl Handler, path: &str, sid: Option<&str>) -> Response {
+        let cookie = sid
+            .map(|sid| format!("Cookie: sid={}\r\n", sid))
+            .unwrap_or_default();
+        let raw = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n{}\r\n", path, cookie);
+        handler.handle_request(&Request::try_from(raw.as_bytes()).unwrap())
+    }
+
+    // Set-Cookie로 받은 세션 ID. 지우는 쿠키면 빈 문자열이다.
+    fn issued(response: &Response) -> Option<String> {
+        let cookie = response.header("Set-Cookie")?;
+        let (value, attributes) = cookie.strip_prefix("sid=")?.split_once(';')?;
+        assert!(attributes.contains("HttpOnly"), "{}", cookie);
+        assert!(attributes.contains("Path=/"), "{}", cookie);
+        assert_eq!(response.header("Cache-Control"), Some("no-store"));
+        Some(value.to_string())
+    }
+
+    fn body(response: &Response) -> &str {
+        std::str::from_utf8(response.body().unwrap_or_default()).unwrap()
+    }
+
+    #[test]
+    fn issues_the_cookie_only_when_the_session_is_created() {
+        let store = Arc::new(MemoryStore::new(10));
+        let handler = sessions(Arc::clone(&store));
+
+        // 값이 없는 방문자에게는 쿠키를 주지 않는다.
+        assert_eq!(issued(&get(&handler, "/", None)), None);
+        assert!(store.is_empty());
+
+        let sid = issued(&get(&handler, "/visit", None)).unwrap();
+        assert!(is_valid_id(&sid));
+        let response = get(&handler, "/visit", Some(&sid));
+        assert_eq!(issued(&response), None);
+        assert_eq!(store.load(&sid).unwrap().values["visits"], "2");
+        assert_eq!(store.len(), 1);
+    }
+
+    #[test]
+    fn rotate_moves_the_session_to_a_new_id() {
+        let store = Arc::new(MemoryStore::new(10));
+        let handler = sessions(Arc::clone(&store));
+        let before = issued(&get(&handler, "/visit", None)).unwrap();
+
+        let response = get(&handler, "/login", Some(&before));
+        let after = issued(&response).unwrap();
+        assert_ne!(after, before);
+        assert_eq!(body(&response), "alice");
+        assert!(store.load(&before).is_none());
+        assert_eq!(store.load(&after).unwrap().values["visits"], "1");
+        // 예전 ID로는 로그인한 세션을 쓸 수 없다.
+        assert_eq!(body(&get(&handler, "/", Some(&before))), "");
+        assert_eq!(body(&get(&handler, "/", Some(&after))), "alice");
+
+        let response = get(&handler, "/logout", Some(&after));
+        assert_eq!(issued(&response).as_deref(), Some(""));
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn sessions_expire_after_the_idle_timeout() {
+        let store = Arc::new(MemoryStore::new(10));
+        let handler = sessions(store).idle_timeout(Duration::from_millis(300));
+        let sid = issued(&get(&handler, "/login", None)).unwrap();
+
+        // 요청이 올 때마다 만료가 미뤄진다.
+        for _ in 0..3 {
+            thread::sleep(Duration::from_millis(150));
+            assert_eq!(body(&get(&handler, "/visit", Some(&sid))), "alice");
+        }
+        thread::sleep(Duration::from_millis(400));
+        assert_eq!(body(&get(&handler, "/", Some(&sid))), "");
+    }
+
+    #[test]
+    fn ignores_malformed_ids() {
+        let store = Arc::new(MemoryStore::new(10));
+        let handler = sessions(Arc::clone(&store));
+        let sid = issued(&get(&handler, "/login", None)).unwrap();
+
+        for forged in [&sid[1..], &format!("{}=", sid), "../../etc/passwd", ""] {
+            assert_eq!(body(&get(&handler, "/", Some(forged))), "", "{}", forged);
+        }
+        // 알 수 없는 ID를 그대로 쓰지 않고 새 ID를 준다. (세션 고정)
+        let forged = "A".repeat(ID_LEN);
+        let issued_id = issued(&get(&handler, "/visit", Some(&forged))).unwrap();
+        assert_ne!(issued_id, forged);
+        assert!(store.load(&forged).is_none());
+    }
+}
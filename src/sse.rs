@@ -0,0 +1,264 @@
+// Server-Sent Events (text/event-stream)
+//
+// 핸들러가 sse::response()를 리턴하면 헤더를 보낸 뒤 연결을 열어 둔 채로 이벤트를 하나씩 보낸다.
+// 연결 하나가 끝날 때까지 작업자 스레드 하나를 차지하므로 구독자 수에 맞게 스레드 개수를 정해야 한다.
+// HTTP/2 요청에는 HTTP_1_1_REQUIRED로 답해서 브라우저가 HTTP/1.1로 다시 연결하게 한다.
+//
+// let (sender, receiver) = mpsc::channel();
+// ... sender.send(Event::new("42").event("temperature")) ...
+// sse::response(request, move |stream| stream.forward(&receiver, Duration::from_secs(15)))
+use crate::http::{Request, Response, StatusCode};
+use std::io::{ErrorKind, Result as IoResult, Write};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+// 이벤트 하나. data만 필수이고 나머지는 필요할 때만 붙인다.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    data: String,
+    event: Option<String>,
+    id: Option<String>,
+    retry: Option<Duration>,
+}
+
+impl Event {
+    // 여러 줄짜리 data는 줄마다 data: 필드로 나눠서 보내고, 브라우저는 다시 줄바꿈으로 이어 붙인다.
+    pub fn new(data: impl Into<String>) -> Self {
+        Self {
+            data: data.into(),
+            event: None,
+            id: None,
+            retry: None,
+        }
+    }
+
+    // 이벤트 이름. 없으면 브라우저에서 message 이벤트로 받는다.
+    pub fn event(mut self, name: impl Into<String>) -> Self {
+        self.event = Some(name.into());
+        self
+    }
+
+    // 다시 연결할 때 브라우저가 Last-Event-ID 헤더로 돌려주는 값
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    // 연결이 끊기면 브라우저가 이만큼 기다렸다가 다시 연결한다.
+    pub fn retry(mut self, retry: Duration) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut out = String::new();
+        // 줄바꿈이 들어가면 필드가 끝나버리므로 한 줄짜리 필드에서는 빼버린다.
+        if let Some(event) = &self.event {
+            out.push_str(&format!("event: {}\n", single_line(event)));
+        }
+        // id에 NUL이 있으면 브라우저가 무시한다.
+        if let Some(id) = &self.id {
+            out.push_str(&format!("id: {}\n", single_line(id).replace('\0', "")));
+        }
+        if let Some(retry) = self.retry {
+            out.push_str(&format!("retry: {}\n", retry.as_millis()));
+        }
+        // \r\n, \r, \n 모두 줄바꿈이다.
+        for line in self.data.replace("\r\n", "\n").split(['\r', '\n']) {
+            out.push_str(&format!("data: {}\n", line));
+        }
+        out.push('\n');
+        out.into_bytes()
+    }
+}
+
+fn single_line(value: &str) -> String {
+    value.replace(['\r', '\n'], "")
+}
+
+// 열려 있는 이벤트 스트림. 쓰기가 실패하면 클라이언트가 연결을 끊은 것이므로 더 보내지 말고 리턴하면 된다.
+pub struct EventStream<'a> {
+    writer: &'a mut dyn Write,
+    last_event_id: Option<String>,
+}
+
+impl EventStream<'_> {
+    // 다시 연결한 브라우저가 마지막으로 받은 이벤트의 id. 이 뒤의 이벤트부터 다시 보내주면 된다.
+    pub fn last_event_id(&self) -> Option<&str> {
+        self.last_event_id.as_deref()
+    }
+
+    pub fn send(&mut self, event: &Event) -> IoResult<()> {
+        self.writer.write_all(&event.to_bytes())?;
+        self.writer.flush()
+    }
+
+    // :로 시작하는 줄은 브라우저가 무시한다. 보낼 이벤트가 없을 때 연결이 살아 있게 하는 하트비트로 쓴다.
+    pub fn comment(&mut self, text: &str) -> IoResult<()> {
+        let mut out = String::new();
+        for line in text.replace("\r\n", "\n").split(['\r', '\n']) {
+            out.push_str(&format!(": {}\n", line));
+        }
+        out.push('\n');
+        self.writer.write_all(out.as_bytes())?;
+        self.writer.flush()
+    }
+
+    // 채널로 들어오는 이벤트를 보낸다. heartbeat 동안 이벤트가 없으면 주석을 보내서 중간의 프록시가 연결을 끊지 않게 하고,
+    // 클라이언트가 떠났는지도 확인한다. 보내는 쪽이 모두 drop 되면 리턴한다.
+    pub fn forward(&mut self, events: &Receiver<Event>, heartbeat: Duration) -> IoResult<()> {
+        loop {
+            match events.recv_timeout(heartbeat) {
+                Ok(event) => self.send(&event)?,
+                Err(RecvTimeoutError::Timeout) => self.comment("heartbeat")?,
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+        }
+    }
+}
+
+// 이벤트 스트림 응답을 만든다. 헤더를 보낸 뒤 작업자 스레드에서 writer가 호출되고, writer가 리턴하면 연결을 닫는다.
+pub fn response(
+    request: &Request,
+    writer: impl FnOnce(&mut EventStream) -> IoResult<()> + Send + 'static,
+) -> Response {
+    let last_event_id = request
+        .header("Last-Event-ID")
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(str::to_string);
+
+    Response::stream(StatusCode::Ok, move |out| {
+        let mut stream = EventStream {
+            writer: out,
+            last_event_id,
+        };
+        match writer(&mut stream) {
+            // 구독자가 떠나는 것은 이벤트 스트림에서 흔한 일이라 오류로 보지 않는다.
+            Err(e) if is_disconnect(&e) => {
+                debug!("Event stream client disconnected : {}", e);
+                Ok(())
+            }
+            result => result,
+        }
+    })
+    .with_header("Content-Type", "text/event-stream; charset=utf-8")
+    .with_header("Cache-Control", "no-cache")
+    // nginx 같은 프록시가 이벤트를 모았다가 보내지 않게 한다.
+    .with_header("X-Accel-Buffering", "no")
+}
+
+fn is_disconnect(e: &std::io::Error) -> bool {
+    matches!(
+        e.kind(),
+        ErrorKind::BrokenPipe
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::TimedOut
+            | ErrorKind::WouldBlock
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::thread;
+
+    fn text(event: Event) -> String {
+        String::from_utf8(event.to_bytes()).unwrap()
+    }
+
+    #[test]
+    fn multi_line_data() {
+        assert_eq!(text(Event::new("hello")), "data: hello\n\n");
+        assert_eq!(
+            text(Event::new("a\nb\r\nc\rd")),
+            "data: a\ndata: b\ndata: c\ndata: d\n\n"
+        );
+        // 빈 줄도 data: 필드로 남겨야 브라우저에서 같은 값이 된다.
+        assert_eq!(text(Event::new("a\n\nb")), "data: a\ndata: \ndata: b\n\n");
+        assert_eq!(text(Event::new("")), "data: \n\n");
+    }
+
+    #[test]
+    fn single_line_fields_drop_line_breaks() {
+        let event = Event::new("x")
+            .event("up\r\ndata: injected")
+            .id("1\n2\r3\0")
+            .retry(Duration::from_secs(3));
+        assert_eq!(
+            text(event),
+            "event: updata: injected\nid: 123\nretry: 3000\ndata: x\n\n"
+        );
+    }
+
+    fn stream_to_vec(
+        request: &str,
+        writer: impl FnOnce(&mut EventStream) -> IoResult<()> + Send + 'static,
+    ) -> Response {
+        let request = Request::try_from(request.as_bytes()).unwrap();
+        let mut buf = Vec::new();
+        response(&request, writer).send(&mut buf).unwrap();
+        Response::try_from(&buf[..]).unwrap()
+    }
+
+    #[test]
+    fn heartbeat_comments_while_idle() {
+        let (sender, receiver) = mpsc::channel();
+        let sending = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            sender.send(Event::new("late")).unwrap();
+        });
+        let response = stream_to_vec("GET /events HTTP/1.1\r\n\r\n", move |stream| {
+            stream.forward(&receiver, Duration::from_millis(20))
+        });
+        sending.join().unwrap();
+
+        assert_eq!(
+            response.header("Content-Type"),
+            Some("text/event-stream; charset=utf-8")
+        );
+        assert_eq!(response.header("Cache-Control"), Some("no-cache"));
+        let body = String::from_utf8(response.body().unwrap().to_vec()).unwrap();
+        // 이벤트가 오기 전에 하트비트를 보내고, 보내는 쪽이 사라지면 끝난다.
+        let (before, after) = body.split_once("data: late\n\n").unwrap();
+        assert!(before.starts_with(": heartbeat\n\n"), "{:?}", body);
+        assert!(
+            before.split(": heartbeat\n\n").all(str::is_empty),
+            "{:?}",
+            body
+        );
+        assert_eq!(after, "");
+    }
+
+    #[test]
+    fn comments_split_lines() {
+        let response = stream_to_vec("GET / HTTP/1.1\r\n\r\n", |stream| stream.comment("a\nb"));
+        assert_eq!(response.body(), Some(&b": a\n: b\n\n"[..]));
+    }
+
+    #[test]
+    fn last_event_id_is_passed_to_the_writer() {
+        let response = stream_to_vec("GET / HTTP/1.1\r\nLast-Event-ID:  42 \r\n\r\n", |stream| {
+            let id = stream.last_event_id().unwrap().to_string();
+            stream.send(&Event::new(id))
+        });
+        assert_eq!(response.body(), Some(&b"data: 42\n\n"[..]));
+
+        let response = stream_to_vec("GET / HTTP/1.1\r\nLast-Event-ID: \r\n\r\n", |stream| {
+            assert_eq!(stream.last_event_id(), None);
+            Ok(())
+        });
+        assert_eq!(response.body(), Some(&b""[..]));
+    }
+
+    #[test]
+    fn client_disconnect_is_not_an_error() {
+        let request = Request::try_from(&b"GET / HTTP/1.1\r\n\r\n"[..]).unwrap();
+        let disconnected = response(&request, |_| Err(ErrorKind::BrokenPipe.into()));
+        assert!(disconnected.send(&mut Vec::new()).is_ok());
+        let failed = response(&request, |_| Err(ErrorKind::InvalidData.into()));
+        assert!(failed.send(&mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,236 @@
+use crate::http::{Request, Response, StatusCode};
+use crate::server::Handler;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+// Host 헤더를 보고 요청을 사이트별 핸들러로 나눠준다.
+// 한 프로세스, 한 포트에서 여러 사이트를 서비스할 수 있다.
+//
+// 찾는 순서는 정확히 일치하는 이름 -> 가장 긴 와일드카드(*.example.test) -> 기본 핸들러 순서다.
+// Host 헤더가 없거나 형식이 잘못되었으면 400, 어디에도 맞지 않으면 421 Misdirected Request로 응답한다.
+pub struct VirtualHosts {
+    exact: HashMap<String, usize>,
+    // (".example.test", 핸들러 번호). 긴 접미사가 먼저 오도록 정렬해 둔다.
+    wildcards: Vec<(String, usize)>,
+    default: Option<usize>,
+    // 여러 이름이 같은 핸들러를 가리킬 수 있도록 핸들러는 한 곳에 모아두고 번호로 가리킨다.
+    handlers: Vec<Box<dyn Handler>>,
+}
+
+impl VirtualHosts {
+    pub fn new() -> Self {
+        Self {
+            exact: HashMap::new(),
+            wildcards: Vec::new(),
+            default: None,
+            handlers: Vec::new(),
+        }
+    }
+
+    // names에는 "example.test" 같은 이름이나 "*.example.test" 같은 와일드카드를 넣을 수 있다.
+    // 와일드카드는 example.test 자체는 포함하지 않고 그 아래의 모든 하위 도메인에 맞는다.
+    pub fn host<S: AsRef<str>>(mut self, names: &[S], handler: impl Handler + 'static) -> Self {
+        let index = self.push(handler);
+        for name in names {
+            let name = normalize(name.as_ref());
+            match name.strip_prefix('*') {
+                Some(suffix) => self.wildcards.push((suffix.to_string(), index)),
+                None => {
+                    self.exact.insert(name, index);
+                }
+            }
+        }
+        self.wildcards
+            .sort_by_key(|(suffix, _)| Reverse(suffix.len()));
+        self
+    }
+
+    // 어떤 이름에도 맞지 않는 요청을 처리할 핸들러
+    pub fn default_host(mut self, handler: impl Handler + 'static) -> Self {
+        self.default = Some(self.push(handler));
+        self
+    }
+
+    fn push(&mut self, handler: impl Handler + 'static) -> usize {
+        self.handlers.push(Box::new(handler));
+        self.handlers.len() - 1
+    }
+
+    fn find(&self, host: &str) -> Option<&dyn Handler> {
+        let index = match self.exact.get(host) {
+            Some(index) => Some(*index),
+            None => self
+                .wildcards
+                .iter()
+                .find(|(suffix, _)| host.len() > suffix.len() && host.ends_with(suffix.as_str()))
+                .map(|(_, index)| *index)
+                .or(self.default),
+        }?;
+        Some(self.handlers[index].as_ref())
+    }
+}
+
+impl Default for VirtualHosts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Handler for VirtualHosts {
+    fn handle_request(&self, request: &Request) -> Response {
+        let host = match request.header("Host").and_then(host_name) {
+            Some(host) => host,
+            None => return Response::new(StatusCode::BadRequest, None),
+        };
+
+        match self.find(&host) {
+            Some(handler) => handler.handle_request(request),
+            None => {
+                debug!("No virtual host for {}", host);
+                Response::new(StatusCode::MisdirectedRequest, None)
+            }
+        }
+    }
+
+    // 호스트마다 다른 인증이나 제한을 걸 수 있으므로 업그레이드 요청도 그 호스트의 핸들러가 검사한다.
+    fn accept_upgrade<'buf>(&self, request: &Request<'buf>) -> Result<Request<'buf>, Response> {
+        let host = match request.header("Host").and_then(host_name) {
+            Some(host) => host,
+            None => return Err(Response::new(StatusCode::BadRequest, None)),
+        };
+        match self.find(&host) {
+            Some(handler) => handler.accept_upgrade(request),
+            None => Err(Response::new(StatusCode::MisdirectedRequest, None)),
+        }
+    }
+}
+
+// "Example.Test:4000" -> "example.test", "[::1]:4000" -> "[::1]"
+// 이름을 비교할 수 있도록 포트와 끝의 점을 떼고 소문자로 바꾼다.
+pub(crate) fn host_name(host: &str) -> Option<String> {
+    let host = host.trim();
+    let name = if host.starts_with('[') {
+        // IPv6 주소는 대괄호 안에 콜론이 들어 있으므로 닫는 대괄호까지를 이름으로 본다.
+        let end = host.find(']')?;
+        let (name, port) = host.split_at(end + 1);
+        if !port.is_empty() && !is_port(port.strip_prefix(':')?) {
+            return None;
+        }
+        name
+    } else {
+        match host.rsplit_once(':') {
+            Some((name, port)) if is_port(port) => name,
+            Some(_) => return None,
+            None => host,
+        }
+    };
+
+    let valid = !name.is_empty()
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-._[]:".contains(&b));
+    if valid {
+        Some(normalize(name))
+    } else {
+        None
+    }
+}
+
+fn is_port(port: &str) -> bool {
+    !port.is_empty() && port.parse::<u16>().is_ok()
+}
+
+fn normalize(name: &str) -> String {
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 응답 본문으로 자기 이름을 돌려준다.
+    struct Site(&'static str);
+
+    impl Handler for Site {
+        fn handle_request(&self, _request: &Request) -> Response {
+            Response::new(StatusCode::Ok, Some(self.0.to_string()))
+        }
+    }
+
+    fn hosts() -> VirtualHosts {
+        VirtualHosts::new()
+            .host(&["example.test", "www.example.test"], Site("exact"))
+            .host(&["*.example.test"], Site("wildcard"))
+            .host(&["*.api.example.test"], Site("api"))
+            .default_host(Site("default"))
+    }
+
+    fn request(hosts: &VirtualHosts, host: Option<&str>) -> Response {
+        let raw = match host {
+            Some(host) => format!("GET / HTTP/1.1\r\nHost: {}\r\n\r\n", host),
+            None => "GET / HTTP/1.1\r\n\r\n".to_string(),
+        };
+        hosts.handle_request(&Request::try_from(raw.as_bytes()).unwrap())
+    }
+
+    fn site(hosts: &VirtualHosts, host: &str) -> String {
+        let response = request(hosts, Some(host));
+        assert_eq!(response.status_code(), StatusCode::Ok, "{}", host);
+        String::from_utf8(response.body().unwrap().to_vec()).unwrap()
+    }
+
+    #[test]
+    fn exact_before_wildcard_before_default() {
+        let hosts = hosts();
+        assert_eq!(site(&hosts, "example.test"), "exact");
+        assert_eq!(site(&hosts, "www.example.test"), "exact");
+        assert_eq!(site(&hosts, "blog.example.test"), "wildcard");
+        // 더 긴 와일드카드가 먼저 맞는다.
+        assert_eq!(site(&hosts, "v1.api.example.test"), "api");
+        assert_eq!(site(&hosts, "other.test"), "default");
+        // 와일드카드는 접미사 자체나 점이 없는 이름에는 맞지 않는다.
+        assert_eq!(site(&hosts, "badexample.test"), "default");
+    }
+
+    #[test]
+    fn host_with_port_and_case() {
+        let hosts = hosts();
+        assert_eq!(site(&hosts, "Example.Test:4000"), "exact");
+        assert_eq!(site(&hosts, "blog.example.test.:8080"), "wildcard");
+        assert_eq!(host_name("[::1]:4000").as_deref(), Some("[::1]"));
+    }
+
+    #[test]
+    fn missing_or_invalid_host_is_bad_request() {
+        let hosts = hosts();
+        for host in [
+            None,
+            Some(""),
+            Some("example.test:port"),
+            Some("exa mple.test"),
+            Some("[::1]x"),
+        ] {
+            assert_eq!(
+                request(&hosts, host).status_code(),
+                StatusCode::BadRequest,
+                "{:?}",
+                host
+            );
+        }
+    }
+
+    #[test]
+    fn no_match_is_misdirected() {
+        let hosts = VirtualHosts::new().host(&["example.test"], Site("exact"));
+        assert_eq!(
+            request(&hosts, Some("other.test")).status_code(),
+            StatusCode::MisdirectedRequest
+        );
+        let raw = "GET /chat HTTP/1.1\r\nHost: other.test\r\nUpgrade: websocket\r\n\r\n";
+        let upgrade = hosts.accept_upgrade(&Request::try_from(raw.as_bytes()).unwrap());
+        assert_eq!(
+            upgrade.unwrap_err().status_code(),
+            StatusCode::MisdirectedRequest
+        );
+    }
+}
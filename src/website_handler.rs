@@ -1,54 +1,341 @@
+use super::compression::{self, Encoding};
 use super::http::{Method, Request, Response, StatusCode};
 use super::server::Handler;
+use std::collections::HashMap;
 use std::fs;
+use std::path::{Path, PathBuf};
+
+// 정적 파일에 쓸 수 있는 메서드. 405와 OPTIONS 응답의 Allow 헤더에 넣는다.
+const ALLOWED_METHODS: &str = "GET, HEAD, OPTIONS";
 
 pub struct WebsiteHandler {
     public_path: String,
+    index: String,
+    // 확장자 -> MIME 타입. 기본 목록에 없는 확장자나 기본값을 바꾸고 싶은 확장자만 넣으면 된다.
+    mime_types: HashMap<String, String>,
+    // 모든 응답에 붙일 헤더
+    headers: Vec<(String, String)>,
+    redirects: Vec<Redirect>,
+}
+
+// from 경로로 들어온 요청을 to 주소로 보낸다.
+#[derive(Clone, Debug)]
+pub struct Redirect {
+    pub from: String,
+    pub to: String,
+    pub status: StatusCode,
 }
 
 impl WebsiteHandler {
     pub fn new(public_path: String) -> Self {
-        Self { public_path }
+        // read_file()에서 정규화된 경로가 public_path로 시작하는지 비교하기 때문에 public_path도 정규화해 둔다.
+        // 그렇지 않으면 "public" 같은 상대 경로를 줬을 때 모든 파일이 디렉터리 횡단으로 판단된다.
+        let public_path = match fs::canonicalize(&public_path) {
+            Ok(path) => path.to_string_lossy().into_owned(),
+            Err(_) => public_path,
+        };
+
+        Self {
+            public_path,
+            index: "index.html".to_string(),
+            mime_types: HashMap::new(),
+            headers: Vec::new(),
+            redirects: Vec::new(),
+        }
+    }
+
+    // 디렉터리 경로(/로 끝나는 경로)로 요청이 오면 보여줄 파일 이름
+    pub fn index(mut self, index: String) -> Self {
+        self.index = index;
+        self
     }
 
-    fn read_file(&self, file_path: &str) -> Option<String> {
+    pub fn mime_types(mut self, mime_types: HashMap<String, String>) -> Self {
+        self.mime_types = mime_types;
+        self
+    }
+
+    pub fn headers(mut self, headers: Vec<(String, String)>) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    pub fn redirects(mut self, redirects: Vec<Redirect>) -> Self {
+        self.redirects = redirects;
+        self
+    }
+
+    // public_path 안에 있는 파일의 실제 경로. 없거나 public_path 밖을 가리키면 None이다.
+    fn resolve(&self, file_path: &str) -> Option<PathBuf> {
         let path = format!("{}/{}", self.public_path, file_path);
 
         // ../../../../name 이런 경로를 /name으로 바꿔준다.
         match fs::canonicalize(path) {
             Ok(path) => {
                 if path.starts_with(&self.public_path) {
-                    fs::read_to_string(path).ok()
+                    Some(path)
                 } else {
-                    println!("Directory Traversal Attack Attempted!");
+                    warn!("Directory Traversal Attack Attempted!");
                     None
                 }
             }
             Err(_) => None,
         }
+    }
+
+    fn read_file(&self, file_path: &str) -> Option<Vec<u8>> {
+        // 이미지 같은 파일도 보낼 수 있도록 문자열이 아니라 바이트로 읽는다.
         // ok()메서드는 Result를 살펴본 다음에 ok 라면 그 값을 받고 그걸 Option으로 변환하게 된다.
         // fs::read_to_string(path).ok()
+        fs::read(self.resolve(file_path)?).ok()
+    }
+
+    fn serve_file(&self, request: &Request, file_path: &str) -> Response {
+        let contents = match self.read_file(file_path) {
+            Some(contents) => contents,
+            None => return Response::new(StatusCode::NotFound, None),
+        };
+        let content_type = self.content_type(file_path);
+
+        // style.css.br, style.css.gz 처럼 미리 압축해 둔 파일이 옆에 있으면 매번 압축하지 않고 그걸 보낸다.
+        let available: Vec<Encoding> = [Encoding::Brotli, Encoding::Gzip]
+            .into_iter()
+            .filter(|&encoding| {
+                precompressed_path(file_path, encoding)
+                    .and_then(|path| self.resolve(&path))
+                    .is_some()
+            })
+            .collect();
+        if available.is_empty() {
+            return Response::from_bytes(StatusCode::Ok, contents)
+                .with_header("Content-Type", content_type);
+        }
+
+        let precompressed = compression::negotiate(request, &available).and_then(|encoding| {
+            let contents = self.read_file(&precompressed_path(file_path, encoding)?)?;
+            Some((encoding, contents))
+        });
+        let mut response = match precompressed {
+            Some((encoding, contents)) => Response::from_bytes(StatusCode::Ok, contents)
+                .with_header("Content-Encoding", encoding.token()),
+            None => Response::from_bytes(StatusCode::Ok, contents),
+        };
+        response.set_header("Content-Type", content_type);
+        compression::add_vary(&mut response, "Accept-Encoding");
+        response
+    }
+
+    // 설정에 지정된 MIME 타입이 있으면 그걸 쓰고, 없으면 기본 목록에서 찾는다.
+    fn content_type(&self, file_path: &str) -> String {
+        let extension = Path::new(file_path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+
+        match self.mime_types.get(&extension) {
+            Some(mime_type) => mime_type.clone(),
+            None => default_mime_type(&extension).to_string(),
+        }
+    }
+
+    fn redirect(&self, path: &str) -> Option<Response> {
+        let redirect = self
+            .redirects
+            .iter()
+            .find(|redirect| redirect.from == path)?;
+        Some(Response::new(redirect.status, None).with_header("Location", redirect.to.as_str()))
+    }
+}
+
+fn precompressed_path(file_path: &str, encoding: Encoding) -> Option<String> {
+    Some(format!("{}.{}", file_path, encoding.extension()?))
+}
+
+fn default_mime_type(extension: &str) -> &'static str {
+    match extension {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "xml" => "application/xml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream",
     }
 }
 
 impl Handler for WebsiteHandler {
-    fn handle_request(&mut self, request: &Request) -> Response {
-        match request.method() {
-            Method::GET => match request.path() {
-                "/" => Response::new(StatusCode::Ok, self.read_file("index.html")),
-                "/hello" => Response::new(StatusCode::Ok, self.read_file("hello.html")),
-                "/hello2" => Response::new(StatusCode::Ok, Some("<h1>Hello</h1>".to_string())),
-                // 아래는 그대로 하면 데렉터리 횡단 취약성을 가지게 된다. 공격자는 서버가 실행되는 시스템에서 임의의 파일을 읽을수 있기 때문이다
-                // path => match self.read_file(path) {
-                //     Some(contents) => Response::new(StatusCode::Ok, Some(contents)),
-                //     None => Response::new(StatusCode::NotFound, None),
-                // },
-                path => match self.read_file(path) {
-                    Some(contents) => Response::new(StatusCode::Ok, Some(contents)),
-                    None => Response::new(StatusCode::NotFound, None),
+    fn handle_request(&self, request: &Request) -> Response {
+        let mut response = match request.method() {
+            // HEAD에도 GET과 같은 응답을 만든다. 본문은 서버가 보내지 않는다.
+            Method::GET | Method::HEAD => match self.redirect(request.path()) {
+                Some(response) => response,
+                None => match request.path() {
+                    "/hello" => self.serve_file(request, "hello.html"),
+                    "/hello2" => Response::new(StatusCode::Ok, Some("<h1>Hello</h1>".to_string())),
+                    // 아래는 그대로 하면 데렉터리 횡단 취약성을 가지게 된다. 공격자는 서버가 실행되는 시스템에서 임의의 파일을 읽을수 있기 때문이다
+                    // path => match self.read_file(path) {
+                    //     Some(contents) => Response::new(StatusCode::Ok, Some(contents)),
+                    //     None => Response::new(StatusCode::NotFound, None),
+                    // },
+                    // "/" 처럼 디렉터리를 요청하면 그 안의 인덱스 파일을 보여준다.
+                    path if path.ends_with('/') => {
+                        self.serve_file(request, &format!("{}{}", path, self.index))
+                    }
+                    path => self.serve_file(request, path),
                 },
             },
-            _ => Response::new(StatusCode::NotFound, None),
+            Method::OPTIONS => {
+                Response::new(StatusCode::NoContent, None).with_header("Allow", ALLOWED_METHODS)
+            }
+            // 정적 파일은 읽기만 할 수 있다. 없는 경로라고 답하지 않고 허용하는 메서드를 알려준다.
+            _ => Response::new(StatusCode::MethodNotAllowed, None)
+                .with_header("Allow", ALLOWED_METHODS),
+        };
+
+        for (name, value) in &self.headers {
+            // 미리 압축한 파일에 붙인 Vary: Accept-Encoding을 덮어쓰면 캐시가 잘못된 본문을 돌려줄 수 있다.
+            if name.eq_ignore_ascii_case("Vary") {
+                for name in value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                {
+                    compression::add_vary(&mut response, name);
+                }
+            } else {
+                response.set_header(name, value.as_str());
+            }
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::testing::TestServer;
+    use crate::server::Server;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // 테스트마다 새로 만드는 디렉터리. 값이 사라질 때 지운다.
+    // 공개 디렉터리는 public이고, 그 옆에 밖에서 읽으면 안 되는 secret.txt를 둔다.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            static NEXT: AtomicUsize = AtomicUsize::new(0);
+            let path = std::env::temp_dir().join(format!(
+                "http_server-website-{}-{}",
+                std::process::id(),
+                NEXT.fetch_add(1, Ordering::Relaxed)
+            ));
+            fs::create_dir_all(path.join("public/docs")).unwrap();
+            fs::write(path.join("public/index.html"), "<h1>index</h1>").unwrap();
+            fs::write(path.join("public/docs/guide.txt"), "guide").unwrap();
+            fs::write(path.join("secret.txt"), "secret").unwrap();
+            Self(path)
+        }
+
+        fn handler(&self) -> WebsiteHandler {
+            WebsiteHandler::new(self.0.join("public").to_string_lossy().into_owned())
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn send(server: &TestServer, method: &str, path: &str) -> Vec<u8> {
+        let mut stream = TcpStream::connect(server.addr()).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            method, path
+        )
+        .unwrap();
+        let mut buf = Vec::new();
+        stream.read_to_end(&mut buf).unwrap();
+        buf
+    }
+
+    fn request(handler: &WebsiteHandler, method: &str, path: &str) -> Response {
+        let raw = format!("{} {} HTTP/1.1\r\nHost: localhost\r\n\r\n", method, path);
+        handler.handle_request(&Request::try_from(raw.as_bytes()).unwrap())
+    }
+
+    #[test]
+    fn head_has_get_headers_without_body() {
+        let dir = TempDir::new();
+        let server = TestServer::start(Server::new("127.0.0.1:0".to_string()), dir.handler());
+        for path in ["/", "/docs/guide.txt", "/missing"] {
+            let get = Response::try_from(&send(&server, "GET", path)[..]).unwrap();
+            let head = Response::try_from_head_response(&send(&server, "HEAD", path)).unwrap();
+            assert_eq!(head.status_code(), get.status_code(), "{}", path);
+            assert_eq!(
+                head.headers().collect::<Vec<_>>(),
+                get.headers().collect::<Vec<_>>(),
+                "{}",
+                path
+            );
+            assert_eq!(head.body(), None);
+        }
+        let get = Response::try_from(&send(&server, "GET", "/")[..]).unwrap();
+        assert_eq!(get.body(), Some(&b"<h1>index</h1>"[..]));
+        assert_eq!(get.header("Content-Type"), Some("text/html; charset=utf-8"));
+        // HEAD 응답을 GET처럼 읽으면 본문이 모자라야 한다.
+        assert!(Response::try_from(&send(&server, "HEAD", "/")[..]).is_err());
+    }
+
+    #[test]
+    fn options_and_unsupported_methods_list_allowed_methods() {
+        let dir = TempDir::new();
+        let handler = dir.handler();
+        let response = request(&handler, "OPTIONS", "/index.html");
+        assert_eq!(response.status_code(), StatusCode::NoContent);
+        assert_eq!(response.header("Allow"), Some("GET, HEAD, OPTIONS"));
+        for method in ["POST", "PUT", "DELETE", "PATCH"] {
+            let response = request(&handler, method, "/index.html");
+            assert_eq!(
+                response.status_code(),
+                StatusCode::MethodNotAllowed,
+                "{}",
+                method
+            );
+            assert_eq!(response.header("Allow"), Some("GET, HEAD, OPTIONS"));
+        }
+    }
+
+    #[test]
+    fn refuses_directory_traversal() {
+        let dir = TempDir::new();
+        let handler = dir.handler();
+        for path in [
+            "/../secret.txt",
+            "/docs/../../secret.txt",
+            "/%2e%2e/secret.txt",
+            "/docs/%2E%2E/%2E%2E/secret.txt",
+        ] {
+            let response = request(&handler, "GET", path);
+            assert_eq!(response.status_code(), StatusCode::NotFound, "{}", path);
+            assert_ne!(response.body(), Some(&b"secret"[..]), "{}", path);
         }
+        // 공개 디렉터리 안에서 오가는 ..는 괜찮다.
+        let response = request(&handler, "GET", "/docs/../docs/guide.txt");
+        assert_eq!(response.body(), Some(&b"guide"[..]));
     }
 }
@@ -0,0 +1,322 @@
+use super::WebSocketError;
+use std::io::{ErrorKind, Read, Write};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(super) enum OpCode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl OpCode {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0x0 => Some(Self::Continuation),
+            0x1 => Some(Self::Text),
+            0x2 => Some(Self::Binary),
+            0x8 => Some(Self::Close),
+            0x9 => Some(Self::Ping),
+            0xa => Some(Self::Pong),
+            _ => None,
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            Self::Continuation => 0x0,
+            Self::Text => 0x1,
+            Self::Binary => 0x2,
+            Self::Close => 0x8,
+            Self::Ping => 0x9,
+            Self::Pong => 0xa,
+        }
+    }
+
+    // Close, Ping, Pong은 제어 프레임으로, 나눠서 보낼 수 없고 125바이트를 넘을 수 없다.
+    pub(super) fn is_control(self) -> bool {
+        matches!(self, Self::Close | Self::Ping | Self::Pong)
+    }
+}
+
+pub(super) struct Frame {
+    pub(super) fin: bool,
+    pub(super) opcode: OpCode,
+    pub(super) payload: Vec<u8>,
+}
+
+//  0                   1                   2                   3
+// +-+-+-+-+-------+-+-------------+-------------------------------+
+// |F|R|R|R| opcode|M| Payload len |    Extended payload length    |
+// |I|S|S|S|  (4)  |A|     (7)     |             (16/64)           |
+// |N|V|V|V|       |S|             |                               |
+// +-+-+-+-+-------+-+-------------+-------------------------------+
+// |                    Masking-key (32비트)                        |
+// +---------------------------------------------------------------+
+// 클라이언트가 보낸 프레임을 읽는다. 클라이언트 프레임은 반드시 마스킹되어 있어야 한다. (RFC 6455 5.2)
+pub(super) fn read_frame(stream: &mut impl Read, max_len: usize) -> Result<Frame, WebSocketError> {
+    let mut header = [0; 2];
+    // 프레임을 하나도 읽지 않은 상태에서 시간이 다 되었다면 다시 읽어도 된다.
+    match stream.read(&mut header[..1]) {
+        Ok(0) => return Err(WebSocketError::Io(ErrorKind::UnexpectedEof.into())),
+        Ok(_) => {}
+        Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+            return Err(WebSocketError::Timeout)
+        }
+        Err(e) => return Err(WebSocketError::Io(e)),
+    }
+    stream.read_exact(&mut header[1..])?;
+
+    let fin = header[0] & 0x80 != 0;
+    // 확장을 협상하지 않았으므로 RSV 비트는 모두 0이어야 한다.
+    if header[0] & 0x70 != 0 {
+        return Err(WebSocketError::Protocol("reserved bits are set"));
+    }
+    let opcode =
+        OpCode::from_u8(header[0] & 0x0f).ok_or(WebSocketError::Protocol("unknown opcode"))?;
+    if header[1] & 0x80 == 0 {
+        return Err(WebSocketError::Protocol("client frames must be masked"));
+    }
+
+    let len = match header[1] & 0x7f {
+        126 => {
+            let mut len = [0; 2];
+            stream.read_exact(&mut len)?;
+            u16::from_be_bytes(len) as u64
+        }
+        127 => {
+            let mut len = [0; 8];
+            stream.read_exact(&mut len)?;
+            let len = u64::from_be_bytes(len);
+            if len >> 63 != 0 {
+                return Err(WebSocketError::Protocol("invalid payload length"));
+            }
+            len
+        }
+        len => len as u64,
+    };
+
+    if opcode.is_control() && (!fin || len > 125) {
+        return Err(WebSocketError::Protocol("invalid control frame"));
+    }
+    // 길이를 보고 바로 거절해야 거대한 버퍼를 할당하지 않는다.
+    if len > max_len as u64 {
+        return Err(WebSocketError::TooLarge);
+    }
+
+    let mut mask = [0; 4];
+    stream.read_exact(&mut mask)?;
+    let mut payload = vec![0; len as usize];
+    stream.read_exact(&mut payload)?;
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+
+    Ok(Frame {
+        fin,
+        opcode,
+        payload,
+    })
+}
+
+// 서버가 보내는 프레임은 마스킹하지 않는다.
+pub(super) fn write_frame(
+    stream: &mut impl Write,
+    fin: bool,
+    opcode: OpCode,
+    payload: &[u8],
+) -> std::io::Result<()> {
+    let mut frame = Vec::with_capacity(10);
+    frame.push(if fin { 0x80 } else { 0 } | opcode.to_u8());
+    match payload.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xffff => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    stream.write_all(&frame)?;
+    stream.flush()
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    // 클라이언트가 보내는 것처럼 마스킹한 프레임. first는 FIN, RSV, opcode가 든 첫 바이트다.
+    pub(in crate::websocket) fn masked(first: u8, payload: &[u8]) -> Vec<u8> {
+        let mask = [0x37, 0xfa, 0x21, 0x3d];
+        let mut frame = vec![first];
+        match payload.len() {
+            len @ 0..=125 => frame.push(0x80 | len as u8),
+            len @ 126..=0xffff => {
+                frame.push(0x80 | 126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                frame.push(0x80 | 127);
+                frame.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        frame
+    }
+
+    fn read(bytes: &[u8]) -> Result<Frame, WebSocketError> {
+        read_frame(&mut Cursor::new(bytes), 1 << 20)
+    }
+
+    fn protocol_error(bytes: &[u8]) -> &'static str {
+        match read(bytes) {
+            Err(WebSocketError::Protocol(reason)) => reason,
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(frame) => panic!("unexpected {:?} frame", frame.opcode),
+        }
+    }
+
+    #[test]
+    fn unmasks_payloads() {
+        // RFC 6455 5.7의 예: 마스킹한 "Hello"
+        let bytes = [
+            0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58,
+        ];
+        let frame = read(&bytes).unwrap();
+        assert!(frame.fin);
+        assert_eq!(frame.opcode, OpCode::Text);
+        assert_eq!(frame.payload, b"Hello");
+    }
+
+    #[test]
+    fn reads_extended_lengths() {
+        for len in [125, 126, 0xffff, 0x10000] {
+            let payload: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let bytes = masked(0x82, &payload);
+            let header = match len {
+                0..=125 => 2,
+                126..=0xffff => 4,
+                _ => 10,
+            };
+            assert_eq!(bytes.len(), header + 4 + len, "{}", len);
+            let frame = read(&bytes).unwrap();
+            assert_eq!(frame.opcode, OpCode::Binary);
+            assert_eq!(frame.payload, payload, "{}", len);
+        }
+    }
+
+    #[test]
+    fn rejects_lengths_with_the_top_bit_set() {
+        let mut bytes = vec![0x82, 0x80 | 127];
+        bytes.extend_from_slice(&(1u64 << 63).to_be_bytes());
+        assert_eq!(protocol_error(&bytes), "invalid payload length");
+    }
+
+    #[test]
+    fn rejects_frames_over_the_limit_before_reading_them() {
+        // 길이만 보고 거절하므로 본문이 없어도 된다.
+        let mut bytes = vec![0x82, 0x80 | 127];
+        bytes.extend_from_slice(&(1u64 << 40).to_be_bytes());
+        assert!(matches!(read(&bytes), Err(WebSocketError::TooLarge)));
+        let bytes = masked(0x82, &[0; 11]);
+        assert!(matches!(
+            read_frame(&mut Cursor::new(bytes), 10),
+            Err(WebSocketError::TooLarge)
+        ));
+    }
+
+    #[test]
+    fn reads_fragments() {
+        let frame = read(&masked(0x01, b"Hel")).unwrap();
+        assert!(!frame.fin);
+        assert_eq!(frame.opcode, OpCode::Text);
+        let frame = read(&masked(0x80, b"lo")).unwrap();
+        assert!(frame.fin);
+        assert_eq!(frame.opcode, OpCode::Continuation);
+    }
+
+    #[test]
+    fn rejects_invalid_frames() {
+        let mut unmasked = masked(0x81, b"hi");
+        unmasked[1] &= 0x7f;
+        assert_eq!(protocol_error(&unmasked), "client frames must be masked");
+        for rsv in [0x40, 0x20, 0x10] {
+            assert_eq!(
+                protocol_error(&masked(0x81 | rsv, b"hi")),
+                "reserved bits are set"
+            );
+        }
+        assert_eq!(protocol_error(&masked(0x83, b"")), "unknown opcode");
+        assert_eq!(protocol_error(&masked(0x8b, b"")), "unknown opcode");
+    }
+
+    #[test]
+    fn rejects_long_or_fragmented_control_frames() {
+        assert!(read(&masked(0x89, &[0; 125])).is_ok());
+        assert_eq!(
+            protocol_error(&masked(0x89, &[0; 126])),
+            "invalid control frame"
+        );
+        for opcode in [0x08, 0x09, 0x0a] {
+            assert_eq!(
+                protocol_error(&masked(opcode, b"")),
+                "invalid control frame"
+            );
+        }
+    }
+
+    #[test]
+    fn closed_streams_and_timeouts() {
+        assert!(matches!(
+            read(b""),
+            Err(WebSocketError::Io(e)) if e.kind() == ErrorKind::UnexpectedEof
+        ));
+        // 프레임 중간에 끊기면 다시 읽을 수 없다.
+        let bytes = masked(0x81, b"Hello");
+        assert!(matches!(
+            read(&bytes[..bytes.len() - 1]),
+            Err(WebSocketError::Io(_))
+        ));
+        // 프레임을 읽기 시작하기 전의 타임아웃은 연결을 닫지 않는다.
+        assert!(matches!(
+            read_frame(&mut TimedOut, 10),
+            Err(WebSocketError::Timeout)
+        ));
+    }
+
+    struct TimedOut;
+
+    impl Read for TimedOut {
+        fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+            Err(ErrorKind::WouldBlock.into())
+        }
+    }
+
+    #[test]
+    fn writes_unmasked_frames() {
+        for len in [0, 125, 126, 0xffff, 0x10000] {
+            let payload = vec![b'x'; len];
+            let mut bytes = Vec::new();
+            write_frame(&mut bytes, true, OpCode::Binary, &payload).unwrap();
+            assert_eq!(bytes[0], 0x82);
+            assert_eq!(bytes[1] & 0x80, 0);
+            assert!(bytes.ends_with(&payload));
+            let header = bytes.len() - len;
+            assert_eq!(
+                header,
+                match len {
+                    0..=125 => 2,
+                    126..=0xffff => 4,
+                    _ => 10,
+                }
+            );
+        }
+    }
+}
@@ -0,0 +1,753 @@
+// WebSocket (RFC 6455) 서버 쪽 구현.
+//
+// 일반 요청처럼 GET으로 들어온 업그레이드 요청을 검사해서 101 Switching Protocols로 응답하고,
+// 그 뒤로는 같은 연결을 WebSocketHandler에게 넘겨서 메시지를 주고받게 한다.
+// 연결 하나가 끝날 때까지 작업자 스레드 하나를 차지하므로 연결 수에 맞게 스레드 개수를 정해야 한다.
+//
+// 업그레이드 요청은 서버 핸들러의 handle_request()로 가지 않지만, 그 전에 Handler::accept_upgrade()를 거친다.
+// Auth는 인증하지 못한 요청을 401로 거절하고 인증한 사용자를 request.principal()로 넘겨주며, RateLimit은
+// 업그레이드 요청 하나를 요청 하나로 센다. Cors는 적용되지 않으므로 Origin은 accept()에서 직접 검사해야 한다.
+//
+// struct Echo;
+// impl WebSocketHandler for Echo {
+//     fn handle(&self, _request: &Request, socket: &mut WebSocket) {
+//         while let Ok(message) = socket.recv() {
+//             match message {
+//                 Message::Text(text) => { let _ = socket.send_text(&text); }
+//                 Message::Close(_) => break,
+//                 _ => {}
+//             }
+//         }
+//     }
+// }
+// let server = Server::new("127.0.0.1:4000".to_string()).websocket("/echo", Echo);
+use crate::http::{Method, Request, Response, StatusCode};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use frame::{read_frame, write_frame, OpCode};
+use sha1::{Digest, Sha1};
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use std::io::{self, Read, Write};
+
+mod frame;
+
+// 클라이언트의 키 뒤에 붙여서 해시하는 고정 문자열
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+
+// Close 프레임에 담는 상태 코드 (RFC 6455 7.4.1)
+pub mod close_code {
+    pub const NORMAL: u16 = 1000;
+    pub const GOING_AWAY: u16 = 1001;
+    pub const PROTOCOL_ERROR: u16 = 1002;
+    pub const UNSUPPORTED_DATA: u16 = 1003;
+    pub const INVALID_DATA: u16 = 1007;
+    pub const POLICY_VIOLATION: u16 = 1008;
+    pub const TOO_LARGE: u16 = 1009;
+    pub const INTERNAL_ERROR: u16 = 1011;
+}
+
+// 경로 하나에 등록하는 WebSocket 핸들러. Handler처럼 여러 작업자 스레드가 함께 쓴다.
+pub trait WebSocketHandler: Send + Sync {
+    // 101로 응답하기 전에 호출된다. Err로 응답을 돌려주면 업그레이드하지 않고 그 응답을 보낸다.
+    // Origin 검사나 인증처럼 연결을 받을지 말지 정할 때 쓴다.
+    fn accept(&self, _request: &Request) -> Result<(), Response> {
+        Ok(())
+    }
+
+    // 지원하는 서브 프로토콜. 클라이언트가 Sec-WebSocket-Protocol로 보낸 것 중 여기에 있는 첫번째 것을 고른다.
+    fn protocols(&self) -> &[&str] {
+        &[]
+    }
+
+    // 조각난 메시지를 합친 크기가 이 값을 넘으면 1009로 연결을 닫는다.
+    fn max_message_size(&self) -> usize {
+        DEFAULT_MAX_MESSAGE_SIZE
+    }
+
+    // 연결이 끝날 때까지 메시지를 주고받는다. 리턴하면 아직 닫히지 않은 연결은 1000으로 닫는다.
+    fn handle(&self, request: &Request, socket: &mut WebSocket);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+    // Ping에는 recv()가 자동으로 Pong을 보낸다. Close를 보낸 뒤에는 보내지 않는다.
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    // 클라이언트가 연결을 닫았다. 상태 코드 없이 닫을 수도 있다.
+    Close(Option<CloseFrame>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloseFrame {
+    pub code: u16,
+    pub reason: String,
+}
+
+pub enum WebSocketError {
+    Io(io::Error),
+    // 클라이언트가 규칙에 맞지 않는 프레임을 보냈다. 1002로 연결을 닫는다.
+    Protocol(&'static str),
+    // 텍스트 메시지나 닫는 이유가 UTF-8이 아니다. 1007로 연결을 닫는다.
+    InvalidUtf8,
+    // 메시지가 max_message_size보다 크다. 1009로 연결을 닫는다.
+    TooLarge,
+    // 읽기 타임아웃이 지나도록 아무 프레임도 오지 않았다. 연결은 그대로이므로 ping()을 보내고 다시 recv()해도 된다.
+    Timeout,
+    // 이미 닫힌 연결이다.
+    Closed,
+}
+
+impl Display for WebSocketError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Protocol(reason) => write!(f, "protocol error: {}", reason),
+            Self::InvalidUtf8 => write!(f, "invalid UTF-8"),
+            Self::TooLarge => write!(f, "message is too large"),
+            Self::Timeout => write!(f, "timed out waiting for a frame"),
+            Self::Closed => write!(f, "connection is closed"),
+        }
+    }
+}
+
+impl Debug for WebSocketError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}", self)
+    }
+}
+
+impl Error for WebSocketError {}
+
+impl From<io::Error> for WebSocketError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+// TcpStream이든 TLS 스트림이든 읽고 쓸 수만 있으면 된다.
+pub trait Stream: Read + Write {}
+
+impl<T: Read + Write> Stream for T {}
+
+// 업그레이드가 끝난 연결 하나
+pub struct WebSocket<'a> {
+    stream: &'a mut dyn Stream,
+    max_message_size: usize,
+    protocol: Option<String>,
+    // 조각으로 나뉘어 오는 중인 메시지의 종류와 지금까지 받은 내용
+    partial: Option<(OpCode, Vec<u8>)>,
+    close_sent: bool,
+    closed: bool,
+}
+
+impl<'a> WebSocket<'a> {
+    fn new(stream: &'a mut dyn Stream, max_message_size: usize, protocol: Option<String>) -> Self {
+        Self {
+            stream,
+            max_message_size,
+            protocol,
+            partial: None,
+            close_sent: false,
+            closed: false,
+        }
+    }
+
+    // 협상된 서브 프로토콜
+    pub fn protocol(&self) -> Option<&str> {
+        self.protocol.as_deref()
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    // 다음 메시지를 기다린다. 조각난 메시지는 다 모아서 하나로 돌려준다.
+    // 클라이언트가 규칙을 어기면 알맞은 상태 코드로 연결을 닫고 오류를 리턴한다.
+    pub fn recv(&mut self) -> Result<Message, WebSocketError> {
+        if self.closed {
+            return Err(WebSocketError::Closed);
+        }
+
+        loop {
+            let frame = match read_frame(&mut self.stream, self.max_message_size) {
+                Ok(frame) => frame,
+                Err(e) => return Err(self.fail(e)),
+            };
+
+            match frame.opcode {
+                OpCode::Ping => {
+                    // Close 뒤에는 아무 프레임도 보낼 수 없다. Pong을 보내려다 실패하면 닫는 중에 recv()가 끝나 버린다.
+                    if !self.close_sent {
+                        self.write(OpCode::Pong, &frame.payload)?;
+                    }
+                    return Ok(Message::Ping(frame.payload));
+                }
+                OpCode::Pong => return Ok(Message::Pong(frame.payload)),
+                OpCode::Close => return self.on_close(frame.payload),
+                OpCode::Text | OpCode::Binary => {
+                    if self.partial.is_some() {
+                        return Err(self.fail(WebSocketError::Protocol(
+                            "new message before the previous one finished",
+                        )));
+                    }
+                    if frame.fin {
+                        return self.message(frame.opcode, frame.payload);
+                    }
+                    self.partial = Some((frame.opcode, frame.payload));
+                }
+                OpCode::Continuation => {
+                    let (opcode, mut data) = match self.partial.take() {
+                        Some(partial) => partial,
+                        None => {
+                            return Err(self
+                                .fail(WebSocketError::Protocol("continuation without a message")))
+                        }
+                    };
+                    if data.len() + frame.payload.len() > self.max_message_size {
+                        return Err(self.fail(WebSocketError::TooLarge));
+                    }
+                    data.extend_from_slice(&frame.payload);
+                    if frame.fin {
+                        return self.message(opcode, data);
+                    }
+                    self.partial = Some((opcode, data));
+                }
+            }
+        }
+    }
+
+    pub fn send(&mut self, message: Message) -> Result<(), WebSocketError> {
+        match message {
+            Message::Text(text) => self.send_text(&text),
+            Message::Binary(data) => self.send_binary(&data),
+            Message::Ping(data) => self.ping(&data),
+            Message::Pong(data) => self.write(OpCode::Pong, &data),
+            Message::Close(None) => self.close_with(&[]),
+            Message::Close(Some(frame)) => self.close(frame.code, &frame.reason),
+        }
+    }
+
+    pub fn send_text(&mut self, text: &str) -> Result<(), WebSocketError> {
+        self.write(OpCode::Text, text.as_bytes())
+    }
+
+    pub fn send_binary(&mut self, data: &[u8]) -> Result<(), WebSocketError> {
+        self.write(OpCode::Binary, data)
+    }
+
+    // 제어 프레임이라 125바이트까지만 보낼 수 있다.
+    pub fn ping(&mut self, data: &[u8]) -> Result<(), WebSocketError> {
+        if data.len() > 125 {
+            return Err(WebSocketError::Protocol(
+                "ping payload is longer than 125 bytes",
+            ));
+        }
+        self.write(OpCode::Ping, data)
+    }
+
+    // Close 프레임을 보내고 클라이언트가 Close로 답할 때까지 남은 메시지를 버린다.
+    pub fn close(&mut self, code: u16, reason: &str) -> Result<(), WebSocketError> {
+        let mut payload = code.to_be_bytes().to_vec();
+        // 상태 코드 2바이트와 합쳐서 125바이트를 넘지 않게 자른다.
+        let mut end = reason.len().min(123);
+        while !reason.is_char_boundary(end) {
+            end -= 1;
+        }
+        payload.extend_from_slice(&reason.as_bytes()[..end]);
+        self.close_with(&payload)
+    }
+
+    fn close_with(&mut self, payload: &[u8]) -> Result<(), WebSocketError> {
+        if self.closed {
+            return Ok(());
+        }
+        if !self.close_sent {
+            self.close_sent = true;
+            self.write(OpCode::Close, payload)?;
+        }
+        loop {
+            match self.recv() {
+                Ok(Message::Close(_)) => return Ok(()),
+                Ok(_) => {}
+                Err(WebSocketError::Closed) => return Ok(()),
+                Err(e) => {
+                    self.closed = true;
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    fn write(&mut self, opcode: OpCode, payload: &[u8]) -> Result<(), WebSocketError> {
+        if self.closed || (self.close_sent && opcode != OpCode::Close) {
+            return Err(WebSocketError::Closed);
+        }
+        write_frame(&mut self.stream, true, opcode, payload).map_err(|e| {
+            self.closed = true;
+            WebSocketError::Io(e)
+        })
+    }
+
+    fn message(&mut self, opcode: OpCode, data: Vec<u8>) -> Result<Message, WebSocketError> {
+        if opcode == OpCode::Binary {
+            return Ok(Message::Binary(data));
+        }
+        match String::from_utf8(data) {
+            Ok(text) => Ok(Message::Text(text)),
+            Err(_) => Err(self.fail(WebSocketError::InvalidUtf8)),
+        }
+    }
+
+    // 클라이언트가 먼저 닫았다면 같은 상태 코드로 답하고, 우리가 먼저 닫았다면 이게 그 답이다.
+    fn on_close(&mut self, payload: Vec<u8>) -> Result<Message, WebSocketError> {
+        let frame = match payload.len() {
+            0 => None,
+            1 => return Err(self.fail(WebSocketError::Protocol("close payload is 1 byte"))),
+            _ => {
+                let code = u16::from_be_bytes([payload[0], payload[1]]);
+                if !is_valid_close_code(code) {
+                    return Err(self.fail(WebSocketError::Protocol("invalid close code")));
+                }
+                let reason = match String::from_utf8(payload[2..].to_vec()) {
+                    Ok(reason) => reason,
+                    Err(_) => return Err(self.fail(WebSocketError::InvalidUtf8)),
+                };
+                Some(CloseFrame { code, reason })
+            }
+        };
+
+        if !self.close_sent {
+            self.close_sent = true;
+            let reply = match &frame {
+                Some(frame) => frame.code.to_be_bytes().to_vec(),
+                None => Vec::new(),
+            };
+            // 상대가 이미 연결을 끊었을 수도 있으니 실패해도 닫힌 것으로 본다.
+            let _ = write_frame(&mut self.stream, true, OpCode::Close, &reply);
+        }
+        self.closed = true;
+        Ok(Message::Close(frame))
+    }
+
+    // 오류에 맞는 상태 코드로 Close를 보내고 연결을 닫힌 상태로 만든다.
+    fn fail(&mut self, e: WebSocketError) -> WebSocketError {
+        let code = match e {
+            WebSocketError::Protocol(_) => close_code::PROTOCOL_ERROR,
+            WebSocketError::InvalidUtf8 => close_code::INVALID_DATA,
+            WebSocketError::TooLarge => close_code::TOO_LARGE,
+            WebSocketError::Timeout => return e,
+            _ => {
+                self.closed = true;
+                return e;
+            }
+        };
+        if !self.close_sent {
+            self.close_sent = true;
+            let _ = write_frame(&mut self.stream, true, OpCode::Close, &code.to_be_bytes());
+        }
+        self.closed = true;
+        e
+    }
+}
+
+// 1004~1006, 1015는 예약되어 있어서 실제 프레임에 담겨 오면 안 된다.
+fn is_valid_close_code(code: u16) -> bool {
+    matches!(code, 1000..=1003 | 1007..=1014 | 3000..=4999)
+}
+
+// 업그레이드 요청을 검사하고, 맞으면 101로 응답한 뒤 handler에게 연결을 넘긴다.
+// 업그레이드할 수 없는 요청이면 알맞은 오류 응답을 보내고 끝낸다.
+pub(crate) fn upgrade(
+    mut stream: &mut dyn Stream,
+    request: &Request,
+    handler: &dyn WebSocketHandler,
+) {
+    let response = match handshake(request, handler) {
+        Ok(response) => response,
+        Err(response) => {
+            if let Err(e) = response.send(&mut stream) {
+                error!("Failed to send response : {}", e);
+            }
+            return;
+        }
+    };
+    let protocol = response
+        .header("Sec-WebSocket-Protocol")
+        .map(str::to_string);
+    if let Err(e) = response.send(&mut stream) {
+        error!("Failed to send response : {}", e);
+        return;
+    }
+
+    let mut socket = WebSocket::new(stream, handler.max_message_size(), protocol);
+    handler.handle(request, &mut socket);
+    if !socket.is_closed() {
+        if let Err(e) = socket.close(close_code::NORMAL, "") {
+            debug!("WebSocket closed without a close handshake : {}", e);
+        }
+    }
+}
+
+fn handshake(request: &Request, handler: &dyn WebSocketHandler) -> Result<Response, Response> {
+    let is_upgrade =
+        has_token(request, "Connection", "upgrade") && has_token(request, "Upgrade", "websocket");
+    if !is_upgrade {
+        return Err(Response::new(StatusCode::UpgradeRequired, None)
+            .with_header("Connection", "Upgrade")
+            .with_header("Upgrade", "websocket"));
+    }
+    if !matches!(request.method(), Method::GET) {
+        return Err(Response::new(StatusCode::BadRequest, None));
+    }
+    if request.header("Sec-WebSocket-Version").map(str::trim) != Some("13") {
+        return Err(Response::new(StatusCode::UpgradeRequired, None)
+            .with_header("Sec-WebSocket-Version", "13"));
+    }
+    // 키는 임의의 16바이트를 base64로 인코딩한 값이어야 한다.
+    let key = request.header("Sec-WebSocket-Key").unwrap_or("").trim();
+    match STANDARD.decode(key) {
+        Ok(bytes) if bytes.len() == 16 => {}
+        _ => return Err(Response::new(StatusCode::BadRequest, None)),
+    }
+
+    handler.accept(request)?;
+
+    let mut response = Response::new(StatusCode::SwitchingProtocols, None)
+        .with_header("Upgrade", "websocket")
+        .with_header("Connection", "Upgrade")
+        .with_header("Sec-WebSocket-Accept", accept_key(key));
+    let mut offered = request
+        .headers()
+        .get_all("Sec-WebSocket-Protocol")
+        .flat_map(|value| value.split(','))
+        .map(str::trim);
+    if let Some(protocol) = offered.find(|offered| handler.protocols().contains(offered)) {
+        response.set_header("Sec-WebSocket-Protocol", protocol);
+    }
+    Ok(response)
+}
+
+// Sec-WebSocket-Accept = base64(SHA-1(키 + GUID))
+fn accept_key(key: &str) -> String {
+    let mut sha1 = Sha1::new();
+    sha1.update(key.as_bytes());
+    sha1.update(ACCEPT_GUID.as_bytes());
+    STANDARD.encode(sha1.finalize())
+}
+
+// "keep-alive, Upgrade" 처럼 쉼표로 나열된 값 중에 token이 있는지 본다.
+fn has_token(request: &Request, name: &str, token: &str) -> bool {
+    request
+        .headers()
+        .get_all(name)
+        .flat_map(|value| value.split(','))
+        .any(|value| value.trim().eq_ignore_ascii_case(token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{Auth, Realm};
+    use crate::rate_limit::{Limit, RateLimit};
+    use crate::server::testing::TestServer;
+    use crate::server::{Handler, Server};
+    use frame::tests::masked;
+    use std::io::Cursor;
+    use std::net::{SocketAddr, TcpStream};
+    use std::time::Duration;
+
+    // 클라이언트가 보낼 바이트를 미리 넣어 두고, 서버가 쓴 바이트를 모은다.
+    struct Pipe {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for Pipe {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Pipe {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Pipe {
+        fn new(frames: &[Vec<u8>]) -> Self {
+            Self {
+                input: Cursor::new(frames.concat()),
+                output: Vec::new(),
+            }
+        }
+
+        // 서버가 보낸 프레임들의 (첫 바이트, 본문). 서버 프레임은 마스크가 없다.
+        fn sent(&self) -> Vec<(u8, Vec<u8>)> {
+            let mut frames = Vec::new();
+            let mut rest = &self.output[..];
+            while !rest.is_empty() {
+                let (len, header) = match rest[1] {
+                    126 => (u16::from_be_bytes([rest[2], rest[3]]) as usize, 4),
+                    127 => (
+                        u64::from_be_bytes(rest[2..10].try_into().unwrap()) as usize,
+                        10,
+                    ),
+                    len => (len as usize, 2),
+                };
+                frames.push((rest[0], rest[header..header + len].to_vec()));
+                rest = &rest[header + len..];
+            }
+            frames
+        }
+    }
+
+    fn close_payload(code: u16, reason: &str) -> Vec<u8> {
+        [&code.to_be_bytes()[..], reason.as_bytes()].concat()
+    }
+
+    // 서버가 보낸 Close 프레임의 상태 코드
+    fn close_code_sent(pipe: &Pipe) -> Option<u16> {
+        pipe.sent()
+            .iter()
+            .find(|(first, _)| *first == 0x88)
+            .map(|(_, payload)| u16::from_be_bytes([payload[0], payload[1]]))
+    }
+
+    fn recv_all(frames: &[Vec<u8>], max_message_size: usize) -> (Vec<Message>, Pipe) {
+        let mut pipe = Pipe::new(frames);
+        let mut messages = Vec::new();
+        let mut socket = WebSocket::new(&mut pipe, max_message_size, None);
+        while let Ok(message) = socket.recv() {
+            messages.push(message);
+        }
+        (messages, pipe)
+    }
+
+    #[test]
+    fn joins_fragmented_messages() {
+        let (messages, pipe) = recv_all(
+            &[
+                masked(0x01, "안녕".as_bytes()),
+                // 조각 사이에 제어 프레임이 끼어들 수 있다.
+                masked(0x89, b"ping"),
+                masked(0x00, b", "),
+                masked(0x80, "세상".as_bytes()),
+                masked(0x82, &[1, 2, 3]),
+            ],
+            DEFAULT_MAX_MESSAGE_SIZE,
+        );
+        assert_eq!(
+            messages,
+            [
+                Message::Ping(b"ping".to_vec()),
+                Message::Text("안녕, 세상".to_string()),
+                Message::Binary(vec![1, 2, 3]),
+            ]
+        );
+        assert_eq!(pipe.sent()[0], (0x8a, b"ping".to_vec()));
+    }
+
+    #[test]
+    fn fragmentation_errors_close_with_1002() {
+        for frames in [
+            vec![masked(0x80, b"continuation first")],
+            vec![masked(0x01, b"one"), masked(0x81, b"two")],
+        ] {
+            let (messages, pipe) = recv_all(&frames, DEFAULT_MAX_MESSAGE_SIZE);
+            assert_eq!(messages, []);
+            assert_eq!(close_code_sent(&pipe), Some(close_code::PROTOCOL_ERROR));
+        }
+    }
+
+    #[test]
+    fn invalid_utf8_closes_with_1007() {
+        for frames in [
+            vec![masked(0x81, &[0xff, 0xfe])],
+            // 글자가 조각 경계에 걸쳐 있어도 합친 뒤에 검사한다.
+            vec![masked(0x01, &"é".as_bytes()[..1]), masked(0x80, &[0x28])],
+            vec![masked(0x88, &[0x03, 0xe8, 0xff])],
+        ] {
+            let (messages, pipe) = recv_all(&frames, DEFAULT_MAX_MESSAGE_SIZE);
+            assert_eq!(messages, []);
+            assert_eq!(close_code_sent(&pipe), Some(close_code::INVALID_DATA));
+        }
+        let (messages, _) = recv_all(
+            &[
+                masked(0x01, &"é".as_bytes()[..1]),
+                masked(0x80, &"é".as_bytes()[1..]),
+            ],
+            DEFAULT_MAX_MESSAGE_SIZE,
+        );
+        assert_eq!(messages, [Message::Text("é".to_string())]);
+    }
+
+    #[test]
+    fn large_messages_close_with_1009() {
+        for frames in [
+            vec![masked(0x82, &[0; 11])],
+            vec![masked(0x02, &[0; 6]), masked(0x80, &[0; 5])],
+        ] {
+            let (messages, pipe) = recv_all(&frames, 10);
+            assert_eq!(messages, []);
+            assert_eq!(close_code_sent(&pipe), Some(close_code::TOO_LARGE));
+        }
+        let (messages, _) = recv_all(&[masked(0x02, &[0; 5]), masked(0x80, &[0; 5])], 10);
+        assert_eq!(messages, [Message::Binary(vec![0; 10])]);
+    }
+
+    #[test]
+    fn invalid_close_frames_close_with_1002() {
+        for payload in [
+            vec![0x03],
+            close_payload(999, ""),
+            close_payload(1005, ""),
+            close_payload(1006, ""),
+            close_payload(1015, ""),
+            close_payload(2000, ""),
+            close_payload(5000, ""),
+        ] {
+            let (messages, pipe) = recv_all(&[masked(0x88, &payload)], DEFAULT_MAX_MESSAGE_SIZE);
+            assert_eq!(messages, [], "{:?}", payload);
+            assert_eq!(close_code_sent(&pipe), Some(close_code::PROTOCOL_ERROR));
+        }
+    }
+
+    #[test]
+    fn answers_the_clients_close() {
+        let (messages, pipe) = recv_all(
+            &[
+                masked(0x88, &close_payload(4001, "bye")),
+                masked(0x81, b"late"),
+            ],
+            DEFAULT_MAX_MESSAGE_SIZE,
+        );
+        assert_eq!(
+            messages,
+            [Message::Close(Some(CloseFrame {
+                code: 4001,
+                reason: "bye".to_string()
+            }))]
+        );
+        assert_eq!(pipe.sent(), [(0x88, 4001u16.to_be_bytes().to_vec())]);
+
+        let (messages, pipe) = recv_all(&[masked(0x88, b"")], DEFAULT_MAX_MESSAGE_SIZE);
+        assert_eq!(messages, [Message::Close(None)]);
+        assert_eq!(pipe.sent(), [(0x88, Vec::new())]);
+    }
+
+    #[test]
+    fn waits_for_the_clients_close() {
+        let mut pipe = Pipe::new(&[
+            masked(0x81, b"in flight"),
+            masked(0x89, b"ping"),
+            masked(0x88, &close_payload(1000, "")),
+        ]);
+        let mut socket = WebSocket::new(&mut pipe, DEFAULT_MAX_MESSAGE_SIZE, None);
+        socket.close(close_code::GOING_AWAY, "restarting").unwrap();
+        assert!(socket.is_closed());
+        assert!(matches!(socket.send_text("x"), Err(WebSocketError::Closed)));
+        assert!(matches!(socket.recv(), Err(WebSocketError::Closed)));
+
+        // Close를 보낸 뒤에는 Ping에 Pong으로 답하지 않는다.
+        assert_eq!(
+            pipe.sent(),
+            [(0x88, close_payload(close_code::GOING_AWAY, "restarting"))]
+        );
+        assert_eq!(pipe.input.position() as usize, pipe.input.get_ref().len());
+    }
+
+    struct NotFound;
+
+    impl Handler for NotFound {
+        fn handle_request(&self, _: &Request) -> Response {
+            Response::new(StatusCode::NotFound, None)
+        }
+    }
+
+    // 인증된 사용자 이름을 보내고 닫는다.
+    struct Whoami;
+
+    impl WebSocketHandler for Whoami {
+        fn handle(&self, request: &Request, socket: &mut WebSocket) {
+            let _ = socket.send_text(request.principal().unwrap_or("anonymous"));
+        }
+    }
+
+    fn server() -> TestServer {
+        let realm = Realm::new("chat").bearer("alice", "secret");
+        let handler = Auth::new(
+            RateLimit::new(NotFound).limit("/chat", Limit::new(1, Duration::from_secs(60))),
+        )
+        .protect("/chat", realm);
+        let server = Server::new("127.0.0.1:0".to_string()).websocket("/chat", Whoami);
+        TestServer::start(server, handler)
+    }
+
+    // 응답 머리와, 101이면 첫 메시지
+    fn upgrade(addr: SocketAddr, token: Option<&str>) -> (String, Option<String>) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let authorization = token
+            .map(|token| format!("Authorization: Bearer {}\r\n", token))
+            .unwrap_or_default();
+        write!(
+            stream,
+            "GET /chat HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\n\
+             Connection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+             Sec-WebSocket-Version: 13\r\n{}\r\n",
+            authorization
+        )
+        .unwrap();
+
+        let mut head = Vec::new();
+        let mut byte = [0];
+        while !head.ends_with(b"\r\n\r\n") {
+            stream.read_exact(&mut byte).unwrap();
+            head.push(byte[0]);
+        }
+        let head = String::from_utf8(head).unwrap();
+        if !head.starts_with("HTTP/1.1 101 ") {
+            return (head, None);
+        }
+        // 서버가 보내는 프레임은 마스크가 없다.
+        let mut header = [0; 2];
+        stream.read_exact(&mut header).unwrap();
+        assert_eq!(header[0], 0x81);
+        let mut text = vec![0; header[1] as usize];
+        stream.read_exact(&mut text).unwrap();
+        (head, Some(String::from_utf8(text).unwrap()))
+    }
+
+    #[test]
+    fn upgrades_go_through_auth_and_rate_limit() {
+        let server = server();
+        let addr = server.addr();
+
+        let (head, message) = upgrade(addr, None);
+        assert!(head.starts_with("HTTP/1.1 401 "), "{}", head);
+        assert!(head.contains("WWW-Authenticate: Bearer"), "{}", head);
+        assert_eq!(message, None);
+
+        let (head, message) = upgrade(addr, Some("wrong"));
+        assert!(head.starts_with("HTTP/1.1 401 "), "{}", head);
+        assert_eq!(message, None);
+
+        // 인증한 사용자가 WebSocket 핸들러까지 전달된다.
+        let (head, message) = upgrade(addr, Some("secret"));
+        assert!(head.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo="));
+        assert_eq!(message.as_deref(), Some("alice"));
+
+        let (head, message) = upgrade(addr, Some("secret"));
+        assert!(head.starts_with("HTTP/1.1 429 "), "{}", head);
+        assert!(head.contains("Retry-After: "), "{}", head);
+        assert_eq!(message, None);
+    }
+}